@@ -0,0 +1,73 @@
+//! The lifecycle shared by the services running on a background thread, such as
+//! [`EventService`][::events::EventService] and [`Watchdog`][::watchdog::Watchdog].
+
+use std::io;
+use std::panic;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Sender;
+use std::sync::Arc;
+use std::thread::JoinHandle;
+
+use threads::ThreadOptions;
+
+/// A thread running until asked to stop, joined when dropped.
+pub struct BackgroundThread<T> {
+    running: Arc<AtomicBool>,
+    thread: Option<JoinHandle<T>>,
+}
+
+impl<T: Send + 'static> BackgroundThread<T> {
+    /// Spawn `f` with `options`, passing it a flag that stays set until the thread is
+    /// asked to stop.
+    pub fn spawn<F>(options: &ThreadOptions, f: F) -> io::Result<BackgroundThread<T>>
+    where
+        F: FnOnce(&AtomicBool) -> T + Send + 'static,
+    {
+        let running = Arc::new(AtomicBool::new(true));
+        let thread = {
+            let running = running.clone();
+            options.spawn(move || f(&running))?
+        };
+        Ok(BackgroundThread {
+            running,
+            thread: Some(thread),
+        })
+    }
+}
+
+impl<T> BackgroundThread<T> {
+    /// Whether the thread hasn't returned yet.
+    pub fn is_running(&self) -> bool {
+        self.thread.as_ref().is_some_and(|t| !t.is_finished())
+    }
+
+    /// Ask the thread to stop and wait for it, propagating its panic. `None` if it
+    /// was already joined.
+    pub fn join(&mut self) -> Option<T> {
+        self.running.store(false, Ordering::SeqCst);
+        match self.thread.take()?.join() {
+            Ok(res) => Some(res),
+            Err(payload) => panic::resume_unwind(payload),
+        }
+    }
+
+    /// Ask the thread to stop and wait for it, ignoring its panic.
+    pub fn stop(&mut self) {
+        self.running.store(false, Ordering::SeqCst);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl<T> Drop for BackgroundThread<T> {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Report `event` to whoever holds the receiving end of `sender`.
+pub fn notify<T>(sender: &Sender<T>, event: T) {
+    // Nobody may be listening anymore; that is not an error.
+    let _ = sender.send(event);
+}
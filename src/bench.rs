@@ -0,0 +1,91 @@
+//! Measuring the data rate a configuration achieves, to compare chunk sizes, latency
+//! timers and the like empirically.
+
+use std::time::{Duration, Instant};
+
+use {Device, Result};
+
+// libftdi's default chunk size, used when none was set through this handle.
+const DEFAULT_CHUNK_SIZE: u32 = 4096;
+
+/// Distribution of the time taken by single read or write calls.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct LatencyStats {
+    pub min: Duration,
+    pub mean: Duration,
+    /// 99th percentile.
+    pub p99: Duration,
+    pub max: Duration,
+}
+
+/// Result of [`Device::measure_read_throughput`] or
+/// [`Device::measure_write_throughput`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Throughput {
+    pub bytes: u64,
+    pub elapsed: Duration,
+    /// Number of read or write calls made.
+    pub calls: usize,
+    pub latency: LatencyStats,
+}
+
+impl Throughput {
+    pub fn bytes_per_sec(&self) -> f64 {
+        self.bytes as f64 / self.elapsed.as_secs_f64().max(f64::MIN_POSITIVE)
+    }
+}
+
+impl Device {
+    /// Read and discard data for `duration`.
+    ///
+    /// Something must be streaming into the device, e.g. a FIFO master or a UART peer.
+    /// Each call reads up to the read chunk size.
+    pub fn measure_read_throughput(&self, duration: Duration) -> Result<Throughput> {
+        let size = self.config().read_chunk_size.unwrap_or(DEFAULT_CHUNK_SIZE);
+        let mut buf = vec![0; size as usize];
+        measure(duration, || self.read_data(&mut buf))
+    }
+
+    /// Write zeroes for `duration`, a write chunk at a time.
+    pub fn measure_write_throughput(&self, duration: Duration) -> Result<Throughput> {
+        let size = self.config().write_chunk_size.unwrap_or(DEFAULT_CHUNK_SIZE);
+        let buf = vec![0; size as usize];
+        measure(duration, || self.write_data(&buf))
+    }
+}
+
+// Repeat `transfer` until `duration` has passed, timing each call.
+fn measure<F>(duration: Duration, mut transfer: F) -> Result<Throughput>
+where
+    F: FnMut() -> Result<usize>,
+{
+    let mut bytes = 0;
+    let mut latencies = Vec::new();
+    let start = Instant::now();
+    while start.elapsed() < duration {
+        let call = Instant::now();
+        bytes += transfer()? as u64;
+        latencies.push(call.elapsed());
+    }
+    let elapsed = start.elapsed();
+
+    latencies.sort();
+    let total: Duration = latencies.iter().sum();
+    let calls = latencies.len();
+    let latency = LatencyStats {
+        min: latencies.first().cloned().unwrap_or_default(),
+        mean: total / calls.max(1) as u32,
+        p99: latencies
+            .get((calls * 99 / 100).min(calls.saturating_sub(1)))
+            .cloned()
+            .unwrap_or_default(),
+        max: latencies.last().cloned().unwrap_or_default(),
+    };
+
+    Ok(Throughput {
+        bytes,
+        elapsed,
+        calls,
+        latency,
+    })
+}
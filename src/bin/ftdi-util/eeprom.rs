@@ -0,0 +1,132 @@
+//! `eeprom` subcommands: dump, write and set.
+
+use std::fs;
+use std::path::Path;
+
+use ftdi::eeprom::EepromConfig;
+use ftdi::Device;
+
+use {reject_extra, CliResult, Selector};
+
+pub const USAGE: &str = "\
+    eeprom dump [--raw|--report]    Print the EEPROM as TOML, as a hex dump, or as
+                                    a readable report
+    eeprom write --from <file>      Program the settings in a TOML (or ftdi_eeprom
+                [--dry-run]         .conf) file, showing what changes
+    eeprom set <key>=<value>...     Change single settings: serial, manufacturer,
+                [--dry-run]         description, vid, pid, max_power, self_powered,
+                                    remote_wakeup";
+
+pub fn run(args: &[String]) -> CliResult<()> {
+    let (selector, rest) = Selector::parse(args.get(1..).unwrap_or(&[]))?;
+    match args.first().map(String::as_str) {
+        Some("dump") => dump(&selector, &rest),
+        Some("write") => write(&selector, &rest),
+        Some("set") => set(&selector, &rest),
+        _ => Err(format!("eeprom needs a subcommand:\n{}", USAGE).into()),
+    }
+}
+
+fn dump(selector: &Selector, args: &[String]) -> CliResult<()> {
+    let raw = args.iter().any(|arg| arg == "--raw");
+    let report = args.iter().any(|arg| arg == "--report");
+    reject_extra(&without(args, &["--raw", "--report"]))?;
+
+    let mut device = selector.open()?;
+    if raw {
+        device.load_eeprom_data()?;
+        for (i, line) in device.get_eeprom_buf()?.chunks(16).enumerate() {
+            let bytes: Vec<String> = line.iter().map(|b| format!("{:02x}", b)).collect();
+            println!("{:04x}: {}", i * 16, bytes.join(" "));
+        }
+    } else if report {
+        print!("{}", device.eeprom_config()?);
+    } else {
+        print!("{}", device.eeprom_config()?.to_toml()?);
+    }
+    Ok(())
+}
+
+fn write(selector: &Selector, args: &[String]) -> CliResult<()> {
+    let dry_run = args.iter().any(|arg| arg == "--dry-run");
+    let rest = without(args, &["--dry-run"]);
+    let path = match &rest[..] {
+        [flag, path] if flag == "--from" => path,
+        _ => return Err("eeprom write needs --from <file>".into()),
+    };
+
+    let text = fs::read_to_string(path)?;
+    let config = if Path::new(path).extension().is_some_and(|ext| ext == "conf") {
+        EepromConfig::from_ftdi_eeprom_conf(&text)?
+    } else {
+        EepromConfig::from_toml(&text)?
+    };
+
+    let mut device = selector.open()?;
+    program(&mut device, &config, dry_run)
+}
+
+fn set(selector: &Selector, args: &[String]) -> CliResult<()> {
+    let dry_run = args.iter().any(|arg| arg == "--dry-run");
+    let assignments = without(args, &["--dry-run"]);
+    if assignments.is_empty() {
+        return Err("eeprom set needs at least one <key>=<value>".into());
+    }
+
+    let mut device = selector.open()?;
+    let mut config = device.eeprom_config()?;
+    for assignment in &assignments {
+        let (key, value) = assignment
+            .split_once('=')
+            .ok_or_else(|| format!("expected <key>=<value>, got '{}'", assignment))?;
+        match key {
+            "serial" => config.serial = value.to_string(),
+            "manufacturer" => config.manufacturer = value.to_string(),
+            "description" => config.description = value.to_string(),
+            "vid" => config.vendor_id = ::parse_hex(value)?,
+            "pid" => config.product_id = ::parse_hex(value)?,
+            "max_power" => config.max_power = value.parse()?,
+            "self_powered" => config.self_powered = value.parse()?,
+            "remote_wakeup" => config.remote_wakeup = value.parse()?,
+            _ => return Err(format!("unknown EEPROM setting '{}'", key).into()),
+        }
+    }
+    program(&mut device, &config, dry_run)
+}
+
+// Show the changes `config` makes, then flash and verify it unless `dry_run`.
+fn program(device: &mut Device, config: &EepromConfig, dry_run: bool) -> CliResult<()> {
+    let changes = device.eeprom_config()?.diff(config);
+    if changes.is_empty() {
+        println!("EEPROM already up to date");
+        return Ok(());
+    }
+    for change in &changes {
+        println!("{}: {} -> {}", change.field, change.old, change.new);
+    }
+    if dry_run {
+        return Ok(());
+    }
+
+    device.apply_eeprom_config(config)?;
+    device.build_eeprom()?;
+    device.write_eeprom()?;
+
+    let mismatches = device.verify_eeprom(config)?;
+    if let Some(change) = mismatches.first() {
+        return Err(format!(
+            "verification failed: {} reads back as {}",
+            change.field, change.new
+        )
+        .into());
+    }
+    println!("EEPROM written; replug the device for the changes to take effect");
+    Ok(())
+}
+
+fn without(args: &[String], flags: &[&str]) -> Vec<String> {
+    args.iter()
+        .filter(|arg| !flags.contains(&arg.as_str()))
+        .cloned()
+        .collect()
+}
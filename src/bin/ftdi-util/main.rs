@@ -0,0 +1,209 @@
+//! Command line utility for inspecting and configuring FTDI devices, with a serial
+//! console.
+
+extern crate safe_ftdi as ftdi;
+
+mod eeprom;
+mod term;
+
+use std::env;
+use std::error::Error;
+use std::process;
+
+use ftdi::{Device, DeviceInfo, Interface};
+
+const USAGE: &str = "\
+Usage: ftdi-util <command> [options]
+
+Commands:
+    list                            List attached devices
+    info                            Show details of one device";
+
+const SELECTION_USAGE: &str = "\
+Device selection (list only filters by --vid and --pid):
+    --vid <hex>         Vendor id, with --pid (default: FTDI's ids)
+    --pid <hex>         Product id
+    --serial <serial>   Serial number
+    --port <path>       USB port path, e.g. 1-4.2
+    --index <n>         n-th matching device (default: 0)
+    --interface <i>     A, B, C, D or any (default: any)";
+
+fn usage() -> String {
+    format!(
+        "{}\n{}\n{}\n\n{}",
+        USAGE,
+        eeprom::USAGE,
+        term::USAGE,
+        SELECTION_USAGE
+    )
+}
+
+type CliResult<T> = Result<T, Box<dyn Error>>;
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let res = match args.first().map(String::as_str) {
+        Some("list") => list(&args[1..]),
+        Some("info") => info(&args[1..]),
+        Some("eeprom") => eeprom::run(&args[1..]),
+        Some("term") => term::run(&args[1..]),
+        Some("-h") | Some("--help") | Some("help") => {
+            println!("{}", usage());
+            Ok(())
+        }
+        _ => {
+            eprintln!("{}", usage());
+            process::exit(2);
+        }
+    };
+
+    if let Err(e) = res {
+        eprint!("ftdi-util: {}", e);
+        let mut source = e.source();
+        while let Some(e) = source {
+            eprint!(": {}", e);
+            source = e.source();
+        }
+        eprintln!();
+        process::exit(1);
+    }
+}
+
+/// Which device to open, parsed from the command line.
+#[derive(Default)]
+struct Selector {
+    vid: u16,
+    pid: u16,
+    serial: Option<String>,
+    port: Option<String>,
+    index: usize,
+    interface: Option<Interface>,
+}
+
+impl Selector {
+    // Parse the selection options, returning the remaining arguments.
+    fn parse(args: &[String]) -> CliResult<(Selector, Vec<String>)> {
+        let mut selector = Selector::default();
+        let mut rest = Vec::new();
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .cloned()
+                    .ok_or_else(|| format!("{} needs a value", arg))
+            };
+            match arg.as_str() {
+                "--vid" => selector.vid = parse_hex(&value()?)?,
+                "--pid" => selector.pid = parse_hex(&value()?)?,
+                "--serial" => selector.serial = Some(value()?),
+                "--port" => selector.port = Some(value()?),
+                "--index" => selector.index = value()?.parse()?,
+                "--interface" => selector.interface = Some(value()?.parse()?),
+                _ => rest.push(arg.clone()),
+            }
+        }
+        Ok((selector, rest))
+    }
+
+    fn find(&self) -> CliResult<DeviceInfo> {
+        let mut matches = Vec::new();
+        for info in ftdi::devices_filtered(self.vid, self.pid) {
+            let info = info?;
+            if self.serial.as_ref().is_some_and(|s| *s != info.serial)
+                || self.port.as_ref().is_some_and(|p| *p != info.port_path)
+            {
+                continue;
+            }
+            matches.push(info);
+        }
+
+        let count = matches.len();
+        matches
+            .into_iter()
+            .nth(self.index)
+            .ok_or_else(|| format!("no matching device (found {})", count).into())
+    }
+
+    fn open(&self) -> CliResult<Device> {
+        let info = self.find()?;
+        Ok(Device::open(
+            &info,
+            self.interface.unwrap_or(Interface::Any),
+        )?)
+    }
+}
+
+fn parse_hex(s: &str) -> CliResult<u16> {
+    let digits = s.trim_start_matches("0x").trim_start_matches("0X");
+    Ok(u16::from_str_radix(digits, 16)?)
+}
+
+fn reject_extra(rest: &[String]) -> CliResult<()> {
+    match rest.first() {
+        Some(arg) => Err(format!("unexpected argument '{}'", arg).into()),
+        None => Ok(()),
+    }
+}
+
+fn chip_name(info: &DeviceInfo) -> String {
+    info.chip_type
+        .map_or_else(|| "unknown".to_string(), |chip| chip.to_string())
+}
+
+fn list(args: &[String]) -> CliResult<()> {
+    let (selector, rest) = Selector::parse(args)?;
+    reject_extra(&rest)?;
+
+    println!(
+        "{:<7} {:<9} {:<8} {:<10} {:<12} DESCRIPTION",
+        "BUS:DEV", "VID:PID", "CHIP", "PORT", "SERIAL"
+    );
+    for info in ftdi::devices_filtered(selector.vid, selector.pid) {
+        let info = info?;
+        println!(
+            "{:03}:{:03} {:04x}:{:04x} {:<8} {:<10} {:<12} {}",
+            info.bus,
+            info.address,
+            info.vendor_id,
+            info.product_id,
+            chip_name(&info),
+            info.port_path,
+            info.serial,
+            info.description
+        );
+    }
+    Ok(())
+}
+
+fn info(args: &[String]) -> CliResult<()> {
+    let (selector, rest) = Selector::parse(args)?;
+    reject_extra(&rest)?;
+
+    let mut device = selector.open()?;
+    let info = device.device_info()?;
+    println!("Manufacturer:  {}", info.manufacturer);
+    println!("Description:   {}", info.description);
+    println!("Serial:        {}", info.serial);
+    println!(
+        "VID:PID:       {:04x}:{:04x}",
+        info.vendor_id, info.product_id
+    );
+    println!("Chip:          {}", chip_name(&info));
+    println!("Interfaces:    {}", device.interface_count());
+    println!("Bus/address:   {:03}:{:03}", info.bus, info.address);
+    println!("Port path:     {}", info.port_path);
+
+    // A blank or missing EEPROM is common; report it rather than failing.
+    match device.verify_eeprom_checksum() {
+        Ok(valid) => {
+            println!(
+                "EEPROM:        {}",
+                if valid { "valid" } else { "bad checksum" }
+            );
+            println!("Max power:     {} mA", device.max_power()?);
+            println!("Self powered:  {}", device.self_powered()?);
+        }
+        Err(e) => println!("EEPROM:        unreadable ({})", e),
+    }
+    Ok(())
+}
@@ -0,0 +1,169 @@
+//! `term` subcommand: a serial console, or a bridge between the UART and TCP.
+
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, TcpListener};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use ftdi::split::{Reader, Writer};
+use ftdi::{DataBits, FlowControl, Parity, StopBits};
+
+use {reject_extra, CliResult, Selector};
+
+pub const USAGE: &str = "\
+    term [--baud <n>] [--line <8N1>]  Open a serial console on stdin/stdout
+         [--flow <f>] [--hex]         (flow: disabled, rts-cts or dtr-dsr)
+         [--listen <addr:port>]       or bridge the UART to TCP clients";
+
+// Wait between polls while the UART is idle.
+const IDLE_POLL: Duration = Duration::from_millis(1);
+
+pub fn run(args: &[String]) -> CliResult<()> {
+    let (selector, rest) = Selector::parse(args)?;
+    let mut baudrate = 115_200;
+    let mut line = (DataBits::Eight, StopBits::One, Parity::None);
+    let mut flow_control = FlowControl::Disabled;
+    let mut hex = false;
+    let mut listen = None;
+
+    let mut extra = Vec::new();
+    let mut args = rest.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .cloned()
+                .ok_or_else(|| format!("{} needs a value", arg))
+        };
+        match arg.as_str() {
+            "--baud" => baudrate = value()?.parse()?,
+            "--line" => line = parse_line(&value()?)?,
+            "--flow" => flow_control = value()?.parse()?,
+            "--hex" => hex = true,
+            "--listen" => listen = Some(value()?),
+            _ => extra.push(arg.clone()),
+        }
+    }
+    reject_extra(&extra)?;
+
+    let uart = selector.open()?.into_uart()?;
+    uart.set_baudrate(baudrate)?;
+    uart.set_line_property(line.0, line.1, line.2)?;
+    uart.set_flow_control(flow_control)?;
+    uart.set_timeouts(100, 1000);
+    uart.purge_usb_buffers()?;
+
+    let (reader, writer) = uart.into_inner().split();
+    match listen {
+        Some(addr) => bridge(reader, writer, &addr),
+        None => console(reader, writer, hex),
+    }
+}
+
+// Parse e.g. "8N1" or "7E1.5".
+fn parse_line(s: &str) -> CliResult<(DataBits, StopBits, Parity)> {
+    let mut chars = s.chars();
+    let bits = match chars.next() {
+        Some('7') => DataBits::Seven,
+        Some('8') => DataBits::Eight,
+        _ => return Err(format!("data bits in '{}' must be 7 or 8", s).into()),
+    };
+    let parity = match chars.next().map(|c| c.to_ascii_uppercase()) {
+        Some('N') => Parity::None,
+        Some('O') => Parity::Odd,
+        Some('E') => Parity::Even,
+        Some('M') => Parity::Mark,
+        Some('S') => Parity::Space,
+        _ => return Err(format!("parity in '{}' must be one of NOEMS", s).into()),
+    };
+    let stop_bits = match chars.as_str() {
+        "1" => StopBits::One,
+        "1.5" => StopBits::OneAndHalf,
+        "2" => StopBits::Two,
+        _ => return Err(format!("stop bits in '{}' must be 1, 1.5 or 2", s).into()),
+    };
+    Ok((bits, stop_bits, parity))
+}
+
+fn console(reader: Reader, writer: Writer, hex: bool) -> CliResult<()> {
+    let done = Arc::new(AtomicBool::new(false));
+    let stdin_done = done.clone();
+    thread::spawn(move || {
+        let mut buf = [0; 256];
+        let stdin = io::stdin();
+        let mut stdin = stdin.lock();
+        while let Ok(n) = stdin.read(&mut buf) {
+            if n == 0 || writer.write_data(&buf[..n]).is_err() {
+                break;
+            }
+        }
+        stdin_done.store(true, Ordering::SeqCst);
+    });
+
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    let mut buf = [0; 4096];
+    while !done.load(Ordering::SeqCst) {
+        let n = reader.read_data(&mut buf)?;
+        if n == 0 {
+            thread::sleep(IDLE_POLL);
+            continue;
+        }
+        if hex {
+            for byte in &buf[..n] {
+                write!(stdout, "{:02x} ", byte)?;
+            }
+        } else {
+            stdout.write_all(&buf[..n])?;
+        }
+        stdout.flush()?;
+    }
+    Ok(())
+}
+
+// Serve TCP clients one at a time, forwarding bytes both ways until they disconnect.
+fn bridge(reader: Reader, mut writer: Writer, addr: &str) -> CliResult<()> {
+    let listener = TcpListener::bind(addr)?;
+    eprintln!("Bridging the UART to {}", listener.local_addr()?);
+
+    let mut buf = [0; 4096];
+    loop {
+        let (stream, peer) = listener.accept()?;
+        stream.set_nodelay(true)?;
+        eprintln!("{} connected", peer);
+
+        let mut incoming = stream.try_clone()?;
+        let uplink = thread::spawn(move || {
+            let mut buf = [0; 4096];
+            let res = (|| -> ftdi::Result<()> {
+                loop {
+                    let n = incoming.read(&mut buf)?;
+                    if n == 0 {
+                        return Ok(());
+                    }
+                    writer.write_data(&buf[..n])?;
+                }
+            })();
+            (writer, res)
+        });
+
+        while !uplink.is_finished() {
+            let n = reader.read_data(&mut buf)?;
+            if n == 0 {
+                thread::sleep(IDLE_POLL);
+            } else if (&stream).write_all(&buf[..n]).is_err() {
+                break;
+            }
+        }
+
+        // Unblock the uplink if the client stopped reading but not writing.
+        let _ = stream.shutdown(Shutdown::Both);
+        let (returned, res) = uplink.join().map_err(|_| "bridge thread panicked")?;
+        writer = returned;
+        match res {
+            Ok(()) | Err(ftdi::error::Error::Io(_)) => eprintln!("{} disconnected", peer),
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
@@ -0,0 +1,940 @@
+//! Helpers for the bitbang modes, including software SPI, I2C and 1-Wire masters.
+
+use std::fmt;
+use std::io;
+use std::iter;
+use std::sync::mpsc::Sender;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use error::{Error, LibUsbError};
+use gpio::{Gpio, PinCondition};
+use i2c;
+use mode::BitbangDevice;
+use spi::{self, BitOrder, SpiMode};
+use {achieved_baudrate, BitModeConfig, Device, Result};
+
+/// The chips clock bitbang data at 16 times the rate passed to `set_baudrate`
+/// (libftdi scales it by 4, the chip by another 4).
+const BITBANG_CLOCK_MULTIPLIER: u32 = 16;
+
+/// Bytes written per round in synchronous bitbang mode, small enough for the
+/// samples to fit the receive buffer of every chip.
+const SYNC_BB_CHUNK: usize = 128;
+
+/// How long an I2C target may stretch the clock by default, as in SMBus.
+const DEFAULT_STRETCH_TIMEOUT: Duration = Duration::from_millis(35);
+
+/// How much output a paced write hands to the chip at once, in time.
+const PACE_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Sample rate a [`OneWire`] master asks for; a few slots fit in one round of
+/// synchronous bitbang at it.
+const ONE_WIRE_SAMPLE_RATE: u32 = 500_000;
+
+// 1-Wire standard speed timings in microseconds, after Maxim's application note 126.
+const ONE_WIRE_RESET_LOW: u32 = 480;
+// When the presence pulse is sampled, after releasing the bus.
+const ONE_WIRE_PRESENCE_SAMPLE: u32 = 70;
+const ONE_WIRE_RESET_RECOVERY: Duration = Duration::from_micros(410);
+const ONE_WIRE_SLOT: u32 = 70;
+const ONE_WIRE_WRITE_ONE_LOW: u32 = 6;
+const ONE_WIRE_WRITE_ZERO_LOW: u32 = 60;
+// When a read slot is sampled, from its start; devices hold a 0 for at least 15 us.
+const ONE_WIRE_READ_SAMPLE: u32 = 13;
+
+const READ_ROM: u8 = 0x33;
+const MATCH_ROM: u8 = 0x55;
+const SKIP_ROM: u8 = 0xcc;
+const SEARCH_ROM: u8 = 0xf0;
+const ALARM_SEARCH: u8 = 0xec;
+
+impl Device {
+    /// Clock `out` onto the pins in [`SyncBB`][BitModeConfig::SyncBB] mode and return
+    /// the pin levels sampled for each written byte.
+    ///
+    /// The chip samples the pins just before applying each written byte; this lines
+    /// the samples up so that `result[i]` holds the levels after `out[i]` was applied.
+    pub fn sync_bitbang_exchange(&self, out: &[u8]) -> Result<Vec<u8>> {
+        match self.bitmode() {
+            Some(BitModeConfig::SyncBB { .. }) => {}
+            _ => {
+                return Err(Error::InvalidArgument(
+                    "Synchronous bitbang mode isn't enabled",
+                ))
+            }
+        }
+
+        let last = match out.last() {
+            Some(&last) => last,
+            None => return Ok(Vec::new()),
+        };
+
+        // Stale samples would shift all results.
+        self.purge_usb_rx_buffer()?;
+
+        // Repeat the last byte to get a sample after it, and drop the one before the first.
+        let mut stimulus = out.to_vec();
+        stimulus.push(last);
+        let mut samples = vec![0; stimulus.len()];
+
+        for (chunk, sampled) in stimulus
+            .chunks(SYNC_BB_CHUNK)
+            .zip(samples.chunks_mut(SYNC_BB_CHUNK))
+        {
+            self.write_data(chunk)?;
+            self.read_exact(sampled)?;
+        }
+
+        samples.remove(0);
+        Ok(samples)
+    }
+
+    /// Rate at which bytes are clocked out to the pins in bitbang mode, from the
+    /// baudrate last set through this handle
+    pub fn bitbang_clock(&self) -> Option<u32> {
+        self.config()
+            .baudrate
+            .map(|baudrate| baudrate.saturating_mul(BITBANG_CLOCK_MULTIPLIER))
+    }
+
+    /// Write `data` no faster than `bytes_per_sec`
+    ///
+    /// The data is handed to the chip in pieces lasting a few milliseconds each, rather
+    /// than all at once, so the chip's FIFO never runs far ahead of the intended
+    /// timing. Returns the number of bytes written, which falls short of `data` only
+    /// if the chip stops taking any.
+    pub fn write_paced(&self, data: &[u8], bytes_per_sec: u32) -> Result<usize> {
+        if bytes_per_sec == 0 {
+            return Err(Error::InvalidArgument("Pacing rate must not be 0"));
+        }
+
+        let chunk = (bytes_per_sec as u128 * PACE_INTERVAL.as_micros() / 1_000_000).max(1);
+        let start = Instant::now();
+        let mut written = 0;
+        while written < data.len() {
+            let due = start + Duration::from_secs_f64(written as f64 / bytes_per_sec as f64);
+            if let Some(wait) = due.checked_duration_since(Instant::now()) {
+                thread::sleep(wait);
+            }
+            // A piece written in part goes on from where the chip stopped.
+            let end = data.len().min(written + chunk as usize);
+            match self.write_data(&data[written..end])? {
+                0 => break,
+                n => written += n,
+            }
+        }
+        Ok(written)
+    }
+}
+
+/// How samples are clocked during a [`Capture`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum CaptureMode {
+    /// The chip samples the pins on its own at the sample rate.
+    Async,
+    /// The chip samples the pins once per byte written, which the capture does as
+    /// fast as the sample rate allows. Slower, but no sample is lost to a full buffer.
+    Sync,
+}
+
+/// A block of consecutive samples, as sent by [`Capture::stream`].
+#[derive(Debug, Clone)]
+pub struct CaptureBlock {
+    /// Index of the first sample since the capture started.
+    pub first_index: u64,
+    /// Time of the first sample since the capture started, from the sample clock.
+    pub timestamp: Duration,
+    /// Pin levels, one byte per sample.
+    pub samples: Vec<u8>,
+}
+
+/// Recording of the pin levels at a fixed rate, see [`Device::capture`].
+pub struct Capture<'a> {
+    device: &'a Device,
+    mode: CaptureMode,
+    sample_rate: u32,
+    next_index: u64,
+}
+
+impl Device {
+    /// Clock bitbang output and sampling at about `hz` bytes per second, returning the
+    /// rate the chip actually achieves
+    ///
+    /// The chip derives the bitbang clock from the baudrate, at 16 times the value
+    /// passed to [`set_baudrate`][Device::set_baudrate], and can only divide its base
+    /// clock by certain divisors; rates beyond the chip's maximum are capped to it.
+    pub fn set_bitbang_sample_rate(&self, hz: u32) -> Result<u32> {
+        if hz < BITBANG_CLOCK_MULTIPLIER {
+            return Err(Error::InvalidArgument("Sample rate too low"));
+        }
+
+        // Round in u64 so rates near u32::MAX don't overflow; the quotient always fits.
+        let multiplier = u64::from(BITBANG_CLOCK_MULTIPLIER);
+        let baudrate = ((u64::from(hz) + multiplier / 2) / multiplier) as u32;
+        self.set_baudrate(baudrate)?;
+        // libftdi asks the chip for 4 times the baudrate in bitbang mode.
+        Ok(achieved_baudrate(self.chip_type(), baudrate.saturating_mul(4)).saturating_mul(4))
+    }
+
+    /// Make all pins inputs and start sampling them at `sample_rate` samples per second
+    ///
+    /// The chip can only approximate the requested rate, see
+    /// [`set_bitbang_sample_rate`][Device::set_bitbang_sample_rate];
+    /// [`Capture::sample_rate`] is the rate achieved.
+    pub fn capture(&self, mode: CaptureMode, sample_rate: u32) -> Result<Capture<'_>> {
+        if sample_rate < BITBANG_CLOCK_MULTIPLIER {
+            return Err(Error::InvalidArgument("Sample rate too low"));
+        }
+
+        let config = match mode {
+            CaptureMode::Async => BitModeConfig::Bitbang { direction: 0 },
+            CaptureMode::Sync => BitModeConfig::SyncBB { direction: 0 },
+        };
+        self.set_bitmode_config(config)?;
+        let sample_rate = self.set_bitbang_sample_rate(sample_rate)?;
+        self.purge_usb_rx_buffer()?;
+
+        Ok(Capture {
+            device: self,
+            mode,
+            sample_rate,
+            next_index: 0,
+        })
+    }
+}
+
+impl<'a> Capture<'a> {
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Index of the next sample [`read`][Capture::read] returns.
+    pub fn next_index(&self) -> u64 {
+        self.next_index
+    }
+
+    /// Time of sample `index` since the capture started.
+    pub fn timestamp(&self, index: u64) -> Duration {
+        let rate = u64::from(self.sample_rate);
+        Duration::new(index / rate, ((index % rate) * 1_000_000_000 / rate) as u32)
+    }
+
+    /// Fill `samples` with the next samples, one byte of pin levels each.
+    pub fn read(&mut self, samples: &mut [u8]) -> Result<()> {
+        if self.mode == CaptureMode::Sync {
+            for chunk in samples.chunks_mut(SYNC_BB_CHUNK) {
+                self.device.write_data(&vec![0; chunk.len()])?;
+                self.device.read_exact(chunk)?;
+            }
+        } else {
+            self.device.read_exact(samples)?;
+        }
+
+        self.next_index += samples.len() as u64;
+        Ok(())
+    }
+
+    /// Send blocks of `block_size` samples to `sender` until the receiver hangs up.
+    pub fn stream(&mut self, block_size: usize, sender: &Sender<CaptureBlock>) -> Result<()> {
+        if block_size == 0 {
+            return Err(Error::InvalidArgument("Block size must not be zero"));
+        }
+
+        loop {
+            let first_index = self.next_index;
+            let mut samples = vec![0; block_size];
+            self.read(&mut samples)?;
+
+            let block = CaptureBlock {
+                first_index,
+                timestamp: self.timestamp(first_index),
+                samples,
+            };
+            if sender.send(block).is_err() {
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Pin assignment of a bitbang [`Spi`] master, as bit numbers 0..7.
+///
+/// Further chip selects can be added by sharing the bus, see
+/// [`SharedSpiBus`][::spi::SharedSpiBus].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct SpiPins {
+    pub sclk: u8,
+    pub mosi: u8,
+    pub miso: u8,
+    /// Chip select, active LOW.
+    pub cs: u8,
+}
+
+/// Software SPI master for chips without MPSSE, such as the FT232R and FT245R.
+///
+/// Writes are turned into one batch of pin states and sent in a single USB transfer.
+/// Reads need a [`read_pins`][BitbangDevice::read_pins] round trip per bit, so they
+/// are a lot slower. Pins not used for SPI keep their direction and level.
+///
+/// Bytes are shifted MSB first unless changed with
+/// [`set_bit_order`][Spi::set_bit_order].
+pub struct Spi<'a> {
+    device: &'a BitbangDevice,
+    pins: SpiPins,
+    mode: SpiMode,
+    bit_order: BitOrder,
+}
+
+impl<'a> Spi<'a> {
+    /// Make SCLK, MOSI and CS outputs and MISO an input, and idle the bus.
+    pub fn new(device: &'a BitbangDevice, pins: SpiPins, mode: SpiMode) -> Result<Spi<'a>> {
+        if [pins.sclk, pins.mosi, pins.miso, pins.cs]
+            .iter()
+            .any(|&pin| pin >= 8)
+        {
+            return Err(Error::InvalidArgument("Bitbang pin must be below 8"));
+        }
+        let outputs: u8 = (1 << pins.sclk) | (1 << pins.mosi) | (1 << pins.cs);
+        if outputs.count_ones() != 3 || outputs & (1 << pins.miso) != 0 {
+            return Err(Error::InvalidArgument("SPI pins must be distinct"));
+        }
+
+        let spi = Spi {
+            device,
+            pins,
+            mode,
+            bit_order: BitOrder::MsbFirst,
+        };
+        let idle = spi.level(device.output_latch(), pins.cs, true);
+        let idle = spi.level(idle, pins.sclk, mode.cpol());
+        device.write_pins(idle)?;
+        device.set_direction((device.direction() | outputs) & !(1 << pins.miso))?;
+        Ok(spi)
+    }
+
+    pub fn mode(&self) -> SpiMode {
+        self.mode
+    }
+
+    /// Switch to another SPI mode, moving the clock to its new idle level.
+    pub fn set_mode(&mut self, mode: SpiMode) -> Result<()> {
+        self.mode = mode;
+        self.device
+            .write_pins(self.idle_state(self.device.output_latch()))
+    }
+
+    pub fn bit_order(&self) -> BitOrder {
+        self.bit_order
+    }
+
+    pub fn set_bit_order(&mut self, bit_order: BitOrder) {
+        self.bit_order = bit_order;
+    }
+
+    pub fn pins(&self) -> SpiPins {
+        self.pins
+    }
+
+    fn level(&self, latch: u8, pin: u8, high: bool) -> u8 {
+        if high {
+            latch | (1 << pin)
+        } else {
+            latch & !(1 << pin)
+        }
+    }
+
+    // The two pin states clocking out `bit`: data set up, then the sampling edge.
+    fn bit_states(&self, latch: u8, bit: bool) -> [u8; 2] {
+        let data = self.level(latch, self.pins.mosi, bit);
+        let idle = self.level(data, self.pins.sclk, self.mode.cpol());
+        let active = self.level(data, self.pins.sclk, !self.mode.cpol());
+        if self.mode.cpha() {
+            [active, idle]
+        } else {
+            [idle, active]
+        }
+    }
+
+    // Returns the clock to idle after the last bit; MOSI is left as it was.
+    fn idle_state(&self, latch: u8) -> u8 {
+        self.level(latch, self.pins.sclk, self.mode.cpol())
+    }
+}
+
+impl<'a> spi::SpiBus for Spi<'a> {
+    fn set_cs(&mut self, pin: u8, active: bool) -> Result<()> {
+        if pin >= 8 {
+            return Err(Error::InvalidArgument("Bitbang pin must be below 8"));
+        }
+        if [self.pins.sclk, self.pins.mosi, self.pins.miso].contains(&pin) {
+            return Err(Error::InvalidArgument(
+                "Chip select must not be an SPI data pin",
+            ));
+        }
+
+        let latch = self.idle_state(self.device.output_latch());
+        self.device.write_pins(self.level(latch, pin, !active))?;
+        if self.device.direction() & (1 << pin) == 0 {
+            self.device
+                .set_direction(self.device.direction() | (1 << pin))?;
+        }
+        Ok(())
+    }
+
+    fn write(&mut self, data: &[u8]) -> Result<()> {
+        let latch = self.device.output_latch();
+        let mut states = Vec::with_capacity(data.len() * 16 + 1);
+        for &byte in data {
+            for &i in &self.bit_order.positions() {
+                states.extend_from_slice(&self.bit_states(latch, byte & (1 << i) != 0));
+            }
+        }
+        states.push(self.idle_state(*states.last().unwrap_or(&latch)));
+        self.device.write_data(&states).map(|_| ())
+    }
+
+    fn read(&mut self, data: &mut [u8]) -> Result<()> {
+        for byte in data.iter_mut() {
+            *byte = 0;
+        }
+        self.transfer(data)
+    }
+
+    fn transfer(&mut self, data: &mut [u8]) -> Result<()> {
+        let latch = self.device.output_latch();
+        for byte in data.iter_mut() {
+            let mut received = 0;
+            for &i in &self.bit_order.positions() {
+                self.device
+                    .write_data(&self.bit_states(latch, *byte & (1 << i) != 0))?;
+                if self.device.read_pins()? & (1 << self.pins.miso) != 0 {
+                    received |= 1 << i;
+                }
+            }
+            *byte = received;
+        }
+        let latch = self.device.output_latch();
+        self.device.write_pins(self.idle_state(latch))
+    }
+}
+
+impl<'a> spi::Spi for Spi<'a> {
+    fn select(&mut self) -> Result<()> {
+        let cs = self.pins.cs;
+        spi::SpiBus::set_cs(self, cs, true)
+    }
+
+    fn deselect(&mut self) -> Result<()> {
+        let cs = self.pins.cs;
+        spi::SpiBus::set_cs(self, cs, false)
+    }
+}
+
+/// Software I2C master for chips without MPSSE, such as the FT232R and FT245R.
+///
+/// The pins are driven open-drain: a line is pulled LOW by making its pin an output
+/// with a LOW level, and released by making it an input, so both lines need
+/// pull-up resistors. Every line change is a USB control transfer, which limits
+/// the bus to a few hundred Hz however high the frequency is set.
+///
+/// Targets may stretch the clock: after releasing SCL, the master waits for it to
+/// go HIGH, up to the [stretch timeout][I2c::set_stretch_timeout].
+pub struct I2c<'a> {
+    device: &'a BitbangDevice,
+    scl: u8,
+    sda: u8,
+    half_period: Duration,
+    stretch_timeout: Duration,
+}
+
+impl<'a> I2c<'a> {
+    /// Release SCL and SDA and set the SCL frequency to `frequency` Hz.
+    pub fn new(device: &'a BitbangDevice, scl: u8, sda: u8, frequency: u32) -> Result<I2c<'a>> {
+        if scl >= 8 || sda >= 8 {
+            return Err(Error::InvalidArgument("Bitbang pin must be below 8"));
+        }
+        if scl == sda {
+            return Err(Error::InvalidArgument("SCL and SDA must be distinct"));
+        }
+
+        let mut i2c = I2c {
+            device,
+            scl,
+            sda,
+            half_period: Duration::from_secs(0),
+            stretch_timeout: DEFAULT_STRETCH_TIMEOUT,
+        };
+        i2c.set_frequency(frequency)?;
+
+        let lines = (1 << scl) | (1 << sda);
+        device.set_direction(device.direction() & !lines)?;
+        device.write_pins(device.output_latch() & !lines)?;
+        Ok(i2c)
+    }
+
+    /// Change the SCL frequency, in Hz.
+    pub fn set_frequency(&mut self, frequency: u32) -> Result<()> {
+        if frequency == 0 {
+            return Err(Error::InvalidArgument("I2C frequency must not be zero"));
+        }
+        self.half_period = Duration::from_secs(1) / (2 * frequency);
+        Ok(())
+    }
+
+    /// How long a target may hold SCL LOW before the transfer fails with a timeout.
+    pub fn set_stretch_timeout(&mut self, timeout: Duration) {
+        self.stretch_timeout = timeout;
+    }
+
+    // Release (HIGH) or pull down (LOW) both lines, then wait half a clock period.
+    fn set_lines(&self, scl: bool, sda: bool) -> Result<()> {
+        let previous = self.device.direction();
+        let mut pulled = previous & !((1 << self.scl) | (1 << self.sda));
+        if !scl {
+            pulled |= 1 << self.scl;
+        }
+        if !sda {
+            pulled |= 1 << self.sda;
+        }
+        if pulled != previous {
+            self.device.set_direction(pulled)?;
+        }
+
+        // A target stretching the clock keeps SCL LOW after we release it.
+        if scl && previous & (1 << self.scl) != 0 {
+            let released = self
+                .device
+                .pin(self.scl)
+                .wait_for(PinCondition::High, self.stretch_timeout)?;
+            if released.is_none() {
+                return Err(Error::LibUsb(LibUsbError::Timeout));
+            }
+        }
+
+        thread::sleep(self.half_period);
+        Ok(())
+    }
+
+    fn sda_is_high(&self) -> Result<bool> {
+        Ok(self.device.read_pins()? & (1 << self.sda) != 0)
+    }
+
+    fn write_bit(&self, bit: bool) -> Result<()> {
+        self.set_lines(false, bit)?;
+        self.set_lines(true, bit)?;
+        self.set_lines(false, bit)
+    }
+
+    fn read_bit(&self) -> Result<bool> {
+        self.set_lines(false, true)?;
+        self.set_lines(true, true)?;
+        let bit = self.sda_is_high()?;
+        self.set_lines(false, true)?;
+        Ok(bit)
+    }
+}
+
+impl<'a> i2c::I2c for I2c<'a> {
+    fn start(&mut self) -> Result<()> {
+        // SDA goes first, in case SCL is already HIGH during a repeated START.
+        self.set_lines(false, true)?;
+        self.set_lines(true, true)?;
+        self.set_lines(true, false)?;
+        self.set_lines(false, false)
+    }
+
+    fn stop(&mut self) -> Result<()> {
+        self.set_lines(false, false)?;
+        self.set_lines(true, false)?;
+        self.set_lines(true, true)
+    }
+
+    fn write_byte(&mut self, byte: u8) -> Result<bool> {
+        for i in (0..8).rev() {
+            self.write_bit(byte & (1 << i) != 0)?;
+        }
+        // The target pulls SDA LOW to acknowledge.
+        Ok(!self.read_bit()?)
+    }
+
+    fn read_byte(&mut self, ack: bool) -> Result<u8> {
+        let mut byte = 0;
+        for i in (0..8).rev() {
+            if self.read_bit()? {
+                byte |= 1 << i;
+            }
+        }
+        self.write_bit(!ack)?;
+        Ok(byte)
+    }
+}
+
+/// 64-bit ROM code of a 1-Wire device: family code, 48-bit serial number and CRC, in
+/// the order they are sent on the bus.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Rom(pub [u8; 8]);
+
+impl Rom {
+    /// Device family, e.g. 0x28 for the DS18B20.
+    pub fn family(&self) -> u8 {
+        self.0[0]
+    }
+
+    pub fn serial(&self) -> u64 {
+        self.0[1..7]
+            .iter()
+            .rev()
+            .fold(0, |serial, &byte| serial << 8 | u64::from(byte))
+    }
+
+    /// Whether the CRC byte matches the rest of the code.
+    pub fn is_valid(&self) -> bool {
+        one_wire_crc(&self.0) == 0
+    }
+}
+
+/// Formats as the Linux `w1` subsystem names devices, e.g. `28-0000054c2ec2`.
+impl fmt::Display for Rom {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:02x}-{:012x}", self.family(), self.serial())
+    }
+}
+
+/// The Dallas/Maxim CRC-8 protecting ROM codes and e.g. the DS18B20 scratchpad.
+///
+/// Data followed by its CRC byte gives 0.
+pub fn one_wire_crc(data: &[u8]) -> u8 {
+    data.iter().fold(0, |mut crc, &byte| {
+        crc ^= byte;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                crc >> 1 ^ 0x8c
+            } else {
+                crc >> 1
+            };
+        }
+        crc
+    })
+}
+
+/// 1-Wire master at standard speed, for sensors such as the DS18B20 and for iButtons.
+///
+/// Devices pull the bus LOW against a pull-up resistor (usually 4.7 kOhm), and it has
+/// to be sampled a few microseconds after the master starts a slot. The chips'
+/// outputs can't release a line by themselves, and a pin's direction only changes
+/// between USB transfers, far too slowly for that. So the bus takes two pins:
+/// `drive` pulls it LOW through a Schottky diode (cathode at the pin) or an
+/// open-drain buffer, and `sense` is connected to it directly.
+///
+/// The chip times the slots in synchronous bitbang mode, a few of them per USB round
+/// trip, so reading a ROM code takes some tens of milliseconds. Other pins are
+/// made inputs.
+pub struct OneWire<'a> {
+    device: &'a Device,
+    drive: u8,
+    sense: u8,
+    sample_rate: u32,
+}
+
+impl<'a> OneWire<'a> {
+    /// Enter synchronous bitbang mode with `drive` as the only output, and release
+    /// the bus.
+    pub fn new(device: &'a Device, drive: u8, sense: u8) -> Result<OneWire<'a>> {
+        if drive >= 8 || sense >= 8 {
+            return Err(Error::InvalidArgument("Bitbang pin must be below 8"));
+        }
+        if drive == sense {
+            return Err(Error::InvalidArgument(
+                "1-Wire drive and sense pins must be distinct",
+            ));
+        }
+
+        device.set_bitmode_config(BitModeConfig::SyncBB {
+            direction: 1 << drive,
+        })?;
+        let one_wire = OneWire {
+            device,
+            drive,
+            sense,
+            sample_rate: device.set_bitbang_sample_rate(ONE_WIRE_SAMPLE_RATE)?,
+        };
+        // Slots must be resolved finely enough, and each must fit in one round.
+        if one_wire.ticks(ONE_WIRE_WRITE_ONE_LOW) == 0
+            || one_wire.ticks(ONE_WIRE_READ_SAMPLE) <= one_wire.ticks(ONE_WIRE_WRITE_ONE_LOW)
+            || one_wire.ticks(ONE_WIRE_SLOT) >= SYNC_BB_CHUNK
+        {
+            return Err(Error::InvalidArgument(
+                "The chip can't time 1-Wire slots at its bitbang rate",
+            ));
+        }
+
+        device.sync_bitbang_exchange(&[one_wire.released()])?;
+        Ok(one_wire)
+    }
+
+    /// Rate at which the chip clocks the bus, see
+    /// [`set_bitbang_sample_rate`][Device::set_bitbang_sample_rate].
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Reset the bus, returning whether any device answered with a presence pulse.
+    pub fn reset(&self) -> Result<bool> {
+        // A round trip in the middle of the LOW pulse only makes it longer.
+        let low = vec![self.pulled(); self.ticks(ONE_WIRE_RESET_LOW)];
+        self.device.sync_bitbang_exchange(&low)?;
+
+        let sample = self.ticks(ONE_WIRE_PRESENCE_SAMPLE);
+        let levels = self
+            .device
+            .sync_bitbang_exchange(&vec![self.released(); sample + 1])?;
+        thread::sleep(ONE_WIRE_RESET_RECOVERY);
+        Ok(!self.is_high(levels[sample - 1]))
+    }
+
+    pub fn write_bit(&self, bit: bool) -> Result<()> {
+        self.slots(&[Some(bit)]).map(|_| ())
+    }
+
+    pub fn read_bit(&self) -> Result<bool> {
+        Ok(self.slots(&[None])?[0])
+    }
+
+    /// Write `data`, LSB first.
+    pub fn write(&self, data: &[u8]) -> Result<()> {
+        let slots: Vec<_> = data
+            .iter()
+            .flat_map(|&byte| (0..8).map(move |i| Some(byte & (1 << i) != 0)))
+            .collect();
+        self.slots(&slots).map(|_| ())
+    }
+
+    /// Fill `data` with bytes read from the bus, LSB first.
+    pub fn read(&self, data: &mut [u8]) -> Result<()> {
+        let bits = self.slots(&vec![None; data.len() * 8])?;
+        for (byte, bits) in data.iter_mut().zip(bits.chunks(8)) {
+            *byte = bits
+                .iter()
+                .enumerate()
+                .fold(0, |byte, (i, &bit)| byte | u8::from(bit) << i);
+        }
+        Ok(())
+    }
+
+    /// Read the ROM code of the only device on the bus.
+    ///
+    /// With several devices their codes collide, which the CRC check catches; use
+    /// [`search`][OneWire::search] instead.
+    pub fn read_rom(&self) -> Result<Rom> {
+        self.reset_present()?;
+        self.write(&[READ_ROM])?;
+        let mut rom = Rom([0; 8]);
+        self.read(&mut rom.0)?;
+        if !rom.is_valid() {
+            return Err(invalid_data(format!(
+                "1-Wire ROM code {} fails its CRC",
+                rom
+            )));
+        }
+        Ok(rom)
+    }
+
+    /// Reset the bus and address the device with ROM code `rom`, which then takes the
+    /// function command written next.
+    pub fn select(&self, rom: &Rom) -> Result<()> {
+        self.reset_present()?;
+        let mut command = vec![MATCH_ROM];
+        command.extend_from_slice(&rom.0);
+        self.write(&command)
+    }
+
+    /// Reset the bus and address every device on it at once, e.g. to start a
+    /// temperature conversion on all sensors.
+    pub fn skip_rom(&self) -> Result<()> {
+        self.reset_present()?;
+        self.write(&[SKIP_ROM])
+    }
+
+    /// Find the ROM codes of all devices on the bus.
+    pub fn search(&self) -> Result<Vec<Rom>> {
+        self.search_with(SEARCH_ROM)
+    }
+
+    /// Find the ROM codes of the devices whose alarm condition is set, e.g. DS18B20
+    /// sensors outside their temperature limits.
+    pub fn alarm_search(&self) -> Result<Vec<Rom>> {
+        self.search_with(ALARM_SEARCH)
+    }
+
+    // The search of Maxim's application note 187: every pass walks the bits of one ROM
+    // code, taking the 1 branch at the last discrepancy where the previous pass took 0.
+    fn search_with(&self, command: u8) -> Result<Vec<Rom>> {
+        let mut roms: Vec<Rom> = Vec::new();
+        let mut last_discrepancy = None;
+        loop {
+            if !self.reset()? {
+                return Ok(roms);
+            }
+            self.write(&[command])?;
+
+            let mut rom = Rom([0; 8]);
+            let mut discrepancy = None;
+            for i in 0..64 {
+                // Every remaining device sends its bit, then the bit's complement.
+                let bits = self.slots(&[None, None])?;
+                let bit = match (bits[0], bits[1]) {
+                    // Nobody answered: no device takes part in this search.
+                    (true, true) if i == 0 => return Ok(roms),
+                    (true, true) => {
+                        return Err(invalid_data(
+                            "1-Wire devices stopped answering during a search".to_string(),
+                        ))
+                    }
+                    (false, false) => {
+                        let bit = match (last_discrepancy, roms.last()) {
+                            (Some(last), Some(previous)) if i < last => {
+                                previous.0[i / 8] & (1 << (i % 8)) != 0
+                            }
+                            (Some(last), _) => i == last,
+                            (None, _) => false,
+                        };
+                        if !bit {
+                            discrepancy = Some(i);
+                        }
+                        bit
+                    }
+                    (bit, _) => bit,
+                };
+                if bit {
+                    rom.0[i / 8] |= 1 << (i % 8);
+                }
+                self.write_bit(bit)?;
+            }
+
+            if !rom.is_valid() {
+                return Err(invalid_data(format!(
+                    "1-Wire ROM code {} fails its CRC",
+                    rom
+                )));
+            }
+            roms.push(rom);
+            match discrepancy {
+                Some(_) => last_discrepancy = discrepancy,
+                None => return Ok(roms),
+            }
+        }
+    }
+
+    fn reset_present(&self) -> Result<()> {
+        if self.reset()? {
+            Ok(())
+        } else {
+            Err(Error::NoPresence)
+        }
+    }
+
+    // Run write (`Some(bit)`) and read (`None`) slots, returning the bits read.
+    fn slots(&self, slots: &[Option<bool>]) -> Result<Vec<bool>> {
+        let mut read = Vec::new();
+        let mut states = Vec::new();
+        let mut samples = Vec::new();
+        for &slot in slots {
+            let low = match slot {
+                Some(true) | None => self.ticks(ONE_WIRE_WRITE_ONE_LOW),
+                Some(false) => self.ticks(ONE_WIRE_WRITE_ZERO_LOW),
+            };
+            let len = self.ticks(ONE_WIRE_SLOT).max(low + 1);
+            // Rounds hold whole slots, since a pause while the bus is LOW would stretch
+            // the slot. The exchange adds a byte to each.
+            if states.len() + len >= SYNC_BB_CHUNK {
+                self.exchange(&mut states, &mut samples, &mut read)?;
+            }
+            if slot.is_none() {
+                samples.push(states.len() + self.ticks(ONE_WIRE_READ_SAMPLE) - 1);
+            }
+            states.extend(iter::repeat_n(self.pulled(), low));
+            states.extend(iter::repeat_n(self.released(), len - low));
+        }
+        self.exchange(&mut states, &mut samples, &mut read)?;
+        Ok(read)
+    }
+
+    // Clock out `states`, appending the levels sampled at `samples` to `read`.
+    fn exchange(
+        &self,
+        states: &mut Vec<u8>,
+        samples: &mut Vec<usize>,
+        read: &mut Vec<bool>,
+    ) -> Result<()> {
+        let levels = self.device.sync_bitbang_exchange(states)?;
+        read.extend(samples.iter().map(|&i| self.is_high(levels[i])));
+        states.clear();
+        samples.clear();
+        Ok(())
+    }
+
+    // Number of bitbang clocks closest to `us` microseconds.
+    fn ticks(&self, us: u32) -> usize {
+        ((u64::from(us) * u64::from(self.sample_rate) + 500_000) / 1_000_000) as usize
+    }
+
+    fn pulled(&self) -> u8 {
+        0
+    }
+
+    fn released(&self) -> u8 {
+        1 << self.drive
+    }
+
+    fn is_high(&self, levels: u8) -> bool {
+        levels & (1 << self.sense) != 0
+    }
+}
+
+fn invalid_data(message: String) -> Error {
+    Error::Io(io::Error::new(io::ErrorKind::InvalidData, message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The ROM code worked through in Maxim application note 27.
+    const AN27_ROM: [u8; 8] = [0x02, 0x1c, 0xb8, 0x01, 0x00, 0x00, 0x00, 0xa2];
+
+    #[test]
+    fn crc_reference_vectors() {
+        assert_eq!(one_wire_crc(b""), 0);
+        // The CRC-8/MAXIM check value.
+        assert_eq!(one_wire_crc(b"123456789"), 0xa1);
+        assert_eq!(one_wire_crc(&AN27_ROM[..7]), 0xa2);
+    }
+
+    #[test]
+    fn crc_of_data_and_crc_is_zero() {
+        assert_eq!(one_wire_crc(&AN27_ROM), 0);
+        assert_eq!(one_wire_crc(b"123456789\xa1"), 0);
+    }
+
+    #[test]
+    fn rom_validity() {
+        assert!(Rom(AN27_ROM).is_valid());
+        for byte in 0..8 {
+            for bit in 0..8 {
+                let mut code = AN27_ROM;
+                code[byte] ^= 1 << bit;
+                assert!(!Rom(code).is_valid(), "bit {} of byte {}", bit, byte);
+            }
+        }
+    }
+
+    #[test]
+    fn rom_fields() {
+        let rom = Rom([0x28, 0xc2, 0x2e, 0x4c, 0x05, 0x00, 0x00, 0x72]);
+        assert!(rom.is_valid());
+        assert_eq!(rom.family(), 0x28);
+        assert_eq!(rom.serial(), 0x0000_054c_2ec2);
+        assert_eq!(rom.to_string(), "28-0000054c2ec2");
+    }
+}
@@ -0,0 +1,92 @@
+//! Running slow device operations on tokio's blocking thread pool.
+//!
+//! Opening a device, EEPROM access and resets block for up to seconds, which would
+//! stall an async executor. The functions here run them through
+//! [`spawn_blocking`][tokio::task::spawn_blocking] and return futures resolving to
+//! their results. A [`Device`] is moved into the operation and handed back along with
+//! the result, whether it succeeded or not.
+//!
+//! They must be called from within a tokio runtime. A panic in the operation is
+//! propagated to the task awaiting it.
+
+use std::future::Future;
+use std::panic;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use tokio::task::{self, JoinHandle};
+
+use {Device, DeviceInfo, Interface, Result};
+
+/// Future of an operation running on the blocking thread pool.
+pub struct Blocking<T> {
+    handle: JoinHandle<T>,
+}
+
+impl<T> Future for Blocking<T> {
+    type Output = T;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<T> {
+        match Pin::new(&mut self.handle).poll(cx) {
+            Poll::Pending => Poll::Pending,
+            Poll::Ready(Ok(value)) => Poll::Ready(value),
+            Poll::Ready(Err(e)) => match e.try_into_panic() {
+                Ok(payload) => panic::resume_unwind(payload),
+                // The runtime shut down before the operation started.
+                Err(e) => panic!("blocking device operation didn't run: {}", e),
+            },
+        }
+    }
+}
+
+fn spawn<F, T>(f: F) -> Blocking<T>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    Blocking {
+        handle: task::spawn_blocking(f),
+    }
+}
+
+/// Run `f` on `device`, for operations not wrapped here.
+pub fn run<F, T>(mut device: Device, f: F) -> Blocking<(Device, Result<T>)>
+where
+    F: FnOnce(&mut Device) -> Result<T> + Send + 'static,
+    T: Send + 'static,
+{
+    spawn(move || {
+        let result = f(&mut device);
+        (device, result)
+    })
+}
+
+/// See [`Device::open`].
+pub fn open(info: DeviceInfo, interface: Interface) -> Blocking<Result<Device>> {
+    spawn(move || Device::open(&info, interface))
+}
+
+/// See [`Device::from_vid_pid`].
+pub fn from_vid_pid(interface: Interface, vid: u16, pid: u16) -> Blocking<Result<Device>> {
+    spawn(move || Device::from_vid_pid(interface, vid, pid))
+}
+
+/// See [`Device::load_eeprom_data`].
+pub fn load_eeprom_data(device: Device) -> Blocking<(Device, Result<()>)> {
+    run(device, |device| device.load_eeprom_data())
+}
+
+/// See [`Device::build_eeprom`].
+pub fn build_eeprom(device: Device) -> Blocking<(Device, Result<usize>)> {
+    run(device, |device| device.build_eeprom())
+}
+
+/// See [`Device::write_eeprom`].
+pub fn write_eeprom(device: Device) -> Blocking<(Device, Result<()>)> {
+    run(device, |device| device.write_eeprom())
+}
+
+/// See [`Device::usb_reset`].
+pub fn usb_reset(device: Device) -> Blocking<(Device, Result<()>)> {
+    run(device, |device| device.usb_reset())
+}
@@ -0,0 +1,157 @@
+//! Buffered reading, for line-oriented protocols such as SCPI or NMEA over the UART,
+//! and for parsers that pull one byte at a time.
+
+use std::io::{self, BufRead, Read, Write};
+
+use {Device, Result};
+
+const DEFAULT_CAPACITY: usize = 4096;
+
+/// A device with a read buffer, implementing [`BufRead`] so that
+/// [`read_line`][BufRead::read_line], [`read_until`][BufRead::read_until] and
+/// [`lines`][BufRead::lines] work on the data it receives.
+///
+/// Unlike [`Device::read_data`], reads wait for data to arrive. They fail with
+/// [`io::ErrorKind::TimedOut`] if the chip sends nothing for longer than the read
+/// timeout (see [`Device::set_timeouts`]); since a UART stream has no end, reads never
+/// report end of file. Writes go straight to the device, so commands can be sent
+/// through the same handle.
+pub struct BufferedDevice {
+    device: Device,
+    buf: Box<[u8]>,
+    pos: usize,
+    filled: usize,
+}
+
+impl BufferedDevice {
+    pub fn new(device: Device) -> BufferedDevice {
+        BufferedDevice::with_capacity(DEFAULT_CAPACITY, device)
+    }
+
+    pub fn with_capacity(capacity: usize, device: Device) -> BufferedDevice {
+        BufferedDevice {
+            device,
+            buf: vec![0; capacity.max(1)].into_boxed_slice(),
+            pos: 0,
+            filled: 0,
+        }
+    }
+
+    pub fn get_ref(&self) -> &Device {
+        &self.device
+    }
+
+    /// Reading from the device directly skips any data already buffered.
+    pub fn get_mut(&mut self) -> &mut Device {
+        &mut self.device
+    }
+
+    /// Data received but not read yet.
+    pub fn buffer(&self) -> &[u8] {
+        &self.buf[self.pos..self.filled]
+    }
+
+    /// Drop any buffered data, e.g. after purging the device's buffers.
+    pub fn discard_buffer(&mut self) {
+        self.pos = 0;
+        self.filled = 0;
+    }
+
+    /// Unwrap the device, losing any buffered data.
+    pub fn into_inner(self) -> Device {
+        self.device
+    }
+}
+
+impl Read for BufferedDevice {
+    fn read(&mut self, data: &mut [u8]) -> io::Result<usize> {
+        if data.is_empty() {
+            return Ok(0);
+        }
+        // Large reads needn't be copied through the buffer.
+        if self.pos == self.filled && data.len() >= self.buf.len() {
+            return self.device.read_some(data).map_err(io::Error::from);
+        }
+        let n = {
+            let available = self.fill_buf()?;
+            let n = available.len().min(data.len());
+            data[..n].copy_from_slice(&available[..n]);
+            n
+        };
+        self.consume(n);
+        Ok(n)
+    }
+}
+
+impl BufRead for BufferedDevice {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if self.pos == self.filled {
+            self.filled = 0;
+            self.pos = 0;
+            self.filled = self.device.read_some(&mut self.buf)?;
+        }
+        Ok(&self.buf[self.pos..self.filled])
+    }
+
+    fn consume(&mut self, amount: usize) {
+        self.pos = (self.pos + amount).min(self.filled);
+    }
+}
+
+impl Write for BufferedDevice {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        self.device.write_data(data).map_err(io::Error::from)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Device {
+    /// Iterate over incoming bytes, like [`Read::bytes`]
+    ///
+    /// Bytes are read a chunk at a time (see
+    /// [`set_read_chunk_size`][Device::set_read_chunk_size]); those fetched but not
+    /// consumed yet are lost when the iterator is dropped. Each byte waits for data to
+    /// arrive; if none does within the read timeout (see [`Device::set_timeouts`]), the
+    /// iterator yields a timeout error, after which it can be polled again. It never
+    /// ends by itself.
+    pub fn bytes(&self) -> Bytes<'_> {
+        let size = self
+            .config()
+            .read_chunk_size
+            .map_or(DEFAULT_CAPACITY, |s| s as usize);
+        Bytes {
+            device: self,
+            buf: vec![0; size.max(1)],
+            pos: 0,
+            filled: 0,
+        }
+    }
+}
+
+/// Iterator over incoming bytes, created by [`Device::bytes`].
+pub struct Bytes<'a> {
+    device: &'a Device,
+    buf: Vec<u8>,
+    pos: usize,
+    filled: usize,
+}
+
+impl<'a> Iterator for Bytes<'a> {
+    type Item = Result<u8>;
+
+    fn next(&mut self) -> Option<Result<u8>> {
+        if self.pos == self.filled {
+            self.pos = 0;
+            self.filled = 0;
+            match self.device.read_some(&mut self.buf) {
+                Ok(read) => self.filled = read,
+                Err(e) => return Some(Err(e)),
+            }
+        }
+        self.pos += 1;
+        Some(Ok(self.buf[self.pos - 1]))
+    }
+}
@@ -0,0 +1,49 @@
+//! Stopping long-running operations from another thread, e.g. a Ctrl-C handler.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use Device;
+
+/// A flag shared between a device and whoever may want to stop its operations.
+///
+/// Clones share the flag. Once cancelled, every read and write of a device using the
+/// token fails with [`Error::Cancelled`][::error::Error::Cancelled], so loops built on
+/// them (e.g. [`read_exact`][Device::read_exact] waiting for data, or a stream of
+/// writes) end at the next transfer instead of waiting out USB timeouts. A transfer
+/// already under way still runs to completion.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    pub fn new() -> CancellationToken {
+        CancellationToken::default()
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Let operations run again, e.g. to reuse the device after a cancelled command.
+    pub fn reset(&self) {
+        self.cancelled.store(false, Ordering::SeqCst);
+    }
+}
+
+impl Device {
+    /// Make reads and writes fail once `token` is cancelled, replacing any token set
+    /// before; `None` removes it.
+    pub fn set_cancellation_token(&self, token: Option<CancellationToken>) {
+        *self.lock_cancel() = token;
+    }
+
+    pub fn cancellation_token(&self) -> Option<CancellationToken> {
+        self.lock_cancel().clone()
+    }
+}
@@ -0,0 +1,66 @@
+//! What the attached chip can do, derived from its family.
+
+use {ChipType, Device, Interface};
+
+/// Kind of configuration memory a chip family uses.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum EepromType {
+    /// An optional external 93C46/56/66 EEPROM.
+    External,
+    /// A 128 byte EEPROM inside the chip (FT232R).
+    Internal,
+    /// 2 KiB of multiple-time programmable memory inside the chip (FT-X).
+    Mtp,
+}
+
+/// Features of one interface of a chip, see [`Device::capabilities`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Capabilities {
+    pub mpsse: bool,
+    pub cbus_bitbang: bool,
+    /// Synchronous 245 FIFO mode.
+    pub sync_fifo: bool,
+    pub max_baudrate: u32,
+    /// Number of interfaces of the chip.
+    pub interfaces: u8,
+    pub eeprom: EepromType,
+}
+
+impl Capabilities {
+    /// Capabilities of `interface` of a `chip`; `Interface::Any` stands for A.
+    pub fn of(chip: ChipType, interface: Interface) -> Capabilities {
+        let first = matches!(interface, Interface::Any | Interface::A);
+        let first_two = first || interface == Interface::B;
+        Capabilities {
+            mpsse: match chip {
+                ChipType::FT2232C | ChipType::FT232H => first,
+                ChipType::FT2232H | ChipType::FT4232H => first_two,
+                _ => false,
+            },
+            cbus_bitbang: matches!(chip, ChipType::FT232R | ChipType::FT232H | ChipType::FTX),
+            sync_fifo: match chip {
+                ChipType::FT2232H | ChipType::FT232H => first,
+                _ => false,
+            },
+            max_baudrate: if chip.is_hi_speed() {
+                12_000_000
+            } else {
+                3_000_000
+            },
+            interfaces: chip.interface_count(),
+            eeprom: match chip {
+                ChipType::FT232R => EepromType::Internal,
+                ChipType::FTX => EepromType::Mtp,
+                _ => EepromType::External,
+            },
+        }
+    }
+}
+
+impl Device {
+    /// Capabilities of the open interface, or `None` if the chip wasn't identified.
+    pub fn capabilities(&self) -> Option<Capabilities> {
+        self.chip_type()
+            .map(|chip| Capabilities::of(chip, self.interface()))
+    }
+}
@@ -0,0 +1,178 @@
+//! Recording the data a device reads and writes.
+//!
+//! A capture is a text file with one transfer per line:
+//!
+//! ```text
+//! # safe-ftdi capture 1
+//! 1520 W 8b86010080000b
+//! 1893 R fa
+//! ```
+//!
+//! Each line holds the time since the capture started in microseconds, `W` for data
+//! written to the chip or `R` for data read from it, and the payload in hex. Lines
+//! starting with `#` are comments. Reads that return no data aren't recorded.
+//!
+//! To play a capture back against the code that made it, load it into a
+//! [`MockDevice`][::mock::MockDevice] with
+//! [`open_capture`][::mock::MockDevice::open_capture].
+
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufWriter, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use error::Error;
+use {Device, Result};
+
+const HEADER: &str = "# safe-ftdi capture 1";
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Direction {
+    /// From the chip to the host.
+    Read,
+    /// From the host to the chip.
+    Write,
+}
+
+/// One transfer in a capture.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Record {
+    /// Time since the capture started.
+    pub time: Duration,
+    pub direction: Direction,
+    pub data: Vec<u8>,
+}
+
+impl Record {
+    /// Parse a line of a capture; `None` for comments and blank lines.
+    pub fn parse(line: &str) -> Result<Option<Record>> {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            return Ok(None);
+        }
+
+        let mut fields = line.split_whitespace();
+        let time = fields.next().and_then(|t| t.parse().ok());
+        let direction = match fields.next() {
+            Some("R") => Some(Direction::Read),
+            Some("W") => Some(Direction::Write),
+            _ => None,
+        };
+        let data = fields.next().and_then(parse_hex);
+        match (time, direction, data, fields.next()) {
+            (Some(time), Some(direction), Some(data), None) => Ok(Some(Record {
+                time: Duration::from_micros(time),
+                direction,
+                data,
+            })),
+            _ => Err(invalid_data(format!("Malformed capture line '{}'", line))),
+        }
+    }
+}
+
+impl fmt::Display for Record {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let direction = match self.direction {
+            Direction::Read => 'R',
+            Direction::Write => 'W',
+        };
+        write!(f, "{} {} ", self.time.as_micros(), direction)?;
+        for byte in &self.data {
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+/// Read all records of a capture.
+pub fn read_capture<R: BufRead>(reader: R) -> Result<Vec<Record>> {
+    let mut records = Vec::new();
+    for line in reader.lines() {
+        records.extend(Record::parse(&line?)?);
+    }
+    Ok(records)
+}
+
+/// Writes transfers to a capture as they happen, see [`Device::start_capture`].
+pub struct Recorder {
+    out: Box<dyn Write + Send>,
+    start: Instant,
+    // First write failure, reported when the capture stops rather than failing the
+    // transfer that was being recorded.
+    error: Option<io::Error>,
+}
+
+impl Recorder {
+    pub fn new<W: Write + Send + 'static>(out: W) -> Result<Recorder> {
+        let mut out: Box<dyn Write + Send> = Box::new(out);
+        writeln!(out, "{}", HEADER)?;
+        Ok(Recorder {
+            out,
+            start: Instant::now(),
+            error: None,
+        })
+    }
+
+    pub fn record(&mut self, direction: Direction, data: &[u8]) {
+        if data.is_empty() || self.error.is_some() {
+            return;
+        }
+        let record = Record {
+            time: self.start.elapsed(),
+            direction,
+            data: data.to_vec(),
+        };
+        if let Err(e) = writeln!(self.out, "{}", record) {
+            self.error = Some(e);
+        }
+    }
+
+    /// Flush the capture, reporting any failure to write it.
+    pub fn finish(mut self) -> Result<()> {
+        match self.error.take() {
+            Some(e) => Err(e.into()),
+            None => Ok(self.out.flush()?),
+        }
+    }
+}
+
+impl Device {
+    /// Record every read and write through this handle to `out`, replacing any capture
+    /// in progress.
+    pub fn start_capture<W: Write + Send + 'static>(&self, out: W) -> Result<()> {
+        let previous = self.lock_capture().replace(Recorder::new(out)?);
+        previous.map_or(Ok(()), Recorder::finish)
+    }
+
+    /// Record to a new file at `path`, see [`start_capture`][Device::start_capture].
+    pub fn capture_to_file<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        self.start_capture(BufWriter::new(File::create(path)?))
+    }
+
+    /// Stop recording, flushing the capture.
+    ///
+    /// Fails if writing any part of the capture failed.
+    pub fn stop_capture(&self) -> Result<()> {
+        let recorder = self.lock_capture().take();
+        recorder.map_or(Ok(()), Recorder::finish)
+    }
+
+    pub fn is_capturing(&self) -> bool {
+        self.lock_capture().is_some()
+    }
+}
+
+fn parse_hex(s: &str) -> Option<Vec<u8>> {
+    if !s.len().is_multiple_of(2) || !s.is_ascii() {
+        return None;
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&s[i..i + 2], 16).ok())
+        .collect()
+}
+
+fn invalid_data(message: String) -> Error {
+    Error::Io(io::Error::new(io::ErrorKind::InvalidData, message))
+}
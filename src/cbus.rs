@@ -0,0 +1,56 @@
+//! Using the spare CBUS pins of FT232R and FT-X chips as slow GPIO.
+//!
+//! CBUS bitbang mode leaves the UART running, so e.g. a reset line or LED can be
+//! driven next to a serial connection. The pins used must be configured as
+//! `IoMode` in the EEPROM first, see [`Device::set_cbus_function`] and
+//! [`Device::set_cbusx_function`].
+
+use error::Error;
+use {BitMode, BitModeConfig, Device, Result};
+
+/// Number of CBUS pins usable in CBUS bitbang mode.
+const CBUS_BITBANG_PINS: u8 = 4;
+
+impl Device {
+    /// Enable CBUS bitbang mode; a HIGH bit in `direction` makes CBUSn an output.
+    ///
+    /// Outputs start out LOW.
+    pub fn enter_cbus_mode(&self, direction: u8) -> Result<()> {
+        if self.capabilities().is_some_and(|c| !c.cbus_bitbang) {
+            return Err(Error::InvalidArgument("The chip has no CBUS bitbang mode"));
+        }
+        if direction >> CBUS_BITBANG_PINS != 0 {
+            return Err(Error::InvalidArgument(
+                "Only CBUS0..3 can be used for bitbang",
+            ));
+        }
+
+        self.set_bitmode_config(BitModeConfig::Cbus {
+            direction,
+            value: 0,
+        })
+    }
+
+    /// Drive the CBUS outputs to the low nibble of `levels`.
+    pub fn cbus_write(&self, levels: u8) -> Result<()> {
+        match self.bitmode() {
+            Some(BitModeConfig::Cbus { direction, .. }) => {
+                self.set_bitmode_config(BitModeConfig::Cbus {
+                    direction,
+                    value: levels & 0x0f,
+                })
+            }
+            _ => Err(Error::InvalidArgument("CBUS bitbang mode isn't enabled")),
+        }
+    }
+
+    /// Read the level of CBUS0..3 into the low nibble.
+    pub fn cbus_read(&self) -> Result<u8> {
+        Ok(self.read_pins()? & 0x0f)
+    }
+
+    /// Return the CBUS pins to their EEPROM-configured functions.
+    pub fn exit_cbus_mode(&self) -> Result<()> {
+        self.set_bitmode(0, BitMode::Reset)
+    }
+}
@@ -0,0 +1,1595 @@
+//! Higher level access to the chip EEPROM, built on top of the decode/build
+//! cycle libftdi provides.
+
+use ftdic;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::os::raw;
+#[cfg(feature = "serde")]
+use toml;
+
+use error::{Error, LibFtdiError};
+use {ftdi_eeprom_value, ChipType, Device, Interface, Result};
+
+pub mod conf;
+
+impl Device {
+    /// Size in bytes of the EEPROM area not used by the configuration and strings.
+    ///
+    /// This is the most data that [`write_eeprom_user_area`][Device::write_eeprom_user_area]
+    /// will accept.
+    pub fn eeprom_user_area_size(&mut self) -> Result<usize> {
+        if !self.eeprom_read {
+            self.load_eeprom_data()?;
+        }
+
+        self.build_eeprom()
+    }
+
+    /// Read `data.len()` bytes from the start of the EEPROM user area, which follows
+    /// the string descriptors.
+    pub fn read_eeprom_user_area(&mut self, data: &mut [u8]) -> Result<()> {
+        // Always re-read, so the image reflects the chip rather than a previous build.
+        self.load_eeprom_data()?;
+
+        let image = self.get_eeprom_buf()?;
+        let addr = eeprom_user_area_offset(self.chip_type(), &image)?;
+
+        if addr + data.len() > image.len() {
+            return Err(Error::InvalidArgument("User area read exceeds EEPROM size"));
+        }
+
+        data.copy_from_slice(&image[addr..addr + data.len()]);
+        Ok(())
+    }
+
+    /// Store `data` in the EEPROM user area, then rebuild the EEPROM image and flash it.
+    ///
+    /// The rest of the configuration is preserved as decoded from the chip.
+    pub fn write_eeprom_user_area(&mut self, data: &[u8]) -> Result<()> {
+        if data.len() > self.eeprom_user_area_size()? {
+            return Err(Error::InvalidArgument(
+                "User data does not fit in EEPROM user area",
+            ));
+        }
+
+        // libftdi copies the user data to USER_DATA_ADDR, which defaults to 0 and
+        // would overwrite the header, so point it past the strings just built.
+        let addr = eeprom_user_area_offset(self.chip_type(), &self.get_eeprom_buf()?)?;
+        self.set_eeprom_value(ftdi_eeprom_value::USER_DATA_ADDR, addr as i32)?;
+
+        let rc = unsafe {
+            ftdic::ftdi_set_eeprom_user_data(
+                self.context.get_ftdi_context(),
+                data.as_ptr() as *const raw::c_char,
+                data.len() as raw::c_int,
+            )
+        };
+        self.context.check_ftdi_error(rc)?;
+
+        let built = self.build_eeprom();
+
+        // libftdi only keeps a pointer to the user data, so forget about it
+        // before `data` goes out of scope.
+        unsafe {
+            ftdic::ftdi_set_eeprom_user_data(
+                self.context.get_ftdi_context(),
+                data.as_ptr() as *const raw::c_char,
+                0,
+            )
+        };
+
+        built?;
+        self.write_eeprom()
+    }
+}
+
+/// Offset of the first string descriptor libftdi writes to the EEPROM.
+fn eeprom_strings_start(chip: Option<ChipType>) -> Result<usize> {
+    match chip {
+        Some(ChipType::AM) | Some(ChipType::BM) => Ok(0x94),
+        Some(ChipType::FT2232C) => Ok(0x96),
+        Some(ChipType::FT232R) => Ok(0x98),
+        Some(ChipType::FT2232H) | Some(ChipType::FT4232H) => Ok(0x9a),
+        Some(ChipType::FT232H) | Some(ChipType::FTX) => Ok(0xa0),
+        None => Err(Error::InvalidArgument(
+            "The EEPROM layout of an unidentified chip is unknown",
+        )),
+    }
+}
+
+/// Offset of the user area in `image`, right after the string descriptors.
+///
+/// libftdi has no getter for it, so it is worked out the way `ftdi_eeprom_build`
+/// lays out the strings: from the chip's first string offset and the descriptor
+/// lengths in the header, wrapping around on 128 byte EEPROMs.
+fn eeprom_user_area_offset(chip: Option<ChipType>, image: &[u8]) -> Result<usize> {
+    if image.len() < 0x80 || !image.len().is_power_of_two() {
+        return Err(Error::InvalidArgument(
+            "EEPROM image has an unexpected size",
+        ));
+    }
+
+    let strings: usize = [0x0f, 0x11, 0x13]
+        .iter()
+        .map(|&i| usize::from(image[i]))
+        .sum();
+    Ok((eeprom_strings_start(chip)? + strings) & (image.len() - 1))
+}
+
+/// Number of CBUS function slots libftdi tracks in the EEPROM (CBUS0–CBUS9).
+const CBUS_PINS: u8 = 10;
+
+/// CBUS pin functions available on the FT232R and FT245R (CBUS0–CBUS4).
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum CbusFunction {
+    TxDen,
+    PwrEn,
+    RxLed,
+    TxLed,
+    TxRxLed,
+    Sleep,
+    Clk48,
+    Clk24,
+    Clk12,
+    Clk6,
+    /// Required for the pin to be usable in [`BitMode::Cbus`][::BitMode::Cbus].
+    IoMode,
+    BitbangWr,
+    BitbangRd,
+}
+
+impl CbusFunction {
+    fn to_raw(self) -> ftdic::ftdi_cbus_func {
+        match self {
+            CbusFunction::TxDen => ftdic::ftdi_cbus_func::CBUS_TXDEN,
+            CbusFunction::PwrEn => ftdic::ftdi_cbus_func::CBUS_PWREN,
+            CbusFunction::RxLed => ftdic::ftdi_cbus_func::CBUS_RXLED,
+            CbusFunction::TxLed => ftdic::ftdi_cbus_func::CBUS_TXLED,
+            CbusFunction::TxRxLed => ftdic::ftdi_cbus_func::CBUS_TXRXLED,
+            CbusFunction::Sleep => ftdic::ftdi_cbus_func::CBUS_SLEEP,
+            CbusFunction::Clk48 => ftdic::ftdi_cbus_func::CBUS_CLK48,
+            CbusFunction::Clk24 => ftdic::ftdi_cbus_func::CBUS_CLK24,
+            CbusFunction::Clk12 => ftdic::ftdi_cbus_func::CBUS_CLK12,
+            CbusFunction::Clk6 => ftdic::ftdi_cbus_func::CBUS_CLK6,
+            CbusFunction::IoMode => ftdic::ftdi_cbus_func::CBUS_IOMODE,
+            CbusFunction::BitbangWr => ftdic::ftdi_cbus_func::CBUS_BB_WR,
+            CbusFunction::BitbangRd => ftdic::ftdi_cbus_func::CBUS_BB_RD,
+        }
+    }
+
+    fn from_raw(raw: ftdic::ftdi_cbus_func) -> Option<CbusFunction> {
+        match raw {
+            ftdic::ftdi_cbus_func::CBUS_TXDEN => Some(CbusFunction::TxDen),
+            ftdic::ftdi_cbus_func::CBUS_PWREN => Some(CbusFunction::PwrEn),
+            ftdic::ftdi_cbus_func::CBUS_RXLED => Some(CbusFunction::RxLed),
+            ftdic::ftdi_cbus_func::CBUS_TXLED => Some(CbusFunction::TxLed),
+            ftdic::ftdi_cbus_func::CBUS_TXRXLED => Some(CbusFunction::TxRxLed),
+            ftdic::ftdi_cbus_func::CBUS_SLEEP => Some(CbusFunction::Sleep),
+            ftdic::ftdi_cbus_func::CBUS_CLK48 => Some(CbusFunction::Clk48),
+            ftdic::ftdi_cbus_func::CBUS_CLK24 => Some(CbusFunction::Clk24),
+            ftdic::ftdi_cbus_func::CBUS_CLK12 => Some(CbusFunction::Clk12),
+            ftdic::ftdi_cbus_func::CBUS_CLK6 => Some(CbusFunction::Clk6),
+            ftdic::ftdi_cbus_func::CBUS_IOMODE => Some(CbusFunction::IoMode),
+            ftdic::ftdi_cbus_func::CBUS_BB_WR => Some(CbusFunction::BitbangWr),
+            ftdic::ftdi_cbus_func::CBUS_BB_RD => Some(CbusFunction::BitbangRd),
+            _ => None,
+        }
+    }
+}
+
+/// CBUS pin functions available on the FT232H (ACBUS0–ACBUS9).
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum CbusHFunction {
+    Tristate,
+    TxLed,
+    RxLed,
+    TxRxLed,
+    PwrEn,
+    Sleep,
+    Drive0,
+    Drive1,
+    /// Required for the pin to be usable in [`BitMode::Cbus`][::BitMode::Cbus].
+    IoMode,
+    TxDen,
+    Clk30,
+    Clk15,
+    Clk7_5,
+}
+
+impl CbusHFunction {
+    fn to_raw(self) -> ftdic::ftdi_cbush_func {
+        match self {
+            CbusHFunction::Tristate => ftdic::ftdi_cbush_func::CBUSH_TRISTATE,
+            CbusHFunction::TxLed => ftdic::ftdi_cbush_func::CBUSH_TXLED,
+            CbusHFunction::RxLed => ftdic::ftdi_cbush_func::CBUSH_RXLED,
+            CbusHFunction::TxRxLed => ftdic::ftdi_cbush_func::CBUSH_TXRXLED,
+            CbusHFunction::PwrEn => ftdic::ftdi_cbush_func::CBUSH_PWREN,
+            CbusHFunction::Sleep => ftdic::ftdi_cbush_func::CBUSH_SLEEP,
+            CbusHFunction::Drive0 => ftdic::ftdi_cbush_func::CBUSH_DRIVE_0,
+            CbusHFunction::Drive1 => ftdic::ftdi_cbush_func::CBUSH_DRIVE1,
+            CbusHFunction::IoMode => ftdic::ftdi_cbush_func::CBUSH_IOMODE,
+            CbusHFunction::TxDen => ftdic::ftdi_cbush_func::CBUSH_TXDEN,
+            CbusHFunction::Clk30 => ftdic::ftdi_cbush_func::CBUSH_CLK30,
+            CbusHFunction::Clk15 => ftdic::ftdi_cbush_func::CBUSH_CLK15,
+            CbusHFunction::Clk7_5 => ftdic::ftdi_cbush_func::CBUSH_CLK7_5,
+        }
+    }
+
+    fn from_raw(raw: ftdic::ftdi_cbush_func) -> Option<CbusHFunction> {
+        match raw {
+            ftdic::ftdi_cbush_func::CBUSH_TRISTATE => Some(CbusHFunction::Tristate),
+            ftdic::ftdi_cbush_func::CBUSH_TXLED => Some(CbusHFunction::TxLed),
+            ftdic::ftdi_cbush_func::CBUSH_RXLED => Some(CbusHFunction::RxLed),
+            ftdic::ftdi_cbush_func::CBUSH_TXRXLED => Some(CbusHFunction::TxRxLed),
+            ftdic::ftdi_cbush_func::CBUSH_PWREN => Some(CbusHFunction::PwrEn),
+            ftdic::ftdi_cbush_func::CBUSH_SLEEP => Some(CbusHFunction::Sleep),
+            ftdic::ftdi_cbush_func::CBUSH_DRIVE_0 => Some(CbusHFunction::Drive0),
+            ftdic::ftdi_cbush_func::CBUSH_DRIVE1 => Some(CbusHFunction::Drive1),
+            ftdic::ftdi_cbush_func::CBUSH_IOMODE => Some(CbusHFunction::IoMode),
+            ftdic::ftdi_cbush_func::CBUSH_TXDEN => Some(CbusHFunction::TxDen),
+            ftdic::ftdi_cbush_func::CBUSH_CLK30 => Some(CbusHFunction::Clk30),
+            ftdic::ftdi_cbush_func::CBUSH_CLK15 => Some(CbusHFunction::Clk15),
+            ftdic::ftdi_cbush_func::CBUSH_CLK7_5 => Some(CbusHFunction::Clk7_5),
+            _ => None,
+        }
+    }
+}
+
+/// CBUS pin functions available on the FT-X series (CBUS0–CBUS3).
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum CbusXFunction {
+    Tristate,
+    TxLed,
+    RxLed,
+    TxRxLed,
+    PwrEn,
+    Sleep,
+    Drive0,
+    Drive1,
+    /// Required for the pin to be usable in [`BitMode::Cbus`][::BitMode::Cbus].
+    IoMode,
+    TxDen,
+    Clk24,
+    Clk12,
+    Clk6,
+    /// Battery charger detected (high when a dedicated charging port is attached).
+    BatDetect,
+    /// Inverted [`BatDetect`][CbusXFunction::BatDetect].
+    BatDetectNeg,
+    I2cTxe,
+    I2cRxf,
+    /// Input sensing VBUS, so a self-powered design can tell when the host goes away.
+    VbusSense,
+    BitbangWr,
+    BitbangRd,
+    /// Toggles on every USB start of frame.
+    TimeStamp,
+    /// High while the device is not in USB suspend.
+    Awake,
+}
+
+impl CbusXFunction {
+    fn to_raw(self) -> ftdic::ftdi_cbusx_func {
+        match self {
+            CbusXFunction::Tristate => ftdic::ftdi_cbusx_func::CBUSX_TRISTATE,
+            CbusXFunction::TxLed => ftdic::ftdi_cbusx_func::CBUSX_TXLED,
+            CbusXFunction::RxLed => ftdic::ftdi_cbusx_func::CBUSX_RXLED,
+            CbusXFunction::TxRxLed => ftdic::ftdi_cbusx_func::CBUSX_TXRXLED,
+            CbusXFunction::PwrEn => ftdic::ftdi_cbusx_func::CBUSX_PWREN,
+            CbusXFunction::Sleep => ftdic::ftdi_cbusx_func::CBUSX_SLEEP,
+            CbusXFunction::Drive0 => ftdic::ftdi_cbusx_func::CBUSX_DRIVE_0,
+            CbusXFunction::Drive1 => ftdic::ftdi_cbusx_func::CBUSX_DRIVE1,
+            CbusXFunction::IoMode => ftdic::ftdi_cbusx_func::CBUSX_IOMODE,
+            CbusXFunction::TxDen => ftdic::ftdi_cbusx_func::CBUSX_TXDEN,
+            CbusXFunction::Clk24 => ftdic::ftdi_cbusx_func::CBUSX_CLK24,
+            CbusXFunction::Clk12 => ftdic::ftdi_cbusx_func::CBUSX_CLK12,
+            CbusXFunction::Clk6 => ftdic::ftdi_cbusx_func::CBUSX_CLK6,
+            CbusXFunction::BatDetect => ftdic::ftdi_cbusx_func::CBUSX_BAT_DETECT,
+            CbusXFunction::BatDetectNeg => ftdic::ftdi_cbusx_func::CBUSX_BAT_DETECT_NEG,
+            CbusXFunction::I2cTxe => ftdic::ftdi_cbusx_func::CBUSX_I2C_TXE,
+            CbusXFunction::I2cRxf => ftdic::ftdi_cbusx_func::CBUSX_I2C_RXF,
+            CbusXFunction::VbusSense => ftdic::ftdi_cbusx_func::CBUSX_VBUS_SENSE,
+            CbusXFunction::BitbangWr => ftdic::ftdi_cbusx_func::CBUSX_BB_WR,
+            CbusXFunction::BitbangRd => ftdic::ftdi_cbusx_func::CBUSX_BB_RD,
+            CbusXFunction::TimeStamp => ftdic::ftdi_cbusx_func::CBUSX_TIME_STAMP,
+            CbusXFunction::Awake => ftdic::ftdi_cbusx_func::CBUSX_AWAKE,
+        }
+    }
+
+    fn from_raw(raw: ftdic::ftdi_cbusx_func) -> Option<CbusXFunction> {
+        match raw {
+            ftdic::ftdi_cbusx_func::CBUSX_TRISTATE => Some(CbusXFunction::Tristate),
+            ftdic::ftdi_cbusx_func::CBUSX_TXLED => Some(CbusXFunction::TxLed),
+            ftdic::ftdi_cbusx_func::CBUSX_RXLED => Some(CbusXFunction::RxLed),
+            ftdic::ftdi_cbusx_func::CBUSX_TXRXLED => Some(CbusXFunction::TxRxLed),
+            ftdic::ftdi_cbusx_func::CBUSX_PWREN => Some(CbusXFunction::PwrEn),
+            ftdic::ftdi_cbusx_func::CBUSX_SLEEP => Some(CbusXFunction::Sleep),
+            ftdic::ftdi_cbusx_func::CBUSX_DRIVE_0 => Some(CbusXFunction::Drive0),
+            ftdic::ftdi_cbusx_func::CBUSX_DRIVE1 => Some(CbusXFunction::Drive1),
+            ftdic::ftdi_cbusx_func::CBUSX_IOMODE => Some(CbusXFunction::IoMode),
+            ftdic::ftdi_cbusx_func::CBUSX_TXDEN => Some(CbusXFunction::TxDen),
+            ftdic::ftdi_cbusx_func::CBUSX_CLK24 => Some(CbusXFunction::Clk24),
+            ftdic::ftdi_cbusx_func::CBUSX_CLK12 => Some(CbusXFunction::Clk12),
+            ftdic::ftdi_cbusx_func::CBUSX_CLK6 => Some(CbusXFunction::Clk6),
+            ftdic::ftdi_cbusx_func::CBUSX_BAT_DETECT => Some(CbusXFunction::BatDetect),
+            ftdic::ftdi_cbusx_func::CBUSX_BAT_DETECT_NEG => Some(CbusXFunction::BatDetectNeg),
+            ftdic::ftdi_cbusx_func::CBUSX_I2C_TXE => Some(CbusXFunction::I2cTxe),
+            ftdic::ftdi_cbusx_func::CBUSX_I2C_RXF => Some(CbusXFunction::I2cRxf),
+            ftdic::ftdi_cbusx_func::CBUSX_VBUS_SENSE => Some(CbusXFunction::VbusSense),
+            ftdic::ftdi_cbusx_func::CBUSX_BB_WR => Some(CbusXFunction::BitbangWr),
+            ftdic::ftdi_cbusx_func::CBUSX_BB_RD => Some(CbusXFunction::BitbangRd),
+            ftdic::ftdi_cbusx_func::CBUSX_TIME_STAMP => Some(CbusXFunction::TimeStamp),
+            ftdic::ftdi_cbusx_func::CBUSX_AWAKE => Some(CbusXFunction::Awake),
+            _ => None,
+        }
+    }
+}
+
+fn cbus_value_name(pin: u8) -> Result<ftdi_eeprom_value> {
+    if pin >= CBUS_PINS {
+        return Err(Error::InvalidArgument("CBUS pin out of range"));
+    }
+
+    Ok(ftdi_eeprom_value(
+        ftdi_eeprom_value::CBUS_FUNCTION_0.0 + raw::c_uint::from(pin),
+    ))
+}
+
+fn unknown_cbus_function() -> Error {
+    Error::LibFtdi(LibFtdiError::new("Unknown CBUS function in EEPROM"))
+}
+
+// CBUS pin configuration. Setters only change the decoded EEPROM structure;
+// call `build_eeprom` and `write_eeprom` to flash the result.
+impl Device {
+    /// Get the FT232R/FT245R function of CBUS pin `pin`.
+    pub fn cbus_function(&mut self, pin: u8) -> Result<CbusFunction> {
+        let raw = self.decoded_eeprom_value(cbus_value_name(pin)?)?;
+        CbusFunction::from_raw(ftdic::ftdi_cbus_func(raw as raw::c_uint))
+            .ok_or_else(unknown_cbus_function)
+    }
+
+    /// Set the FT232R/FT245R function of CBUS pin `pin`.
+    pub fn set_cbus_function(&mut self, pin: u8, function: CbusFunction) -> Result<()> {
+        self.stage_eeprom_value(cbus_value_name(pin)?, function.to_raw().0 as i32)
+    }
+
+    /// Get the FT232H function of ACBUS pin `pin`.
+    pub fn cbush_function(&mut self, pin: u8) -> Result<CbusHFunction> {
+        let raw = self.decoded_eeprom_value(cbus_value_name(pin)?)?;
+        CbusHFunction::from_raw(ftdic::ftdi_cbush_func(raw as raw::c_uint))
+            .ok_or_else(unknown_cbus_function)
+    }
+
+    /// Set the FT232H function of ACBUS pin `pin`.
+    pub fn set_cbush_function(&mut self, pin: u8, function: CbusHFunction) -> Result<()> {
+        self.stage_eeprom_value(cbus_value_name(pin)?, function.to_raw().0 as i32)
+    }
+
+    /// Get the FT-X function of CBUS pin `pin`.
+    pub fn cbusx_function(&mut self, pin: u8) -> Result<CbusXFunction> {
+        let raw = self.decoded_eeprom_value(cbus_value_name(pin)?)?;
+        CbusXFunction::from_raw(ftdic::ftdi_cbusx_func(raw as raw::c_uint))
+            .ok_or_else(unknown_cbus_function)
+    }
+
+    /// Set the FT-X function of CBUS pin `pin`.
+    pub fn set_cbusx_function(&mut self, pin: u8, function: CbusXFunction) -> Result<()> {
+        self.stage_eeprom_value(cbus_value_name(pin)?, function.to_raw().0 as i32)
+    }
+
+    // Read a value from the decoded EEPROM, loading it from the chip first if needed.
+    fn decoded_eeprom_value(&mut self, value_name: ftdi_eeprom_value) -> Result<i32> {
+        if !self.eeprom_read {
+            self.load_eeprom_data()?;
+        }
+
+        self.get_eeprom_value(value_name)
+    }
+
+    // Change a value in the decoded EEPROM without discarding other staged changes.
+    fn stage_eeprom_value(&mut self, value_name: ftdi_eeprom_value, value: i32) -> Result<()> {
+        if !self.eeprom_read {
+            self.load_eeprom_data()?;
+        }
+
+        let rc = unsafe {
+            ftdic::ftdi_set_eeprom_value(self.context.get_ftdi_context(), value_name, value)
+        };
+        self.context.check_ftdi_error(rc)
+    }
+}
+
+/// Number of CBUS pins on FT-X parts that can be assigned a function in the EEPROM.
+const FTX_CBUS_PINS: u8 = 4;
+
+// RS-485 direction control on FT-X parts, which have no dedicated RS-485 setting and
+// drive TXDEN from a CBUS pin instead.
+impl Device {
+    /// Get the CBUS pin driving the RS-485 TXDEN signal of a FT-X part, if any.
+    pub fn ftx_txden_pin(&mut self) -> Result<Option<u8>> {
+        for pin in 0..FTX_CBUS_PINS {
+            if self.cbusx_function(pin)? == CbusXFunction::TxDen {
+                return Ok(Some(pin));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Drive the RS-485 TXDEN signal of a FT-X part from CBUS pin `pin`, or stop driving it
+    /// with `None`.
+    ///
+    /// The chip then asserts TXDEN around every transmitted character, so an RS-485
+    /// transceiver switches direction without the host toggling RTS. Any other pin
+    /// previously assigned TXDEN is tristated.
+    pub fn set_ftx_txden_pin(&mut self, pin: Option<u8>) -> Result<()> {
+        let chip_type = unsafe { (*self.context.get_ftdi_context()).type_ };
+        if chip_type != ftdic::ftdi_chip_type::TYPE_230X {
+            return Err(Error::InvalidArgument(
+                "TXDEN on CBUS requires a FT-X device",
+            ));
+        }
+
+        if pin.iter().any(|&pin| pin >= FTX_CBUS_PINS) {
+            return Err(Error::InvalidArgument("CBUS pin out of range"));
+        }
+
+        for other in 0..FTX_CBUS_PINS {
+            if Some(other) != pin && self.cbusx_function(other)? == CbusXFunction::TxDen {
+                self.set_cbusx_function(other, CbusXFunction::Tristate)?;
+            }
+        }
+
+        match pin {
+            Some(pin) => self.set_cbusx_function(pin, CbusXFunction::TxDen),
+            None => Ok(()),
+        }
+    }
+}
+
+// Channel type and driver encodings from ftdi.h; bindgen does not pick these up.
+const CHANNEL_IS_UART: i32 = 0x0;
+const CHANNEL_IS_FIFO: i32 = 0x1;
+const CHANNEL_IS_OPTO: i32 = 0x2;
+const CHANNEL_IS_CPU: i32 = 0x4;
+const CHANNEL_IS_FT1284: i32 = 0x8;
+const DRIVER_VCP: i32 = 0x8;
+
+/// Hardware personality of a channel on FT2232x/FT4232H/FT232H parts.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum ChannelType {
+    Uart,
+    Fifo,
+    Opto,
+    CpuFifo,
+    FT1284,
+}
+
+/// Host driver a channel asks to be bound to.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum ChannelDriver {
+    D2xx,
+    Vcp,
+}
+
+fn channel_value_name(
+    channel: Interface,
+    a: ftdi_eeprom_value,
+    b: ftdi_eeprom_value,
+    c: Option<ftdi_eeprom_value>,
+    d: Option<ftdi_eeprom_value>,
+) -> Result<ftdi_eeprom_value> {
+    let name = match channel {
+        Interface::A => Some(a),
+        Interface::B => Some(b),
+        Interface::C => c,
+        Interface::D => d,
+        Interface::Any => None,
+    };
+
+    name.ok_or(Error::InvalidArgument("Channel has no such EEPROM setting"))
+}
+
+// Per-channel configuration of multi-channel chips.
+impl Device {
+    /// Get the hardware mode of channel A or B.
+    pub fn channel_type(&mut self, channel: Interface) -> Result<ChannelType> {
+        let name = channel_value_name(
+            channel,
+            ftdi_eeprom_value::CHANNEL_A_TYPE,
+            ftdi_eeprom_value::CHANNEL_B_TYPE,
+            None,
+            None,
+        )?;
+
+        match self.decoded_eeprom_value(name)? {
+            CHANNEL_IS_UART => Ok(ChannelType::Uart),
+            CHANNEL_IS_FIFO => Ok(ChannelType::Fifo),
+            CHANNEL_IS_OPTO => Ok(ChannelType::Opto),
+            CHANNEL_IS_CPU => Ok(ChannelType::CpuFifo),
+            CHANNEL_IS_FT1284 => Ok(ChannelType::FT1284),
+            _ => Err(Error::LibFtdi(LibFtdiError::new(
+                "Unknown channel type in EEPROM",
+            ))),
+        }
+    }
+
+    /// Set the hardware mode of channel A or B. Channels C and D of the FT4232H are always UARTs.
+    pub fn set_channel_type(
+        &mut self,
+        channel: Interface,
+        channel_type: ChannelType,
+    ) -> Result<()> {
+        let name = channel_value_name(
+            channel,
+            ftdi_eeprom_value::CHANNEL_A_TYPE,
+            ftdi_eeprom_value::CHANNEL_B_TYPE,
+            None,
+            None,
+        )?;
+
+        let value = match channel_type {
+            ChannelType::Uart => CHANNEL_IS_UART,
+            ChannelType::Fifo => CHANNEL_IS_FIFO,
+            ChannelType::Opto => CHANNEL_IS_OPTO,
+            ChannelType::CpuFifo => CHANNEL_IS_CPU,
+            ChannelType::FT1284 => CHANNEL_IS_FT1284,
+        };
+
+        self.stage_eeprom_value(name, value)
+    }
+
+    /// Get the host driver requested by a channel.
+    pub fn channel_driver(&mut self, channel: Interface) -> Result<ChannelDriver> {
+        let name = channel_value_name(
+            channel,
+            ftdi_eeprom_value::CHANNEL_A_DRIVER,
+            ftdi_eeprom_value::CHANNEL_B_DRIVER,
+            Some(ftdi_eeprom_value::CHANNEL_C_DRIVER),
+            Some(ftdi_eeprom_value::CHANNEL_D_DRIVER),
+        )?;
+
+        // The FT232H keeps the VCP flag in a different bit, but any non-zero value means VCP.
+        if self.decoded_eeprom_value(name)? != 0 {
+            Ok(ChannelDriver::Vcp)
+        } else {
+            Ok(ChannelDriver::D2xx)
+        }
+    }
+
+    /// Set the host driver requested by a channel.
+    pub fn set_channel_driver(&mut self, channel: Interface, driver: ChannelDriver) -> Result<()> {
+        let name = channel_value_name(
+            channel,
+            ftdi_eeprom_value::CHANNEL_A_DRIVER,
+            ftdi_eeprom_value::CHANNEL_B_DRIVER,
+            Some(ftdi_eeprom_value::CHANNEL_C_DRIVER),
+            Some(ftdi_eeprom_value::CHANNEL_D_DRIVER),
+        )?;
+
+        let value = match driver {
+            ChannelDriver::D2xx => 0,
+            ChannelDriver::Vcp => DRIVER_VCP,
+        };
+
+        self.stage_eeprom_value(name, value)
+    }
+
+    /// Get whether a FT4232H channel is configured for RS-485 (TXDEN on the RI pin).
+    pub fn channel_rs485(&mut self, channel: Interface) -> Result<bool> {
+        let name = channel_value_name(
+            channel,
+            ftdi_eeprom_value::CHANNEL_A_RS485,
+            ftdi_eeprom_value::CHANNEL_B_RS485,
+            Some(ftdi_eeprom_value::CHANNEL_C_RS485),
+            Some(ftdi_eeprom_value::CHANNEL_D_RS485),
+        )?;
+
+        Ok(self.decoded_eeprom_value(name)? != 0)
+    }
+
+    /// Configure a FT4232H channel for RS-485 (TXDEN on the RI pin).
+    pub fn set_channel_rs485(&mut self, channel: Interface, enable: bool) -> Result<()> {
+        let name = channel_value_name(
+            channel,
+            ftdi_eeprom_value::CHANNEL_A_RS485,
+            ftdi_eeprom_value::CHANNEL_B_RS485,
+            Some(ftdi_eeprom_value::CHANNEL_C_RS485),
+            Some(ftdi_eeprom_value::CHANNEL_D_RS485),
+        )?;
+
+        self.stage_eeprom_value(name, enable as i32)
+    }
+
+    /// Get whether the I/O pins of FT2232C/D channel A or B use high drive current.
+    pub fn channel_high_current(&mut self, channel: Interface) -> Result<bool> {
+        let name = channel_value_name(
+            channel,
+            ftdi_eeprom_value::HIGH_CURRENT_A,
+            ftdi_eeprom_value::HIGH_CURRENT_B,
+            None,
+            None,
+        )?;
+
+        Ok(self.decoded_eeprom_value(name)? != 0)
+    }
+
+    /// Enable high drive current on the I/O pins of FT2232C/D channel A or B.
+    pub fn set_channel_high_current(&mut self, channel: Interface, enable: bool) -> Result<()> {
+        let name = channel_value_name(
+            channel,
+            ftdi_eeprom_value::HIGH_CURRENT_A,
+            ftdi_eeprom_value::HIGH_CURRENT_B,
+            None,
+            None,
+        )?;
+
+        self.stage_eeprom_value(name, enable as i32)
+    }
+}
+
+/// Serial settings the FT232H uses when its channel is in [`ChannelType::FT1284`] mode.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Ft1284Config {
+    /// The clock idles HIGH rather than LOW.
+    pub clock_idle_high: bool,
+    /// Data is shifted least significant bit first.
+    pub lsb_first: bool,
+    /// The host may only send after the peripheral signals it is ready.
+    pub flow_control: bool,
+}
+
+// FT1284 serial interface settings.
+impl Device {
+    /// Get the FT1284 settings of the FT232H.
+    pub fn ft1284_config(&mut self) -> Result<Ft1284Config> {
+        Ok(Ft1284Config {
+            clock_idle_high: self.decoded_eeprom_value(ftdi_eeprom_value::CLOCK_POLARITY)? != 0,
+            lsb_first: self.decoded_eeprom_value(ftdi_eeprom_value::DATA_ORDER)? != 0,
+            flow_control: self.decoded_eeprom_value(ftdi_eeprom_value::FLOW_CONTROL)? != 0,
+        })
+    }
+
+    /// Set the FT1284 settings of the FT232H. They take effect once the channel is
+    /// also set to [`ChannelType::FT1284`] and the EEPROM is written and reloaded.
+    pub fn set_ft1284_config(&mut self, config: Ft1284Config) -> Result<()> {
+        self.stage_eeprom_value(
+            ftdi_eeprom_value::CLOCK_POLARITY,
+            config.clock_idle_high as i32,
+        )?;
+        self.stage_eeprom_value(ftdi_eeprom_value::DATA_ORDER, config.lsb_first as i32)?;
+        self.stage_eeprom_value(ftdi_eeprom_value::FLOW_CONTROL, config.flow_control as i32)
+    }
+}
+
+/// Highest bMaxPower a USB 2.0 bus-powered device may request, in mA.
+const MAX_POWER_LIMIT_MA: u16 = 500;
+/// bMaxPower is stored in units of 2 mA.
+const MAX_POWER_MA_PER_UNIT: u16 = 2;
+
+// USB configuration descriptor power options.
+impl Device {
+    /// Get the maximum bus current the device requests, in mA.
+    pub fn max_power(&mut self) -> Result<u16> {
+        Ok(self.decoded_eeprom_value(ftdi_eeprom_value::MAX_POWER)? as u16)
+    }
+
+    /// Set the maximum bus current the device requests, in mA.
+    ///
+    /// The value must be an even number of milliamps no greater than 500, as that is
+    /// all the configuration descriptor can represent.
+    pub fn set_max_power(&mut self, milliamps: u16) -> Result<()> {
+        if milliamps > MAX_POWER_LIMIT_MA {
+            return Err(Error::InvalidArgument("Max power exceeds 500 mA"));
+        }
+
+        if milliamps / MAX_POWER_MA_PER_UNIT * MAX_POWER_MA_PER_UNIT != milliamps {
+            return Err(Error::InvalidArgument(
+                "Max power must be a multiple of 2 mA",
+            ));
+        }
+
+        self.stage_eeprom_value(ftdi_eeprom_value::MAX_POWER, i32::from(milliamps))
+    }
+
+    /// Get whether the device reports itself as self-powered.
+    pub fn self_powered(&mut self) -> Result<bool> {
+        Ok(self.decoded_eeprom_value(ftdi_eeprom_value::SELF_POWERED)? != 0)
+    }
+
+    /// Set whether the device reports itself as self-powered.
+    pub fn set_self_powered(&mut self, enable: bool) -> Result<()> {
+        self.stage_eeprom_value(ftdi_eeprom_value::SELF_POWERED, enable as i32)
+    }
+
+    /// Get whether the device advertises remote wakeup support.
+    pub fn remote_wakeup(&mut self) -> Result<bool> {
+        Ok(self.decoded_eeprom_value(ftdi_eeprom_value::REMOTE_WAKEUP)? != 0)
+    }
+
+    /// Set whether the device advertises remote wakeup support.
+    pub fn set_remote_wakeup(&mut self, enable: bool) -> Result<()> {
+        self.stage_eeprom_value(ftdi_eeprom_value::REMOTE_WAKEUP, enable as i32)
+    }
+
+    /// Get whether weak pull-downs are enabled on the I/O pins during USB suspend.
+    pub fn suspend_pull_downs(&mut self) -> Result<bool> {
+        Ok(self.decoded_eeprom_value(ftdi_eeprom_value::SUSPEND_PULL_DOWNS)? != 0)
+    }
+
+    /// Enable weak pull-downs on the I/O pins during USB suspend.
+    pub fn set_suspend_pull_downs(&mut self, enable: bool) -> Result<()> {
+        self.stage_eeprom_value(ftdi_eeprom_value::SUSPEND_PULL_DOWNS, enable as i32)
+    }
+}
+
+// UART signal inversion bits, from ftdi.h; bindgen does not pick these up.
+const INVERT_TXD: i32 = 0x01;
+const INVERT_RXD: i32 = 0x02;
+const INVERT_RTS: i32 = 0x04;
+const INVERT_CTS: i32 = 0x08;
+const INVERT_DTR: i32 = 0x10;
+const INVERT_DSR: i32 = 0x20;
+const INVERT_DCD: i32 = 0x40;
+const INVERT_RI: i32 = 0x80;
+
+/// UART signals whose polarity is inverted by FT232R and FT-X parts.
+///
+/// Inverting every signal turns the TTL-level UART into one with RS-232 polarity, which
+/// is occasionally enough to talk to an RS-232 port directly.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct UartInversion {
+    pub txd: bool,
+    pub rxd: bool,
+    pub rts: bool,
+    pub cts: bool,
+    pub dtr: bool,
+    pub dsr: bool,
+    pub dcd: bool,
+    pub ri: bool,
+}
+
+impl UartInversion {
+    fn bits(self) -> [(bool, i32); 8] {
+        [
+            (self.txd, INVERT_TXD),
+            (self.rxd, INVERT_RXD),
+            (self.rts, INVERT_RTS),
+            (self.cts, INVERT_CTS),
+            (self.dtr, INVERT_DTR),
+            (self.dsr, INVERT_DSR),
+            (self.dcd, INVERT_DCD),
+            (self.ri, INVERT_RI),
+        ]
+    }
+
+    fn to_raw(self) -> i32 {
+        self.bits()
+            .iter()
+            .filter(|&&(set, _)| set)
+            .fold(0, |raw, &(_, bit)| raw | bit)
+    }
+
+    fn from_raw(raw: i32) -> UartInversion {
+        UartInversion {
+            txd: raw & INVERT_TXD != 0,
+            rxd: raw & INVERT_RXD != 0,
+            rts: raw & INVERT_RTS != 0,
+            cts: raw & INVERT_CTS != 0,
+            dtr: raw & INVERT_DTR != 0,
+            dsr: raw & INVERT_DSR != 0,
+            dcd: raw & INVERT_DCD != 0,
+            ri: raw & INVERT_RI != 0,
+        }
+    }
+}
+
+// UART signal polarity.
+impl Device {
+    /// Get which UART signals the EEPROM inverts. Only meaningful on FT232R and FT-X parts.
+    pub fn uart_inversion(&mut self) -> Result<UartInversion> {
+        Ok(UartInversion::from_raw(
+            self.decoded_eeprom_value(ftdi_eeprom_value::INVERT)?,
+        ))
+    }
+
+    /// Set which UART signals the EEPROM inverts. Only meaningful on FT232R and FT-X parts.
+    pub fn set_uart_inversion(&mut self, inversion: UartInversion) -> Result<()> {
+        self.stage_eeprom_value(ftdi_eeprom_value::INVERT, inversion.to_raw())
+    }
+}
+
+// Pin group flags, from ftdi.h; bindgen does not pick these up.
+const SLOW_SLEW: i32 = 0x4;
+const IS_SCHMITT: i32 = 0x8;
+/// H-series and FT-X parts split their I/O pins into up to four groups.
+const PIN_GROUPS: u8 = 4;
+
+/// Output drive strength of a pin group.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum DriveStrength {
+    Ma4,
+    Ma8,
+    Ma12,
+    Ma16,
+}
+
+/// Electrical settings shared by a group of I/O pins.
+///
+/// Which pins form a group depends on the chip: on the FT2232H groups 0 to 3 are
+/// channel A low/high and channel B low/high, on the FT4232H they are channels A to D,
+/// and on the FT232H and FT-X parts group 0 is the data bus and group 1 the CBUS pins.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct PinGroup {
+    pub drive: DriveStrength,
+    pub slow_slew: bool,
+    pub schmitt: bool,
+}
+
+impl Default for PinGroup {
+    fn default() -> PinGroup {
+        PinGroup {
+            drive: DriveStrength::Ma4,
+            slow_slew: false,
+            schmitt: false,
+        }
+    }
+}
+
+fn pin_group_value_names(
+    group: u8,
+) -> Result<(ftdi_eeprom_value, ftdi_eeprom_value, ftdi_eeprom_value)> {
+    match group {
+        0 => Ok((
+            ftdi_eeprom_value::GROUP0_DRIVE,
+            ftdi_eeprom_value::GROUP0_SLEW,
+            ftdi_eeprom_value::GROUP0_SCHMITT,
+        )),
+        1 => Ok((
+            ftdi_eeprom_value::GROUP1_DRIVE,
+            ftdi_eeprom_value::GROUP1_SLEW,
+            ftdi_eeprom_value::GROUP1_SCHMITT,
+        )),
+        2 => Ok((
+            ftdi_eeprom_value::GROUP2_DRIVE,
+            ftdi_eeprom_value::GROUP2_SLEW,
+            ftdi_eeprom_value::GROUP2_SCHMITT,
+        )),
+        3 => Ok((
+            ftdi_eeprom_value::GROUP3_DRIVE,
+            ftdi_eeprom_value::GROUP3_SLEW,
+            ftdi_eeprom_value::GROUP3_SCHMITT,
+        )),
+        _ => Err(Error::InvalidArgument("Pin group out of range")),
+    }
+}
+
+// Pin group drive strength, slew rate and input hysteresis.
+impl Device {
+    /// Get the electrical settings of pin group `group` (0 to 3).
+    pub fn pin_group(&mut self, group: u8) -> Result<PinGroup> {
+        let (drive, slew, schmitt) = pin_group_value_names(group)?;
+
+        let drive = match self.decoded_eeprom_value(drive)? {
+            0 => DriveStrength::Ma4,
+            1 => DriveStrength::Ma8,
+            2 => DriveStrength::Ma12,
+            _ => DriveStrength::Ma16,
+        };
+
+        Ok(PinGroup {
+            drive,
+            slow_slew: self.decoded_eeprom_value(slew)? != 0,
+            schmitt: self.decoded_eeprom_value(schmitt)? != 0,
+        })
+    }
+
+    /// Set the electrical settings of pin group `group` (0 to 3).
+    pub fn set_pin_group(&mut self, group: u8, settings: PinGroup) -> Result<()> {
+        let (drive, slew, schmitt) = pin_group_value_names(group)?;
+
+        let raw_drive = match settings.drive {
+            DriveStrength::Ma4 => 0,
+            DriveStrength::Ma8 => 1,
+            DriveStrength::Ma12 => 2,
+            DriveStrength::Ma16 => 3,
+        };
+
+        // libftdi only honours the exact flag values here, not any non-zero value.
+        self.stage_eeprom_value(drive, raw_drive)?;
+        self.stage_eeprom_value(slew, if settings.slow_slew { SLOW_SLEW } else { 0 })?;
+        self.stage_eeprom_value(schmitt, if settings.schmitt { IS_SCHMITT } else { 0 })
+    }
+}
+
+/// Every value libftdi can report from a decoded EEPROM, in libftdi's order.
+const EEPROM_FIELDS: [(&str, ftdi_eeprom_value); 57] = [
+    ("VENDOR_ID", ftdi_eeprom_value::VENDOR_ID),
+    ("PRODUCT_ID", ftdi_eeprom_value::PRODUCT_ID),
+    ("SELF_POWERED", ftdi_eeprom_value::SELF_POWERED),
+    ("REMOTE_WAKEUP", ftdi_eeprom_value::REMOTE_WAKEUP),
+    ("IS_NOT_PNP", ftdi_eeprom_value::IS_NOT_PNP),
+    ("SUSPEND_DBUS7", ftdi_eeprom_value::SUSPEND_DBUS7),
+    ("IN_IS_ISOCHRONOUS", ftdi_eeprom_value::IN_IS_ISOCHRONOUS),
+    ("OUT_IS_ISOCHRONOUS", ftdi_eeprom_value::OUT_IS_ISOCHRONOUS),
+    ("SUSPEND_PULL_DOWNS", ftdi_eeprom_value::SUSPEND_PULL_DOWNS),
+    ("USE_SERIAL", ftdi_eeprom_value::USE_SERIAL),
+    ("USB_VERSION", ftdi_eeprom_value::USB_VERSION),
+    ("USE_USB_VERSION", ftdi_eeprom_value::USE_USB_VERSION),
+    ("MAX_POWER", ftdi_eeprom_value::MAX_POWER),
+    ("CHANNEL_A_TYPE", ftdi_eeprom_value::CHANNEL_A_TYPE),
+    ("CHANNEL_B_TYPE", ftdi_eeprom_value::CHANNEL_B_TYPE),
+    ("CHANNEL_A_DRIVER", ftdi_eeprom_value::CHANNEL_A_DRIVER),
+    ("CHANNEL_B_DRIVER", ftdi_eeprom_value::CHANNEL_B_DRIVER),
+    ("CBUS_FUNCTION_0", ftdi_eeprom_value::CBUS_FUNCTION_0),
+    ("CBUS_FUNCTION_1", ftdi_eeprom_value::CBUS_FUNCTION_1),
+    ("CBUS_FUNCTION_2", ftdi_eeprom_value::CBUS_FUNCTION_2),
+    ("CBUS_FUNCTION_3", ftdi_eeprom_value::CBUS_FUNCTION_3),
+    ("CBUS_FUNCTION_4", ftdi_eeprom_value::CBUS_FUNCTION_4),
+    ("CBUS_FUNCTION_5", ftdi_eeprom_value::CBUS_FUNCTION_5),
+    ("CBUS_FUNCTION_6", ftdi_eeprom_value::CBUS_FUNCTION_6),
+    ("CBUS_FUNCTION_7", ftdi_eeprom_value::CBUS_FUNCTION_7),
+    ("CBUS_FUNCTION_8", ftdi_eeprom_value::CBUS_FUNCTION_8),
+    ("CBUS_FUNCTION_9", ftdi_eeprom_value::CBUS_FUNCTION_9),
+    ("HIGH_CURRENT", ftdi_eeprom_value::HIGH_CURRENT),
+    ("HIGH_CURRENT_A", ftdi_eeprom_value::HIGH_CURRENT_A),
+    ("HIGH_CURRENT_B", ftdi_eeprom_value::HIGH_CURRENT_B),
+    ("INVERT", ftdi_eeprom_value::INVERT),
+    ("GROUP0_DRIVE", ftdi_eeprom_value::GROUP0_DRIVE),
+    ("GROUP0_SCHMITT", ftdi_eeprom_value::GROUP0_SCHMITT),
+    ("GROUP0_SLEW", ftdi_eeprom_value::GROUP0_SLEW),
+    ("GROUP1_DRIVE", ftdi_eeprom_value::GROUP1_DRIVE),
+    ("GROUP1_SCHMITT", ftdi_eeprom_value::GROUP1_SCHMITT),
+    ("GROUP1_SLEW", ftdi_eeprom_value::GROUP1_SLEW),
+    ("GROUP2_DRIVE", ftdi_eeprom_value::GROUP2_DRIVE),
+    ("GROUP2_SCHMITT", ftdi_eeprom_value::GROUP2_SCHMITT),
+    ("GROUP2_SLEW", ftdi_eeprom_value::GROUP2_SLEW),
+    ("GROUP3_DRIVE", ftdi_eeprom_value::GROUP3_DRIVE),
+    ("GROUP3_SCHMITT", ftdi_eeprom_value::GROUP3_SCHMITT),
+    ("GROUP3_SLEW", ftdi_eeprom_value::GROUP3_SLEW),
+    ("CHIP_SIZE", ftdi_eeprom_value::CHIP_SIZE),
+    ("CHIP_TYPE", ftdi_eeprom_value::CHIP_TYPE),
+    ("POWER_SAVE", ftdi_eeprom_value::POWER_SAVE),
+    ("CLOCK_POLARITY", ftdi_eeprom_value::CLOCK_POLARITY),
+    ("DATA_ORDER", ftdi_eeprom_value::DATA_ORDER),
+    ("FLOW_CONTROL", ftdi_eeprom_value::FLOW_CONTROL),
+    ("CHANNEL_C_DRIVER", ftdi_eeprom_value::CHANNEL_C_DRIVER),
+    ("CHANNEL_D_DRIVER", ftdi_eeprom_value::CHANNEL_D_DRIVER),
+    ("CHANNEL_A_RS485", ftdi_eeprom_value::CHANNEL_A_RS485),
+    ("CHANNEL_B_RS485", ftdi_eeprom_value::CHANNEL_B_RS485),
+    ("CHANNEL_C_RS485", ftdi_eeprom_value::CHANNEL_C_RS485),
+    ("CHANNEL_D_RS485", ftdi_eeprom_value::CHANNEL_D_RS485),
+    ("RELEASE_NUMBER", ftdi_eeprom_value::RELEASE_NUMBER),
+    (
+        "EXTERNAL_OSCILLATOR",
+        ftdi_eeprom_value::EXTERNAL_OSCILLATOR,
+    ),
+];
+
+/// A single field of the decoded EEPROM, as returned by
+/// [`eeprom_decode_report`][Device::eeprom_decode_report].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct EepromField {
+    pub name: &'static str,
+    pub value: i32,
+}
+
+/// Compute the checksum FTDI chips expect in the last word of the EEPROM image.
+fn eeprom_checksum(image: &[u8], chip_type: ftdic::ftdi_chip_type) -> u16 {
+    let mut checksum: u16 = 0xAAAA;
+    let mut i = 0;
+
+    while i < image.len() / 2 - 1 {
+        // The FT-X user area in MTP memory is not covered by the checksum.
+        if chip_type == ftdic::ftdi_chip_type::TYPE_230X && i == 0x12 {
+            i = 0x40;
+        }
+
+        let value = u16::from(image[i * 2]) | (u16::from(image[i * 2 + 1]) << 8);
+        checksum = (value ^ checksum).rotate_left(1);
+        i += 1;
+    }
+
+    checksum
+}
+
+// Image validation.
+impl Device {
+    /// Read the EEPROM from the chip and check its stored checksum, without decoding it.
+    ///
+    /// Returns `false` for a blank or corrupt EEPROM.
+    pub fn verify_eeprom_checksum(&mut self) -> Result<bool> {
+        let rc = unsafe { ftdic::ftdi_read_eeprom(self.context.get_ftdi_context()) };
+        self.context.check_ftdi_error(rc)?;
+
+        let image = self.get_eeprom_buf()?;
+        let chip_type = unsafe { (*self.context.get_ftdi_context()).type_ };
+
+        let len = image.len();
+        let stored = u16::from(image[len - 2]) | (u16::from(image[len - 1]) << 8);
+        Ok(stored == eeprom_checksum(&image, chip_type))
+    }
+
+    /// Read and decode the EEPROM, returning every field libftdi's verbose decode would print.
+    pub fn eeprom_decode_report(&mut self) -> Result<Vec<EepromField>> {
+        self.load_eeprom_data()?;
+
+        let mut fields = Vec::with_capacity(EEPROM_FIELDS.len());
+        for &(name, value_name) in EEPROM_FIELDS.iter() {
+            fields.push(EepromField {
+                name,
+                value: self.get_eeprom_value(value_name)?,
+            });
+        }
+
+        Ok(fields)
+    }
+}
+
+// Provisioning of the device ID strings.
+impl Device {
+    /// Program a new serial number, verifying it and restoring the old EEPROM on failure.
+    pub fn provision_serial(&mut self, serial: &str) -> Result<()> {
+        self.provision_strings(None, None, Some(serial))
+    }
+
+    /// Program a new manufacturer string, verifying it and restoring the old EEPROM on failure.
+    pub fn provision_manufacturer(&mut self, manufacturer: &str) -> Result<()> {
+        self.provision_strings(Some(manufacturer), None, None)
+    }
+
+    /// Program a new description string, verifying it and restoring the old EEPROM on failure.
+    pub fn provision_description(&mut self, description: &str) -> Result<()> {
+        self.provision_strings(None, Some(description), None)
+    }
+
+    /// Program any of the device ID strings in one read, build, write and verify cycle.
+    ///
+    /// If writing or verification fails, the EEPROM image read at the start is written back
+    /// before the error is returned. The error is always the original failure; if writing
+    /// the old image back fails too, that is only logged (with the `tracing` feature).
+    pub fn provision_strings(
+        &mut self,
+        manufacturer: Option<&str>,
+        description: Option<&str>,
+        serial: Option<&str>,
+    ) -> Result<()> {
+        self.load_eeprom_data()?;
+        let original = self.get_eeprom_buf()?;
+
+        self.eeprom_set_strings(manufacturer, description, serial)?;
+        self.build_eeprom()?;
+
+        let res = self.write_eeprom().and_then(|_| {
+            let written = self.eeprom_get_strings()?;
+            let matches = manufacturer.iter().all(|&m| m == written.manufacturer)
+                && description.iter().all(|&d| d == written.description)
+                && serial.iter().all(|&s| s == written.serial);
+
+            if matches {
+                Ok(())
+            } else {
+                Err(Error::LibFtdi(LibFtdiError::new(
+                    "EEPROM verification failed",
+                )))
+            }
+        });
+
+        if res.is_err() {
+            // The original failure is what the caller needs; a failed rollback is only logged.
+            let rollback = self
+                .set_eeprom_buf(&original)
+                .and_then(|_| self.write_eeprom());
+            #[cfg(feature = "tracing")]
+            {
+                if let Err(ref e) = rollback {
+                    tracing::warn!(error = %e, "EEPROM rollback failed");
+                }
+            }
+            let _ = rollback;
+        }
+
+        res
+    }
+}
+
+/// CBUS pin functions of a chip, laid out according to its CBUS function set.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum CbusConfig {
+    FT232R([CbusFunction; 5]),
+    FT232H([CbusHFunction; 10]),
+    FTX([CbusXFunction; 4]),
+}
+
+/// Snapshot of the typed EEPROM settings of a device.
+///
+/// Obtain one with [`eeprom_config`][Device::eeprom_config] and stage it on a device with
+/// [`apply_eeprom_config`][Device::apply_eeprom_config]. With the `serde` feature enabled,
+/// it can also be stored as TOML.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct EepromConfig {
+    pub vendor_id: u16,
+    pub product_id: u16,
+    pub manufacturer: String,
+    pub description: String,
+    pub serial: String,
+    pub use_serial: bool,
+    pub max_power: u16,
+    pub self_powered: bool,
+    pub remote_wakeup: bool,
+    pub suspend_pull_downs: bool,
+    pub channel_a_type: ChannelType,
+    pub channel_b_type: ChannelType,
+    pub channel_a_driver: ChannelDriver,
+    pub channel_b_driver: ChannelDriver,
+    pub channel_c_driver: ChannelDriver,
+    pub channel_d_driver: ChannelDriver,
+    pub channel_a_rs485: bool,
+    pub channel_b_rs485: bool,
+    pub channel_c_rs485: bool,
+    pub channel_d_rs485: bool,
+    pub high_current_a: bool,
+    pub high_current_b: bool,
+    pub invert: UartInversion,
+    pub pin_groups: [PinGroup; PIN_GROUPS as usize],
+    /// `None` for chips without configurable CBUS pins.
+    pub cbus: Option<CbusConfig>,
+}
+
+/// A setting that differs between two [`EepromConfig`]s, as found by
+/// [`EepromConfig::diff`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EepromChange {
+    /// Name of the `EepromConfig` field, with an index for array elements.
+    pub field: String,
+    pub old: String,
+    pub new: String,
+}
+
+fn push_change<T: fmt::Debug + PartialEq>(
+    changes: &mut Vec<EepromChange>,
+    field: &str,
+    old: &T,
+    new: &T,
+) {
+    if old != new {
+        changes.push(EepromChange {
+            field: field.to_string(),
+            old: format!("{:?}", old),
+            new: format!("{:?}", new),
+        });
+    }
+}
+
+fn push_array_changes<T: fmt::Debug + PartialEq>(
+    changes: &mut Vec<EepromChange>,
+    field: &str,
+    old: &[T],
+    new: &[T],
+) {
+    for (i, (old, new)) in old.iter().zip(new).enumerate() {
+        push_change(changes, &format!("{}[{}]", field, i), old, new);
+    }
+}
+
+impl EepromConfig {
+    /// List every setting that differs between `self` (the old configuration) and `other`.
+    ///
+    /// CBUS pins are compared one by one when both configurations use the same CBUS
+    /// function set, and as a whole otherwise.
+    pub fn diff(&self, other: &EepromConfig) -> Vec<EepromChange> {
+        let mut changes = Vec::new();
+
+        push_change(&mut changes, "vendor_id", &self.vendor_id, &other.vendor_id);
+        push_change(
+            &mut changes,
+            "product_id",
+            &self.product_id,
+            &other.product_id,
+        );
+        push_change(
+            &mut changes,
+            "manufacturer",
+            &self.manufacturer,
+            &other.manufacturer,
+        );
+        push_change(
+            &mut changes,
+            "description",
+            &self.description,
+            &other.description,
+        );
+        push_change(&mut changes, "serial", &self.serial, &other.serial);
+        push_change(
+            &mut changes,
+            "use_serial",
+            &self.use_serial,
+            &other.use_serial,
+        );
+        push_change(&mut changes, "max_power", &self.max_power, &other.max_power);
+        push_change(
+            &mut changes,
+            "self_powered",
+            &self.self_powered,
+            &other.self_powered,
+        );
+        push_change(
+            &mut changes,
+            "remote_wakeup",
+            &self.remote_wakeup,
+            &other.remote_wakeup,
+        );
+        push_change(
+            &mut changes,
+            "suspend_pull_downs",
+            &self.suspend_pull_downs,
+            &other.suspend_pull_downs,
+        );
+        push_change(
+            &mut changes,
+            "channel_a_type",
+            &self.channel_a_type,
+            &other.channel_a_type,
+        );
+        push_change(
+            &mut changes,
+            "channel_b_type",
+            &self.channel_b_type,
+            &other.channel_b_type,
+        );
+        push_change(
+            &mut changes,
+            "channel_a_driver",
+            &self.channel_a_driver,
+            &other.channel_a_driver,
+        );
+        push_change(
+            &mut changes,
+            "channel_b_driver",
+            &self.channel_b_driver,
+            &other.channel_b_driver,
+        );
+        push_change(
+            &mut changes,
+            "channel_c_driver",
+            &self.channel_c_driver,
+            &other.channel_c_driver,
+        );
+        push_change(
+            &mut changes,
+            "channel_d_driver",
+            &self.channel_d_driver,
+            &other.channel_d_driver,
+        );
+        push_change(
+            &mut changes,
+            "channel_a_rs485",
+            &self.channel_a_rs485,
+            &other.channel_a_rs485,
+        );
+        push_change(
+            &mut changes,
+            "channel_b_rs485",
+            &self.channel_b_rs485,
+            &other.channel_b_rs485,
+        );
+        push_change(
+            &mut changes,
+            "channel_c_rs485",
+            &self.channel_c_rs485,
+            &other.channel_c_rs485,
+        );
+        push_change(
+            &mut changes,
+            "channel_d_rs485",
+            &self.channel_d_rs485,
+            &other.channel_d_rs485,
+        );
+        push_change(
+            &mut changes,
+            "high_current_a",
+            &self.high_current_a,
+            &other.high_current_a,
+        );
+        push_change(
+            &mut changes,
+            "high_current_b",
+            &self.high_current_b,
+            &other.high_current_b,
+        );
+        push_change(&mut changes, "invert", &self.invert, &other.invert);
+        push_array_changes(
+            &mut changes,
+            "pin_groups",
+            &self.pin_groups,
+            &other.pin_groups,
+        );
+
+        match (&self.cbus, &other.cbus) {
+            (&Some(CbusConfig::FT232R(ref old)), &Some(CbusConfig::FT232R(ref new))) => {
+                push_array_changes(&mut changes, "cbus", old, new)
+            }
+            (&Some(CbusConfig::FT232H(ref old)), &Some(CbusConfig::FT232H(ref new))) => {
+                push_array_changes(&mut changes, "cbus", old, new)
+            }
+            (&Some(CbusConfig::FTX(ref old)), &Some(CbusConfig::FTX(ref new))) => {
+                push_array_changes(&mut changes, "cbus", old, new)
+            }
+            (old, new) => push_change(&mut changes, "cbus", old, new),
+        }
+
+        changes
+    }
+}
+
+fn yes_no(value: bool) -> &'static str {
+    if value {
+        "yes"
+    } else {
+        "no"
+    }
+}
+
+/// A human-readable report of every setting, grouped into USB descriptors, power,
+/// channels and pins, e.g. for bug reports.
+impl fmt::Display for EepromConfig {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "USB descriptors:")?;
+        writeln!(f, "  {:<20}{:#06x}", "Vendor ID:", self.vendor_id)?;
+        writeln!(f, "  {:<20}{:#06x}", "Product ID:", self.product_id)?;
+        writeln!(f, "  {:<20}{:?}", "Manufacturer:", self.manufacturer)?;
+        writeln!(f, "  {:<20}{:?}", "Description:", self.description)?;
+        writeln!(
+            f,
+            "  {:<20}{:?}{}",
+            "Serial:",
+            self.serial,
+            if self.use_serial {
+                ""
+            } else {
+                " (not reported)"
+            }
+        )?;
+
+        writeln!(f, "Power:")?;
+        writeln!(f, "  {:<20}{} mA", "Max power:", self.max_power)?;
+        writeln!(f, "  {:<20}{}", "Self powered:", yes_no(self.self_powered))?;
+        writeln!(
+            f,
+            "  {:<20}{}",
+            "Remote wakeup:",
+            yes_no(self.remote_wakeup)
+        )?;
+        writeln!(
+            f,
+            "  {:<20}{}",
+            "Suspend pull-downs:",
+            yes_no(self.suspend_pull_downs)
+        )?;
+
+        writeln!(f, "Channels:")?;
+        let channels = [
+            (
+                "A",
+                Some(self.channel_a_type),
+                self.channel_a_driver,
+                self.channel_a_rs485,
+                Some(self.high_current_a),
+            ),
+            (
+                "B",
+                Some(self.channel_b_type),
+                self.channel_b_driver,
+                self.channel_b_rs485,
+                Some(self.high_current_b),
+            ),
+            ("C", None, self.channel_c_driver, self.channel_c_rs485, None),
+            ("D", None, self.channel_d_driver, self.channel_d_rs485, None),
+        ];
+        for &(name, channel_type, driver, rs485, high_current) in &channels {
+            write!(f, "  {}: ", name)?;
+            if let Some(channel_type) = channel_type {
+                write!(f, "{:?}, ", channel_type)?;
+            }
+            write!(f, "{:?} driver, RS-485 {}", driver, yes_no(rs485))?;
+            if let Some(high_current) = high_current {
+                write!(f, ", high current {}", yes_no(high_current))?;
+            }
+            writeln!(f)?;
+        }
+
+        writeln!(f, "Pins:")?;
+        let inverted: Vec<&str> = ["TXD", "RXD", "RTS", "CTS", "DTR", "DSR", "DCD", "RI"]
+            .iter()
+            .zip(self.invert.bits().iter())
+            .filter(|&(_, &(set, _))| set)
+            .map(|(&name, _)| name)
+            .collect();
+        if inverted.is_empty() {
+            writeln!(f, "  {:<20}none", "Inverted UART:")?;
+        } else {
+            writeln!(f, "  {:<20}{}", "Inverted UART:", inverted.join(", "))?;
+        }
+        for (i, group) in self.pin_groups.iter().enumerate() {
+            writeln!(
+                f,
+                "  {:<20}{:?}, slow slew {}, Schmitt trigger {}",
+                format!("Group {}:", i),
+                group.drive,
+                yes_no(group.slow_slew),
+                yes_no(group.schmitt)
+            )?;
+        }
+        let cbus: Vec<String> = match self.cbus {
+            Some(CbusConfig::FT232R(ref pins)) => pins.iter().map(|p| format!("{:?}", p)).collect(),
+            Some(CbusConfig::FT232H(ref pins)) => pins.iter().map(|p| format!("{:?}", p)).collect(),
+            Some(CbusConfig::FTX(ref pins)) => pins.iter().map(|p| format!("{:?}", p)).collect(),
+            None => Vec::new(),
+        };
+        for (i, function) in cbus.iter().enumerate() {
+            writeln!(f, "  {:<20}{}", format!("CBUS{}:", i), function)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl EepromConfig {
+    /// Parse a configuration previously stored with [`to_toml`][EepromConfig::to_toml].
+    pub fn from_toml(s: &str) -> std::result::Result<EepromConfig, toml::de::Error> {
+        toml::from_str(s)
+    }
+
+    /// Serialize the configuration as TOML.
+    pub fn to_toml(&self) -> std::result::Result<String, toml::ser::Error> {
+        toml::to_string(self)
+    }
+}
+
+// Whole-configuration access.
+impl Device {
+    /// Read and decode the EEPROM, returning all typed settings at once.
+    pub fn eeprom_config(&mut self) -> Result<EepromConfig> {
+        self.load_eeprom_data()?;
+        let strings = self.eeprom_get_strings()?;
+
+        let chip_type = unsafe { (*self.context.get_ftdi_context()).type_ };
+        let cbus = match chip_type {
+            ftdic::ftdi_chip_type::TYPE_R => {
+                let mut functions = [CbusFunction::TxDen; 5];
+                for (pin, function) in functions.iter_mut().enumerate() {
+                    *function = self.cbus_function(pin as u8)?;
+                }
+                Some(CbusConfig::FT232R(functions))
+            }
+            ftdic::ftdi_chip_type::TYPE_232H => {
+                let mut functions = [CbusHFunction::Tristate; 10];
+                for (pin, function) in functions.iter_mut().enumerate() {
+                    *function = self.cbush_function(pin as u8)?;
+                }
+                Some(CbusConfig::FT232H(functions))
+            }
+            ftdic::ftdi_chip_type::TYPE_230X => {
+                let mut functions = [CbusXFunction::Tristate; 4];
+                for (pin, function) in functions.iter_mut().enumerate() {
+                    *function = self.cbusx_function(pin as u8)?;
+                }
+                Some(CbusConfig::FTX(functions))
+            }
+            _ => None,
+        };
+
+        let mut pin_groups = [PinGroup::default(); PIN_GROUPS as usize];
+        for (group, settings) in pin_groups.iter_mut().enumerate() {
+            *settings = self.pin_group(group as u8)?;
+        }
+
+        Ok(EepromConfig {
+            vendor_id: self.decoded_eeprom_value(ftdi_eeprom_value::VENDOR_ID)? as u16,
+            product_id: self.decoded_eeprom_value(ftdi_eeprom_value::PRODUCT_ID)? as u16,
+            manufacturer: strings.manufacturer,
+            description: strings.description,
+            serial: strings.serial,
+            use_serial: self.decoded_eeprom_value(ftdi_eeprom_value::USE_SERIAL)? != 0,
+            max_power: self.max_power()?,
+            self_powered: self.self_powered()?,
+            remote_wakeup: self.remote_wakeup()?,
+            suspend_pull_downs: self.suspend_pull_downs()?,
+            channel_a_type: self.channel_type(Interface::A)?,
+            channel_b_type: self.channel_type(Interface::B)?,
+            channel_a_driver: self.channel_driver(Interface::A)?,
+            channel_b_driver: self.channel_driver(Interface::B)?,
+            channel_c_driver: self.channel_driver(Interface::C)?,
+            channel_d_driver: self.channel_driver(Interface::D)?,
+            channel_a_rs485: self.channel_rs485(Interface::A)?,
+            channel_b_rs485: self.channel_rs485(Interface::B)?,
+            channel_c_rs485: self.channel_rs485(Interface::C)?,
+            channel_d_rs485: self.channel_rs485(Interface::D)?,
+            high_current_a: self.channel_high_current(Interface::A)?,
+            high_current_b: self.channel_high_current(Interface::B)?,
+            invert: self.uart_inversion()?,
+            pin_groups,
+            cbus,
+        })
+    }
+
+    /// Stage every setting of `config` in the decoded EEPROM structure.
+    ///
+    /// Call [`build_eeprom`][Device::build_eeprom] and [`write_eeprom`][Device::write_eeprom]
+    /// afterwards to flash it.
+    pub fn apply_eeprom_config(&mut self, config: &EepromConfig) -> Result<()> {
+        self.stage_eeprom_value(ftdi_eeprom_value::VENDOR_ID, i32::from(config.vendor_id))?;
+        self.stage_eeprom_value(ftdi_eeprom_value::PRODUCT_ID, i32::from(config.product_id))?;
+        self.eeprom_set_strings(
+            Some(&config.manufacturer),
+            Some(&config.description),
+            Some(&config.serial),
+        )?;
+        self.stage_eeprom_value(ftdi_eeprom_value::USE_SERIAL, config.use_serial as i32)?;
+        self.set_max_power(config.max_power)?;
+        self.set_self_powered(config.self_powered)?;
+        self.set_remote_wakeup(config.remote_wakeup)?;
+        self.set_suspend_pull_downs(config.suspend_pull_downs)?;
+        self.set_channel_type(Interface::A, config.channel_a_type)?;
+        self.set_channel_type(Interface::B, config.channel_b_type)?;
+        self.set_channel_driver(Interface::A, config.channel_a_driver)?;
+        self.set_channel_driver(Interface::B, config.channel_b_driver)?;
+        self.set_channel_driver(Interface::C, config.channel_c_driver)?;
+        self.set_channel_driver(Interface::D, config.channel_d_driver)?;
+        self.set_channel_rs485(Interface::A, config.channel_a_rs485)?;
+        self.set_channel_rs485(Interface::B, config.channel_b_rs485)?;
+        self.set_channel_rs485(Interface::C, config.channel_c_rs485)?;
+        self.set_channel_rs485(Interface::D, config.channel_d_rs485)?;
+        self.set_channel_high_current(Interface::A, config.high_current_a)?;
+        self.set_channel_high_current(Interface::B, config.high_current_b)?;
+        self.set_uart_inversion(config.invert)?;
+        for (group, &settings) in config.pin_groups.iter().enumerate() {
+            self.set_pin_group(group as u8, settings)?;
+        }
+
+        match config.cbus {
+            Some(CbusConfig::FT232R(functions)) => {
+                for (pin, &function) in functions.iter().enumerate() {
+                    self.set_cbus_function(pin as u8, function)?;
+                }
+            }
+            Some(CbusConfig::FT232H(functions)) => {
+                for (pin, &function) in functions.iter().enumerate() {
+                    self.set_cbush_function(pin as u8, function)?;
+                }
+            }
+            Some(CbusConfig::FTX(functions)) => {
+                for (pin, &function) in functions.iter().enumerate() {
+                    self.set_cbusx_function(pin as u8, function)?;
+                }
+            }
+            None => {}
+        }
+
+        Ok(())
+    }
+
+    /// Read the configuration back from the chip and compare it against `expected`.
+    ///
+    /// Returns the settings that differ, so an empty list means the EEPROM holds
+    /// exactly `expected`. Use this after [`write_eeprom`][Device::write_eeprom] to
+    /// confirm a flash succeeded.
+    pub fn verify_eeprom(&mut self, expected: &EepromConfig) -> Result<Vec<EepromChange>> {
+        let actual = self.eeprom_config()?;
+        Ok(expected.diff(&actual))
+    }
+}
@@ -0,0 +1,381 @@
+//! Reader for the configuration files of libftdi's `ftdi_eeprom` utility.
+//!
+//! The format is a list of `key = value` assignments, one per line. Values are
+//! booleans (`true`/`false`), integers (decimal, `0x` hex or `0` octal), bare
+//! words, or double-quoted strings. `#` and `//` start a comment.
+
+use std::error;
+use std::fmt;
+
+use super::{
+    CbusConfig, CbusFunction, CbusHFunction, CbusXFunction, ChannelDriver, ChannelType,
+    DriveStrength, EepromConfig, PinGroup, UartInversion, PIN_GROUPS,
+};
+
+/// Error encountered while parsing a `ftdi_eeprom` configuration file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfError {
+    /// 1-based line number of the offending assignment.
+    pub line: usize,
+    pub reason: &'static str,
+}
+
+impl fmt::Display for ConfError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.reason)
+    }
+}
+
+impl error::Error for ConfError {}
+
+// Keys ftdi_eeprom understands that have no counterpart in `EepromConfig`.
+const IGNORED_KEYS: [&str; 11] = [
+    "in_is_isochronous",
+    "out_is_isochronous",
+    "change_usb_version",
+    "usb_version",
+    "default_pid",
+    "eeprom_type",
+    "filename",
+    "flash_raw",
+    "high_current",
+    "user_data_addr",
+    "user_data_file",
+];
+
+// Value names accepted by ftdi_eeprom for each CBUS function set, in encoding order.
+const CBUS_NAMES: [(&str, CbusFunction); 13] = [
+    ("TXDEN", CbusFunction::TxDen),
+    ("PWREN", CbusFunction::PwrEn),
+    ("RXLED", CbusFunction::RxLed),
+    ("TXLED", CbusFunction::TxLed),
+    ("TXRXLED", CbusFunction::TxRxLed),
+    ("SLEEP", CbusFunction::Sleep),
+    ("CLK48", CbusFunction::Clk48),
+    ("CLK24", CbusFunction::Clk24),
+    ("CLK12", CbusFunction::Clk12),
+    ("CLK6", CbusFunction::Clk6),
+    ("IOMODE", CbusFunction::IoMode),
+    ("BB_WR", CbusFunction::BitbangWr),
+    ("BB_RD", CbusFunction::BitbangRd),
+];
+
+const CBUSH_NAMES: [(&str, CbusHFunction); 13] = [
+    ("TRISTATE", CbusHFunction::Tristate),
+    ("TXLED", CbusHFunction::TxLed),
+    ("RXLED", CbusHFunction::RxLed),
+    ("TXRXLED", CbusHFunction::TxRxLed),
+    ("PWREN", CbusHFunction::PwrEn),
+    ("SLEEP", CbusHFunction::Sleep),
+    ("DRIVE_0", CbusHFunction::Drive0),
+    ("DRIVE1", CbusHFunction::Drive1),
+    ("IOMODE", CbusHFunction::IoMode),
+    ("TXDEN", CbusHFunction::TxDen),
+    ("CLK30", CbusHFunction::Clk30),
+    ("CLK15", CbusHFunction::Clk15),
+    ("CLK7_5", CbusHFunction::Clk7_5),
+];
+
+const CBUSX_NAMES: [(&str, CbusXFunction); 22] = [
+    ("TRISTATE", CbusXFunction::Tristate),
+    ("TXLED", CbusXFunction::TxLed),
+    ("RXLED", CbusXFunction::RxLed),
+    ("TXRXLED", CbusXFunction::TxRxLed),
+    ("PWREN", CbusXFunction::PwrEn),
+    ("SLEEP", CbusXFunction::Sleep),
+    ("DRIVE_0", CbusXFunction::Drive0),
+    ("DRIVE1", CbusXFunction::Drive1),
+    ("IOMODE", CbusXFunction::IoMode),
+    ("TXDEN", CbusXFunction::TxDen),
+    ("CLK24", CbusXFunction::Clk24),
+    ("CLK12", CbusXFunction::Clk12),
+    ("CLK6", CbusXFunction::Clk6),
+    ("BAT_DETECT", CbusXFunction::BatDetect),
+    ("BAT_DETECT_NEG", CbusXFunction::BatDetectNeg),
+    ("I2C_TXE", CbusXFunction::I2cTxe),
+    ("I2C_RXF", CbusXFunction::I2cRxf),
+    ("VBUS_SENSE", CbusXFunction::VbusSense),
+    ("BB_WR", CbusXFunction::BitbangWr),
+    ("BB_RD", CbusXFunction::BitbangRd),
+    ("TIME_STAMP", CbusXFunction::TimeStamp),
+    ("AWAKE", CbusXFunction::Awake),
+];
+
+const DRIVE_STRENGTH_NAMES: [(&str, DriveStrength); 4] = [
+    ("4MA", DriveStrength::Ma4),
+    ("8MA", DriveStrength::Ma8),
+    ("12MA", DriveStrength::Ma12),
+    ("16MA", DriveStrength::Ma16),
+];
+
+const CHANNEL_TYPE_NAMES: [(&str, ChannelType); 5] = [
+    ("UART", ChannelType::Uart),
+    ("FIFO", ChannelType::Fifo),
+    ("OPTO", ChannelType::Opto),
+    ("CPU", ChannelType::CpuFifo),
+    ("FT1284", ChannelType::FT1284),
+];
+
+// The values ftdi_eeprom assumes for keys missing from a file.
+fn ftdi_eeprom_defaults() -> EepromConfig {
+    EepromConfig {
+        vendor_id: 0x0403,
+        product_id: 0x6001,
+        manufacturer: "Acme Inc.".to_string(),
+        description: "USB Serial Converter".to_string(),
+        serial: "08-15".to_string(),
+        use_serial: false,
+        max_power: 0,
+        self_powered: true,
+        remote_wakeup: true,
+        suspend_pull_downs: false,
+        channel_a_type: ChannelType::Uart,
+        channel_b_type: ChannelType::Uart,
+        channel_a_driver: ChannelDriver::Vcp,
+        channel_b_driver: ChannelDriver::Vcp,
+        channel_c_driver: ChannelDriver::Vcp,
+        channel_d_driver: ChannelDriver::Vcp,
+        channel_a_rs485: false,
+        channel_b_rs485: false,
+        channel_c_rs485: false,
+        channel_d_rs485: false,
+        high_current_a: false,
+        high_current_b: false,
+        invert: UartInversion::default(),
+        pin_groups: [PinGroup::default(); PIN_GROUPS as usize],
+        cbus: None,
+    }
+}
+
+fn strip_comment(line: &str) -> &str {
+    let mut in_string = false;
+    let bytes = line.as_bytes();
+
+    for (i, &b) in bytes.iter().enumerate() {
+        match b {
+            b'"' => in_string = !in_string,
+            b'#' if !in_string => return &line[..i],
+            b'/' if !in_string && bytes.get(i + 1) == Some(&b'/') => return &line[..i],
+            _ => {}
+        }
+    }
+
+    line
+}
+
+fn parse_string(value: &str) -> Option<String> {
+    if value.len() >= 2 && value.starts_with('"') && value.ends_with('"') {
+        Some(value[1..value.len() - 1].to_string())
+    } else if !value.contains('"') {
+        Some(value.to_string())
+    } else {
+        None
+    }
+}
+
+fn parse_bool(value: &str) -> Option<bool> {
+    match value {
+        "true" | "yes" | "on" => Some(true),
+        "false" | "no" | "off" => Some(false),
+        _ => None,
+    }
+}
+
+fn parse_int(value: &str) -> Option<i64> {
+    if value.starts_with("0x") || value.starts_with("0X") {
+        i64::from_str_radix(&value[2..], 16).ok()
+    } else if value.len() > 1 && value.starts_with('0') {
+        i64::from_str_radix(&value[1..], 8).ok()
+    } else {
+        value.parse().ok()
+    }
+}
+
+fn lookup<T: Copy>(table: &[(&str, T)], value: &str) -> Option<T> {
+    table
+        .iter()
+        .find(|&&(name, _)| name == value)
+        .map(|&(_, item)| item)
+}
+
+// Parse the pin number following a `cbus`, `cbush` or `cbusx` key prefix.
+fn cbus_pin(key: &str, prefix: &str, pins: usize) -> Option<usize> {
+    if !key.starts_with(prefix) {
+        return None;
+    }
+
+    key[prefix.len()..].parse().ok().filter(|&pin| pin < pins)
+}
+
+// Split a `group<N>_<setting>` key into the group number and setting name.
+fn pin_group_key(key: &str) -> Option<(usize, &str)> {
+    let rest = key.strip_prefix("group")?;
+    let (group, setting) = match rest.as_bytes() {
+        [digit, b'_', ..] if digit.is_ascii_digit() => (usize::from(digit - b'0'), &rest[2..]),
+        _ => return None,
+    };
+
+    if group < PIN_GROUPS as usize {
+        Some((group, setting))
+    } else {
+        None
+    }
+}
+
+impl EepromConfig {
+    /// Parse the contents of an `ftdi_eeprom` configuration file.
+    ///
+    /// Keys missing from the file take the same defaults `ftdi_eeprom` would use. Keys that
+    /// only affect how `ftdi_eeprom` itself operates (e.g. `filename`) are accepted and ignored.
+    pub fn from_ftdi_eeprom_conf(conf: &str) -> Result<EepromConfig, ConfError> {
+        let mut config = ftdi_eeprom_defaults();
+
+        for (index, line) in conf.lines().enumerate() {
+            let err = |reason| ConfError {
+                line: index + 1,
+                reason,
+            };
+
+            let line = strip_comment(line).trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let mut parts = line.splitn(2, '=');
+            let key = parts.next().unwrap_or("").trim();
+            let value = match parts.next() {
+                Some(value) => value.trim(),
+                None => return Err(err("expected `key = value`")),
+            };
+
+            let bool_value = || parse_bool(value).ok_or_else(|| err("expected a boolean"));
+            let int_value = |max: i64| {
+                parse_int(value)
+                    .filter(|&v| v >= 0 && v <= max)
+                    .ok_or_else(|| err("expected an integer in range"))
+            };
+            let string_value = || parse_string(value).ok_or_else(|| err("malformed string"));
+            let channel_type = || {
+                parse_string(value)
+                    .and_then(|v| lookup(&CHANNEL_TYPE_NAMES, &v))
+                    .ok_or_else(|| err("unknown channel type"))
+            };
+            let driver = || {
+                bool_value().map(|vcp| {
+                    if vcp {
+                        ChannelDriver::Vcp
+                    } else {
+                        ChannelDriver::D2xx
+                    }
+                })
+            };
+
+            match key {
+                "vendor_id" => config.vendor_id = int_value(0xFFFF)? as u16,
+                "product_id" => config.product_id = int_value(0xFFFF)? as u16,
+                "manufacturer" => config.manufacturer = string_value()?,
+                "product" => config.description = string_value()?,
+                "serial" => config.serial = string_value()?,
+                "use_serial" => config.use_serial = bool_value()?,
+                "max_power" => config.max_power = int_value(500)? as u16,
+                "self_powered" => config.self_powered = bool_value()?,
+                "remote_wakeup" => config.remote_wakeup = bool_value()?,
+                "suspend_pull_downs" => config.suspend_pull_downs = bool_value()?,
+                "cha_type" => config.channel_a_type = channel_type()?,
+                "chb_type" => config.channel_b_type = channel_type()?,
+                "cha_vcp" => config.channel_a_driver = driver()?,
+                "chb_vcp" => config.channel_b_driver = driver()?,
+                "chc_vcp" => config.channel_c_driver = driver()?,
+                "chd_vcp" => config.channel_d_driver = driver()?,
+                "cha_rs485" => config.channel_a_rs485 = bool_value()?,
+                "chb_rs485" => config.channel_b_rs485 = bool_value()?,
+                "chc_rs485" => config.channel_c_rs485 = bool_value()?,
+                "chd_rs485" => config.channel_d_rs485 = bool_value()?,
+                _ if IGNORED_KEYS.contains(&key) => {}
+                "invert_txd" => config.invert.txd = bool_value()?,
+                "invert_rxd" => config.invert.rxd = bool_value()?,
+                "invert_rts" => config.invert.rts = bool_value()?,
+                "invert_cts" => config.invert.cts = bool_value()?,
+                "invert_dtr" => config.invert.dtr = bool_value()?,
+                "invert_dsr" => config.invert.dsr = bool_value()?,
+                "invert_dcd" => config.invert.dcd = bool_value()?,
+                "invert_ri" => config.invert.ri = bool_value()?,
+                _ => {
+                    let name = parse_string(value).unwrap_or_default();
+
+                    if let Some((group, setting)) = pin_group_key(key) {
+                        let settings = &mut config.pin_groups[group];
+                        match setting {
+                            "drive" => {
+                                settings.drive = lookup(&DRIVE_STRENGTH_NAMES, &name.to_uppercase())
+                                    .ok_or_else(|| err("unknown drive strength"))?
+                            }
+                            "slew" => settings.slow_slew = bool_value()?,
+                            "schmitt" => settings.schmitt = bool_value()?,
+                            _ => return Err(err("unknown key")),
+                        }
+                        continue;
+                    }
+
+                    // Check the longer prefixes first, since they all start with "cbus".
+                    if let Some(pin) = cbus_pin(key, "cbush", 10) {
+                        let function = lookup(&CBUSH_NAMES, &name)
+                            .ok_or_else(|| err("unknown CBUS function"))?;
+                        match config.cbus {
+                            None => {
+                                let mut functions = [CbusHFunction::Tristate; 10];
+                                functions[pin] = function;
+                                config.cbus = Some(CbusConfig::FT232H(functions));
+                            }
+                            Some(CbusConfig::FT232H(ref mut functions)) => {
+                                functions[pin] = function
+                            }
+                            Some(_) => return Err(err("mixed CBUS function sets")),
+                        }
+                    } else if let Some(pin) = cbus_pin(key, "cbusx", 4) {
+                        let function = lookup(&CBUSX_NAMES, &name)
+                            .ok_or_else(|| err("unknown CBUS function"))?;
+                        match config.cbus {
+                            None => {
+                                let mut functions = [
+                                    CbusXFunction::TxDen,
+                                    CbusXFunction::RxLed,
+                                    CbusXFunction::TxLed,
+                                    CbusXFunction::Sleep,
+                                ];
+                                functions[pin] = function;
+                                config.cbus = Some(CbusConfig::FTX(functions));
+                            }
+                            Some(CbusConfig::FTX(ref mut functions)) => functions[pin] = function,
+                            Some(_) => return Err(err("mixed CBUS function sets")),
+                        }
+                    } else if let Some(pin) = cbus_pin(key, "cbus", 5) {
+                        let function = lookup(&CBUS_NAMES, &name)
+                            .ok_or_else(|| err("unknown CBUS function"))?;
+                        match config.cbus {
+                            None => {
+                                let mut functions = [
+                                    CbusFunction::TxLed,
+                                    CbusFunction::RxLed,
+                                    CbusFunction::TxDen,
+                                    CbusFunction::PwrEn,
+                                    CbusFunction::Sleep,
+                                ];
+                                functions[pin] = function;
+                                config.cbus = Some(CbusConfig::FT232R(functions));
+                            }
+                            Some(CbusConfig::FT232R(ref mut functions)) => {
+                                functions[pin] = function
+                            }
+                            Some(_) => return Err(err("mixed CBUS function sets")),
+                        }
+                    } else {
+                        return Err(err("unknown key"));
+                    }
+                }
+            }
+        }
+
+        Ok(config)
+    }
+}
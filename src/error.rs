@@ -1,23 +1,193 @@
 use std;
 use std::fmt;
+use std::io;
+
+use usbc::constants::*;
 
 #[derive(Debug)]
 pub enum Error {
     LibFtdi(LibFtdiError),
+    LibUsb(LibUsbError),
+    /// The device was unplugged or otherwise went away while in use.
+    Disconnected,
+    /// An argument was rejected before reaching libftdi.
+    InvalidArgument(&'static str),
+    /// The device exists but another driver or program prevented opening it.
+    DeviceBusy(BusyReason),
+    /// An I2C target didn't acknowledge its address or a written byte.
+    Nack,
+    /// No 1-Wire device answered a bus reset with a presence pulse.
+    NoPresence,
+    /// An SWD transfer was not accepted, see [`SwdFault`].
+    Swd(SwdFault),
+    /// Socket or file I/O failed, e.g. while serving a network protocol.
+    Io(io::Error),
+    /// The operation was stopped through a [`CancellationToken`][::cancel::CancellationToken].
+    Cancelled,
+    /// The context already has a device open, e.g. in [`Context::set_interface`][::Context::set_interface].
+    AlreadyOpen,
     MallocFailure,
 }
 
+/// Why an SWD transfer failed, see [`Error::Swd`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SwdFault {
+    /// The target kept answering WAIT.
+    Wait,
+    /// The target answered FAULT; the sticky error flags in CTRL/STAT say why.
+    Fault,
+    /// The acknowledgement was none of OK, WAIT or FAULT, e.g. because nothing
+    /// answered.
+    Protocol(u8),
+    /// Data read from the target had the wrong parity.
+    Parity,
+}
+
+/// Why a device could not be opened, see [`Error::DeviceBusy`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum BusyReason {
+    /// The Linux `ftdi_sio` kernel driver is bound to the interface.
+    KernelDriver,
+    /// FTDI's D2XX/VCP driver (or the OS's own VCP driver) owns the device.
+    VendorDriver,
+    /// The current user may not access the device.
+    AccessDenied,
+    /// Another program has claimed the interface.
+    OtherProgram,
+}
+
+impl BusyReason {
+    /// Suggestion on how to free the device.
+    pub fn hint(self) -> &'static str {
+        match self {
+            BusyReason::KernelDriver => {
+                "unbind it (e.g. `sudo rmmod ftdi_sio`) or allow this process to detach kernel drivers"
+            },
+            BusyReason::VendorDriver => {
+                if cfg!(target_os = "macos") {
+                    "unload the VCP driver, e.g. `sudo kextunload -b com.apple.driver.AppleUSBFTDI` \
+                     or `sudo kextunload -b com.FTDI.driver.FTDIUSBSerialDriver`"
+                } else {
+                    "replace it with the WinUSB driver for this interface, e.g. using Zadig"
+                }
+            },
+            BusyReason::AccessDenied => {
+                "add a udev rule granting your user access to the device, or run with elevated privileges"
+            },
+            BusyReason::OtherProgram => {
+                "close any other program using the device"
+            }
+        }
+    }
+}
+
+/// Errors reported by libusb, either directly or passed through by libftdi.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LibUsbError {
+    Io,
+    InvalidParam,
+    Access,
+    NoDevice,
+    NotFound,
+    Busy,
+    Timeout,
+    Overflow,
+    Pipe,
+    Interrupted,
+    NoMem,
+    NotSupported,
+    Other,
+}
+
+impl LibUsbError {
+    pub fn from_code(code : i32) -> LibUsbError {
+        match code {
+            LIBUSB_ERROR_IO => LibUsbError::Io,
+            LIBUSB_ERROR_INVALID_PARAM => LibUsbError::InvalidParam,
+            LIBUSB_ERROR_ACCESS => LibUsbError::Access,
+            LIBUSB_ERROR_NO_DEVICE => LibUsbError::NoDevice,
+            LIBUSB_ERROR_NOT_FOUND => LibUsbError::NotFound,
+            LIBUSB_ERROR_BUSY => LibUsbError::Busy,
+            LIBUSB_ERROR_TIMEOUT => LibUsbError::Timeout,
+            LIBUSB_ERROR_OVERFLOW => LibUsbError::Overflow,
+            LIBUSB_ERROR_PIPE => LibUsbError::Pipe,
+            LIBUSB_ERROR_INTERRUPTED => LibUsbError::Interrupted,
+            LIBUSB_ERROR_NO_MEM => LibUsbError::NoMem,
+            LIBUSB_ERROR_NOT_SUPPORTED => LibUsbError::NotSupported,
+            _ => LibUsbError::Other,
+        }
+    }
+
+    pub fn code(self) -> i32 {
+        match self {
+            LibUsbError::Io => LIBUSB_ERROR_IO,
+            LibUsbError::InvalidParam => LIBUSB_ERROR_INVALID_PARAM,
+            LibUsbError::Access => LIBUSB_ERROR_ACCESS,
+            LibUsbError::NoDevice => LIBUSB_ERROR_NO_DEVICE,
+            LibUsbError::NotFound => LIBUSB_ERROR_NOT_FOUND,
+            LibUsbError::Busy => LIBUSB_ERROR_BUSY,
+            LibUsbError::Timeout => LIBUSB_ERROR_TIMEOUT,
+            LibUsbError::Overflow => LIBUSB_ERROR_OVERFLOW,
+            LibUsbError::Pipe => LIBUSB_ERROR_PIPE,
+            LibUsbError::Interrupted => LIBUSB_ERROR_INTERRUPTED,
+            LibUsbError::NoMem => LIBUSB_ERROR_NO_MEM,
+            LibUsbError::NotSupported => LIBUSB_ERROR_NOT_SUPPORTED,
+            LibUsbError::Other => LIBUSB_ERROR_OTHER,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct LibFtdiError {
-    // From looking at libftdi library, the error string is always a static
-    // string literal, so this lifetime is safe.
-    err_str : &'static str,
+    err_str : String,
+    // Return code of the failing libftdi call, if the error came from one.
+    code : Option<i32>,
 }
 
 impl LibFtdiError {
-    pub fn new(err_str : &'static str) -> LibFtdiError {
+    pub fn new<S: Into<String>>(err_str : S) -> LibFtdiError {
+        LibFtdiError {
+                err_str: err_str.into(),
+                code: None,
+        }
+    }
+
+    pub fn with_code<S: Into<String>>(err_str : S, code : i32) -> LibFtdiError {
         LibFtdiError {
-                err_str,
+                err_str: err_str.into(),
+                code: Some(code),
+        }
+    }
+
+    /// Return code of the libftdi function that failed, e.g. -666 for "device
+    /// unavailable". `None` for errors detected by this crate.
+    pub fn code(&self) -> Option<i32> {
+        self.code
+    }
+}
+
+impl Error {
+    /// Numeric code behind this error, if any.
+    ///
+    /// The two variants carrying one use different code spaces: `LibFtdi` gives the
+    /// return code of the failing libftdi function (see [`LibFtdiError::code`]), while
+    /// `LibUsb` gives the `LIBUSB_ERROR_*` code (see [`LibUsbError::code`]). The same
+    /// number can mean different things in each, so match on the variant before
+    /// interpreting it. All other variants return `None`.
+    pub fn code(&self) -> Option<i32> {
+        match self {
+            Error::LibFtdi(e) => e.code(),
+            Error::LibUsb(e) => Some(e.code()),
+            Error::Disconnected
+            | Error::InvalidArgument(_)
+            | Error::DeviceBusy(_)
+            | Error::Nack
+            | Error::NoPresence
+            | Error::Swd(_)
+            | Error::Io(_)
+            | Error::Cancelled
+            | Error::AlreadyOpen
+            | Error::MallocFailure => None,
         }
     }
 }
@@ -25,9 +195,39 @@ impl LibFtdiError {
 
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match &*self {
+        match self {
             Error::LibFtdi(e) => {
-                e.fmt(f)
+                write!(f, "libftdi error: {}", e)
+            },
+            Error::LibUsb(e) => {
+                write!(f, "libusb error: {}", e)
+            },
+            Error::Disconnected => {
+                write!(f, "device disconnected")
+            },
+            Error::InvalidArgument(msg) => {
+                write!(f, "invalid argument: {}", msg)
+            },
+            Error::DeviceBusy(reason) => {
+                write!(f, "unable to open device: {}; {}", reason, reason.hint())
+            },
+            Error::Nack => {
+                write!(f, "I2C target did not acknowledge")
+            },
+            Error::NoPresence => {
+                write!(f, "no 1-Wire device answered the reset")
+            },
+            Error::Swd(fault) => {
+                write!(f, "SWD transfer failed: {}", fault)
+            },
+            Error::Io(e) => {
+                write!(f, "I/O error: {}", e)
+            },
+            Error::Cancelled => {
+                write!(f, "operation cancelled")
+            },
+            Error::AlreadyOpen => {
+                write!(f, "a device is already open on this context")
             },
             Error::MallocFailure => {
                 write!(f, "malloc() failure")
@@ -36,23 +236,109 @@ impl fmt::Display for Error {
     }
 }
 
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Error {
+        Error::Io(e)
+    }
+}
+
+// For the `Read`/`Write` impls; timeouts keep their kind so callers can retry.
+impl From<Error> for io::Error {
+    fn from(e: Error) -> io::Error {
+        match e {
+            Error::Io(e) => e,
+            Error::LibUsb(LibUsbError::Timeout) => io::Error::new(io::ErrorKind::TimedOut, e),
+            e => io::Error::other(e),
+        }
+    }
+}
+
 impl fmt::Display for LibFtdiError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{}", self.err_str)
     }
 }
 
+impl fmt::Display for BusyReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let msg = match self {
+            BusyReason::KernelDriver => "the ftdi_sio kernel driver is using it",
+            BusyReason::VendorDriver => "the FTDI D2XX/VCP driver is using it",
+            BusyReason::AccessDenied => "permission denied",
+            BusyReason::OtherProgram => "another program has claimed it",
+        };
+        write!(f, "{}", msg)
+    }
+}
+
+impl fmt::Display for SwdFault {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SwdFault::Wait => write!(f, "target kept answering WAIT"),
+            SwdFault::Fault => write!(f, "target answered FAULT"),
+            SwdFault::Protocol(ack) => write!(f, "invalid acknowledgement {:#05b}", ack),
+            SwdFault::Parity => write!(f, "parity error in read data"),
+        }
+    }
+}
+
+impl fmt::Display for LibUsbError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let msg = match self {
+            LibUsbError::Io => "input/output error",
+            LibUsbError::InvalidParam => "invalid parameter",
+            LibUsbError::Access => "access denied (insufficient permissions)",
+            LibUsbError::NoDevice => "no such device (it may have been disconnected)",
+            LibUsbError::NotFound => "entity not found",
+            LibUsbError::Busy => "resource busy",
+            LibUsbError::Timeout => "operation timed out",
+            LibUsbError::Overflow => "overflow",
+            LibUsbError::Pipe => "pipe error (endpoint stalled)",
+            LibUsbError::Interrupted => "system call interrupted",
+            LibUsbError::NoMem => "insufficient memory",
+            LibUsbError::NotSupported => "operation not supported or unimplemented on this platform",
+            LibUsbError::Other => "other error",
+        };
+        write!(f, "{}", msg)
+    }
+}
+
 impl std::error::Error for Error {
-    fn cause(&self) -> Option<&dyn std::error::Error> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
         match *self {
+            // These already show the wrapped error's message, so skip to its source.
             Error::LibFtdi(ref ftdi_err) => {
-                Some(ftdi_err)
+                ftdi_err.source()
             },
-            Error::MallocFailure => {
+            Error::LibUsb(ref usb_err) => {
+                usb_err.source()
+            },
+            Error::Io(ref io_err) => {
+                io_err.source()
+            },
+            Error::Disconnected
+            | Error::InvalidArgument(_)
+            | Error::DeviceBusy(_)
+            | Error::Nack
+            | Error::NoPresence
+            | Error::Swd(_)
+            | Error::Cancelled
+            | Error::AlreadyOpen
+            | Error::MallocFailure => {
                 None
             }
         }
     }
 }
 
+// Errors must be usable across threads and inside boxed error types such as
+// `anyhow::Error`; fail the build if a variant ever breaks that.
+#[allow(dead_code)]
+fn assert_error_is_send_sync() {
+    fn check<E: std::error::Error + Send + Sync + 'static>() {}
+    check::<Error>();
+}
+
 impl std::error::Error for LibFtdiError {}
+
+impl std::error::Error for LibUsbError {}
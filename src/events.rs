@@ -0,0 +1,242 @@
+//! Consuming a device as a stream of events from a service thread, for GUI or
+//! actor-based applications that can't block on reads.
+
+use std::sync::atomic::Ordering;
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use background::BackgroundThread;
+use error::Error;
+use ftdic;
+use shared::SharedDevice;
+use threads::ThreadOptions;
+use {Device, Result};
+
+/// Modem and line status of a UART, as returned by [`Device::poll_modem_status`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ModemStatus {
+    /// Clear to send.
+    pub cts: bool,
+    /// Data set ready.
+    pub dsr: bool,
+    /// Ring indicator.
+    pub ri: bool,
+    /// Data carrier detect.
+    pub dcd: bool,
+    /// Received data was lost because the chip's buffer was full.
+    pub overrun: bool,
+    pub parity_error: bool,
+    pub framing_error: bool,
+    /// A break condition was received.
+    pub break_received: bool,
+}
+
+impl ModemStatus {
+    /// Decode the status word, modem status in the low byte and line status in the
+    /// high byte.
+    pub fn from_raw(raw: u16) -> ModemStatus {
+        ModemStatus {
+            cts: raw & 0x0010 != 0,
+            dsr: raw & 0x0020 != 0,
+            ri: raw & 0x0040 != 0,
+            dcd: raw & 0x0080 != 0,
+            overrun: raw & 0x0200 != 0,
+            parity_error: raw & 0x0400 != 0,
+            framing_error: raw & 0x0800 != 0,
+            break_received: raw & 0x1000 != 0,
+        }
+    }
+}
+
+impl Device {
+    /// Read the modem and line status from the chip
+    pub fn poll_modem_status(&self) -> Result<ModemStatus> {
+        let mut status = 0;
+        let rc =
+            unsafe { ftdic::ftdi_poll_modem_status(self.context.get_ftdi_context(), &mut status) };
+        self.context.check_ftdi_error(rc)?;
+        Ok(ModemStatus::from_raw(status))
+    }
+
+    /// Hand the device to a service thread reporting its events, see [`EventService`].
+    ///
+    /// Fails with [`Error::Io`] if the thread can't be created.
+    pub fn events(self) -> Result<(EventService, Receiver<DeviceEvent>)> {
+        EventService::spawn(SharedDevice::new(self), EventConfig::default())
+    }
+}
+
+#[derive(Debug)]
+pub enum DeviceEvent {
+    /// Data received from the chip.
+    Data(Vec<u8>),
+    /// The modem or line status changed; also sent once at the start.
+    ModemStatus(ModemStatus),
+    /// A read or status poll failed; the service keeps going.
+    Error(Error),
+    /// The device went away; this is the last event.
+    Disconnected,
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct EventConfig {
+    /// Largest amount of data in one [`DeviceEvent::Data`].
+    pub chunk_size: usize,
+    /// How often to poll the modem status.
+    pub status_interval: Duration,
+    /// How long to wait before retrying after an error.
+    pub retry_delay: Duration,
+}
+
+impl Default for EventConfig {
+    fn default() -> EventConfig {
+        EventConfig {
+            chunk_size: 4096,
+            status_interval: Duration::from_millis(50),
+            retry_delay: Duration::from_millis(100),
+        }
+    }
+}
+
+/// Reads a device on a background thread, delivering what happens as
+/// [`DeviceEvent`]s.
+///
+/// The device is locked only for each read or poll, so it can still be written to
+/// through [`device`][EventService::device]. The thread stops when dropped, through
+/// [`stop`][EventService::stop], when the device disconnects or when the receiver is
+/// dropped.
+pub struct EventService {
+    device: SharedDevice,
+    thread: BackgroundThread<()>,
+}
+
+impl EventService {
+    /// Start the service thread.
+    ///
+    /// Fails with [`Error::Io`] if the thread can't be created.
+    pub fn spawn(
+        device: SharedDevice,
+        config: EventConfig,
+    ) -> Result<(EventService, Receiver<DeviceEvent>)> {
+        EventService::spawn_with(device, config, &ThreadOptions::new())
+    }
+
+    /// Like [`spawn`][EventService::spawn], but create the service thread with
+    /// `options`, e.g. to raise its priority for sustained high-rate reads.
+    pub fn spawn_with(
+        device: SharedDevice,
+        config: EventConfig,
+        options: &ThreadOptions,
+    ) -> Result<(EventService, Receiver<DeviceEvent>)> {
+        let (sender, events) = mpsc::channel();
+        let thread = {
+            let device = device.clone();
+            BackgroundThread::spawn(options, move |running| {
+                let mut buf = vec![0; config.chunk_size.max(1)];
+                let mut status = None;
+                let mut next_poll = Instant::now();
+
+                while running.load(Ordering::SeqCst) {
+                    let mut events = Vec::new();
+                    {
+                        let device = device.lock();
+                        match device.read_data(&mut buf) {
+                            Ok(0) => {}
+                            Ok(n) => events.push(DeviceEvent::Data(buf[..n].to_vec())),
+                            Err(e) => events.push(DeviceEvent::from(e)),
+                        }
+                        if Instant::now() >= next_poll {
+                            next_poll = Instant::now() + config.status_interval;
+                            match device.poll_modem_status() {
+                                Ok(new) if status != Some(new) => {
+                                    status = Some(new);
+                                    events.push(DeviceEvent::ModemStatus(new));
+                                }
+                                Ok(_) => {}
+                                Err(e) => events.push(DeviceEvent::from(e)),
+                            }
+                        }
+                    }
+
+                    let mut failed = false;
+                    for event in events {
+                        let disconnected = matches!(event, DeviceEvent::Disconnected);
+                        failed |= matches!(event, DeviceEvent::Error(_));
+                        // Stop once nobody listens anymore.
+                        if sender.send(event).is_err() || disconnected {
+                            return;
+                        }
+                    }
+                    if failed {
+                        thread::sleep(config.retry_delay);
+                    }
+                }
+            })?
+        };
+
+        let service = EventService { device, thread };
+        Ok((service, events))
+    }
+
+    /// The device, e.g. to write to it while events are delivered.
+    pub fn device(&self) -> &SharedDevice {
+        &self.device
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.thread.is_running()
+    }
+
+    /// Stop the service thread and return the device; unless it was cloned, get the
+    /// [`Device`] itself back with [`SharedDevice::try_unwrap`].
+    pub fn stop(self) -> SharedDevice {
+        let EventService { device, mut thread } = self;
+        thread.join();
+        device
+    }
+}
+
+impl From<Error> for DeviceEvent {
+    fn from(e: Error) -> DeviceEvent {
+        match e {
+            Error::Disconnected => DeviceEvent::Disconnected,
+            e => DeviceEvent::Error(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type Field = fn(&ModemStatus) -> bool;
+
+    #[test]
+    fn modem_status_bits() {
+        let idle = ModemStatus::from_raw(0x6001);
+        assert_eq!(idle, ModemStatus::from_raw(0));
+
+        let fields: [(u16, Field); 8] = [
+            (0x0010, |s| s.cts),
+            (0x0020, |s| s.dsr),
+            (0x0040, |s| s.ri),
+            (0x0080, |s| s.dcd),
+            (0x0200, |s| s.overrun),
+            (0x0400, |s| s.parity_error),
+            (0x0800, |s| s.framing_error),
+            (0x1000, |s| s.break_received),
+        ];
+        for &(bit, field) in &fields {
+            let status = ModemStatus::from_raw(bit);
+            assert!(field(&status), "{:#06x}", bit);
+            let others = fields.iter().filter(|&&(other, _)| other != bit);
+            assert!(
+                others.clone().all(|&(_, field)| !field(&status)),
+                "{:#06x}",
+                bit
+            );
+            assert!(!field(&ModemStatus::from_raw(!bit)), "{:#06x}", bit);
+        }
+    }
+}
@@ -0,0 +1,180 @@
+//! Asynchronous 245 FIFO mode, the parallel interface of the FT245R and FT245BM and of
+//! FT2232C/D/H and FT232H channels that their EEPROM configures as FIFOs.
+//!
+//! Bytes move over the eight data pins under control of the peripheral, with two
+//! status outputs of the chip for flow control:
+//!
+//! * `RXF#` is low while the chip holds data from the host that the peripheral hasn't
+//!   taken with `RD#` yet. While the peripheral doesn't read, the chip's receive
+//!   buffer fills up and further writes from the host stall.
+//! * `TXE#` is low while the peripheral may write a byte with `WR#`. It goes high
+//!   while the chip's transmit buffer is full because the host isn't reading; no data
+//!   is lost, the peripheral has to wait.
+//!
+//! Both are driven by the chip and can't be read back from the host, since the pins
+//! belong to the FIFO rather than to a GPIO port. [`Fifo245`] handles them through
+//! their effect on transfers instead: writes keep going as long as the peripheral
+//! takes data and fail with a timeout once it stops, and reads wait for the
+//! peripheral to send something. Bytes written by the peripheral that don't fill a
+//! USB packet are sent to the host once the latency timer expires, or at once when
+//! the peripheral strobes `SIWU#`.
+//!
+//! The synchronous FIFO of the FT2232H and FT232H is a different mode, entered with
+//! [`BitMode::SyncFF`] on a channel configured like this.
+
+use std::io::{self, Read, Write};
+
+use eeprom::ChannelType;
+use error::{Error, LibUsbError};
+use {BitMode, ChipType, Device, Interface, Result};
+
+/// Latency timer set by [`Device::into_fifo245`], in milliseconds.
+///
+/// Short, so that the bytes of a peripheral sending less than a packet at a time
+/// don't sit in the chip for the default 16 ms.
+pub const FIFO_LATENCY: u8 = 2;
+
+/// Read and write chunk size set by [`Device::into_fifo245`].
+///
+/// The largest read chunk libftdi accepts on Linux, so long transfers take fewer USB
+/// requests.
+pub const FIFO_CHUNK_SIZE: u32 = 16384;
+
+impl Device {
+    /// Reset the chip to asynchronous 245 FIFO mode, with a latency timer and chunk
+    /// sizes suited to streaming, see [`FIFO_LATENCY`] and [`FIFO_CHUNK_SIZE`].
+    ///
+    /// On the FT2232C/D/H and FT232H, the EEPROM has to configure the channel as a
+    /// FIFO. Other chips can't be told apart from their UART variants (e.g. the FT245R
+    /// from the FT232R), so this is up to the caller.
+    pub fn into_fifo245(mut self) -> Result<Fifo245> {
+        match self.chip_type() {
+            Some(ChipType::FT4232H) => {
+                return Err(Error::InvalidArgument("The FT4232H has no FIFO mode"))
+            }
+            Some(ChipType::FT2232C) | Some(ChipType::FT2232H) | Some(ChipType::FT232H) => {
+                let channel = match self.interface() {
+                    Interface::Any => Interface::A,
+                    interface => interface,
+                };
+                if self.channel_type(channel)? != ChannelType::Fifo {
+                    return Err(Error::InvalidArgument(
+                        "EEPROM does not configure the channel as a 245 FIFO",
+                    ));
+                }
+            }
+            _ => {}
+        }
+
+        self.set_bitmode(0, BitMode::Reset)?;
+        self.set_latency_timer(FIFO_LATENCY)?;
+        self.set_read_chunk_size(FIFO_CHUNK_SIZE)?;
+        self.set_write_chunk_size(FIFO_CHUNK_SIZE)?;
+        Ok(Fifo245 { device: self })
+    }
+}
+
+/// A channel in asynchronous 245 FIFO mode, see [`Device::into_fifo245`].
+///
+/// Besides the methods below, it implements [`Read`] and [`Write`], with timeouts
+/// reported as [`io::ErrorKind::TimedOut`].
+pub struct Fifo245 {
+    device: Device,
+}
+
+impl Fifo245 {
+    /// Wait for data from the peripheral, returning how much was read.
+    ///
+    /// Fails with a timeout error if the peripheral writes nothing within the read
+    /// timeout, see [`set_timeouts`][Fifo245::set_timeouts].
+    pub fn read(&self, data: &mut [u8]) -> Result<usize> {
+        self.device.read_some(data)
+    }
+
+    /// See [`Device::read_exact`].
+    pub fn read_exact(&self, data: &mut [u8]) -> Result<()> {
+        self.device.read_exact(data)
+    }
+
+    /// Send all of `data`, waiting while the peripheral drains the chip's buffer.
+    ///
+    /// Fails with a timeout error if the peripheral stops reading, leaving `RXF#` low,
+    /// for longer than the write timeout. Part of `data` may have been sent by then.
+    pub fn write_all(&self, mut data: &[u8]) -> Result<()> {
+        while !data.is_empty() {
+            let written = self.device.write_data(data)?;
+            if written == 0 {
+                return Err(Error::LibUsb(LibUsbError::Timeout));
+            }
+            data = &data[written..];
+        }
+        Ok(())
+    }
+
+    /// See [`Device::read_data`].
+    pub fn read_data(&self, data: &mut [u8]) -> Result<usize> {
+        self.device.read_data(data)
+    }
+
+    /// See [`Device::write_data`].
+    pub fn write_data(&self, data: &[u8]) -> Result<usize> {
+        self.device.write_data(data)
+    }
+
+    /// See [`Device::set_latency_timer`].
+    pub fn set_latency_timer(&self, latency: u8) -> Result<()> {
+        self.device.set_latency_timer(latency)
+    }
+
+    /// See [`Device::set_read_chunk_size`].
+    pub fn set_read_chunk_size(&self, size: u32) -> Result<()> {
+        self.device.set_read_chunk_size(size)
+    }
+
+    /// See [`Device::set_write_chunk_size`].
+    pub fn set_write_chunk_size(&self, size: u32) -> Result<()> {
+        self.device.set_write_chunk_size(size)
+    }
+
+    /// See [`Device::set_timeouts`].
+    pub fn set_timeouts(&self, read_timeout: i32, write_timeout: i32) {
+        self.device.set_timeouts(read_timeout, write_timeout)
+    }
+
+    /// See [`Device::purge_usb_buffers`].
+    pub fn purge_usb_buffers(&self) -> Result<()> {
+        self.device.purge_usb_buffers()
+    }
+
+    pub fn chip_type(&self) -> Option<ChipType> {
+        self.device.chip_type()
+    }
+
+    pub fn device(&self) -> &Device {
+        &self.device
+    }
+
+    /// Return the underlying device, leaving the chip in FIFO mode.
+    pub fn into_inner(self) -> Device {
+        self.device
+    }
+}
+
+impl Read for Fifo245 {
+    fn read(&mut self, data: &mut [u8]) -> io::Result<usize> {
+        Fifo245::read(self, data).map_err(io::Error::from)
+    }
+}
+
+impl Write for Fifo245 {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        match self.device.write_data(data)? {
+            0 if !data.is_empty() => Err(Error::LibUsb(LibUsbError::Timeout).into()),
+            written => Ok(written),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,254 @@
+//! Atmel/Adesto AT45DB DataFlash chips.
+//!
+//! Unlike 25-series parts, DataFlash is programmed a page at a time through an
+//! on-chip SRAM buffer. Pages hold 264, 528 or 1056 bytes out of the box, or 256,
+//! 512 or 1024 once the chip is switched to power-of-two page sizes; the page size
+//! is detected from the status register.
+
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::{JedecId, CHIP_ERASE_TIMEOUT, OPERATION_TIMEOUT, READ_CHUNK, READ_JEDEC_ID};
+use error::{Error, LibUsbError};
+use spi::Spi;
+use Result;
+
+const READ_STATUS: u8 = 0xD7;
+const CONTINUOUS_READ: u8 = 0x0B;
+const BUFFER1_WRITE: u8 = 0x84;
+const BUFFER1_TO_PAGE_ERASE: u8 = 0x83;
+const BUFFER1_TO_PAGE: u8 = 0x88;
+const PAGE_ERASE: u8 = 0x81;
+const SECTOR_ERASE: u8 = 0x7C;
+const CHIP_ERASE: [u8; 4] = [0xC7, 0x94, 0x80, 0x9A];
+const POWER_OF_TWO_PAGES: [u8; 4] = [0x3D, 0x2A, 0x80, 0xA6];
+
+const STATUS_READY: u8 = 0x80;
+/// Set if the chip uses power-of-two page sizes.
+const STATUS_POWER_OF_TWO: u8 = 0x01;
+
+/// An AT45DB DataFlash chip.
+pub struct DataFlash<S> {
+    spi: S,
+    page_count: u32,
+    page_size: usize,
+    // Position of the page number in an address.
+    page_shift: u32,
+}
+
+impl<S: Spi> DataFlash<S> {
+    /// Detect the density and page size of the chip from its status register.
+    pub fn new(mut spi: S) -> Result<DataFlash<S>> {
+        let status = read_status(&mut spi)?;
+
+        // Density code in bits 5..2 of the status register.
+        let (page_count, page_size) = match (status >> 2) & 0x0f {
+            0b0011 => (512, 264),
+            0b0101 => (1024, 264),
+            0b0111 => (2048, 264),
+            0b1001 => (4096, 264),
+            0b1011 => (4096, 528),
+            0b1101 => (8192, 528),
+            0b1111 => (8192, 1056),
+            _ => return Err(Error::InvalidArgument("Unknown DataFlash density")),
+        };
+        let page_size = if status & STATUS_POWER_OF_TWO != 0 {
+            (page_size as usize).next_power_of_two() / 2
+        } else {
+            page_size as usize
+        };
+
+        Ok(DataFlash {
+            spi,
+            page_count,
+            page_size,
+            page_shift: page_size.next_power_of_two().trailing_zeros(),
+        })
+    }
+
+    /// Return the SPI master.
+    pub fn into_inner(self) -> S {
+        self.spi
+    }
+
+    pub fn page_size(&self) -> usize {
+        self.page_size
+    }
+
+    pub fn page_count(&self) -> u32 {
+        self.page_count
+    }
+
+    /// Whether the chip was switched to power-of-two page sizes.
+    pub fn has_power_of_two_pages(&self) -> bool {
+        self.page_size.is_power_of_two()
+    }
+
+    /// Switch the chip to power-of-two page sizes.
+    ///
+    /// This can only be done once and can't be undone. The chip must be power cycled
+    /// before the new page size takes effect; create a new `DataFlash` afterwards.
+    pub fn enable_power_of_two_pages(&mut self) -> Result<()> {
+        self.spi.command(&POWER_OF_TWO_PAGES, &mut [])?;
+        self.wait_ready(OPERATION_TIMEOUT)
+    }
+
+    pub fn jedec_id(&mut self) -> Result<JedecId> {
+        let mut id = [0; 3];
+        self.spi.command(&[READ_JEDEC_ID], &mut id)?;
+        Ok(JedecId {
+            manufacturer: id[0],
+            memory_type: id[1],
+            capacity: id[2],
+        })
+    }
+
+    pub fn read_status(&mut self) -> Result<u8> {
+        read_status(&mut self.spi)
+    }
+
+    /// Poll the status register until the chip finishes programming or erasing.
+    ///
+    /// Fails with a timeout error if it is still busy after `timeout`.
+    pub fn wait_ready(&mut self, timeout: Duration) -> Result<()> {
+        let start = Instant::now();
+        while self.read_status()? & STATUS_READY == 0 {
+            if start.elapsed() >= timeout {
+                return Err(Error::LibUsb(LibUsbError::Timeout));
+            }
+            thread::sleep(Duration::from_millis(1));
+        }
+        Ok(())
+    }
+
+    // `opcode` followed by the address of byte `offset` in `page`.
+    fn address_command(&self, opcode: u8, page: u32, offset: usize) -> Result<[u8; 4]> {
+        if page >= self.page_count {
+            return Err(Error::InvalidArgument("DataFlash page out of range"));
+        }
+        let address = (page << self.page_shift) | offset as u32;
+        Ok([
+            opcode,
+            (address >> 16) as u8,
+            (address >> 8) as u8,
+            address as u8,
+        ])
+    }
+
+    // Send a command that starts an internal operation and wait for it to finish.
+    fn run(&mut self, cmd: &[u8], timeout: Duration) -> Result<()> {
+        // The operation starts when chip select is released.
+        self.spi.command(cmd, &mut [])?;
+        self.wait_ready(timeout)
+    }
+
+    /// Erase and program `page` with `data`, which must not be longer than a page.
+    ///
+    /// A shorter `data` is padded with 0xFF.
+    pub fn write_page(&mut self, page: u32, data: &[u8]) -> Result<()> {
+        self.load_buffer(data)?;
+        let cmd = self.address_command(BUFFER1_TO_PAGE_ERASE, page, 0)?;
+        self.run(&cmd, OPERATION_TIMEOUT)
+    }
+
+    /// Like [`write_page`][DataFlash::write_page], but for a page that was erased
+    /// before, which is faster.
+    pub fn program_page(&mut self, page: u32, data: &[u8]) -> Result<()> {
+        self.load_buffer(data)?;
+        let cmd = self.address_command(BUFFER1_TO_PAGE, page, 0)?;
+        self.run(&cmd, OPERATION_TIMEOUT)
+    }
+
+    fn load_buffer(&mut self, data: &[u8]) -> Result<()> {
+        if data.len() > self.page_size {
+            return Err(Error::InvalidArgument("Data longer than a DataFlash page"));
+        }
+
+        let mut buffer = vec![0xff; self.page_size];
+        buffer[..data.len()].copy_from_slice(data);
+        self.spi.select()?;
+        let res = self
+            .spi
+            .write(&[BUFFER1_WRITE, 0, 0, 0])
+            .and_then(|_| self.spi.write(&buffer));
+        let deselected = self.spi.deselect();
+        res.and(deselected)
+    }
+
+    pub fn erase_page(&mut self, page: u32) -> Result<()> {
+        let cmd = self.address_command(PAGE_ERASE, page, 0)?;
+        self.run(&cmd, OPERATION_TIMEOUT)
+    }
+
+    /// Erase the sector containing `page`; sector sizes depend on the density.
+    ///
+    /// Sector 0 is split in two (0a and 0b), which must be erased separately.
+    pub fn erase_sector(&mut self, page: u32) -> Result<()> {
+        let cmd = self.address_command(SECTOR_ERASE, page, 0)?;
+        self.run(&cmd, OPERATION_TIMEOUT)
+    }
+
+    pub fn erase_chip(&mut self) -> Result<()> {
+        self.run(&CHIP_ERASE, CHIP_ERASE_TIMEOUT)
+    }
+
+    /// Fill `data` starting at the beginning of `page`, continuing into the following
+    /// pages.
+    pub fn read(&mut self, page: u32, data: &mut [u8]) -> Result<()> {
+        let mut cmd = self.address_command(CONTINUOUS_READ, page, 0)?.to_vec();
+        // The read needs a dummy byte before the data.
+        cmd.push(0);
+        self.spi.command(&cmd, data)
+    }
+
+    /// Write `data` a page at a time from `first_page`, erasing each page first.
+    /// `progress` is called with the bytes written so far and the total.
+    pub fn program<F>(&mut self, first_page: u32, data: &[u8], mut progress: F) -> Result<()>
+    where
+        F: FnMut(usize, usize),
+    {
+        let mut done = 0;
+        progress(0, data.len());
+        for (page, chunk) in (first_page..).zip(data.chunks(self.page_size)) {
+            self.write_page(page, chunk)?;
+            done += chunk.len();
+            progress(done, data.len());
+        }
+        Ok(())
+    }
+
+    /// Compare the contents from `first_page` with `data`, returning the index into
+    /// `data` of the first difference. `progress` is called with the bytes compared so
+    /// far and the total.
+    pub fn verify<F>(
+        &mut self,
+        first_page: u32,
+        data: &[u8],
+        mut progress: F,
+    ) -> Result<Option<usize>>
+    where
+        F: FnMut(usize, usize),
+    {
+        // Read whole pages, since reads can only start at a page boundary.
+        let pages_per_chunk = (READ_CHUNK / self.page_size).max(1);
+        let mut contents = vec![0; pages_per_chunk * self.page_size];
+        let mut done = 0;
+        progress(0, data.len());
+        for (i, expected) in data.chunks(contents.len()).enumerate() {
+            let contents = &mut contents[..expected.len()];
+            self.read(first_page + (i * pages_per_chunk) as u32, contents)?;
+            if let Some(j) = contents.iter().zip(expected).position(|(a, b)| a != b) {
+                return Ok(Some(done + j));
+            }
+            done += expected.len();
+            progress(done, data.len());
+        }
+        Ok(None)
+    }
+}
+
+fn read_status<S: Spi>(spi: &mut S) -> Result<u8> {
+    let mut status = [0];
+    spi.command(&[READ_STATUS], &mut status)?;
+    Ok(status[0])
+}
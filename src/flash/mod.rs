@@ -0,0 +1,239 @@
+//! Programming SPI NOR flash chips, such as the configuration flash of FPGA boards.
+//!
+//! [`SpiFlash`] works over any [`Spi`] master, e.g. [`mpsse::Spi`][::mpsse::Spi] or
+//! [`bitbang::Spi`][::bitbang::Spi], and uses the command set common to 25-series
+//! parts with 3-byte addresses. Atmel/Adesto DataFlash chips have their own
+//! command set, see [`dataflash`].
+
+use std::thread;
+use std::time::{Duration, Instant};
+
+use error::{Error, LibUsbError};
+use spi::Spi;
+use Result;
+
+pub mod dataflash;
+
+const WRITE_ENABLE: u8 = 0x06;
+const READ_STATUS: u8 = 0x05;
+const PAGE_PROGRAM: u8 = 0x02;
+const FAST_READ: u8 = 0x0B;
+const SECTOR_ERASE: u8 = 0x20;
+const BLOCK_ERASE: u8 = 0xD8;
+const CHIP_ERASE: u8 = 0xC7;
+const READ_JEDEC_ID: u8 = 0x9F;
+
+/// Status register bit set while a program or erase is in progress.
+const STATUS_BUSY: u8 = 0x01;
+
+pub const PAGE_SIZE: usize = 256;
+pub const SECTOR_SIZE: usize = 4096;
+pub const BLOCK_SIZE: usize = 65536;
+
+/// Bytes read per round by [`SpiFlash::verify`] and reported as progress.
+const READ_CHUNK: usize = 4096;
+
+/// How long to wait for a sector or page operation; chip erase gets its own timeout.
+const OPERATION_TIMEOUT: Duration = Duration::from_secs(5);
+const CHIP_ERASE_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Manufacturer and device identification, as returned by the JEDEC ID command.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct JedecId {
+    pub manufacturer: u8,
+    pub memory_type: u8,
+    pub capacity: u8,
+}
+
+impl JedecId {
+    /// Size in bytes, if the capacity byte follows the usual power of two encoding.
+    pub fn size(&self) -> Option<usize> {
+        match self.capacity {
+            0x10..=0x1f => Some(1 << self.capacity),
+            _ => None,
+        }
+    }
+}
+
+/// A 25-series SPI NOR flash chip.
+pub struct SpiFlash<S> {
+    spi: S,
+}
+
+impl<S: Spi> SpiFlash<S> {
+    pub fn new(spi: S) -> SpiFlash<S> {
+        SpiFlash { spi }
+    }
+
+    /// Return the SPI master.
+    pub fn into_inner(self) -> S {
+        self.spi
+    }
+
+    pub fn jedec_id(&mut self) -> Result<JedecId> {
+        let mut id = [0; 3];
+        self.spi.command(&[READ_JEDEC_ID], &mut id)?;
+        Ok(JedecId {
+            manufacturer: id[0],
+            memory_type: id[1],
+            capacity: id[2],
+        })
+    }
+
+    pub fn read_status(&mut self) -> Result<u8> {
+        let mut status = [0];
+        self.spi.command(&[READ_STATUS], &mut status)?;
+        Ok(status[0])
+    }
+
+    /// Poll the status register until the chip finishes programming or erasing.
+    ///
+    /// Fails with a timeout error if it is still busy after `timeout`.
+    pub fn wait_ready(&mut self, timeout: Duration) -> Result<()> {
+        let start = Instant::now();
+        while self.read_status()? & STATUS_BUSY != 0 {
+            if start.elapsed() >= timeout {
+                return Err(Error::LibUsb(LibUsbError::Timeout));
+            }
+            thread::sleep(Duration::from_millis(1));
+        }
+        Ok(())
+    }
+
+    pub fn write_enable(&mut self) -> Result<()> {
+        self.spi.command(&[WRITE_ENABLE], &mut [])
+    }
+
+    // Run a write-enabled command taking an address and wait for it to finish.
+    fn address_command(
+        &mut self,
+        opcode: u8,
+        address: u32,
+        data: &[u8],
+        timeout: Duration,
+    ) -> Result<()> {
+        if address >> 24 != 0 {
+            return Err(Error::InvalidArgument("Flash address must fit in 24 bits"));
+        }
+
+        self.write_enable()?;
+        self.spi.select()?;
+        let res = self
+            .spi
+            .write(&address_bytes(opcode, address))
+            .and_then(|_| self.spi.write(data));
+        let deselected = self.spi.deselect();
+        res.and(deselected)?;
+        self.wait_ready(timeout)
+    }
+
+    /// Erase the 4 KiB sector containing `address`.
+    pub fn erase_sector(&mut self, address: u32) -> Result<()> {
+        self.address_command(SECTOR_ERASE, address, &[], OPERATION_TIMEOUT)
+    }
+
+    /// Erase the 64 KiB block containing `address`.
+    pub fn erase_block(&mut self, address: u32) -> Result<()> {
+        self.address_command(BLOCK_ERASE, address, &[], OPERATION_TIMEOUT)
+    }
+
+    pub fn erase_chip(&mut self) -> Result<()> {
+        self.write_enable()?;
+        self.spi.command(&[CHIP_ERASE], &mut [])?;
+        self.wait_ready(CHIP_ERASE_TIMEOUT)
+    }
+
+    /// Program `data` into a single page; it must not cross a page boundary.
+    pub fn program_page(&mut self, address: u32, data: &[u8]) -> Result<()> {
+        if address as usize % PAGE_SIZE + data.len() > PAGE_SIZE {
+            return Err(Error::InvalidArgument("Data crosses a flash page boundary"));
+        }
+        self.address_command(PAGE_PROGRAM, address, data, OPERATION_TIMEOUT)
+    }
+
+    /// Fill `data` starting at `address`.
+    pub fn read(&mut self, address: u32, data: &mut [u8]) -> Result<()> {
+        if address >> 24 != 0 {
+            return Err(Error::InvalidArgument("Flash address must fit in 24 bits"));
+        }
+
+        let mut cmd = address_bytes(FAST_READ, address).to_vec();
+        // Fast read needs a dummy byte before the data.
+        cmd.push(0);
+        self.spi.command(&cmd, data)
+    }
+
+    /// Erase the sectors covering `len` bytes from `address`, using block erases where
+    /// possible. `progress` is called with the bytes erased so far and the total.
+    pub fn erase<F>(&mut self, address: u32, len: usize, mut progress: F) -> Result<()>
+    where
+        F: FnMut(usize, usize),
+    {
+        let start = address as usize / SECTOR_SIZE * SECTOR_SIZE;
+        let end = (address as usize + len).div_ceil(SECTOR_SIZE) * SECTOR_SIZE;
+
+        let mut pos = start;
+        progress(0, end - start);
+        while pos < end {
+            if pos.is_multiple_of(BLOCK_SIZE) && end - pos >= BLOCK_SIZE {
+                self.erase_block(pos as u32)?;
+                pos += BLOCK_SIZE;
+            } else {
+                self.erase_sector(pos as u32)?;
+                pos += SECTOR_SIZE;
+            }
+            progress(pos - start, end - start);
+        }
+        Ok(())
+    }
+
+    /// Program `data` from `address` a page at a time; the area must be erased.
+    /// `progress` is called with the bytes written so far and the total.
+    pub fn program<F>(&mut self, address: u32, data: &[u8], mut progress: F) -> Result<()>
+    where
+        F: FnMut(usize, usize),
+    {
+        let mut done = 0;
+        progress(0, data.len());
+        while done < data.len() {
+            let page_offset = (address as usize + done) % PAGE_SIZE;
+            let len = (PAGE_SIZE - page_offset).min(data.len() - done);
+            self.program_page(address + done as u32, &data[done..done + len])?;
+            done += len;
+            progress(done, data.len());
+        }
+        Ok(())
+    }
+
+    /// Compare the flash contents from `address` with `data`, returning the address of
+    /// the first difference. `progress` is called with the bytes compared so far and
+    /// the total.
+    pub fn verify<F>(&mut self, address: u32, data: &[u8], mut progress: F) -> Result<Option<u32>>
+    where
+        F: FnMut(usize, usize),
+    {
+        let mut contents = vec![0; READ_CHUNK];
+        let mut done = 0;
+        progress(0, data.len());
+        for expected in data.chunks(READ_CHUNK) {
+            let contents = &mut contents[..expected.len()];
+            self.read(address + done as u32, contents)?;
+            if let Some(i) = contents.iter().zip(expected).position(|(a, b)| a != b) {
+                return Ok(Some(address + (done + i) as u32));
+            }
+            done += expected.len();
+            progress(done, data.len());
+        }
+        Ok(None)
+    }
+}
+
+// `opcode` followed by a 24-bit address, MSB first.
+fn address_bytes(opcode: u8, address: u32) -> [u8; 4] {
+    [
+        opcode,
+        (address >> 16) as u8,
+        (address >> 8) as u8,
+        address as u8,
+    ]
+}
@@ -0,0 +1,171 @@
+//! Loading bitstreams into FPGAs over MPSSE.
+//!
+//! The bitstream is clocked out on ADBUS0 (to CCLK or SPI_SCK) and ADBUS1 (to DIN
+//! or SPI_SI), MSB first with data changing on the falling edge; the reset, DONE
+//! and chip select lines can be any other GPIO pins.
+
+use std::thread;
+use std::time::Duration;
+
+use error::Error;
+use gpio::{Gpio, PinCondition};
+use mode::MpsseDevice;
+use mpsse::{DO_WRITE, MAX_SHIFT_LEN, WRITE_NEG};
+use Result;
+
+const CCLK: u16 = 1 << 0;
+const DIN: u16 = 1 << 1;
+
+/// How long DONE may take to go HIGH after the last clocks.
+const DONE_TIMEOUT: Duration = Duration::from_millis(100);
+
+// ECP5 sysCONFIG commands, each followed by three operand bytes.
+const ECP5_ISC_ENABLE: u8 = 0xC6;
+const ECP5_ISC_DISABLE: u8 = 0x26;
+const ECP5_BITSTREAM_BURST: u8 = 0x7A;
+
+/// How a family of FPGAs is configured.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Family {
+    /// Lattice iCE40 in SPI slave mode.
+    Ice40,
+    /// Lattice ECP5 in SPI slave mode.
+    Ecp5,
+    /// Xilinx slave serial mode.
+    XilinxSlaveSerial,
+}
+
+/// The control pins of the FPGA, as GPIO pin numbers.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ConfigPins {
+    /// CRESET_B, PROGRAMN or PROGRAM_B: pulsed LOW to clear the FPGA.
+    pub reset: u8,
+    /// CDONE or DONE, HIGH once configured.
+    pub done: u8,
+    /// SPI_SS_B or SN; slave serial mode has no chip select.
+    pub cs: Option<u8>,
+}
+
+impl MpsseDevice {
+    /// Configure an FPGA with `bitstream`, returning whether it signalled DONE.
+    ///
+    /// Set the clock with [`set_clock_frequency`][MpsseDevice::set_clock_frequency]
+    /// first. `progress` is called with the bytes sent so far and the total.
+    pub fn configure_fpga<F>(
+        &self,
+        family: Family,
+        pins: ConfigPins,
+        bitstream: &[u8],
+        mut progress: F,
+    ) -> Result<bool>
+    where
+        F: FnMut(usize, usize),
+    {
+        let mut used = vec![pins.reset, pins.done];
+        used.extend(pins.cs);
+        if used.iter().any(|&pin| pin < 2 || pin >= self.gpio_count()) {
+            return Err(Error::InvalidArgument(
+                "FPGA control pins must be GPIO pins other than ADBUS0..1",
+            ));
+        }
+        if family != Family::XilinxSlaveSerial && pins.cs.is_none() {
+            return Err(Error::InvalidArgument(
+                "SPI configuration needs a chip select",
+            ));
+        }
+
+        // Drive the clock, data, reset and chip select; DONE is an input.
+        let mut outputs = CCLK | DIN | (1 << pins.reset);
+        if let Some(cs) = pins.cs {
+            outputs |= 1 << cs;
+        }
+        let levels = self.gpio_output_latch() & !(CCLK | DIN);
+        self.set_gpio(
+            levels,
+            (self.gpio_direction() | outputs) & !(1 << pins.done),
+        )?;
+
+        match family {
+            Family::Ice40 => {
+                // The iCE40 enters SPI slave mode if SS is LOW when it leaves reset.
+                set_pin(self, pins.cs, false)?;
+                pulse_reset(self, pins.reset, Duration::from_micros(1200))?;
+                set_pin(self, pins.cs, true)?;
+                shift(self, &[0], |_| {})?;
+
+                set_pin(self, pins.cs, false)?;
+                shift(self, bitstream, |done| progress(done, bitstream.len()))?;
+                set_pin(self, pins.cs, true)?;
+                // At least 49 clocks to start up.
+                shift(self, &[0; 7], |_| {})?;
+            }
+            Family::Ecp5 => {
+                pulse_reset(self, pins.reset, Duration::from_millis(50))?;
+                set_pin(self, pins.cs, false)?;
+                shift(self, &[ECP5_ISC_ENABLE, 0, 0, 0], |_| {})?;
+                set_pin(self, pins.cs, true)?;
+
+                set_pin(self, pins.cs, false)?;
+                shift(self, &[ECP5_BITSTREAM_BURST, 0, 0, 0], |_| {})?;
+                shift(self, bitstream, |done| progress(done, bitstream.len()))?;
+                set_pin(self, pins.cs, true)?;
+
+                set_pin(self, pins.cs, false)?;
+                shift(self, &[ECP5_ISC_DISABLE, 0, 0, 0], |_| {})?;
+                set_pin(self, pins.cs, true)?;
+            }
+            Family::XilinxSlaveSerial => {
+                // Leave time for INIT_B to go HIGH after clearing.
+                pulse_reset(self, pins.reset, Duration::from_millis(5))?;
+                shift(self, bitstream, |done| progress(done, bitstream.len()))?;
+            }
+        }
+
+        let done = self
+            .pin(pins.done)
+            .wait_for(PinCondition::High, DONE_TIMEOUT)?
+            .is_some();
+        if done {
+            // Clocks to finish the startup sequence.
+            shift(self, &[0; 8], |_| {})?;
+        }
+        Ok(done)
+    }
+}
+
+fn set_pin(device: &MpsseDevice, pin: Option<u8>, high: bool) -> Result<()> {
+    let pin = match pin {
+        Some(pin) => pin,
+        None => return Ok(()),
+    };
+    let latch = device.gpio_output_latch();
+    let latch = if high {
+        latch | (1 << pin)
+    } else {
+        latch & !(1 << pin)
+    };
+    device.set_gpio(latch, device.gpio_direction())
+}
+
+// Hold the FPGA in reset briefly, then wait `clear_time` for it to clear itself.
+fn pulse_reset(device: &MpsseDevice, reset: u8, clear_time: Duration) -> Result<()> {
+    set_pin(device, Some(reset), false)?;
+    thread::sleep(Duration::from_millis(1));
+    set_pin(device, Some(reset), true)?;
+    thread::sleep(clear_time);
+    Ok(())
+}
+
+// Clock out `data`, calling `progress` with the bytes sent after each command.
+fn shift<F: FnMut(usize)>(device: &MpsseDevice, data: &[u8], mut progress: F) -> Result<()> {
+    let mut done = 0;
+    for chunk in data.chunks(MAX_SHIFT_LEN) {
+        let count = chunk.len() - 1;
+        let mut cmd = vec![DO_WRITE | WRITE_NEG, count as u8, (count >> 8) as u8];
+        cmd.extend_from_slice(chunk);
+        device.write_data(&cmd)?;
+        done += chunk.len();
+        progress(done);
+    }
+    Ok(())
+}
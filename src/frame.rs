@@ -0,0 +1,172 @@
+//! Reassembling a byte stream into fixed-size frames.
+//!
+//! USB splits the stream at arbitrary points, so a read may return half a frame or
+//! several frames at once. A [`FrameReader`] collects the pieces and hands out whole
+//! frames. With a sync word, each frame must start with it; after noise or dropped
+//! bytes, the reader skips ahead to the next sync word.
+
+use {Device, Result};
+
+/// Collects a byte stream into frames of a fixed length.
+#[derive(Debug, Clone)]
+pub struct FrameReader {
+    frame_len: usize,
+    sync: Vec<u8>,
+    pending: Vec<u8>,
+    skipped: u64,
+}
+
+impl FrameReader {
+    /// Frames of `frame_len` bytes, taken back to back.
+    ///
+    /// Panics if `frame_len` is 0.
+    pub fn new(frame_len: usize) -> FrameReader {
+        assert!(frame_len > 0, "Frames must not be empty");
+        FrameReader {
+            frame_len,
+            sync: Vec::new(),
+            pending: Vec::new(),
+            skipped: 0,
+        }
+    }
+
+    /// Only take frames starting with `sync`, which counts towards the frame length.
+    ///
+    /// Panics if `sync` is longer than a frame.
+    pub fn with_sync(mut self, sync: &[u8]) -> FrameReader {
+        assert!(
+            sync.len() <= self.frame_len,
+            "Sync word longer than a frame"
+        );
+        self.sync = sync.to_vec();
+        self
+    }
+
+    pub fn frame_len(&self) -> usize {
+        self.frame_len
+    }
+
+    /// Bytes dropped so far while searching for the sync word.
+    pub fn skipped(&self) -> u64 {
+        self.skipped
+    }
+
+    /// Add received data.
+    pub fn push(&mut self, data: &[u8]) {
+        self.pending.extend_from_slice(data);
+    }
+
+    /// Take the next complete frame, if there is one.
+    pub fn pop_frame(&mut self) -> Option<Vec<u8>> {
+        self.sync_up();
+        if self.pending.len() < self.frame_len {
+            return None;
+        }
+        Some(self.pending.drain(..self.frame_len).collect())
+    }
+
+    /// Read from `device` until a frame is complete.
+    ///
+    /// Fails with a timeout error if the chip sends nothing for longer than the read
+    /// timeout (see [`Device::set_timeouts`]); the partial frame is kept for the next
+    /// call.
+    pub fn read_frame(&mut self, device: &Device) -> Result<Vec<u8>> {
+        let mut buf = vec![0; self.frame_len];
+        loop {
+            if let Some(frame) = self.pop_frame() {
+                return Ok(frame);
+            }
+            let read = device.read_some(&mut buf)?;
+            self.push(&buf[..read]);
+        }
+    }
+
+    /// Drop data not yet taken as a frame, e.g. after purging the device's buffers.
+    pub fn clear(&mut self) {
+        self.pending.clear();
+    }
+
+    // Drop bytes in front of the first sync word.
+    fn sync_up(&mut self) {
+        if self.sync.is_empty() || self.pending.starts_with(&self.sync) {
+            return;
+        }
+        let start = self
+            .pending
+            .windows(self.sync.len())
+            .position(|w| w == &self.sync[..])
+            // The end may hold the beginning of a sync word.
+            .unwrap_or_else(|| {
+                let n = self.pending.len();
+                n.saturating_sub(self.sync.len() - 1)
+            });
+        self.pending.drain(..start);
+        self.skipped += start as u64;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn back_to_back_frames() {
+        let mut reader = FrameReader::new(3);
+        reader.push(&[1, 2, 3, 4, 5, 6, 7]);
+        assert_eq!(reader.pop_frame(), Some(vec![1, 2, 3]));
+        assert_eq!(reader.pop_frame(), Some(vec![4, 5, 6]));
+        assert_eq!(reader.pop_frame(), None);
+        reader.push(&[8, 9]);
+        assert_eq!(reader.pop_frame(), Some(vec![7, 8, 9]));
+        assert_eq!(reader.skipped(), 0);
+    }
+
+    #[test]
+    fn back_to_back_synced_frames() {
+        let mut reader = FrameReader::new(4).with_sync(&[0xaa, 0x55]);
+        reader.push(&[0xaa, 0x55, 1, 2, 0xaa, 0x55, 3, 4]);
+        assert_eq!(reader.pop_frame(), Some(vec![0xaa, 0x55, 1, 2]));
+        assert_eq!(reader.pop_frame(), Some(vec![0xaa, 0x55, 3, 4]));
+        assert_eq!(reader.pop_frame(), None);
+        assert_eq!(reader.skipped(), 0);
+    }
+
+    #[test]
+    fn truncated_frame_waits_for_the_rest() {
+        let mut reader = FrameReader::new(4).with_sync(&[0xaa, 0x55]);
+        reader.push(&[0xaa, 0x55, 1]);
+        assert_eq!(reader.pop_frame(), None);
+        reader.push(&[2]);
+        assert_eq!(reader.pop_frame(), Some(vec![0xaa, 0x55, 1, 2]));
+        assert_eq!(reader.skipped(), 0);
+    }
+
+    #[test]
+    fn resync_after_garbage() {
+        let mut reader = FrameReader::new(4).with_sync(&[0xaa, 0x55]);
+        reader.push(&[1, 0xaa, 2, 0x55, 0xaa, 0x55, 3, 4, 0xaa, 0x55, 5, 6]);
+        assert_eq!(reader.pop_frame(), Some(vec![0xaa, 0x55, 3, 4]));
+        assert_eq!(reader.skipped(), 4);
+        assert_eq!(reader.pop_frame(), Some(vec![0xaa, 0x55, 5, 6]));
+        assert_eq!(reader.skipped(), 4);
+    }
+
+    #[test]
+    fn sync_word_split_across_reads() {
+        let mut reader = FrameReader::new(4).with_sync(&[0xaa, 0x55]);
+        reader.push(&[1, 2, 0xaa]);
+        assert_eq!(reader.pop_frame(), None);
+        assert_eq!(reader.skipped(), 2);
+        reader.push(&[0x55, 3, 4]);
+        assert_eq!(reader.pop_frame(), Some(vec![0xaa, 0x55, 3, 4]));
+        assert_eq!(reader.skipped(), 2);
+    }
+
+    #[test]
+    fn garbage_without_sync_word_is_dropped() {
+        let mut reader = FrameReader::new(4).with_sync(&[0xaa, 0x55]);
+        reader.push(&[1, 2, 3, 4, 5]);
+        assert_eq!(reader.pop_frame(), None);
+        assert_eq!(reader.skipped(), 4);
+    }
+}
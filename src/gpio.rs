@@ -0,0 +1,322 @@
+//! Access to single I/O pins of a device in a GPIO-capable mode.
+//!
+//! Devices implementing [`Gpio`] track the levels last written to their outputs, so
+//! changing one [`Pin`] leaves the others as they were. A [`Port`] accesses all pins
+//! at once.
+
+use std::cmp;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use error::Error;
+use mode::{BitbangDevice, MpsseDevice};
+use {Device, Result};
+
+/// How long to poll a pin flat out before backing off.
+const BUSY_POLL_TIME: Duration = Duration::from_millis(10);
+const MIN_POLL_INTERVAL: Duration = Duration::from_micros(100);
+const MAX_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Direction {
+    Input,
+    Output,
+}
+
+/// What [`Device::wait_for_pin`] and [`Pin::wait_for`] wait for.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PinCondition {
+    High,
+    Low,
+    /// A LOW to HIGH transition after the wait started.
+    Rising,
+    /// A HIGH to LOW transition after the wait started.
+    Falling,
+    AnyEdge,
+}
+
+// Poll `read_level` until `condition` holds, returning how long that took, or `None`
+// on timeout. Each poll is a USB round trip, so only back off on long waits.
+fn wait_for_level<F>(
+    mut read_level: F,
+    condition: PinCondition,
+    timeout: Duration,
+) -> Result<Option<Duration>>
+where
+    F: FnMut() -> Result<bool>,
+{
+    let start = Instant::now();
+    let mut level = read_level()?;
+    let mut previous = level;
+    let mut interval = Duration::from_secs(0);
+
+    loop {
+        let met = match condition {
+            PinCondition::High => level,
+            PinCondition::Low => !level,
+            PinCondition::Rising => !previous && level,
+            PinCondition::Falling => previous && !level,
+            PinCondition::AnyEdge => previous != level,
+        };
+        if met {
+            return Ok(Some(start.elapsed()));
+        }
+
+        let elapsed = start.elapsed();
+        if elapsed >= timeout {
+            return Ok(None);
+        }
+        if elapsed >= BUSY_POLL_TIME {
+            interval = cmp::min(cmp::max(interval * 2, MIN_POLL_INTERVAL), MAX_POLL_INTERVAL);
+            thread::sleep(cmp::min(interval, timeout - elapsed));
+        }
+
+        previous = level;
+        level = read_level()?;
+    }
+}
+
+impl Device {
+    /// Wait until bitbang pin `pin` meets `condition`, returning how long that took
+    ///
+    /// Returns `None` if `timeout` expired first. The pins are polled through
+    /// [`read_pins`][Device::read_pins], so pulses shorter than a USB round trip
+    /// (about a millisecond) can be missed.
+    pub fn wait_for_pin(
+        &self,
+        pin: u8,
+        condition: PinCondition,
+        timeout: Duration,
+    ) -> Result<Option<Duration>> {
+        if pin >= 8 {
+            return Err(Error::InvalidArgument("Bitbang pin must be below 8"));
+        }
+
+        wait_for_level(
+            || Ok(self.read_pins()? & (1 << pin) != 0),
+            condition,
+            timeout,
+        )
+    }
+}
+
+/// A bank of pins whose levels and directions are written all at once.
+///
+/// Bit `n` of the masks refers to pin `n`; a HIGH bit in a direction mask makes a
+/// pin an output.
+pub trait Gpio {
+    /// Number of pins, at most 16.
+    fn pin_count(&self) -> u8;
+
+    fn direction_mask(&self) -> u16;
+
+    /// Levels last written to the pins.
+    fn output_latch(&self) -> u16;
+
+    fn write_directions(&self, directions: u16) -> Result<()>;
+
+    fn write_outputs(&self, levels: u16) -> Result<()>;
+
+    /// Read the current level of all pins.
+    fn read_levels(&self) -> Result<u16>;
+
+    /// Handles to each of the pins.
+    fn pins(&self) -> Vec<Pin<'_, Self>>
+    where
+        Self: Sized,
+    {
+        (0..self.pin_count())
+            .map(|index| Pin { gpio: self, index })
+            .collect()
+    }
+
+    /// Handle to all pins at once.
+    fn port(&self) -> Port<'_, Self>
+    where
+        Self: Sized,
+    {
+        Port { gpio: self }
+    }
+
+    /// Handle to pin `index`. Panics if there is no such pin.
+    fn pin(&self, index: u8) -> Pin<'_, Self>
+    where
+        Self: Sized,
+    {
+        assert!(index < self.pin_count(), "no pin {}", index);
+        Pin { gpio: self, index }
+    }
+}
+
+/// A single pin of a [`Gpio`] device.
+pub struct Pin<'a, G: 'a> {
+    gpio: &'a G,
+    index: u8,
+}
+
+impl<'a, G: Gpio> Pin<'a, G> {
+    pub fn index(&self) -> u8 {
+        self.index
+    }
+
+    fn bit(&self) -> u16 {
+        1 << self.index
+    }
+
+    pub fn direction(&self) -> Direction {
+        if self.gpio.direction_mask() & self.bit() != 0 {
+            Direction::Output
+        } else {
+            Direction::Input
+        }
+    }
+
+    pub fn set_direction(&self, direction: Direction) -> Result<()> {
+        let mask = self.gpio.direction_mask();
+        let mask = match direction {
+            Direction::Output => mask | self.bit(),
+            Direction::Input => mask & !self.bit(),
+        };
+        self.gpio.write_directions(mask)
+    }
+
+    /// Drive the pin HIGH (if it is an output), leaving the other pins alone.
+    pub fn set_high(&self) -> Result<()> {
+        self.set(true)
+    }
+
+    /// Drive the pin LOW (if it is an output), leaving the other pins alone.
+    pub fn set_low(&self) -> Result<()> {
+        self.set(false)
+    }
+
+    pub fn set(&self, high: bool) -> Result<()> {
+        let latch = self.gpio.output_latch();
+        let latch = if high {
+            latch | self.bit()
+        } else {
+            latch & !self.bit()
+        };
+        self.gpio.write_outputs(latch)
+    }
+
+    pub fn toggle(&self) -> Result<()> {
+        self.set(!self.is_set_high())
+    }
+
+    /// Whether the pin was last driven HIGH, without asking the chip.
+    pub fn is_set_high(&self) -> bool {
+        self.gpio.output_latch() & self.bit() != 0
+    }
+
+    /// Read the level currently on the pin.
+    pub fn is_high(&self) -> Result<bool> {
+        Ok(self.gpio.read_levels()? & self.bit() != 0)
+    }
+
+    pub fn is_low(&self) -> Result<bool> {
+        self.is_high().map(|high| !high)
+    }
+
+    /// Wait until the pin meets `condition`, see [`Device::wait_for_pin`].
+    pub fn wait_for(&self, condition: PinCondition, timeout: Duration) -> Result<Option<Duration>> {
+        wait_for_level(|| self.is_high(), condition, timeout)
+    }
+}
+
+/// All pins of a [`Gpio`] device, read and written in one operation.
+pub struct Port<'a, G: 'a> {
+    gpio: &'a G,
+}
+
+impl<'a, G: Gpio> Port<'a, G> {
+    /// Which pins are outputs; a HIGH bit is an output.
+    pub fn direction(&self) -> u16 {
+        self.gpio.direction_mask()
+    }
+
+    pub fn set_direction(&self, directions: u16) -> Result<()> {
+        self.gpio.write_directions(directions)
+    }
+
+    /// Read the level currently on each pin.
+    pub fn read(&self) -> Result<u16> {
+        self.gpio.read_levels()
+    }
+
+    /// Drive all output pins at once.
+    pub fn write(&self, levels: u16) -> Result<()> {
+        self.gpio.write_outputs(levels)
+    }
+
+    /// Levels last written, without asking the chip.
+    pub fn output_latch(&self) -> u16 {
+        self.gpio.output_latch()
+    }
+
+    /// Drive only the pins selected by `mask` to their level in `levels`.
+    pub fn write_masked(&self, levels: u16, mask: u16) -> Result<()> {
+        self.modify(|latch| (latch & !mask) | (levels & mask))
+    }
+
+    /// Compute new output levels from the ones last written and drive them.
+    ///
+    /// This works on the tracked output levels rather than reading the pins, so inputs
+    /// and outputs loaded down by external circuitry don't leak into the result.
+    pub fn modify<F: FnOnce(u16) -> u16>(&self, f: F) -> Result<()> {
+        self.gpio.write_outputs(f(self.gpio.output_latch()))
+    }
+}
+
+impl Gpio for BitbangDevice {
+    fn pin_count(&self) -> u8 {
+        8
+    }
+
+    fn direction_mask(&self) -> u16 {
+        self.direction().into()
+    }
+
+    fn output_latch(&self) -> u16 {
+        BitbangDevice::output_latch(self).into()
+    }
+
+    fn write_directions(&self, directions: u16) -> Result<()> {
+        self.set_direction(directions as u8)
+    }
+
+    fn write_outputs(&self, levels: u16) -> Result<()> {
+        self.write_pins(levels as u8)
+    }
+
+    fn read_levels(&self) -> Result<u16> {
+        self.read_pins().map(u16::from)
+    }
+}
+
+/// All 16 MPSSE pins on the FT232H and FT2232H, otherwise only ADBUS0..7.
+impl Gpio for MpsseDevice {
+    fn pin_count(&self) -> u8 {
+        self.gpio_count()
+    }
+
+    fn direction_mask(&self) -> u16 {
+        self.gpio_direction()
+    }
+
+    fn output_latch(&self) -> u16 {
+        self.gpio_output_latch()
+    }
+
+    fn write_directions(&self, directions: u16) -> Result<()> {
+        self.set_gpio(self.gpio_output_latch(), directions)
+    }
+
+    fn write_outputs(&self, levels: u16) -> Result<()> {
+        self.set_gpio(levels, self.gpio_direction())
+    }
+
+    fn read_levels(&self) -> Result<u16> {
+        self.read_gpio()
+    }
+}
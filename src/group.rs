@@ -0,0 +1,82 @@
+//! Running the same operation on many devices at once, e.g. on a production
+//! programming station.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+use {list_devices_filtered, Device, DeviceInfo, Interface, Result};
+
+/// Outcome of running an operation on one device of a [`DeviceGroup`].
+#[derive(Debug)]
+pub struct DeviceResult<T> {
+    pub info: DeviceInfo,
+    /// Also holds the error if the device could not be opened.
+    pub result: Result<T>,
+}
+
+/// A set of devices that an operation is run against in parallel.
+pub struct DeviceGroup {
+    devices: Vec<DeviceInfo>,
+    interface: Interface,
+}
+
+impl DeviceGroup {
+    /// Group the given devices, opening `interface` of each when running.
+    pub fn new(devices: Vec<DeviceInfo>, interface: Interface) -> DeviceGroup {
+        DeviceGroup { devices, interface }
+    }
+
+    /// Group every attached device with the given vendor and product ids.
+    pub fn find(vid: u16, pid: u16, interface: Interface) -> Result<DeviceGroup> {
+        Ok(DeviceGroup::new(
+            list_devices_filtered(vid, pid)?,
+            interface,
+        ))
+    }
+
+    /// Devices in the group.
+    pub fn devices(&self) -> &[DeviceInfo] {
+        &self.devices
+    }
+
+    /// Open each device and run `f` on it, using up to `workers` threads.
+    ///
+    /// Devices are opened on the worker threads, so a failure to open one device does
+    /// not stop the others. Results are returned in the order of
+    /// [`devices`][DeviceGroup::devices].
+    pub fn run<T, F>(&self, workers: usize, f: F) -> Vec<DeviceResult<T>>
+    where
+        T: Send,
+        F: Fn(&mut Device) -> Result<T> + Sync,
+    {
+        let next = AtomicUsize::new(0);
+        let results = Mutex::new(Vec::with_capacity(self.devices.len()));
+        let workers = workers.max(1).min(self.devices.len());
+
+        thread::scope(|scope| {
+            for _ in 0..workers {
+                scope.spawn(|| loop {
+                    let index = next.fetch_add(1, Ordering::SeqCst);
+                    let info = match self.devices.get(index) {
+                        Some(info) => info,
+                        None => break,
+                    };
+
+                    let result = Device::open(info, self.interface).and_then(|mut dev| f(&mut dev));
+                    results.lock().unwrap().push((index, result));
+                });
+            }
+        });
+
+        let mut results = results.into_inner().unwrap();
+        results.sort_by_key(|&(index, _)| index);
+        results
+            .into_iter()
+            .map(|(index, result)| DeviceResult {
+                info: self.devices[index].clone(),
+                result,
+            })
+            .collect()
+    }
+}
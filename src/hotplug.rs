@@ -0,0 +1,167 @@
+//! Notifications when devices are plugged in or unplugged, built on libusb hotplug
+//! callbacks.
+
+use std::os::raw;
+use std::ptr;
+use std::sync::atomic::Ordering;
+use std::sync::mpsc::{self, Receiver, Sender};
+
+use ftdic;
+use usbc;
+use usbc::constants::*;
+
+use background::{self, BackgroundThread};
+use error::{Error, LibUsbError};
+use threads::ThreadOptions;
+use {usb_device_info, DeviceInfo, Result};
+
+/// How long the event thread waits in libusb before checking whether it should stop.
+const POLL_INTERVAL_US: i32 = 100_000;
+
+/// FTDI's USB vendor id, watched for when no vendor id is given.
+const FTDI_VENDOR_ID: u16 = 0x0403;
+
+#[derive(Debug, Clone)]
+pub enum HotplugEvent {
+    Arrived(DeviceInfo),
+    Left(DeviceInfo),
+}
+
+/// Watches for matching devices being attached and detached.
+///
+/// Events are handled on a background thread and delivered through
+/// [`events`][HotplugWatcher::events]. Devices already attached when the watcher is
+/// created are reported as arrivals first. libusb forbids opening devices from
+/// hotplug callbacks, so the strings in the reported [`DeviceInfo`] are empty.
+pub struct HotplugWatcher {
+    context: *mut usbc::libusb_context,
+    handle: usbc::libusb_hotplug_callback_handle,
+    sender: *mut Sender<HotplugEvent>,
+    thread: BackgroundThread<()>,
+    events: Receiver<HotplugEvent>,
+}
+
+// The libusb context is thread-safe; this just lets the event thread borrow it.
+struct EventContext(*mut usbc::libusb_context);
+
+unsafe impl Send for EventContext {}
+
+extern "system" fn hotplug_callback(
+    _ctx: *mut usbc::libusb_context,
+    device: *mut usbc::libusb_device,
+    event: usbc::libusb_hotplug_event,
+    user_data: *mut raw::c_void,
+) -> raw::c_int {
+    let sender = unsafe { &*(user_data as *const Sender<HotplugEvent>) };
+
+    if let Ok(info) = usb_device_info(device) {
+        let event = if event == LIBUSB_HOTPLUG_EVENT_DEVICE_ARRIVED {
+            HotplugEvent::Arrived(info)
+        } else {
+            HotplugEvent::Left(info)
+        };
+        background::notify(sender, event);
+    }
+
+    // Keep the callback registered.
+    0
+}
+
+impl HotplugWatcher {
+    /// Start watching for devices with the given vendor and product ids.
+    ///
+    /// A `None` vendor id watches FTDI's, like [`list_devices`][::list_devices]; pass it
+    /// explicitly for chips programmed with a custom one. A `None` product id matches any
+    /// product of the vendor.
+    pub fn new(vid: Option<u16>, pid: Option<u16>) -> Result<HotplugWatcher> {
+        if unsafe { usbc::libusb_has_capability(LIBUSB_CAP_HAS_HOTPLUG) } == 0 {
+            return Err(Error::LibUsb(LibUsbError::NotSupported));
+        }
+
+        let mut context = ptr::null_mut();
+        let rc = unsafe { usbc::libusb_init(&mut context) };
+        if rc < 0 {
+            return Err(Error::LibUsb(LibUsbError::from_code(rc)));
+        }
+
+        let (sender, events) = mpsc::channel();
+        let sender = Box::into_raw(Box::new(sender));
+
+        let mut handle = 0;
+        let rc = unsafe {
+            usbc::libusb_hotplug_register_callback(
+                context,
+                LIBUSB_HOTPLUG_EVENT_DEVICE_ARRIVED | LIBUSB_HOTPLUG_EVENT_DEVICE_LEFT,
+                LIBUSB_HOTPLUG_ENUMERATE,
+                raw::c_int::from(vid.unwrap_or(FTDI_VENDOR_ID)),
+                pid.map_or(LIBUSB_HOTPLUG_MATCH_ANY, raw::c_int::from),
+                LIBUSB_HOTPLUG_MATCH_ANY,
+                hotplug_callback,
+                sender as *mut raw::c_void,
+                &mut handle,
+            )
+        };
+        if rc < 0 {
+            unsafe {
+                drop(Box::from_raw(sender));
+                usbc::libusb_exit(context);
+            }
+            return Err(Error::LibUsb(LibUsbError::from_code(rc)));
+        }
+
+        let event_context = EventContext(context);
+        let thread = BackgroundThread::spawn(&ThreadOptions::new(), move |running| {
+            let timeout = ftdic::timeval {
+                tv_sec: 0,
+                tv_usec: POLL_INTERVAL_US.into(),
+            };
+
+            while running.load(Ordering::SeqCst) {
+                unsafe {
+                    usbc::libusb_handle_events_timeout_completed(
+                        event_context.0,
+                        &timeout,
+                        ptr::null_mut(),
+                    )
+                };
+            }
+        });
+        let thread = match thread {
+            Ok(thread) => thread,
+            Err(e) => {
+                unsafe {
+                    usbc::libusb_hotplug_deregister_callback(context, handle);
+                    drop(Box::from_raw(sender));
+                    usbc::libusb_exit(context);
+                }
+                return Err(Error::Io(e));
+            }
+        };
+
+        Ok(HotplugWatcher {
+            context,
+            handle,
+            sender,
+            thread,
+            events,
+        })
+    }
+
+    /// Channel on which attach and detach events arrive.
+    pub fn events(&self) -> &Receiver<HotplugEvent> {
+        &self.events
+    }
+}
+
+impl Drop for HotplugWatcher {
+    fn drop(&mut self) {
+        unsafe { usbc::libusb_hotplug_deregister_callback(self.context, self.handle) };
+        // The event thread must be done with the context and the sender.
+        self.thread.stop();
+
+        unsafe {
+            drop(Box::from_raw(self.sender));
+            usbc::libusb_exit(self.context);
+        }
+    }
+}
@@ -0,0 +1,182 @@
+//! I2C master interface shared by the bitbang and MPSSE implementations.
+
+use error::Error;
+use Result;
+
+/// An I2C bus master.
+///
+/// The byte-level operations are the building blocks; [`transaction`][I2c::transaction]
+/// runs a complete transaction with a 7- or 10-bit target address, and
+/// [`write`][I2c::write], [`read`][I2c::read] and [`write_read`][I2c::write_read]
+/// cover the common cases with a 7-bit address.
+pub trait I2c {
+    /// Send a START condition, or a repeated START inside a transaction.
+    fn start(&mut self) -> Result<()>;
+
+    /// Send a STOP condition.
+    fn stop(&mut self) -> Result<()>;
+
+    /// Clock out `byte` and return whether the target acknowledged it.
+    fn write_byte(&mut self, byte: u8) -> Result<bool>;
+
+    /// Clock in a byte, acknowledging it if `ack` is set. The last byte of a read
+    /// must not be acknowledged.
+    fn read_byte(&mut self, ack: bool) -> Result<u8>;
+
+    /// Probe the addresses 0x08 to 0x77 and return the ones a target acknowledges.
+    ///
+    /// The reserved addresses are skipped. Each probe is an address-only write,
+    /// which most targets ignore.
+    fn scan(&mut self) -> Result<Vec<u8>> {
+        let mut found = Vec::new();
+        for address in 0x08..0x78 {
+            self.start()?;
+            let acked = self.write_byte(address << 1);
+            let stopped = self.stop();
+            if acked? {
+                found.push(address);
+            }
+            stopped?;
+        }
+        Ok(found)
+    }
+
+    /// Run `operations` on the target at `address` as one transaction.
+    ///
+    /// The address is sent at the start and again after a repeated START wherever
+    /// the direction changes; consecutive operations in the same direction continue
+    /// without one. The transaction always ends with a STOP.
+    fn transaction(&mut self, address: Address, operations: &mut [Operation]) -> Result<()> {
+        address.check()?;
+        bracket(self, |bus| {
+            let count = operations.len();
+            let mut previous = None;
+            for i in 0..count {
+                let read = operations[i].is_read();
+                if previous != Some(read) {
+                    if previous.is_some() {
+                        bus.start()?;
+                    }
+                    send_address(bus, address, read)?;
+                }
+                previous = Some(read);
+
+                // Only the last byte before a STOP or repeated START is NACKed.
+                let last = i + 1 == count || operations[i + 1].is_read() != read;
+                match operations[i] {
+                    Operation::Write(data) => send_bytes(bus, data)?,
+                    Operation::Read(ref mut data) => receive_bytes(bus, data, last)?,
+                }
+            }
+            Ok(())
+        })
+    }
+
+    /// Write `data` to the target at `address`.
+    fn write(&mut self, address: u8, data: &[u8]) -> Result<()> {
+        self.transaction(address.into(), &mut [Operation::Write(data)])
+    }
+
+    /// Fill `data` from the target at `address`.
+    fn read(&mut self, address: u8, data: &mut [u8]) -> Result<()> {
+        self.transaction(address.into(), &mut [Operation::Read(data)])
+    }
+
+    /// Write `data`, then read `response` after a repeated START, e.g. to read a
+    /// register.
+    fn write_read(&mut self, address: u8, data: &[u8], response: &mut [u8]) -> Result<()> {
+        self.transaction(
+            address.into(),
+            &mut [Operation::Write(data), Operation::Read(response)],
+        )
+    }
+}
+
+/// A target address.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Address {
+    SevenBit(u8),
+    TenBit(u16),
+}
+
+impl Address {
+    fn check(self) -> Result<()> {
+        match self {
+            Address::SevenBit(address) if address >= 0x80 => {
+                Err(Error::InvalidArgument("I2C address must be 7 bits"))
+            }
+            Address::TenBit(address) if address >= 0x400 => {
+                Err(Error::InvalidArgument("I2C address must be 10 bits"))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+impl From<u8> for Address {
+    fn from(address: u8) -> Address {
+        Address::SevenBit(address)
+    }
+}
+
+/// One step of an [`I2c::transaction`].
+#[derive(Debug)]
+pub enum Operation<'a> {
+    Write(&'a [u8]),
+    Read(&'a mut [u8]),
+}
+
+impl<'a> Operation<'a> {
+    fn is_read(&self) -> bool {
+        match self {
+            Operation::Write(_) => false,
+            Operation::Read(_) => true,
+        }
+    }
+}
+
+// Run `f` between START and STOP, sending the STOP even if `f` fails.
+fn bracket<B, F>(bus: &mut B, f: F) -> Result<()>
+where
+    B: I2c + ?Sized,
+    F: FnOnce(&mut B) -> Result<()>,
+{
+    bus.start()?;
+    let res = f(bus);
+    let stopped = bus.stop();
+    res.and(stopped)
+}
+
+fn send_address<B: I2c + ?Sized>(bus: &mut B, address: Address, read: bool) -> Result<()> {
+    match address {
+        Address::SevenBit(address) => send_bytes(bus, &[(address << 1) | read as u8]),
+        Address::TenBit(address) => {
+            // 0b11110 followed by the two high address bits.
+            let header = 0xf0 | ((address >> 7) as u8 & 0x06);
+            send_bytes(bus, &[header, address as u8])?;
+            if read {
+                // Reads address the target for writing first, then turn around.
+                bus.start()?;
+                send_bytes(bus, &[header | 0x01])?;
+            }
+            Ok(())
+        }
+    }
+}
+
+fn send_bytes<B: I2c + ?Sized>(bus: &mut B, data: &[u8]) -> Result<()> {
+    for &byte in data {
+        if !bus.write_byte(byte)? {
+            return Err(Error::Nack);
+        }
+    }
+    Ok(())
+}
+
+fn receive_bytes<B: I2c + ?Sized>(bus: &mut B, data: &mut [u8], last: bool) -> Result<()> {
+    let len = data.len();
+    for (i, byte) in data.iter_mut().enumerate() {
+        *byte = bus.read_byte(!last || i + 1 < len)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,131 @@
+//! 24xx-series I2C EEPROMs, such as board identification EEPROMs.
+
+use std::time::{Duration, Instant};
+
+use error::{Error, LibUsbError};
+use i2c::I2c;
+use Result;
+
+/// How long a write cycle may take; datasheets give at most 5 or 10 ms.
+const WRITE_CYCLE_TIMEOUT: Duration = Duration::from_millis(20);
+
+/// A 24xx EEPROM, from the 24C01 up to 128 KiB parts.
+///
+/// Parts up to 2 KiB (24C16) take a single address byte and 24C32 and larger ones
+/// two. Address bits beyond those go into the low bits of the device address, as
+/// on the 24C04 to 24C16 and 128 KiB parts.
+pub struct I2cEeprom<B> {
+    bus: B,
+    address: u8,
+    size: usize,
+    page_size: usize,
+}
+
+impl<B: I2c> I2cEeprom<B> {
+    /// An EEPROM of `size` bytes at 7-bit `address`, written `page_size` bytes at a
+    /// time (e.g. 8 bytes for a 24C02 and 32 for a 24C32).
+    pub fn new(bus: B, address: u8, size: usize, page_size: usize) -> Result<I2cEeprom<B>> {
+        if address >= 0x80 {
+            return Err(Error::InvalidArgument("I2C address must be 7 bits"));
+        }
+        if size == 0 || size > 0x2_0000 || !size.is_power_of_two() {
+            return Err(Error::InvalidArgument("Unsupported EEPROM size"));
+        }
+        if page_size == 0 || !page_size.is_power_of_two() || page_size > size {
+            return Err(Error::InvalidArgument("Unsupported EEPROM page size"));
+        }
+
+        Ok(I2cEeprom {
+            bus,
+            address,
+            size,
+            page_size,
+        })
+    }
+
+    /// Return the I2C master.
+    pub fn into_inner(self) -> B {
+        self.bus
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn page_size(&self) -> usize {
+        self.page_size
+    }
+
+    fn address_bytes(&self) -> usize {
+        if self.size <= 0x800 {
+            1
+        } else {
+            2
+        }
+    }
+
+    // The device address and memory address bytes selecting `offset`.
+    fn locate(&self, offset: usize) -> (u8, Vec<u8>) {
+        let bytes = self.address_bytes();
+        let device = self.address | (offset >> (8 * bytes)) as u8;
+        let memory = (0..bytes)
+            .rev()
+            .map(|i| (offset >> (8 * i)) as u8)
+            .collect();
+        (device, memory)
+    }
+
+    fn check_range(&self, offset: usize, len: usize) -> Result<()> {
+        if offset.checked_add(len).is_none_or(|end| end > self.size) {
+            return Err(Error::InvalidArgument("EEPROM access out of range"));
+        }
+        Ok(())
+    }
+
+    /// Fill `data` starting at `offset`, in one sequential read.
+    pub fn read(&mut self, offset: usize, data: &mut [u8]) -> Result<()> {
+        self.check_range(offset, data.len())?;
+        if data.is_empty() {
+            return Ok(());
+        }
+
+        let (device, memory) = self.locate(offset);
+        self.bus.write_read(device, &memory, data)
+    }
+
+    /// Write `data` starting at `offset`, a page at a time, waiting for each write
+    /// cycle to finish.
+    pub fn write(&mut self, offset: usize, data: &[u8]) -> Result<()> {
+        self.check_range(offset, data.len())?;
+
+        let mut done = 0;
+        while done < data.len() {
+            let position = offset + done;
+            let len = (self.page_size - position % self.page_size).min(data.len() - done);
+            let (device, mut cmd) = self.locate(position);
+            cmd.extend_from_slice(&data[done..done + len]);
+            self.bus.write(device, &cmd)?;
+            self.wait_ready(device)?;
+            done += len;
+        }
+        Ok(())
+    }
+
+    // The EEPROM ignores its address until the write cycle is over.
+    fn wait_ready(&mut self, device: u8) -> Result<()> {
+        let start = Instant::now();
+        loop {
+            self.bus.start()?;
+            let acked = self.bus.write_byte(device << 1);
+            let stopped = self.bus.stop();
+            if acked? {
+                return stopped;
+            }
+            stopped?;
+
+            if start.elapsed() >= WRITE_CYCLE_TIMEOUT {
+                return Err(Error::LibUsb(LibUsbError::Timeout));
+            }
+        }
+    }
+}
@@ -0,0 +1,472 @@
+//! JTAG master on the MPSSE, with TAP state tracking and addressing of single
+//! devices in a multi-device chain.
+//!
+//! Bit vectors are passed as bytes, LSB first: bit `i` is `data[i / 8] >> (i % 8)`.
+//! The first bit shifted out on TDI is bit 0, and so is the first bit received on TDO.
+
+use std::collections::VecDeque;
+
+use error::Error;
+use mode::MpsseDevice;
+use mpsse::{Commands, LSB, WRITE_NEG};
+use Result;
+
+/// Test clock, ADBUS0.
+pub const TCK: u8 = 0;
+/// Test data into the chain, ADBUS1.
+pub const TDI: u8 = 1;
+/// Test data out of the chain, ADBUS2.
+pub const TDO: u8 = 2;
+/// Test mode select, ADBUS3.
+pub const TMS: u8 = 3;
+
+// TDI and TMS change on the falling edge, TDO is sampled on the rising one.
+const FLAGS: u8 = LSB | WRITE_NEG;
+// Longest TMS sequence a single command takes.
+const MAX_TMS_BITS: usize = 7;
+
+/// States of the TAP controller.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum TapState {
+    TestLogicReset,
+    RunTestIdle,
+    SelectDrScan,
+    CaptureDr,
+    ShiftDr,
+    Exit1Dr,
+    PauseDr,
+    Exit2Dr,
+    UpdateDr,
+    SelectIrScan,
+    CaptureIr,
+    ShiftIr,
+    Exit1Ir,
+    PauseIr,
+    Exit2Ir,
+    UpdateIr,
+}
+
+impl TapState {
+    const ALL: [TapState; 16] = [
+        TapState::TestLogicReset,
+        TapState::RunTestIdle,
+        TapState::SelectDrScan,
+        TapState::CaptureDr,
+        TapState::ShiftDr,
+        TapState::Exit1Dr,
+        TapState::PauseDr,
+        TapState::Exit2Dr,
+        TapState::UpdateDr,
+        TapState::SelectIrScan,
+        TapState::CaptureIr,
+        TapState::ShiftIr,
+        TapState::Exit1Ir,
+        TapState::PauseIr,
+        TapState::Exit2Ir,
+        TapState::UpdateIr,
+    ];
+
+    /// The state after one clock with TMS at `tms`.
+    pub fn next(self, tms: bool) -> TapState {
+        use self::TapState::*;
+
+        match (self, tms) {
+            (TestLogicReset, false) => RunTestIdle,
+            (TestLogicReset, true) => TestLogicReset,
+            (RunTestIdle, false) => RunTestIdle,
+            (RunTestIdle, true) => SelectDrScan,
+            (SelectDrScan, false) => CaptureDr,
+            (SelectDrScan, true) => SelectIrScan,
+            (CaptureDr, false) | (Exit2Dr, false) => ShiftDr,
+            (CaptureDr, true) => Exit1Dr,
+            (ShiftDr, false) => ShiftDr,
+            (ShiftDr, true) => Exit1Dr,
+            (Exit1Dr, false) | (PauseDr, false) => PauseDr,
+            (Exit1Dr, true) | (Exit2Dr, true) => UpdateDr,
+            (PauseDr, true) => Exit2Dr,
+            (UpdateDr, false) | (UpdateIr, false) => RunTestIdle,
+            (UpdateDr, true) | (UpdateIr, true) => SelectDrScan,
+            (SelectIrScan, false) => CaptureIr,
+            (SelectIrScan, true) => TestLogicReset,
+            (CaptureIr, false) | (Exit2Ir, false) => ShiftIr,
+            (CaptureIr, true) => Exit1Ir,
+            (ShiftIr, false) => ShiftIr,
+            (ShiftIr, true) => Exit1Ir,
+            (Exit1Ir, false) | (PauseIr, false) => PauseIr,
+            (Exit1Ir, true) | (Exit2Ir, true) => UpdateIr,
+            (PauseIr, true) => Exit2Ir,
+        }
+    }
+
+    /// The shortest TMS sequence leading from this state to `to`.
+    pub fn path(self, to: TapState) -> Vec<bool> {
+        // Breadth-first search; every state is reachable from every other.
+        let mut paths: Vec<Option<Vec<bool>>> = vec![None; TapState::ALL.len()];
+        let mut queue = VecDeque::new();
+        paths[self.index()] = Some(Vec::new());
+        queue.push_back(self);
+        while let Some(state) = queue.pop_front() {
+            if state == to {
+                break;
+            }
+            for &tms in &[false, true] {
+                let next = state.next(tms);
+                if paths[next.index()].is_none() {
+                    let mut path = paths[state.index()].clone().unwrap();
+                    path.push(tms);
+                    paths[next.index()] = Some(path);
+                    queue.push_back(next);
+                }
+            }
+        }
+        paths[to.index()].take().unwrap()
+    }
+
+    fn index(self) -> usize {
+        TapState::ALL.iter().position(|&s| s == self).unwrap()
+    }
+}
+
+/// A JTAG master on ADBUS0..3 of an MPSSE device.
+///
+/// Scans end in Run-Test/Idle.
+pub struct Jtag<'a> {
+    device: &'a MpsseDevice,
+    state: TapState,
+}
+
+impl<'a> Jtag<'a> {
+    /// Claim the JTAG pins, set TCK as close to `frequency` Hz as possible and reset
+    /// the TAPs. The other GPIO pins keep their levels and directions.
+    pub fn new(device: &'a MpsseDevice, frequency: u32) -> Result<Jtag<'a>> {
+        let levels = (device.gpio_output_latch() & !0x0f) | (1 << TMS);
+        let directions = (device.gpio_direction() & !0x0f) | (1 << TCK) | (1 << TDI) | (1 << TMS);
+        device.set_gpio(levels, directions)?;
+        device.set_clock_frequency(frequency)?;
+
+        let mut jtag = Jtag {
+            device,
+            state: TapState::TestLogicReset,
+        };
+        jtag.reset()?;
+        Ok(jtag)
+    }
+
+    /// Set TCK as close to `frequency` Hz as possible, returning the actual frequency.
+    pub fn set_frequency(&self, frequency: u32) -> Result<u32> {
+        self.device.set_clock_frequency(frequency)
+    }
+
+    /// The TAP state the chain is in.
+    pub fn state(&self) -> TapState {
+        self.state
+    }
+
+    /// Move to Test-Logic-Reset by holding TMS HIGH for five clocks, whatever state
+    /// the TAPs are in.
+    pub fn reset(&mut self) -> Result<()> {
+        self.device
+            .execute(&Commands::new().write_tms(FLAGS, 0x1f, 5, false))?;
+        self.state = TapState::TestLogicReset;
+        Ok(())
+    }
+
+    /// Move to `state` along the shortest path.
+    pub fn goto(&mut self, state: TapState) -> Result<()> {
+        let path = self.state.path(state);
+        self.device.execute(&tms(Commands::new(), &path))?;
+        self.state = state;
+        Ok(())
+    }
+
+    /// Move to Run-Test/Idle and stay there for `cycles` clocks.
+    pub fn run_test(&mut self, cycles: usize) -> Result<()> {
+        let mut path = self.state.path(TapState::RunTestIdle);
+        path.resize(path.len() + cycles, false);
+        self.device.execute(&tms(Commands::new(), &path))?;
+        self.state = TapState::RunTestIdle;
+        Ok(())
+    }
+
+    /// Shift the first `bits` bits of `tdi` into the instruction register and return
+    /// the bits shifted out.
+    pub fn shift_ir(&mut self, tdi: &[u8], bits: usize) -> Result<Vec<u8>> {
+        self.scan(TapState::ShiftIr, tdi, bits)
+    }
+
+    /// Shift the first `bits` bits of `tdi` into the selected data register and
+    /// return the bits shifted out.
+    pub fn shift_dr(&mut self, tdi: &[u8], bits: usize) -> Result<Vec<u8>> {
+        self.scan(TapState::ShiftDr, tdi, bits)
+    }
+
+    fn scan(&mut self, shift: TapState, tdi: &[u8], bits: usize) -> Result<Vec<u8>> {
+        if bits == 0 {
+            return Err(Error::InvalidArgument(
+                "JTAG scan must shift at least one bit",
+            ));
+        }
+        if tdi.len() != bits.div_ceil(8) {
+            return Err(Error::InvalidArgument(
+                "JTAG scan data must be exactly as long as the bit count",
+            ));
+        }
+
+        // All but the last bit are shifted as data; the last one goes out together
+        // with the TMS HIGH that leaves the shift state.
+        let body = bits - 1;
+        let (full, rest) = (body / 8, body % 8);
+        let mut commands = tms(Commands::new(), &self.state.path(shift));
+        if full > 0 {
+            commands = commands.transfer_bytes(FLAGS, &tdi[..full]);
+        }
+        if rest > 0 {
+            commands = commands.transfer_bits(FLAGS, tdi[full], rest as u8);
+        }
+        let exit = match shift {
+            TapState::ShiftIr => TapState::Exit1Ir,
+            _ => TapState::Exit1Dr,
+        };
+        commands = commands
+            .transfer_tms(FLAGS, 0b1, 1, bit(tdi, body))
+            .write_tms(FLAGS, 0b01, 2, bit(tdi, body));
+        let response = self.device.execute(&commands)?;
+        self.state = exit.next(true).next(false);
+
+        // Partial shifts fill their byte from the top.
+        let mut tdo = vec![0; tdi.len()];
+        tdo[..full].copy_from_slice(&response[..full]);
+        if rest > 0 {
+            tdo[full] = response[full] >> (8 - rest);
+        }
+        set_bit(&mut tdo, body, response[response.len() - 1] & 0x80 != 0);
+        Ok(tdo)
+    }
+
+    /// Clock `bits` cycles with TMS and TDI taken bit by bit from `tms` and `tdi`,
+    /// returning what was sampled on TDO.
+    ///
+    /// This drives the TAPs directly, e.g. on behalf of a remote JTAG client; the
+    /// tracked state follows along.
+    pub fn shift_raw(&mut self, tms: &[u8], tdi: &[u8], bits: usize) -> Result<Vec<u8>> {
+        let len = bits.div_ceil(8);
+        if tms.len() != len || tdi.len() != len {
+            return Err(Error::InvalidArgument(
+                "JTAG scan data must be exactly as long as the bit count",
+            ));
+        }
+
+        // Runs with TMS LOW are shifted as data once TMS is known to be LOW; every
+        // other bit is clocked with its own TMS command.
+        let mut commands = Commands::new();
+        let mut segments = Vec::new();
+        let mut tms_low = false;
+        let mut i = 0;
+        while i < bits {
+            if tms_low && !bit(tms, i) {
+                let run = (i..bits).take_while(|&j| !bit(tms, j)).count();
+                let mut packed = vec![0; run.div_ceil(8)];
+                for j in 0..run {
+                    set_bit(&mut packed, j, bit(tdi, i + j));
+                }
+                let (full, rest) = (run / 8, run % 8);
+                if full > 0 {
+                    commands = commands.transfer_bytes(FLAGS, &packed[..full]);
+                    segments.push(Segment::Bytes(i, full));
+                }
+                if rest > 0 {
+                    commands = commands.transfer_bits(FLAGS, packed[full], rest as u8);
+                    segments.push(Segment::Bits(i + full * 8, rest));
+                }
+                i += run;
+            } else {
+                commands = commands.transfer_tms(FLAGS, bit(tms, i) as u8, 1, bit(tdi, i));
+                segments.push(Segment::Bits(i, 1));
+                tms_low = !bit(tms, i);
+                i += 1;
+            }
+        }
+        let response = if bits > 0 {
+            self.device.execute(&commands)?
+        } else {
+            Vec::new()
+        };
+        for i in 0..bits {
+            self.state = self.state.next(bit(tms, i));
+        }
+
+        let mut tdo = vec![0; len];
+        let mut pos = 0;
+        for segment in segments {
+            match segment {
+                Segment::Bytes(start, count) => {
+                    for j in 0..count * 8 {
+                        set_bit(&mut tdo, start + j, bit(&response[pos..], j));
+                    }
+                    pos += count;
+                }
+                Segment::Bits(start, count) => {
+                    // Partial shifts fill their byte from the top.
+                    let byte = [response[pos] >> (8 - count)];
+                    for j in 0..count {
+                        set_bit(&mut tdo, start + j, bit(&byte, j));
+                    }
+                    pos += 1;
+                }
+            }
+        }
+        Ok(tdo)
+    }
+
+    /// Return the device, leaving the TAPs in their current state.
+    pub fn into_inner(self) -> &'a MpsseDevice {
+        self.device
+    }
+}
+
+/// Several TAPs daisy-chained between TDI and TDO, with one of them addressed.
+///
+/// Devices are numbered from TDO, i.e. in the order their IDCODEs are read out
+/// after a reset. Every scan loads BYPASS into the other devices' instruction
+/// registers and pads their one-bit bypass registers in data scans, so the target
+/// is addressed as if it were alone on the chain.
+pub struct Chain<'a> {
+    jtag: Jtag<'a>,
+    ir_lengths: Vec<usize>,
+    target: usize,
+}
+
+impl<'a> Chain<'a> {
+    /// Address device `target` of a chain whose instruction registers are
+    /// `ir_lengths` bits long, putting every device into BYPASS.
+    pub fn new(jtag: Jtag<'a>, ir_lengths: &[usize], target: usize) -> Result<Chain<'a>> {
+        if ir_lengths.is_empty() || ir_lengths.contains(&0) {
+            return Err(Error::InvalidArgument(
+                "JTAG chain needs devices with non-empty instruction registers",
+            ));
+        }
+
+        let mut chain = Chain {
+            jtag,
+            ir_lengths: ir_lengths.to_vec(),
+            target: 0,
+        };
+        chain.select(target)?;
+
+        // All ones is BYPASS on every device.
+        let total: usize = chain.ir_lengths.iter().sum();
+        chain.jtag.shift_ir(&vec![0xff; total.div_ceil(8)], total)?;
+        Ok(chain)
+    }
+
+    /// Number of devices in the chain.
+    pub fn device_count(&self) -> usize {
+        self.ir_lengths.len()
+    }
+
+    /// The addressed device.
+    pub fn target(&self) -> usize {
+        self.target
+    }
+
+    /// Address device `target` in the following scans.
+    pub fn select(&mut self, target: usize) -> Result<()> {
+        if target >= self.ir_lengths.len() {
+            return Err(Error::InvalidArgument("JTAG chain has no such device"));
+        }
+        self.target = target;
+        Ok(())
+    }
+
+    /// Shift `tdi` into the target's instruction register and BYPASS into all
+    /// others. `bits` must be the target's instruction register length.
+    pub fn shift_ir(&mut self, tdi: &[u8], bits: usize) -> Result<Vec<u8>> {
+        if bits != self.ir_lengths[self.target] {
+            return Err(Error::InvalidArgument(
+                "Instruction length doesn't match the target device",
+            ));
+        }
+
+        let offset = self.ir_lengths[..self.target].iter().sum();
+        let total = self.ir_lengths.iter().sum();
+        self.padded_scan(TapState::ShiftIr, tdi, bits, offset, total, true)
+    }
+
+    /// Shift `tdi` into the target's selected data register, with the other devices'
+    /// bypass registers padded.
+    pub fn shift_dr(&mut self, tdi: &[u8], bits: usize) -> Result<Vec<u8>> {
+        let total = bits + self.ir_lengths.len() - 1;
+        self.padded_scan(TapState::ShiftDr, tdi, bits, self.target, total, false)
+    }
+
+    // Shift `tdi` at bit `offset` of a `total` bit scan padded with `pad`, and return
+    // the same bits of what comes out.
+    fn padded_scan(
+        &mut self,
+        shift: TapState,
+        tdi: &[u8],
+        bits: usize,
+        offset: usize,
+        total: usize,
+        pad: bool,
+    ) -> Result<Vec<u8>> {
+        if tdi.len() != bits.div_ceil(8) {
+            return Err(Error::InvalidArgument(
+                "JTAG scan data must be exactly as long as the bit count",
+            ));
+        }
+
+        let mut padded = vec![if pad { 0xff } else { 0 }; total.div_ceil(8)];
+        for i in 0..bits {
+            set_bit(&mut padded, offset + i, bit(tdi, i));
+        }
+        let response = self.jtag.scan(shift, &padded, total)?;
+
+        let mut tdo = vec![0; tdi.len()];
+        for i in 0..bits {
+            set_bit(&mut tdo, i, bit(&response, offset + i));
+        }
+        Ok(tdo)
+    }
+
+    /// The JTAG master, e.g. to move between TAP states.
+    pub fn jtag(&mut self) -> &mut Jtag<'a> {
+        &mut self.jtag
+    }
+
+    /// Return the JTAG master, leaving the devices' instructions as they are.
+    pub fn into_inner(self) -> Jtag<'a> {
+        self.jtag
+    }
+}
+
+// Bits of a `shift_raw` response: whole bytes or a partial byte, and the bit they
+// start at.
+enum Segment {
+    Bytes(usize, usize),
+    Bits(usize, usize),
+}
+
+// Append commands clocking `path` out on TMS.
+fn tms(mut commands: Commands, path: &[bool]) -> Commands {
+    for chunk in path.chunks(MAX_TMS_BITS) {
+        let bits = chunk
+            .iter()
+            .enumerate()
+            .fold(0, |bits, (i, &tms)| bits | ((tms as u8) << i));
+        commands = commands.write_tms(FLAGS, bits, chunk.len() as u8, false);
+    }
+    commands
+}
+
+fn bit(data: &[u8], i: usize) -> bool {
+    data[i / 8] & (1 << (i % 8)) != 0
+}
+
+fn set_bit(data: &mut [u8], i: usize, value: bool) {
+    if value {
+        data[i / 8] |= 1 << (i % 8);
+    } else {
+        data[i / 8] &= !(1 << (i % 8));
+    }
+}
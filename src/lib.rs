@@ -1,17 +1,174 @@
 extern crate libftdi1_sys as ftdic;
+extern crate libusb1_sys as usbc;
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(feature = "serde")]
+extern crate toml;
+#[cfg(feature = "tokio")]
+extern crate tokio;
+#[cfg(feature = "tracing")]
+extern crate tracing;
+#[cfg(test)]
+extern crate proptest;
 
 pub use ftdic::ftdi_eeprom_value;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::cell::{Cell, OnceCell};
+use std::convert::TryFrom;
 use std::ffi::{CStr, CString};
+use std::fmt;
 use std::marker::PhantomData;
 use std::os::raw;
 use std::pin::Pin;
-use std::time::Duration;
-
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+mod background;
+pub mod bench;
+pub mod bitbang;
+#[cfg(feature = "tokio")]
+pub mod blocking;
+pub mod buffered;
+pub mod cancel;
+pub mod capabilities;
+pub mod capture;
+pub mod cbus;
+pub mod eeprom;
 pub mod error;
-use error::{Error, LibFtdiError};
+pub mod events;
+pub mod fifo;
+#[cfg(feature = "flash")]
+pub mod flash;
+#[cfg(feature = "fpga")]
+pub mod fpga;
+pub mod frame;
+pub mod gpio;
+pub mod group;
+pub mod hotplug;
+pub mod i2c;
+pub mod i2c_eeprom;
+pub mod jtag;
+pub mod midi;
+pub mod mock;
+pub mod mode;
+pub mod monitor;
+pub mod mpsse;
+pub mod multi;
+pub mod pattern;
+pub mod power;
+pub mod reconnect;
+pub mod remote_bitbang;
+pub mod scope;
+pub mod selftest;
+pub mod shared;
+pub mod spi;
+pub mod split;
+pub mod stream;
+pub mod swd;
+pub mod threads;
+pub mod watchdog;
+#[cfg(feature = "ws2812")]
+pub mod ws2812;
+#[cfg(feature = "xvc")]
+pub mod xvc;
+#[cfg(test)]
+mod tests;
+use error::{BusyReason, Error, LibFtdiError, LibUsbError};
+
+// Convert a string argument for libftdi, rejecting interior NUL bytes.
+fn to_cstring<S: Into<Vec<u8>>>(s: S) -> Result<CString> {
+    CString::new(s).map_err(|_| Error::InvalidArgument("String contains a NUL byte"))
+}
+
+// Payload of trace events.
+#[cfg(feature = "tracing")]
+fn hex(data: &[u8]) -> String {
+    data.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Size of the buffers string descriptors are fetched into.
+const STRING_BUF_LEN: usize = 100;
+
+/// Largest string buffer tried; USB string descriptors hold at most 126 characters,
+/// and EEPROM strings are bounded by the EEPROM size.
+const MAX_STRING_BUF_LEN: usize = 4096;
+
+// Baud rate the chip actually runs at when libftdi is asked for `baudrate`, following
+// libftdi's divisor calculation: the chip divides its 3 MHz (or, on the H series,
+// 12 MHz) base clock by a divisor with three fractional bits.
+fn achieved_baudrate(chip_type: Option<ChipType>, baudrate: u32) -> u32 {
+    let (clk, clk_div): (u64, u64) = match chip_type {
+        Some(chip) if chip.is_hi_speed() && u64::from(baudrate) * 10 > 120_000_000 / 0x3fff => {
+            (120_000_000, 10)
+        }
+        _ => (48_000_000, 16),
+    };
+    let baudrate = u64::from(baudrate.max(1));
+    let best = if baudrate >= clk / clk_div {
+        clk / clk_div
+    } else if baudrate >= clk / (clk_div + clk_div / 2) {
+        clk / (clk_div + clk_div / 2)
+    } else if baudrate >= clk / (2 * clk_div) {
+        clk / (2 * clk_div)
+    } else {
+        // In eighths, rounded to nearest.
+        let divisor = (clk * 16 / clk_div / baudrate).div_ceil(2).min(0x1ffff);
+        (clk * 16 / clk_div / divisor).div_ceil(2)
+    };
+    best as u32
+}
+
+// Convert a NUL-terminated string filled in by libftdi. `c_char` is unsigned on
+// some platforms (e.g. ARM), so go through bytes rather than assuming `i8`.
+fn string_from_c_buf(buf: &[raw::c_char]) -> String {
+    let bytes: Vec<u8> = buf
+        .iter()
+        .take_while(|&&c| c != 0)
+        .map(|&c| c as u8)
+        .collect();
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+// How a server serving one client at a time (`remote_bitbang`, XVC) takes the end of
+// a client: only a gone device stops the server, any other error just ends that
+// client.
+fn client_done(_server: &str, res: Result<()>) -> Result<()> {
+    match res {
+        Err(Error::Disconnected) => Err(Error::Disconnected),
+        #[cfg(feature = "tracing")]
+        Err(e) => {
+            tracing::warn!(error = %e, "{} client failed", _server);
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Largest buffer a single libftdi read or write call can take.
+const MAX_TRANSFER_SIZE: usize = raw::c_int::MAX as usize;
+
+/// Return code libftdi uses when no device is open.
+const FTDI_DEVICE_UNAVAILABLE: raw::c_int = -666;
+
+/// Largest EEPROM image libftdi knows how to handle, in bytes.
+const MAX_EEPROM_SIZE: usize = 256;
 
 /// Low-level wrapper around a ftdi_context instance
-pub struct Context(*mut ftdic::ftdi_context);
+///
+/// A context may be moved to another thread, but libftdi does no locking of its
+/// own, so it is not `Sync`.
+pub struct Context {
+    ftdi: *mut ftdic::ftdi_context,
+    // Set once the USB handle is shared with clones, see `Device::try_clone`.
+    shared_usb: OnceCell<Arc<SharedUsb>>,
+    // Set when libusb is set up by the application rather than libftdi.
+    usb_context: Option<UsbContext>,
+}
+
+// The context and the libusb state it owns aren't tied to the thread that created them.
+unsafe impl Send for Context {}
 
 pub type Result<T> = std::result::Result<T, Error>;
 
@@ -22,11 +179,39 @@ impl Context {
         if ctx.is_null() {
             Err(Error::MallocFailure)
         } else {
-            Ok(Context(ctx))
+            Ok(Context {
+                ftdi: ctx,
+                shared_usb: OnceCell::new(),
+                usb_context: None,
+            })
+        }
+    }
+
+    /// Like [`new`][Context::new], but using `usb_context` instead of setting up libusb
+    /// anew
+    ///
+    /// Many contexts can share one libusb context, e.g. to apply its options to all
+    /// devices or to avoid running libusb's machinery once per device.
+    pub fn with_usb_context(usb_context: &UsbContext) -> Result<Context> {
+        let mut context = Context::new()?;
+        let ctx = context.get_ftdi_context();
+        unsafe {
+            usbc::libusb_exit((*ctx).usb_ctx as *mut usbc::libusb_context);
+            (*ctx).usb_ctx = usb_context.as_raw() as *mut _;
         }
+        context.usb_context = Some(usb_context.clone());
+        Ok(context)
     }
 
+    /// Choose the interface to open on multi-channel chips
+    ///
+    /// Fails with [`Error::AlreadyOpen`] once a device is open, since the interface
+    /// is claimed when opening.
     pub fn set_interface(&self, interface: Interface) -> Result<()> {
+        if self.is_open() {
+            return Err(Error::AlreadyOpen);
+        }
+
         let interface = match interface {
             Interface::Any => ftdic::ftdi_interface::INTERFACE_ANY,
             Interface::A => ftdic::ftdi_interface::INTERFACE_A,
@@ -39,35 +224,398 @@ impl Context {
         self.check_ftdi_error(rc)
     }
 
+    /// Choose what happens to a kernel driver (e.g. ftdi_sio) bound to the interface
+    /// when it is opened. Defaults to [`ModuleDetachMode::AutoDetach`].
+    pub fn set_module_detach_mode(&self, mode: ModuleDetachMode) -> Result<()> {
+        let mode = match mode {
+            ModuleDetachMode::AutoDetach => ftdic::ftdi_module_detach_mode::AUTO_DETACH_SIO_MODULE,
+            ModuleDetachMode::DontDetach => ftdic::ftdi_module_detach_mode::DONT_DETACH_SIO_MODULE,
+            #[cfg(feature = "libftdi1-5")]
+            ModuleDetachMode::AutoDetachReattach => {
+                ftdic::ftdi_module_detach_mode::AUTO_DETACH_REATACH_SIO_MODULE
+            }
+            #[cfg(not(feature = "libftdi1-5"))]
+            ModuleDetachMode::AutoDetachReattach => {
+                return Err(Error::InvalidArgument(
+                    "Reattaching the kernel driver needs libftdi 1.5",
+                ))
+            }
+        };
+
+        unsafe { (*self.get_ftdi_context()).module_detach_mode = mode };
+        Ok(())
+    }
+
     pub fn check_ftdi_error(&self, rc: raw::c_int) -> Result<()> {
-        if rc < 0 {
-            // From looking at libftdi library, the error string is always a static
-            // string literal.
+        if rc == FTDI_DEVICE_UNAVAILABLE || (rc < 0 && usb_device_gone(self.get_ftdi_context())) {
+            Err(Error::Disconnected)
+        } else if rc < 0 {
             let slice = unsafe {
                 let err_raw = ftdic::ftdi_get_error_string(self.get_ftdi_context());
                 CStr::from_ptr(err_raw)
             };
 
-            // If UTF8 validation fails, no point in continuing.
-            Err(Error::LibFtdi(LibFtdiError::new(slice.to_str().unwrap())))
+            Err(Error::LibFtdi(LibFtdiError::with_code(
+                slice.to_string_lossy(),
+                rc,
+            )))
+        } else {
+            Ok(())
+        }
+    }
+
+    // Like check_ftdi_error(), but for the ftdi_usb_open*() family, whose failures to
+    // open or claim the device are usually caused by another driver holding it.
+    // `port_path` narrows down the driver check to the device that was opened.
+    //
+    // The return codes of these functions overlap with different meanings, so the
+    // error message tells which step failed.
+    fn check_open_error(&self, rc: raw::c_int, port_path: Option<&str>) -> Result<()> {
+        if rc >= 0 {
+            return Ok(());
+        }
+
+        let msg = unsafe { CStr::from_ptr(ftdic::ftdi_get_error_string(self.get_ftdi_context())) };
+        let msg = msg.to_bytes();
+
+        let open_failed =
+            msg.starts_with(b"libusb_open() failed") || msg.starts_with(b"usb_open() failed");
+        let claim_failed = msg.starts_with(b"unable to claim usb device")
+            || msg.starts_with(b"unable to set usb configuration");
+
+        let reason = if open_failed {
+            if cfg!(windows) {
+                BusyReason::VendorDriver
+            } else {
+                BusyReason::AccessDenied
+            }
+        } else if msg.starts_with(b"inappropriate permissions") {
+            // The kernel driver is attached and we may not detach it.
+            if sio_driver_bound(port_path) {
+                BusyReason::KernelDriver
+            } else {
+                BusyReason::AccessDenied
+            }
+        } else if claim_failed {
+            if cfg!(target_os = "macos") {
+                BusyReason::VendorDriver
+            } else if sio_driver_bound(port_path) {
+                BusyReason::KernelDriver
+            } else {
+                BusyReason::OtherProgram
+            }
+        } else {
+            return self.check_ftdi_error(rc);
+        };
+
+        Err(Error::DeviceBusy(reason))
+    }
+
+    // For ftdi_write_data(), which turns every libusb error into -1, so only the probe
+    // for an unplugged device can tell a disconnect from other failures. Its error
+    // string is fixed, so it isn't read back from the context (see `split`).
+    fn check_write_error(&self, rc: raw::c_int) -> Result<()> {
+        if rc == FTDI_DEVICE_UNAVAILABLE || (rc < 0 && usb_device_gone(self.get_ftdi_context())) {
+            Err(Error::Disconnected)
+        } else if rc < 0 {
+            Err(Error::LibFtdi(LibFtdiError::with_code("usb bulk write failed", rc)))
         } else {
             Ok(())
         }
     }
 
+    // For raw libusb results and ftdi_read_data(), which passes libusb transfer errors
+    // through as-is, apart from its own "USB device unavailable" code.
+    fn check_transfer_error(&self, rc: raw::c_int) -> Result<()> {
+        if rc == usbc::constants::LIBUSB_ERROR_NO_DEVICE {
+            Err(Error::Disconnected)
+        } else if rc < 0 && rc != FTDI_DEVICE_UNAVAILABLE {
+            Err(Error::LibUsb(LibUsbError::from_code(rc)))
+        } else {
+            self.check_ftdi_error(rc)
+        }
+    }
+
     #[inline]
     pub fn get_ftdi_context(&self) -> *mut ftdic::ftdi_context {
-        self.0
+        self.ftdi
+    }
+
+    /// Give up ownership of the underlying ftdi_context, e.g. to hand it to a C library
+    ///
+    /// The caller becomes responsible for freeing it with `ftdi_free()`. Fails, returning
+    /// the context back, if its USB handle is shared with clones of a [`Device`] or it
+    /// uses a shared [`UsbContext`], since others still rely on those.
+    pub fn into_raw(self) -> std::result::Result<*mut ftdic::ftdi_context, Context> {
+        if self.is_shared() || self.usb_context.is_some() {
+            return Err(self);
+        }
+
+        let ctx = self.get_ftdi_context();
+        std::mem::forget(self);
+        Ok(ctx)
+    }
+
+    /// Take ownership of a ftdi_context, freeing it with `ftdi_free()` when dropped
+    ///
+    /// # Safety
+    ///
+    /// `ctx` must come from `ftdi_new()` (or be initialized by `ftdi_init()` on memory from
+    /// `malloc()`), and nothing else may use or free it afterwards.
+    pub unsafe fn from_raw(ctx: *mut ftdic::ftdi_context) -> Context {
+        Context {
+            ftdi: ctx,
+            shared_usb: OnceCell::new(),
+            usb_context: None,
+        }
+    }
+
+    // Take the USB handle and libusb context away from libftdi, so they stay open
+    // until every context sharing them is gone.
+    fn share_usb(&self) -> Arc<SharedUsb> {
+        self.shared_usb
+            .get_or_init(|| {
+                let ctx = self.get_ftdi_context();
+                Arc::new(SharedUsb {
+                    context: unsafe { (*ctx).usb_ctx as *mut usbc::libusb_context },
+                    handle: unsafe { (*ctx).usb_dev as *mut usbc::libusb_device_handle },
+                    interface: unsafe { (*ctx).interface },
+                    usb_context: self.usb_context.clone(),
+                })
+            })
+            .clone()
+    }
+
+    fn is_shared(&self) -> bool {
+        self.shared_usb.get().is_some()
+    }
+
+    /// Whether a device is open on this context
+    pub fn is_open(&self) -> bool {
+        !unsafe { (*self.get_ftdi_context()).usb_dev }.is_null()
+    }
+
+    /// Interface set through [`set_interface`][Context::set_interface], or in use
+    /// once a device is open; [`Interface::Any`] reads back as [`Interface::A`]
+    pub fn interface(&self) -> Interface {
+        match unsafe { (*self.get_ftdi_context()).interface } {
+            1 => Interface::B,
+            2 => Interface::C,
+            3 => Interface::D,
+            _ => Interface::A,
+        }
+    }
+
+    // Vendor and product id of the open device, if any.
+    fn usb_ids(&self) -> Option<(u16, u16)> {
+        let usb_dev =
+            unsafe { (*self.get_ftdi_context()).usb_dev as *mut usbc::libusb_device_handle };
+        if usb_dev.is_null() {
+            return None;
+        }
+
+        let info = usb_device_info(unsafe { usbc::libusb_get_device(usb_dev) }).ok()?;
+        Some((info.vendor_id, info.product_id))
+    }
+}
+
+impl fmt::Debug for Context {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let ctx = unsafe { &*self.get_ftdi_context() };
+        let mut s = f.debug_struct("Context");
+
+        match self.usb_ids() {
+            Some((vid, pid)) => s
+                .field("vendor_id", &format_args!("{:#06x}", vid))
+                .field("product_id", &format_args!("{:#06x}", pid)),
+            None => s.field("open", &false),
+        };
+
+        s.field("chip_type", &ChipType::from_raw(ctx.type_))
+            .field("interface", &self.interface())
+            .field("baudrate", &ctx.baudrate)
+            .field("bitbang_mode", &format_args!("{:#04x}", ctx.bitbang_mode))
+            .field("bitbang_enabled", &(ctx.bitbang_enabled != 0))
+            .finish()
+    }
+}
+
+// USB handle shared by a device and its clones, closed along with the last of them.
+struct SharedUsb {
+    context: *mut usbc::libusb_context,
+    handle: *mut usbc::libusb_device_handle,
+    interface: raw::c_int,
+    // The application's libusb context, if the handle was opened on one.
+    usb_context: Option<UsbContext>,
+}
+
+// libusb handles and contexts may be used from any thread.
+unsafe impl Send for SharedUsb {}
+unsafe impl Sync for SharedUsb {}
+
+impl Drop for SharedUsb {
+    fn drop(&mut self) {
+        unsafe {
+            usbc::libusb_release_interface(self.handle, self.interface);
+            usbc::libusb_close(self.handle);
+            if self.usb_context.is_none() {
+                usbc::libusb_exit(self.context);
+            }
+        }
+    }
+}
+
+/// A libusb context that several [`Context`]s can share, see
+/// [`Context::with_usb_context`]
+///
+/// Clones refer to the same libusb context, which is torn down once the last clone
+/// and the last context using it are gone.
+#[derive(Clone)]
+pub struct UsbContext {
+    inner: Arc<OwnedUsbContext>,
+}
+
+struct OwnedUsbContext(*mut usbc::libusb_context);
+
+// libusb contexts may be used from any thread.
+unsafe impl Send for OwnedUsbContext {}
+unsafe impl Sync for OwnedUsbContext {}
+
+impl Drop for OwnedUsbContext {
+    fn drop(&mut self) {
+        unsafe { usbc::libusb_exit(self.0) };
+    }
+}
+
+/// Verbosity of libusb's own messages, printed to stderr
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    None,
+    Error,
+    Warning,
+    Info,
+    Debug,
+}
+
+impl UsbContext {
+    pub fn new() -> Result<UsbContext> {
+        let mut ctx = std::ptr::null_mut();
+        let rc = unsafe { usbc::libusb_init(&mut ctx) };
+        if rc < 0 {
+            return Err(Error::LibUsb(LibUsbError::from_code(rc)));
+        }
+        Ok(UsbContext {
+            inner: Arc::new(OwnedUsbContext(ctx)),
+        })
+    }
+
+    pub fn set_log_level(&self, level: LogLevel) -> Result<()> {
+        let level = match level {
+            LogLevel::None => usbc::constants::LIBUSB_LOG_LEVEL_NONE,
+            LogLevel::Error => usbc::constants::LIBUSB_LOG_LEVEL_ERROR,
+            LogLevel::Warning => usbc::constants::LIBUSB_LOG_LEVEL_WARNING,
+            LogLevel::Info => usbc::constants::LIBUSB_LOG_LEVEL_INFO,
+            LogLevel::Debug => usbc::constants::LIBUSB_LOG_LEVEL_DEBUG,
+        };
+        let rc = unsafe {
+            usbc::libusb_set_option(
+                self.as_raw(),
+                usbc::constants::LIBUSB_OPTION_LOG_LEVEL,
+                level,
+            )
+        };
+        if rc < 0 {
+            return Err(Error::LibUsb(LibUsbError::from_code(rc)));
+        }
+        Ok(())
+    }
+
+    /// The underlying libusb context, e.g. to set options not covered here
+    pub fn as_raw(&self) -> *mut usbc::libusb_context {
+        self.inner.0
+    }
+}
+
+impl fmt::Debug for UsbContext {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("UsbContext").field(&self.inner.0).finish()
+    }
+}
+
+// Whether the Linux ftdi_sio driver is bound to an interface of the device at
+// `port_path`, or of any device if it is not known. sysfs names interfaces
+// "<port path>:<config>.<interface>", the same port path format as ours.
+#[cfg(target_os = "linux")]
+fn sio_driver_bound(port_path: Option<&str>) -> bool {
+    let entries = match std::fs::read_dir("/sys/bus/usb/drivers/ftdi_sio") {
+        Ok(entries) => entries,
+        Err(_) => return false,
+    };
+
+    entries.filter_map(|e| e.ok()).any(|e| {
+        let name = e.file_name();
+        let name = name.to_string_lossy();
+        match name.find(':') {
+            Some(colon) => port_path.iter().all(|&p| name[..colon] == *p),
+            None => false,
+        }
+    })
+}
+
+#[cfg(not(target_os = "linux"))]
+fn sio_driver_bound(_port_path: Option<&str>) -> bool {
+    false
+}
+
+/// Timeout of the request used to check whether a device is still attached.
+const DISCONNECT_PROBE_TIMEOUT_MS: raw::c_uint = 100;
+
+// After a failed call, check whether the device behind `ctx` has gone away. Most
+// libftdi functions collapse libusb errors into their own codes, so ask the device
+// for its status to find out.
+fn usb_device_gone(ctx: *mut ftdic::ftdi_context) -> bool {
+    let usb_dev = unsafe { (*ctx).usb_dev as *mut usbc::libusb_device_handle };
+    if usb_dev.is_null() {
+        return false;
     }
+
+    let mut status = [0u8; 2];
+    let rc = unsafe {
+        usbc::libusb_control_transfer(
+            usb_dev,
+            usbc::constants::LIBUSB_ENDPOINT_IN
+                | usbc::constants::LIBUSB_REQUEST_TYPE_STANDARD
+                | usbc::constants::LIBUSB_RECIPIENT_DEVICE,
+            usbc::constants::LIBUSB_REQUEST_GET_STATUS,
+            0,
+            0,
+            status.as_mut_ptr(),
+            status.len() as u16,
+            DISCONNECT_PROBE_TIMEOUT_MS,
+        )
+    };
+    rc == usbc::constants::LIBUSB_ERROR_NO_DEVICE
 }
 
 impl Drop for Context {
     fn drop(&mut self) {
-        unsafe { ftdic::ftdi_free(self.get_ftdi_context()) }
+        let ctx = self.get_ftdi_context();
+        unsafe {
+            // Leave a shared handle to SharedUsb, dropped right after this.
+            if self.is_shared() {
+                (*ctx).usb_dev = std::ptr::null_mut();
+            }
+            // The libusb context belongs to SharedUsb or the application.
+            if self.is_shared() || self.usb_context.is_some() {
+                (*ctx).usb_ctx = std::ptr::null_mut();
+            }
+            ftdic::ftdi_free(ctx)
+        }
     }
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum Interface {
     Any,
     A,
@@ -76,7 +624,50 @@ pub enum Interface {
     D,
 }
 
+/// What to do with a kernel driver bound to an interface, see
+/// [`Context::set_module_detach_mode`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum ModuleDetachMode {
+    /// Detach the driver when opening, leaving it detached after closing.
+    AutoDetach,
+    /// Fail to open an interface a driver is bound to, so e.g. a console port is
+    /// never taken over.
+    DontDetach,
+    /// Detach the driver when opening and reattach it on close (libftdi 1.5).
+    AutoDetachReattach,
+}
+
+/// Number of data bits in a UART character.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum DataBits {
+    Seven,
+    Eight,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum StopBits {
+    One,
+    OneAndHalf,
+    Two,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Parity {
+    None,
+    Odd,
+    Even,
+    /// Parity bit always set.
+    Mark,
+    /// Parity bit always clear.
+    Space,
+}
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum FlowControl {
     Disabled,
     RtsCts,
@@ -84,6 +675,7 @@ pub enum FlowControl {
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum BitMode {
     Reset,
     Bitbang,
@@ -96,175 +688,1080 @@ pub enum BitMode {
     FT1284,
 }
 
-pub struct AsyncRead<'b> {
-    phantom: PhantomData<&'b mut [u8]>,
-    transfer_control: *mut ftdic::ftdi_transfer_control,
+/// FTDI chip families, as distinguished by libftdi
+///
+/// The family is detected from the device release number when a device is opened,
+/// see [`Device::chip_type`], so it doesn't depend on the (reprogrammable) product id.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum ChipType {
+    /// FT8U232AM
+    AM,
+    /// FT232BM/BL and FT245BM/BL
+    BM,
+    /// FT2232C/D/L
+    FT2232C,
+    /// FT232R and FT245R
+    FT232R,
+    FT2232H,
+    FT4232H,
+    FT232H,
+    /// FT-X series: FT200XD, FT201X, FT220X, FT221X, FT230X, FT231X, FT234XD, FT240X
+    FTX,
 }
 
-impl<'b> AsyncRead<'b> {
-    /// Wait for completion of the transfer.
-    pub fn wait(self) -> Result<usize> {
-        let rc = unsafe { ftdic::ftdi_transfer_data_done(self.transfer_control) };
-        if rc < 0 {
-            Err(Error::LibFtdi(LibFtdiError::new(
-                "Error completing transfer",
-            )))
-        } else {
-            Ok(rc as usize)
+impl ChipType {
+    fn from_raw(raw: ftdic::ftdi_chip_type) -> Option<ChipType> {
+        match raw {
+            ftdic::ftdi_chip_type::TYPE_AM => Some(ChipType::AM),
+            ftdic::ftdi_chip_type::TYPE_BM => Some(ChipType::BM),
+            ftdic::ftdi_chip_type::TYPE_2232C => Some(ChipType::FT2232C),
+            ftdic::ftdi_chip_type::TYPE_R => Some(ChipType::FT232R),
+            ftdic::ftdi_chip_type::TYPE_2232H => Some(ChipType::FT2232H),
+            ftdic::ftdi_chip_type::TYPE_4232H => Some(ChipType::FT4232H),
+            ftdic::ftdi_chip_type::TYPE_232H => Some(ChipType::FT232H),
+            ftdic::ftdi_chip_type::TYPE_230X => Some(ChipType::FTX),
+            _ => None,
         }
     }
 
-    /// Cancel transfer and wait for completion.
-    pub fn cancel(self, timeout: Duration) {
-        let mut time = ftdic::timeval {
-            tv_sec: (timeout.as_secs() as i32).into(),
-            tv_usec: (timeout.subsec_micros() as i32).into(),
-        };
+    /// Whether the chip is one of the hi-speed (480 Mbit/s) H series
+    pub fn is_hi_speed(self) -> bool {
+        matches!(
+            self,
+            ChipType::FT2232H | ChipType::FT4232H | ChipType::FT232H
+        )
+    }
+
+    /// Number of interfaces (channels) the chip provides
+    pub fn interface_count(self) -> u8 {
+        match self {
+            ChipType::FT2232C | ChipType::FT2232H => 2,
+            ChipType::FT4232H => 4,
+            _ => 1,
+        }
+    }
 
-        unsafe { ftdic::ftdi_transfer_data_cancel(self.transfer_control, &mut time) };
+    // Same detection libftdi performs when opening a device.
+    fn from_descriptor(desc: &usbc::libusb_device_descriptor) -> Option<ChipType> {
+        match desc.bcdDevice {
+            0x400 => Some(ChipType::BM),
+            0x200 if desc.iSerialNumber == 0 => Some(ChipType::BM),
+            0x200 => Some(ChipType::AM),
+            0x500 => Some(ChipType::FT2232C),
+            0x600 => Some(ChipType::FT232R),
+            0x700 => Some(ChipType::FT2232H),
+            0x800 => Some(ChipType::FT4232H),
+            0x900 => Some(ChipType::FT232H),
+            0x1000 => Some(ChipType::FTX),
+            _ => None,
+        }
     }
 }
 
-/// High level control for a FTDI device
-pub struct Device {
-    context: Context,
-    eeprom_read: bool,
+// Parse a value by its `Display` name, ignoring case.
+fn parse_name<T: Copy + fmt::Display>(all: &[T], s: &str, err: &'static str) -> Result<T> {
+    all.iter()
+        .cloned()
+        .find(|v| v.to_string().eq_ignore_ascii_case(s))
+        .ok_or(Error::InvalidArgument(err))
 }
 
-impl Device {
-    /// Opens the first device with a given vendor and product ids
-    pub fn from_vid_pid(interface: Interface, vid: u16, pid: u16) -> Result<Device> {
-        Device::from_description_serial(interface, vid, pid, None, None)
-    }
+impl Interface {
+    const ALL: [Interface; 5] = [
+        Interface::Any,
+        Interface::A,
+        Interface::B,
+        Interface::C,
+        Interface::D,
+    ];
+}
 
-    /// Opens the first device with a given, vendor id, product id, description, and serial
-    pub fn from_description_serial(
-        interface: Interface,
-        vid: u16,
-        pid: u16,
-        description: Option<String>,
-        serial: Option<String>,
-    ) -> Result<Device> {
-        Device::from_description_serial_index(interface, vid, pid, description, serial, 0)
-    }
+/// Converts from libftdi's numbering: 0 is any interface, 1 is A and so on.
+impl TryFrom<u8> for Interface {
+    type Error = Error;
 
-    /// Opens the index-th device with a given, vendor id, product id, description, and serial
-    pub fn from_description_serial_index(
-        interface: Interface,
-        vid: u16,
-        pid: u16,
-        description: Option<String>,
-        serial: Option<String>,
-        index: u32,
-    ) -> Result<Device> {
-        let context = Context::new()?;
-        context.set_interface(interface)?;
+    fn try_from(raw: u8) -> Result<Interface> {
+        Interface::ALL
+            .get(raw as usize)
+            .cloned()
+            .ok_or(Error::InvalidArgument("Unknown interface"))
+    }
+}
 
-        let (desc, desc_supplied) = match description {
-            Some(d) => (CString::new(d).unwrap().into_raw(), true),
-            None => (std::ptr::null_mut(), false),
-        };
-        let (ser, ser_supplied) = match serial {
-            Some(s) => (CString::new(s).unwrap().into_raw(), true),
-            None => (std::ptr::null_mut(), false),
+impl fmt::Display for Interface {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Interface::Any => "any",
+            Interface::A => "A",
+            Interface::B => "B",
+            Interface::C => "C",
+            Interface::D => "D",
         };
+        f.write_str(name)
+    }
+}
 
-        let rc = unsafe {
-            ftdic::ftdi_usb_open_desc_index(
-                context.0,
-                vid as raw::c_int,
-                pid as raw::c_int,
-                desc,
-                ser,
-                index as raw::c_uint,
-            )
-        };
+impl FromStr for Interface {
+    type Err = Error;
 
-        if desc_supplied {
-            drop(unsafe { CString::from_raw(desc) }); // String must be manually free'd
-        }
-        if ser_supplied {
-            drop(unsafe { CString::from_raw(ser) }); // String must be manually free'd
-        }
-        context.check_ftdi_error(rc)?;
-        Ok(Device {
-            context,
-            eeprom_read: false,
-        })
+    fn from_str(s: &str) -> Result<Interface> {
+        parse_name(&Interface::ALL, s, "Unknown interface")
     }
+}
 
-    /// Opens the device at a given USB bus and device address
-    pub fn from_bus_addr(interface: Interface, bus: u8, addr: u8) -> Result<Device> {
-        let context = Context::new()?;
-        context.set_interface(interface)?;
+impl FlowControl {
+    const ALL: [FlowControl; 3] = [
+        FlowControl::Disabled,
+        FlowControl::RtsCts,
+        FlowControl::DtrDsr,
+    ];
+}
 
-        let rc = unsafe { ftdic::ftdi_usb_open_bus_addr(context.get_ftdi_context(), bus, addr) };
-        context.check_ftdi_error(rc)?;
-        Ok(Device {
-            context,
-            eeprom_read: false,
-        })
+/// Converts from the high byte of libftdi's `SIO_*_HS` values.
+impl TryFrom<u8> for FlowControl {
+    type Error = Error;
+
+    fn try_from(raw: u8) -> Result<FlowControl> {
+        FlowControl::ALL
+            .get(raw as usize)
+            .cloned()
+            .ok_or(Error::InvalidArgument("Unknown flow control"))
     }
+}
 
-    /// Opens the ftdi-device described by a description-string
-    ///
-    /// Intended to be used for parsing a device-description given as commandline argument
-    ///
-    /// - `d:<devicenode>` path of bus and device-node (e.g. "003/001") within usb device tree (usually at /proc/bus/usb/)
-    /// - `i:<vendor>:<product>` first device with given vendor and product id, ids can be decimal, octal (preceded by "0") or hex (preceded by "0x")
-    /// - `i:<vendor>:<product>:<index>` as above with index being the number of the device (starting with 0) if there are more than one
-    /// - `s:<vendor>:<product>:<serial>` first device with given vendor id, product id and serial string
-    pub fn from_description_string(interface: Interface, description: String) -> Result<Device> {
-        let context = Context::new()?;
-        context.set_interface(interface)?;
+impl fmt::Display for FlowControl {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            FlowControl::Disabled => "disabled",
+            FlowControl::RtsCts => "rts-cts",
+            FlowControl::DtrDsr => "dtr-dsr",
+        };
+        f.write_str(name)
+    }
+}
 
-        let desc = CString::new(description).unwrap().into_raw();
-        let rc = unsafe { ftdic::ftdi_usb_open_string(context.get_ftdi_context(), desc) };
-        drop(unsafe { CString::from_raw(desc) }); // String must be manually free'd
+impl FromStr for FlowControl {
+    type Err = Error;
 
-        context.check_ftdi_error(rc)?;
-        Ok(Device {
-            context,
-            eeprom_read: false,
-        })
+    fn from_str(s: &str) -> Result<FlowControl> {
+        parse_name(&FlowControl::ALL, s, "Unknown flow control")
     }
+}
 
-    /// Set the special event character
-    pub fn set_event_char(&self, event_char: u8, enable: bool) -> Result<()> {
-        let rc = unsafe {
-            ftdic::ftdi_set_event_char(
-                self.context.get_ftdi_context(),
-                event_char as raw::c_uchar,
-                enable as raw::c_uchar,
-            )
-        };
-        self.context.check_ftdi_error(rc)
+impl BitMode {
+    const ALL: [BitMode; 9] = [
+        BitMode::Reset,
+        BitMode::Bitbang,
+        BitMode::Mpsse,
+        BitMode::SyncBB,
+        BitMode::Mcu,
+        BitMode::Opto,
+        BitMode::Cbus,
+        BitMode::SyncFF,
+        BitMode::FT1284,
+    ];
+}
+
+/// Converts from libftdi's `BITMODE_*` values.
+impl TryFrom<u8> for BitMode {
+    type Error = Error;
+
+    fn try_from(raw: u8) -> Result<BitMode> {
+        match raw {
+            0x00 => Ok(BitMode::Reset),
+            0x01 => Ok(BitMode::Bitbang),
+            0x02 => Ok(BitMode::Mpsse),
+            0x04 => Ok(BitMode::SyncBB),
+            0x08 => Ok(BitMode::Mcu),
+            0x10 => Ok(BitMode::Opto),
+            0x20 => Ok(BitMode::Cbus),
+            0x40 => Ok(BitMode::SyncFF),
+            0x80 => Ok(BitMode::FT1284),
+            _ => Err(Error::InvalidArgument("Unknown bitmode")),
+        }
     }
+}
 
-    /// Set error character
-    pub fn set_error_char(&self, error_char: u8, enable: bool) -> Result<()> {
-        let rc = unsafe {
-            ftdic::ftdi_set_error_char(
-                self.context.get_ftdi_context(),
-                error_char as raw::c_uchar,
-                enable as raw::c_uchar,
-            )
+impl fmt::Display for BitMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            BitMode::Reset => "reset",
+            BitMode::Bitbang => "bitbang",
+            BitMode::Mpsse => "mpsse",
+            BitMode::SyncBB => "syncbb",
+            BitMode::Mcu => "mcu",
+            BitMode::Opto => "opto",
+            BitMode::Cbus => "cbus",
+            BitMode::SyncFF => "syncff",
+            BitMode::FT1284 => "ft1284",
         };
-        self.context.check_ftdi_error(rc)
+        f.write_str(name)
     }
+}
 
-    /// Sets the chip baud rate
-    pub fn set_baudrate(&self, baudrate: u32) -> Result<()> {
-        let rc = unsafe { ftdic::ftdi_set_baudrate(self.context.0, baudrate as raw::c_int) };
+impl FromStr for BitMode {
+    type Err = Error;
 
-        self.context.check_ftdi_error(rc)
+    fn from_str(s: &str) -> Result<BitMode> {
+        parse_name(&BitMode::ALL, s, "Unknown bitmode")
+    }
+}
+
+/// A [`BitMode`] together with what its bitmask means in that mode
+///
+/// Used with [`Device::set_bitmode_config`] instead of a raw `(bitmask, mode)` pair.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum BitModeConfig {
+    Reset,
+    /// A HIGH bit in `direction` makes a pin an output.
+    Bitbang {
+        direction: u8,
+    },
+    /// Pin directions are set with MPSSE commands instead.
+    Mpsse,
+    /// A HIGH bit in `direction` makes a pin an output.
+    SyncBB {
+        direction: u8,
+    },
+    Mcu,
+    Opto,
+    /// The low nibbles of `direction` and `value` configure CBUS0..3.
+    Cbus {
+        direction: u8,
+        value: u8,
+    },
+    SyncFF,
+    FT1284,
+}
+
+impl BitModeConfig {
+    pub fn mode(self) -> BitMode {
+        match self {
+            BitModeConfig::Reset => BitMode::Reset,
+            BitModeConfig::Bitbang { .. } => BitMode::Bitbang,
+            BitModeConfig::Mpsse => BitMode::Mpsse,
+            BitModeConfig::SyncBB { .. } => BitMode::SyncBB,
+            BitModeConfig::Mcu => BitMode::Mcu,
+            BitModeConfig::Opto => BitMode::Opto,
+            BitModeConfig::Cbus { .. } => BitMode::Cbus,
+            BitModeConfig::SyncFF => BitMode::SyncFF,
+            BitModeConfig::FT1284 => BitMode::FT1284,
+        }
+    }
+
+    /// The bitmask to pass to [`Device::set_bitmode`].
+    pub fn bitmask(self) -> u8 {
+        match self {
+            BitModeConfig::Bitbang { direction } | BitModeConfig::SyncBB { direction } => direction,
+            BitModeConfig::Cbus { direction, value } => (direction << 4) | (value & 0x0f),
+            _ => 0,
+        }
+    }
+
+    /// Interpret a raw `(bitmask, mode)` pair as passed to [`Device::set_bitmode`].
+    pub fn from_parts(bitmask: u8, mode: BitMode) -> BitModeConfig {
+        match mode {
+            BitMode::Reset => BitModeConfig::Reset,
+            BitMode::Bitbang => BitModeConfig::Bitbang { direction: bitmask },
+            BitMode::Mpsse => BitModeConfig::Mpsse,
+            BitMode::SyncBB => BitModeConfig::SyncBB { direction: bitmask },
+            BitMode::Mcu => BitModeConfig::Mcu,
+            BitMode::Opto => BitModeConfig::Opto,
+            BitMode::Cbus => BitModeConfig::Cbus {
+                direction: bitmask >> 4,
+                value: bitmask & 0x0f,
+            },
+            BitMode::SyncFF => BitModeConfig::SyncFF,
+            BitMode::FT1284 => BitModeConfig::FT1284,
+        }
+    }
+}
+
+impl ChipType {
+    const ALL: [ChipType; 8] = [
+        ChipType::AM,
+        ChipType::BM,
+        ChipType::FT2232C,
+        ChipType::FT232R,
+        ChipType::FT2232H,
+        ChipType::FT4232H,
+        ChipType::FT232H,
+        ChipType::FTX,
+    ];
+}
+
+/// Converts from libftdi's `ftdi_chip_type` numbering.
+impl TryFrom<u8> for ChipType {
+    type Error = Error;
+
+    fn try_from(raw: u8) -> Result<ChipType> {
+        ChipType::ALL
+            .get(raw as usize)
+            .cloned()
+            .ok_or(Error::InvalidArgument("Unknown chip type"))
+    }
+}
+
+impl fmt::Display for ChipType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            ChipType::AM => "AM",
+            ChipType::BM => "BM",
+            ChipType::FT2232C => "FT2232C",
+            ChipType::FT232R => "FT232R",
+            ChipType::FT2232H => "FT2232H",
+            ChipType::FT4232H => "FT4232H",
+            ChipType::FT232H => "FT232H",
+            ChipType::FTX => "FT-X",
+        };
+        f.write_str(name)
+    }
+}
+
+impl FromStr for ChipType {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<ChipType> {
+        parse_name(&ChipType::ALL, s, "Unknown chip type")
+    }
+}
+
+/// How long each round of waiting for a cancelled transfer may take when an async
+/// transfer is dropped while in flight.
+const DROP_CANCEL_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// A read submitted by [`Device::read_data_async`]
+///
+/// Dropping it before [`wait`][AsyncRead::wait] cancels the transfer and waits until
+/// libusb no longer writes to the buffer. It must not be leaked, see the safety
+/// contract of [`read_data_async`][Device::read_data_async].
+pub struct AsyncRead<'a, 'b> {
+    device: &'a Device,
+    phantom: PhantomData<&'b mut [u8]>,
+    buf: *mut u8,
+    transfer_control: *mut ftdic::ftdi_transfer_control,
+}
+
+impl<'a, 'b> AsyncRead<'a, 'b> {
+    /// Wait for completion of the transfer.
+    pub fn wait(mut self) -> Result<usize> {
+        let transfer_control = take_transfer(&mut self.transfer_control);
+        self.device
+            .complete_transfer(transfer_control, capture::Direction::Read, self.buf)
+    }
+
+    /// Cancel transfer and wait for completion.
+    pub fn cancel(mut self, timeout: Duration) {
+        let transfer_control = take_transfer(&mut self.transfer_control);
+        self.device
+            .cancel_transfer(transfer_control, capture::Direction::Read, timeout)
+    }
+}
+
+impl<'a, 'b> Drop for AsyncRead<'a, 'b> {
+    fn drop(&mut self) {
+        self.device
+            .cancel_in_flight(&mut self.transfer_control, capture::Direction::Read);
+    }
+}
+
+/// A write submitted by [`Device::write_data_async`]
+///
+/// Dropping it before [`wait`][AsyncWrite::wait] cancels the transfer, see
+/// [`AsyncRead`].
+pub struct AsyncWrite<'a, 'b> {
+    device: &'a Device,
+    phantom: PhantomData<&'b [u8]>,
+    buf: *const u8,
+    transfer_control: *mut ftdic::ftdi_transfer_control,
+}
+
+impl<'a, 'b> AsyncWrite<'a, 'b> {
+    /// Wait for completion of the transfer.
+    pub fn wait(mut self) -> Result<usize> {
+        let transfer_control = take_transfer(&mut self.transfer_control);
+        self.device
+            .complete_transfer(transfer_control, capture::Direction::Write, self.buf)
+    }
+
+    /// Cancel transfer and wait for completion.
+    pub fn cancel(mut self, timeout: Duration) {
+        let transfer_control = take_transfer(&mut self.transfer_control);
+        self.device
+            .cancel_transfer(transfer_control, capture::Direction::Write, timeout)
+    }
+}
+
+impl<'a, 'b> Drop for AsyncWrite<'a, 'b> {
+    fn drop(&mut self) {
+        self.device
+            .cancel_in_flight(&mut self.transfer_control, capture::Direction::Write);
+    }
+}
+
+/// A read filling its whole buffer, submitted by [`Device::read_exact_async`]
+///
+/// Dropping it before [`wait`][AsyncReadExact::wait] cancels the transfer in
+/// progress, see [`AsyncRead`].
+pub struct AsyncReadExact<'a, 'b> {
+    device: &'a Device,
+    phantom: PhantomData<&'b mut [u8]>,
+    buf: *mut u8,
+    len: usize,
+    done: usize,
+    transfer_control: *mut ftdic::ftdi_transfer_control,
+}
+
+impl<'a, 'b> AsyncReadExact<'a, 'b> {
+    /// Wait until the buffer is full, submitting the rest after each short transfer.
+    pub fn wait(mut self) -> Result<()> {
+        loop {
+            let read = self.device.complete_transfer(
+                take_transfer(&mut self.transfer_control),
+                capture::Direction::Read,
+                unsafe { self.buf.add(self.done) },
+            )?;
+            self.done += read;
+            if self.done == self.len {
+                return Ok(());
+            }
+            if read == 0 {
+                return Err(Error::LibUsb(LibUsbError::Timeout));
+            }
+            let rest = (self.len - self.done).min(MAX_TRANSFER_SIZE);
+            self.transfer_control = self
+                .device
+                .submit_read(unsafe { self.buf.add(self.done) }, rest)?;
+        }
+    }
+
+    /// Cancel the transfer in progress and wait for completion.
+    pub fn cancel(mut self, timeout: Duration) {
+        let transfer_control = take_transfer(&mut self.transfer_control);
+        self.device
+            .cancel_transfer(transfer_control, capture::Direction::Read, timeout)
+    }
+}
+
+impl<'a, 'b> Drop for AsyncReadExact<'a, 'b> {
+    fn drop(&mut self) {
+        self.device
+            .cancel_in_flight(&mut self.transfer_control, capture::Direction::Read);
+    }
+}
+
+/// A write of a whole buffer, submitted by [`Device::write_all_async`]
+///
+/// Dropping it before [`wait`][AsyncWriteAll::wait] cancels the transfer in
+/// progress, see [`AsyncRead`].
+pub struct AsyncWriteAll<'a, 'b> {
+    device: &'a Device,
+    phantom: PhantomData<&'b [u8]>,
+    buf: *const u8,
+    len: usize,
+    done: usize,
+    transfer_control: *mut ftdic::ftdi_transfer_control,
+}
+
+impl<'a, 'b> AsyncWriteAll<'a, 'b> {
+    /// Wait until all data is written, submitting the rest after each short transfer.
+    pub fn wait(mut self) -> Result<()> {
+        loop {
+            let written = self.device.complete_transfer(
+                take_transfer(&mut self.transfer_control),
+                capture::Direction::Write,
+                unsafe { self.buf.add(self.done) },
+            )?;
+            self.done += written;
+            if self.done == self.len {
+                return Ok(());
+            }
+            if written == 0 {
+                return Err(Error::LibUsb(LibUsbError::Timeout));
+            }
+            let rest = (self.len - self.done).min(MAX_TRANSFER_SIZE);
+            self.transfer_control = self
+                .device
+                .submit_write(unsafe { self.buf.add(self.done) }, rest)?;
+        }
+    }
+
+    /// Cancel the transfer in progress and wait for completion.
+    pub fn cancel(mut self, timeout: Duration) {
+        let transfer_control = take_transfer(&mut self.transfer_control);
+        self.device
+            .cancel_transfer(transfer_control, capture::Direction::Write, timeout)
+    }
+}
+
+impl<'a, 'b> Drop for AsyncWriteAll<'a, 'b> {
+    fn drop(&mut self) {
+        self.device
+            .cancel_in_flight(&mut self.transfer_control, capture::Direction::Write);
+    }
+}
+
+// Hand out the transfer in flight, leaving none behind for `Drop` to cancel.
+fn take_transfer(
+    transfer_control: &mut *mut ftdic::ftdi_transfer_control,
+) -> *mut ftdic::ftdi_transfer_control {
+    std::mem::replace(transfer_control, std::ptr::null_mut())
+}
+
+// Wait for a submitted transfer, which frees its transfer control.
+fn transfer_done(transfer_control: *mut ftdic::ftdi_transfer_control) -> Result<usize> {
+    let ctx = unsafe { (*transfer_control).ftdi };
+    let rc = unsafe { ftdic::ftdi_transfer_data_done(transfer_control) };
+    if rc < 0 && usb_device_gone(ctx) {
+        Err(Error::Disconnected)
+    } else if rc < 0 {
+        Err(Error::LibFtdi(LibFtdiError::with_code(
+            "Error completing transfer",
+            rc,
+        )))
+    } else {
+        Ok(rc as usize)
+    }
+}
+
+fn transfer_cancel(transfer_control: *mut ftdic::ftdi_transfer_control, timeout: Duration) {
+    let mut time = ftdic::timeval {
+        tv_sec: (timeout.as_secs() as i32).into(),
+        tv_usec: (timeout.subsec_micros() as i32).into(),
+    };
+
+    unsafe { ftdic::ftdi_transfer_data_cancel(transfer_control, &mut time) };
+}
+
+/// Settings applied to a [`Device`] through its setters, as returned by
+/// [`Device::config`]. `None` means the setting was never changed.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct DeviceConfig {
+    pub baudrate: Option<u32>,
+    pub line_property: Option<(DataBits, StopBits, Parity)>,
+    /// Bitmask and mode last passed to [`set_bitmode`][Device::set_bitmode].
+    pub bitmode: Option<(u8, BitMode)>,
+    pub latency_timer: Option<u8>,
+    /// Read and write timeouts, in ms.
+    pub timeouts: Option<(i32, i32)>,
+    pub flow_control: Option<FlowControl>,
+    /// XON and XOFF characters; replaces `flow_control` when set.
+    pub flow_control_xonxoff: Option<(u8, u8)>,
+    /// Event character and whether it is enabled.
+    pub event_char: Option<(u8, bool)>,
+    pub read_chunk_size: Option<u32>,
+    pub write_chunk_size: Option<u32>,
+}
+
+// Opening devices on an existing context, so a context returned by
+// `Device::close_keep_context` can be reused. The context is consumed either way;
+// on failure it is dropped along with the error.
+impl Context {
+    /// See [`Device::from_vid_pid`].
+    pub fn open_vid_pid(self, interface: Interface, vid: u16, pid: u16) -> Result<Device> {
+        self.open_description_serial(interface, vid, pid, None, None)
+    }
+
+    /// See [`Device::from_description_serial`].
+    pub fn open_description_serial(
+        self,
+        interface: Interface,
+        vid: u16,
+        pid: u16,
+        description: Option<&str>,
+        serial: Option<&str>,
+    ) -> Result<Device> {
+        self.open_description_serial_index(interface, vid, pid, description, serial, 0)
+    }
+
+    /// See [`Device::from_description_serial_index`].
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip(self), err)
+    )]
+    pub fn open_description_serial_index(
+        self,
+        interface: Interface,
+        vid: u16,
+        pid: u16,
+        description: Option<&str>,
+        serial: Option<&str>,
+        index: u32,
+    ) -> Result<Device> {
+        let description = description.map(to_cstring).transpose()?;
+        let serial = serial.map(to_cstring).transpose()?;
+
+        self.set_interface(interface)?;
+
+        let desc = description.as_ref().map_or(std::ptr::null(), |d| d.as_ptr());
+        let ser = serial.as_ref().map_or(std::ptr::null(), |s| s.as_ptr());
+
+        let rc = unsafe {
+            ftdic::ftdi_usb_open_desc_index(
+                self.get_ftdi_context(),
+                vid as raw::c_int,
+                pid as raw::c_int,
+                desc,
+                ser,
+                index as raw::c_uint,
+            )
+        };
+
+        self.check_open_error(rc, None)?;
+        Ok(Device::from_context(self))
+    }
+
+    /// See [`Device::from_bus_addr`].
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip(self), err)
+    )]
+    pub fn open_bus_addr(self, interface: Interface, bus: u8, addr: u8) -> Result<Device> {
+        self.set_interface(interface)?;
+
+        let rc = unsafe { ftdic::ftdi_usb_open_bus_addr(self.get_ftdi_context(), bus, addr) };
+        self.check_open_error(rc, None)?;
+        Ok(Device::from_context(self))
+    }
+
+    /// See [`Device::from_port_path`].
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip(self), err)
+    )]
+    pub fn open_port_path(self, interface: Interface, port_path: &str) -> Result<Device> {
+        match self.find_usb_device(|dev| usb_port_path(dev) == port_path)? {
+            Some(dev) => self.open_usb_device(interface, dev, Some(port_path)),
+            None => Err(Error::InvalidArgument("No device at the given port path")),
+        }
+    }
+
+    /// See [`Device::open`].
+    pub fn open(self, info: &DeviceInfo, interface: Interface) -> Result<Device> {
+        let found = self.find_usb_device(|dev| {
+            usb_device_info(dev).is_ok_and(|dev| dev.bus == info.bus && dev.address == info.address)
+        })?;
+        let dev = match found {
+            Some(dev) => dev,
+            None => return Err(Error::Disconnected),
+        };
+
+        // Bus addresses get reused, so make sure it's still the unit that was listed
+        // before claiming it. An empty serial means the listing skipped the strings,
+        // or the unit has none.
+        let current = usb_device_info(dev.0)?;
+        let serial_matches =
+            info.serial.is_empty() || usb_device_strings(&self, dev.0)?.serial == info.serial;
+        if current.vendor_id != info.vendor_id
+            || current.product_id != info.product_id
+            || current.port_path != info.port_path
+            || !serial_matches
+        {
+            return Err(Error::InvalidArgument(
+                "Device at this address is no longer the listed unit",
+            ));
+        }
+
+        let port_path = current.port_path;
+        self.open_usb_device(interface, dev, Some(&port_path))
+    }
+
+    // Find the first attached device matching `pred`.
+    fn find_usb_device<P>(&self, mut pred: P) -> Result<Option<UsbDeviceRef>>
+    where
+        P: FnMut(*mut usbc::libusb_device) -> bool,
+    {
+        let usb_ctx = unsafe { (*self.get_ftdi_context()).usb_ctx as *mut usbc::libusb_context };
+        let mut list: *const *mut usbc::libusb_device = std::ptr::null();
+        let count = unsafe { usbc::libusb_get_device_list(usb_ctx, &mut list) };
+        if count < 0 {
+            return Err(Error::LibUsb(LibUsbError::from_code(count as raw::c_int)));
+        }
+
+        let devices = unsafe { std::slice::from_raw_parts(list, count as usize) };
+        let found = devices.iter().cloned().find(|&dev| pred(dev));
+        let found = found.map(|dev| UsbDeviceRef(unsafe { usbc::libusb_ref_device(dev) }));
+        unsafe { usbc::libusb_free_device_list(list, 1) };
+        Ok(found)
+    }
+
+    fn open_usb_device(
+        self,
+        interface: Interface,
+        dev: UsbDeviceRef,
+        port_path: Option<&str>,
+    ) -> Result<Device> {
+        self.set_interface(interface)?;
+
+        let rc = unsafe { ftdic::ftdi_usb_open_dev(self.get_ftdi_context(), dev.0 as *mut _) };
+        self.check_open_error(rc, port_path)?;
+        Ok(Device::from_context(self))
+    }
+
+    /// See [`Device::from_description_string`].
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip(self, description), err)
+    )]
+    pub fn open_description_string<S: Into<Vec<u8>>>(
+        self,
+        interface: Interface,
+        description: S,
+    ) -> Result<Device> {
+        let desc = to_cstring(description)?;
+
+        self.set_interface(interface)?;
+
+        let rc = unsafe { ftdic::ftdi_usb_open_string(self.get_ftdi_context(), desc.as_ptr()) };
+
+        self.check_open_error(rc, None)?;
+        Ok(Device::from_context(self))
+    }
+}
+
+// A reference to a libusb device, kept after the device list is freed.
+struct UsbDeviceRef(*mut usbc::libusb_device);
+
+impl Drop for UsbDeviceRef {
+    fn drop(&mut self) {
+        unsafe { usbc::libusb_unref_device(self.0) };
+    }
+}
+
+/// High level control for a FTDI device
+///
+/// Like [`Context`], a device is `Send` but not `Sync`. Use
+/// [`SharedDevice`][shared::SharedDevice] to issue commands from several threads, or
+/// [`split`][Device::split] to read and write from different threads.
+pub struct Device {
+    context: Context,
+    eeprom_read: bool,
+    config: Cell<DeviceConfig>,
+    capture: Mutex<Option<capture::Recorder>>,
+    cancel: Mutex<Option<cancel::CancellationToken>>,
+    // Async reads submitted and not completed or cancelled yet; libftdi's read buffer
+    // must not be reallocated while any is.
+    reads_in_flight: Cell<usize>,
+}
+
+impl Device {
+    fn from_context(context: Context) -> Device {
+        let device = Device {
+            context,
+            eeprom_read: false,
+            config: Cell::new(DeviceConfig::default()),
+            capture: Mutex::new(None),
+            cancel: Mutex::new(None),
+            reads_in_flight: Cell::new(0),
+        };
+        #[cfg(feature = "tracing")]
+        tracing::info!(
+            interface = ?device.interface(),
+            chip_type = ?device.chip_type(),
+            "device opened"
+        );
+        device
+    }
+
+    // The recorder is only ever held for one write to it, so a panic can't leave it
+    // half updated.
+    fn lock_capture(&self) -> MutexGuard<'_, Option<capture::Recorder>> {
+        self.capture.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn lock_cancel(&self) -> MutexGuard<'_, Option<cancel::CancellationToken>> {
+        self.cancel.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    // Fail if the transfer about to start was cancelled.
+    fn check_cancelled(&self) -> Result<()> {
+        match *self.lock_cancel() {
+            Some(ref token) if token.is_cancelled() => Err(Error::Cancelled),
+            _ => Ok(()),
+        }
+    }
+
+    // Hand a completed transfer to the capture and tracing, if enabled.
+    fn log_transfer(&self, direction: capture::Direction, data: &[u8]) {
+        #[cfg(feature = "tracing")]
+        {
+            if !data.is_empty() {
+                tracing::debug!(?direction, len = data.len(), "transfer");
+                tracing::trace!(?direction, data = %hex(data), "transfer data");
+            }
+        }
+        if let Some(recorder) = self.lock_capture().as_mut() {
+            recorder.record(direction, data);
+        }
+    }
+
+    // Record a successfully applied setting.
+    fn update_config<F: FnOnce(&mut DeviceConfig)>(&self, f: F) {
+        let mut config = self.config.get();
+        f(&mut config);
+        self.config.set(config);
+    }
+
+    /// Settings changed through this handle so far
+    pub fn config(&self) -> DeviceConfig {
+        self.config.get()
+    }
+
+    /// Apply every setting present in `config`, e.g. to restore the state of another handle
+    pub fn apply_config(&self, config: &DeviceConfig) -> Result<()> {
+        if let Some(size) = config.read_chunk_size {
+            self.set_read_chunk_size(size)?;
+        }
+        if let Some(size) = config.write_chunk_size {
+            self.set_write_chunk_size(size)?;
+        }
+        if let Some((read_timeout, write_timeout)) = config.timeouts {
+            self.set_timeouts(read_timeout, write_timeout);
+        }
+        if let Some(baudrate) = config.baudrate {
+            self.set_baudrate(baudrate)?;
+        }
+        if let Some((bits, stop_bits, parity)) = config.line_property {
+            self.set_line_property(bits, stop_bits, parity)?;
+        }
+        if let Some(flow_control) = config.flow_control {
+            self.set_flow_control(flow_control)?;
+        }
+        #[cfg(feature = "libftdi1-5")]
+        if let Some((xon, xoff)) = config.flow_control_xonxoff {
+            self.set_flow_control_xonxoff(xon, xoff)?;
+        }
+        #[cfg(not(feature = "libftdi1-5"))]
+        if config.flow_control_xonxoff.is_some() {
+            return Err(Error::InvalidArgument(
+                "XON/XOFF flow control needs libftdi 1.5",
+            ));
+        }
+        if let Some((event_char, enable)) = config.event_char {
+            self.set_event_char(event_char, enable)?;
+        }
+        if let Some(latency) = config.latency_timer {
+            self.set_latency_timer(latency)?;
+        }
+        if let Some((bitmask, mode)) = config.bitmode {
+            self.set_bitmode(bitmask, mode)?;
+        }
+        Ok(())
+    }
+
+    /// Opens the first device with a given vendor and product ids
+    pub fn from_vid_pid(interface: Interface, vid: u16, pid: u16) -> Result<Device> {
+        Context::new()?.open_vid_pid(interface, vid, pid)
+    }
+
+    /// Opens the first device with a given, vendor id, product id, description, and serial
+    pub fn from_description_serial(
+        interface: Interface,
+        vid: u16,
+        pid: u16,
+        description: Option<&str>,
+        serial: Option<&str>,
+    ) -> Result<Device> {
+        Context::new()?.open_description_serial(interface, vid, pid, description, serial)
+    }
+
+    /// Opens the index-th device with a given, vendor id, product id, description, and serial
+    pub fn from_description_serial_index(
+        interface: Interface,
+        vid: u16,
+        pid: u16,
+        description: Option<&str>,
+        serial: Option<&str>,
+        index: u32,
+    ) -> Result<Device> {
+        Context::new()?.open_description_serial_index(
+            interface,
+            vid,
+            pid,
+            description,
+            serial,
+            index,
+        )
+    }
+
+    /// Opens the device at a given USB bus and device address
+    pub fn from_bus_addr(interface: Interface, bus: u8, addr: u8) -> Result<Device> {
+        Context::new()?.open_bus_addr(interface, bus, addr)
+    }
+
+    /// Opens the device attached at a given physical USB port
+    ///
+    /// `port_path` has the form used in [`DeviceInfo::port_path`], e.g. `"1-4.2"` for port 2
+    /// of the hub on port 4 of bus 1. Unlike bus addresses, port paths stay the same when a
+    /// device is replugged into the same port, and unlike serial numbers, they tell apart
+    /// boards with blank or duplicated serials.
+    pub fn from_port_path(interface: Interface, port_path: &str) -> Result<Device> {
+        Context::new()?.open_port_path(interface, port_path)
+    }
+
+    /// Opens exactly the device described by an entry returned from [`list_devices`]
+    ///
+    /// The device is located by the USB bus and address captured at enumeration time,
+    /// failing with [`Error::Disconnected`] if nothing is attached there anymore.
+    /// Since the bus may hand that address to another device after an unplug, the
+    /// device's IDs, port path and serial are checked against `info` before it is
+    /// opened, and a mismatch fails with [`Error::InvalidArgument`].
+    pub fn open(info: &DeviceInfo, interface: Interface) -> Result<Device> {
+        Context::new()?.open(info, interface)
+    }
+
+    /// Opens the ftdi-device described by a description-string
+    ///
+    /// Intended to be used for parsing a device-description given as commandline argument
+    ///
+    /// - `d:<devicenode>` path of bus and device-node (e.g. "003/001") within usb device tree (usually at /proc/bus/usb/)
+    /// - `i:<vendor>:<product>` first device with given vendor and product id, ids can be decimal, octal (preceded by "0") or hex (preceded by "0x")
+    /// - `i:<vendor>:<product>:<index>` as above with index being the number of the device (starting with 0) if there are more than one
+    /// - `s:<vendor>:<product>:<serial>` first device with given vendor id, product id and serial string
+    pub fn from_description_string<S: Into<Vec<u8>>>(
+        interface: Interface,
+        description: S,
+    ) -> Result<Device> {
+        Context::new()?.open_description_string(interface, description)
+    }
+
+    /// Set the special event character
+    pub fn set_event_char(&self, event_char: u8, enable: bool) -> Result<()> {
+        let rc = unsafe {
+            ftdic::ftdi_set_event_char(
+                self.context.get_ftdi_context(),
+                event_char as raw::c_uchar,
+                enable as raw::c_uchar,
+            )
+        };
+        self.context.check_ftdi_error(rc)?;
+        self.update_config(|c| c.event_char = Some((event_char, enable)));
+        Ok(())
+    }
+
+    /// Set error character
+    pub fn set_error_char(&self, error_char: u8, enable: bool) -> Result<()> {
+        let rc = unsafe {
+            ftdic::ftdi_set_error_char(
+                self.context.get_ftdi_context(),
+                error_char as raw::c_uchar,
+                enable as raw::c_uchar,
+            )
+        };
+        self.context.check_ftdi_error(rc)
+    }
+
+    /// Sets the chip baud rate
+    pub fn set_baudrate(&self, baudrate: u32) -> Result<()> {
+        let rc = unsafe {
+            ftdic::ftdi_set_baudrate(self.context.get_ftdi_context(), baudrate as raw::c_int)
+        };
+
+        self.context.check_ftdi_error(rc)?;
+        #[cfg(feature = "tracing")]
+        tracing::debug!(baudrate, "baud rate set");
+        self.update_config(|c| c.baudrate = Some(baudrate));
+        Ok(())
+    }
+
+    /// Like [`set_baudrate`][Device::set_baudrate], but first checks that `baudrate`
+    /// makes sense for the current bitmode and chip, returning the rate achieved
+    ///
+    /// Fails instead of setting anything in modes the baudrate doesn't clock (e.g.
+    /// MPSSE, which has its own clock divisor), for rates beyond what the chip can do,
+    /// and for rates the chip can't get within 3% of. In bitbang modes libftdi asks the
+    /// chip for 4 times the rate, which counts towards its maximum.
+    pub fn set_baudrate_checked(&self, baudrate: u32) -> Result<u32> {
+        if baudrate == 0 {
+            return Err(Error::InvalidArgument("Baudrate must not be 0"));
+        }
+        match self.bitmode() {
+            None
+            | Some(BitModeConfig::Reset)
+            | Some(BitModeConfig::Bitbang { .. })
+            | Some(BitModeConfig::SyncBB { .. }) => {}
+            Some(BitModeConfig::Cbus { .. }) => {
+                // The UART keeps running, but libftdi treats it as bitbang.
+                #[cfg(feature = "tracing")]
+                tracing::warn!(baudrate, "baud rate quadrupled in CBUS bitbang mode");
+            }
+            Some(_) => {
+                return Err(Error::InvalidArgument(
+                    "The baudrate has no effect in this bitmode",
+                ))
+            }
+        }
+
+        let bitbang = unsafe { (*self.context.get_ftdi_context()).bitbang_enabled != 0 };
+        let factor = if bitbang { 4 } else { 1 };
+        let requested = u64::from(baudrate) * factor;
+        let max = self
+            .capabilities()
+            .map_or(3_000_000, |capabilities| capabilities.max_baudrate);
+        if requested > u64::from(max) {
+            return Err(Error::InvalidArgument(if bitbang {
+                "Baudrate too high for the chip, counting 4 times in bitbang mode"
+            } else {
+                "Baudrate too high for the chip"
+            }));
+        }
+
+        let achieved = u64::from(achieved_baudrate(self.chip_type(), requested as u32));
+        if achieved.abs_diff(requested) * 100 > requested * 3 {
+            return Err(Error::InvalidArgument(
+                "The chip can't get within 3% of the baudrate",
+            ));
+        }
+
+        self.set_baudrate(baudrate)?;
+        Ok((achieved / factor) as u32)
+    }
+
+    /// Set the UART character format, e.g. 8N1
+    pub fn set_line_property(
+        &self,
+        bits: DataBits,
+        stop_bits: StopBits,
+        parity: Parity,
+    ) -> Result<()> {
+        let raw_bits = match bits {
+            DataBits::Seven => ftdic::ftdi_bits_type::BITS_7,
+            DataBits::Eight => ftdic::ftdi_bits_type::BITS_8,
+        };
+        let raw_stop_bits = match stop_bits {
+            StopBits::One => ftdic::ftdi_stopbits_type::STOP_BIT_1,
+            StopBits::OneAndHalf => ftdic::ftdi_stopbits_type::STOP_BIT_15,
+            StopBits::Two => ftdic::ftdi_stopbits_type::STOP_BIT_2,
+        };
+        let raw_parity = match parity {
+            Parity::None => ftdic::ftdi_parity_type::NONE,
+            Parity::Odd => ftdic::ftdi_parity_type::ODD,
+            Parity::Even => ftdic::ftdi_parity_type::EVEN,
+            Parity::Mark => ftdic::ftdi_parity_type::MARK,
+            Parity::Space => ftdic::ftdi_parity_type::SPACE,
+        };
+
+        let rc = unsafe {
+            ftdic::ftdi_set_line_property(
+                self.context.get_ftdi_context(),
+                raw_bits,
+                raw_stop_bits,
+                raw_parity,
+            )
+        };
+
+        self.context.check_ftdi_error(rc)?;
+        self.update_config(|c| c.line_property = Some((bits, stop_bits, parity)));
+        Ok(())
     }
 
     /// Enable/disable bitbang modes.
     ///
     /// A HIGH/ON bit configures a line as output, and vice versa
     pub fn set_bitmode(&self, bitmask: u8, mode: BitMode) -> Result<()> {
-        let mode = match mode {
+        let raw_mode = match mode {
             BitMode::Reset => ftdic::ftdi_mpsse_mode::BITMODE_RESET.0,
             BitMode::Bitbang => ftdic::ftdi_mpsse_mode::BITMODE_BITBANG.0,
             BitMode::Mpsse => ftdic::ftdi_mpsse_mode::BITMODE_MPSSE.0,
@@ -280,11 +1777,29 @@ impl Device {
             ftdic::ftdi_set_bitmode(
                 self.context.get_ftdi_context(),
                 bitmask as raw::c_uchar,
-                mode as raw::c_uchar,
+                raw_mode as raw::c_uchar,
             )
         };
 
-        self.context.check_ftdi_error(rc)
+        self.context.check_ftdi_error(rc)?;
+        #[cfg(feature = "tracing")]
+        tracing::debug!(bitmask, ?mode, "bitmode set");
+        self.update_config(|c| c.bitmode = Some((bitmask, mode)));
+        Ok(())
+    }
+
+    /// Enable a bitmode along with its settings, see [`BitModeConfig`]
+    pub fn set_bitmode_config(&self, config: BitModeConfig) -> Result<()> {
+        self.set_bitmode(config.bitmask(), config.mode())
+    }
+
+    /// Bitmode last set through this handle, or `None` if it was never changed
+    ///
+    /// The chip isn't queried, so a mode set by another program or handle isn't reflected.
+    pub fn bitmode(&self) -> Option<BitModeConfig> {
+        self.config()
+            .bitmode
+            .map(|(bitmask, mode)| BitModeConfig::from_parts(bitmask, mode))
     }
 
     /// Set latency timer
@@ -294,7 +1809,9 @@ impl Device {
             ftdic::ftdi_set_latency_timer(self.context.get_ftdi_context(), latency as raw::c_uchar)
         };
 
-        self.context.check_ftdi_error(rc)
+        self.context.check_ftdi_error(rc)?;
+        self.update_config(|c| c.latency_timer = Some(latency));
+        Ok(())
     }
 
     /// Set USB read/write timeouts
@@ -302,42 +1819,67 @@ impl Device {
         let ctx = self.context.get_ftdi_context();
         unsafe { (*ctx).usb_read_timeout = read_timeout as raw::c_int };
         unsafe { (*ctx).usb_write_timeout = write_timeout as raw::c_int };
+        self.update_config(|c| c.timeouts = Some((read_timeout, write_timeout)));
     }
 
     /// Set flowcontrol for ftdi chip
     /// Note: Do not use this function to enable XON/XOFF mode, use [`set_flow_control_xonxoff`][Device::set_flow_control_xonxoff] instead.
     pub fn set_flow_control(&self, flow_control: FlowControl) -> Result<()> {
-        let flow_control = match flow_control {
+        let raw_flow_control = match flow_control {
             FlowControl::Disabled => ftdic::SIO_DISABLE_FLOW_CTRL,
             FlowControl::RtsCts => ftdic::SIO_RTS_CTS_HS,
             FlowControl::DtrDsr => ftdic::SIO_DTR_DSR_HS,
         };
 
         let rc = unsafe {
-            ftdic::ftdi_setflowctrl(self.context.get_ftdi_context(), flow_control as i32)
+            ftdic::ftdi_setflowctrl(self.context.get_ftdi_context(), raw_flow_control)
         };
 
-        self.context.check_ftdi_error(rc)
+        self.context.check_ftdi_error(rc)?;
+        self.update_config(|c| {
+            c.flow_control = Some(flow_control);
+            c.flow_control_xonxoff = None;
+        });
+        Ok(())
     }
 
     /// Set XON/XOFF flowcontrol for ftdi chip
+    ///
+    /// Needs libftdi 1.5 and the `libftdi1-5` feature.
+    #[cfg(feature = "libftdi1-5")]
     pub fn set_flow_control_xonxoff(&self, xon: u8, xoff: u8) -> Result<()> {
         let rc =
             unsafe { ftdic::ftdi_setflowctrl_xonxoff(self.context.get_ftdi_context(), xon, xoff) };
 
-        self.context.check_ftdi_error(rc)
+        self.context.check_ftdi_error(rc)?;
+        self.update_config(|c| {
+            c.flow_control = None;
+            c.flow_control_xonxoff = Some((xon, xoff));
+        });
+        Ok(())
     }
 
     /// Configure read buffer chunk size. Default is 4096.
     /// This is capped to 16,384 on Linux by libftdi1
+    ///
+    /// Fails while an async read is in flight, since libftdi reallocates the read
+    /// buffer the transfer fills.
     pub fn set_read_chunk_size(&self, size: u32) -> Result<()> {
+        if self.reads_in_flight.get() > 0 {
+            return Err(Error::InvalidArgument(
+                "Can't change the read chunk size with an async read in flight",
+            ));
+        }
+
         let rc = unsafe {
             ftdic::ftdi_read_data_set_chunksize(
                 self.context.get_ftdi_context(),
                 size as raw::c_uint,
             )
         };
-        self.context.check_ftdi_error(rc)
+        self.context.check_ftdi_error(rc)?;
+        self.update_config(|c| c.read_chunk_size = Some(size));
+        Ok(())
     }
 
     /// Configure write buffer chunk size. Default is 4096.
@@ -348,10 +1890,19 @@ impl Device {
                 size as raw::c_uint,
             )
         };
+        self.context.check_ftdi_error(rc)?;
+        self.update_config(|c| c.write_chunk_size = Some(size));
+        Ok(())
+    }
+
+    /// Resets the chip's UART engine and clears the internal read buffer
+    pub fn usb_reset(&self) -> Result<()> {
+        let rc = unsafe { ftdic::ftdi_usb_reset(self.context.get_ftdi_context()) };
         self.context.check_ftdi_error(rc)
     }
 
     /// Clears the RX and TX FIFOs on the chip and the internal read buffer.
+    #[cfg(feature = "libftdi1-5")]
     pub fn purge_usb_buffers(&self) -> Result<()> {
         let rc = unsafe { ftdic::ftdi_tcioflush(self.context.get_ftdi_context()) };
 
@@ -359,73 +1910,520 @@ impl Device {
     }
 
     /// Clears the read buffer on the chip and the internal read buffer.
+    #[cfg(feature = "libftdi1-5")]
     pub fn purge_usb_rx_buffer(&self) -> Result<()> {
         let rc = unsafe { ftdic::ftdi_tciflush(self.context.get_ftdi_context()) };
 
         self.context.check_ftdi_error(rc)
     }
 
-    /// Clears the write buffer on the chip.
-    pub fn purge_usb_tx_buffer(&self) -> Result<()> {
-        let rc = unsafe { ftdic::ftdi_tcoflush(self.context.get_ftdi_context()) };
-
-        self.context.check_ftdi_error(rc)
+    /// Clears the write buffer on the chip.
+    #[cfg(feature = "libftdi1-5")]
+    pub fn purge_usb_tx_buffer(&self) -> Result<()> {
+        let rc = unsafe { ftdic::ftdi_tcoflush(self.context.get_ftdi_context()) };
+
+        self.context.check_ftdi_error(rc)
+    }
+
+    /// Clears the RX and TX FIFOs on the chip and the internal read buffer.
+    #[cfg(not(feature = "libftdi1-5"))]
+    pub fn purge_usb_buffers(&self) -> Result<()> {
+        self.purge(true, true)
+    }
+
+    /// Clears the read buffer on the chip and the internal read buffer.
+    #[cfg(not(feature = "libftdi1-5"))]
+    pub fn purge_usb_rx_buffer(&self) -> Result<()> {
+        self.purge(true, false)
+    }
+
+    /// Clears the write buffer on the chip.
+    #[cfg(not(feature = "libftdi1-5"))]
+    pub fn purge_usb_tx_buffer(&self) -> Result<()> {
+        self.purge(false, true)
+    }
+
+    // libftdi 1.4 lacks the tc*flush functions, and its ftdi_usb_purge_* functions
+    // are named after the opposite direction; send the requests libftdi 1.5 does.
+    // The split halves use this with any libftdi, since it reports errors without
+    // libftdi's error string.
+    fn purge(&self, rx: bool, tx: bool) -> Result<()> {
+        const SIO_RESET_REQUEST: u8 = 0;
+        const SIO_TCIFLUSH: u16 = 2;
+        const SIO_TCOFLUSH: u16 = 1;
+
+        let ctx = self.context.get_ftdi_context();
+        let (index, timeout) = unsafe { ((*ctx).index as u16, (*ctx).usb_write_timeout) };
+        let timeout = Duration::from_millis(timeout.max(0) as u64);
+        for &(purge, value) in &[(rx, SIO_TCIFLUSH), (tx, SIO_TCOFLUSH)] {
+            if purge {
+                self.control_transfer(
+                    usbc::constants::LIBUSB_REQUEST_TYPE_VENDOR
+                        | usbc::constants::LIBUSB_RECIPIENT_DEVICE
+                        | usbc::constants::LIBUSB_ENDPOINT_OUT,
+                    SIO_RESET_REQUEST,
+                    value,
+                    index,
+                    &mut [],
+                    timeout,
+                )?;
+            }
+        }
+
+        if rx {
+            unsafe {
+                (*ctx).readbuffer_offset = 0;
+                (*ctx).readbuffer_remaining = 0;
+            }
+        }
+        Ok(())
+    }
+
+    /// Issue a USB control request on the device, e.g. a vendor request libftdi has no
+    /// function for
+    ///
+    /// Bit 7 of `request_type` gives the direction: for device-to-host requests, up to
+    /// `data.len()` bytes are received into `data`, otherwise all of `data` is sent.
+    /// Returns the number of bytes transferred. libftdi's own requests put the
+    /// interface number (1 for A, 2 for B, ...) into `index`. A zero `timeout` waits
+    /// forever.
+    ///
+    /// Requests that change settings behind libftdi's back, such as the baud rate or
+    /// bitmode, leave [`config`][Device::config] out of date.
+    pub fn control_transfer(
+        &self,
+        request_type: u8,
+        request: u8,
+        value: u16,
+        index: u16,
+        data: &mut [u8],
+        timeout: Duration,
+    ) -> Result<usize> {
+        if data.len() > u16::MAX as usize {
+            return Err(Error::InvalidArgument(
+                "Control transfers carry at most 65535 bytes",
+            ));
+        }
+        let timeout = timeout.as_millis().min(raw::c_uint::MAX as u128) as raw::c_uint;
+
+        let rc = unsafe {
+            usbc::libusb_control_transfer(
+                (*self.context.get_ftdi_context()).usb_dev as *mut _,
+                request_type,
+                request,
+                value,
+                index,
+                data.as_mut_ptr(),
+                data.len() as u16,
+                timeout,
+            )
+        };
+        self.context.check_transfer_error(rc)?;
+        Ok(rc as usize)
+    }
+
+    /// Read straight from the bulk IN endpoint of the interface, bypassing libftdi
+    ///
+    /// Unlike [`read_data`][Device::read_data], the two modem status bytes the chip puts
+    /// at the start of every packet (see [`max_packet_size`][Device::max_packet_size])
+    /// are kept. Data libftdi already buffered for `read_data` isn't returned here, so
+    /// purge or drain it before switching between the two.
+    ///
+    /// Returns the number of bytes received, which may be fewer than requested if the
+    /// timeout expires after some data arrived. A zero `timeout` waits forever.
+    pub fn bulk_read_raw(&self, data: &mut [u8], timeout: Duration) -> Result<usize> {
+        let endpoint = unsafe { (*self.context.get_ftdi_context()).out_ep };
+        self.bulk_transfer(endpoint as u8, data.as_mut_ptr(), data.len(), timeout)
+    }
+
+    /// Write straight to the bulk OUT endpoint of the interface, bypassing libftdi
+    ///
+    /// Nothing is buffered on the way, so this can be interleaved with
+    /// [`write_data`][Device::write_data]. Returns the number of bytes sent, which may be
+    /// fewer than `data.len()` if the timeout expires. A zero `timeout` waits forever.
+    pub fn bulk_write_raw(&self, data: &[u8], timeout: Duration) -> Result<usize> {
+        let endpoint = unsafe { (*self.context.get_ftdi_context()).in_ep };
+        // libusb only reads from the buffer of an OUT transfer.
+        self.bulk_transfer(
+            endpoint as u8,
+            data.as_ptr() as *mut u8,
+            data.len(),
+            timeout,
+        )
+    }
+
+    /// Size of the USB packets of the interface, each of which starts with two modem
+    /// status bytes when read raw
+    pub fn max_packet_size(&self) -> usize {
+        unsafe { (*self.context.get_ftdi_context()).max_packet_size as usize }
+    }
+
+    fn bulk_transfer(
+        &self,
+        endpoint: u8,
+        data: *mut u8,
+        len: usize,
+        timeout: Duration,
+    ) -> Result<usize> {
+        if len > MAX_TRANSFER_SIZE {
+            return Err(Error::InvalidArgument(
+                "Buffer too large for a single transfer",
+            ));
+        }
+        let timeout = timeout.as_millis().min(raw::c_uint::MAX as u128) as raw::c_uint;
+
+        let mut transferred: raw::c_int = 0;
+        let rc = unsafe {
+            usbc::libusb_bulk_transfer(
+                (*self.context.get_ftdi_context()).usb_dev as *mut _,
+                endpoint,
+                data,
+                len as raw::c_int,
+                &mut transferred,
+                timeout,
+            )
+        };
+        // A timeout still reports what got through before it.
+        if rc == usbc::constants::LIBUSB_ERROR_TIMEOUT && transferred > 0 {
+            return Ok(transferred as usize);
+        }
+        self.context.check_transfer_error(rc)?;
+        Ok(transferred as usize)
+    }
+
+    /// Directly read pin state, circumventing the read buffer. Useful for bitbang mode.
+    pub fn read_pins(&self) -> Result<u8> {
+        let mut pins: u8 = 0;
+        let pins_ptr = std::slice::from_mut(&mut pins).as_mut_ptr();
+
+        let rc = unsafe { ftdic::ftdi_read_pins(self.context.get_ftdi_context(), pins_ptr) };
+
+        self.context.check_ftdi_error(rc)?;
+        Ok(pins)
+    }
+
+    /// Reads data in chunks (see [`set_read_chunk_size`][Device::set_read_chunk_size]) from the chip.
+    ///
+    /// Returns the number of bytes read, which is less than `data.len()` if the chip had
+    /// no more data available.
+    pub fn read_data(&self, data: &mut [u8]) -> Result<usize> {
+        self.check_cancelled()?;
+        let mut total = 0;
+
+        // libftdi takes an int length, so split buffers it cannot describe.
+        for chunk in data.chunks_mut(MAX_TRANSFER_SIZE) {
+            let rc = unsafe {
+                ftdic::ftdi_read_data(
+                    self.context.get_ftdi_context(),
+                    chunk.as_mut_ptr(),
+                    chunk.len() as raw::c_int,
+                )
+            };
+            self.context.check_transfer_error(rc)?;
+
+            total += rc as usize;
+            if (rc as usize) < chunk.len() {
+                break;
+            }
+        }
+
+        self.log_transfer(capture::Direction::Read, &data[..total]);
+        Ok(total)
+    }
+
+    /// Reads until `data` is full
+    ///
+    /// Fails with a timeout error if the chip sends nothing for longer than the read
+    /// timeout (see [`set_timeouts`][Device::set_timeouts]).
+    pub fn read_exact(&self, data: &mut [u8]) -> Result<()> {
+        let timeout = unsafe { (*self.context.get_ftdi_context()).usb_read_timeout };
+        let timeout = Duration::from_millis(timeout.max(0) as u64);
+
+        let mut filled = 0;
+        let mut last_progress = Instant::now();
+        while filled < data.len() {
+            let read = self.read_data(&mut data[filled..])?;
+            if read > 0 {
+                filled += read;
+                last_progress = Instant::now();
+            } else if last_progress.elapsed() >= timeout {
+                return Err(Error::LibUsb(LibUsbError::Timeout));
+            }
+        }
+        Ok(())
+    }
+
+    // Reads into `data` until at least a byte arrives, failing like `read_exact`.
+    fn read_some(&self, data: &mut [u8]) -> Result<usize> {
+        let timeout = unsafe { (*self.context.get_ftdi_context()).usb_read_timeout };
+        let timeout = Duration::from_millis(timeout.max(0) as u64);
+
+        let start = Instant::now();
+        loop {
+            let read = self.read_data(data)?;
+            if read > 0 || data.is_empty() {
+                return Ok(read);
+            }
+            if start.elapsed() >= timeout {
+                return Err(Error::LibUsb(LibUsbError::Timeout));
+            }
+        }
+    }
+
+    /// Reads one frame ending in `event_char` into `frame`, returning its length
+    ///
+    /// Enables `event_char` as the event character (see
+    /// [`set_event_char`][Device::set_event_char]), so the chip sends a frame as soon as
+    /// its last byte arrives instead of waiting for the latency timer. Data after the
+    /// event character is kept for the next read. If `frame` fills up first, the
+    /// returned length is `frame.len()` and the frame continues in the next read.
+    ///
+    /// Fails with a timeout error if no complete frame arrives within `timeout`; the
+    /// partial frame is dropped.
+    pub fn read_frame(&self, event_char: u8, frame: &mut [u8], timeout: Duration) -> Result<usize> {
+        if self.config().event_char != Some((event_char, true)) {
+            self.set_event_char(event_char, true)?;
+        }
+
+        let start = Instant::now();
+        let mut filled = 0;
+        while filled < frame.len() {
+            // Take what libftdi already buffered up to the event character, or else
+            // make it fetch more with a one byte read.
+            let pending = self.pending_read_data();
+            let len = match pending.iter().position(|&b| b == event_char) {
+                Some(i) => i + 1,
+                None => pending.len().max(1),
+            };
+            let len = len.min(frame.len() - filled);
+
+            let read = self.read_data(&mut frame[filled..filled + len])?;
+            filled += read;
+            if read > 0 && frame[filled - 1] == event_char {
+                return Ok(filled);
+            }
+            if read == 0 && start.elapsed() >= timeout {
+                return Err(Error::LibUsb(LibUsbError::Timeout));
+            }
+        }
+        Ok(filled)
+    }
+
+    // Data libftdi has received but not handed out yet.
+    fn pending_read_data(&self) -> &[u8] {
+        unsafe {
+            let ctx = self.context.get_ftdi_context();
+            if (*ctx).readbuffer.is_null() || (*ctx).readbuffer_remaining == 0 {
+                return &[];
+            }
+            std::slice::from_raw_parts(
+                (*ctx).readbuffer.add((*ctx).readbuffer_offset as usize),
+                (*ctx).readbuffer_remaining as usize,
+            )
+        }
+    }
+
+    /// Reads data from the chip. Does not wait for completion of the transfer nor does it make sure that the transfer was successful.
+    ///
+    /// # Safety
+    ///
+    /// The returned handle must be waited for, cancelled or dropped. Leaking it (e.g.
+    /// with [`std::mem::forget`]) lets libusb keep using `buf` and the device after their
+    /// borrows end. [`transfer_scope`][Device::transfer_scope] offers the same transfers
+    /// without this contract.
+    pub unsafe fn read_data_async<'a, 'b>(
+        &'a self,
+        mut buf: Pin<&'b mut [u8]>,
+    ) -> Result<AsyncRead<'a, 'b>> {
+        if buf.len() > MAX_TRANSFER_SIZE {
+            return Err(Error::InvalidArgument(
+                "Buffer too large for a single transfer",
+            ));
+        }
+
+        Ok(AsyncRead {
+            device: self,
+            phantom: PhantomData,
+            buf: buf.as_mut_ptr(),
+            transfer_control: self.submit_read(buf.as_mut_ptr(), buf.len())?,
+        })
+    }
+
+    /// Writes data to the chip. Does not wait for completion of the transfer nor does it
+    /// make sure that the transfer was successful.
+    ///
+    /// # Safety
+    ///
+    /// See [`read_data_async`][Device::read_data_async].
+    pub unsafe fn write_data_async<'a, 'b>(
+        &'a self,
+        buf: Pin<&'b [u8]>,
+    ) -> Result<AsyncWrite<'a, 'b>> {
+        if buf.len() > MAX_TRANSFER_SIZE {
+            return Err(Error::InvalidArgument(
+                "Buffer too large for a single transfer",
+            ));
+        }
+
+        Ok(AsyncWrite {
+            device: self,
+            phantom: PhantomData,
+            buf: buf.as_ptr(),
+            transfer_control: self.submit_write(buf.as_ptr(), buf.len())?,
+        })
+    }
+
+    /// Starts reading until `buf` is full, see [`AsyncReadExact::wait`]
+    ///
+    /// Unlike [`read_data_async`][Device::read_data_async], buffers of any size are
+    /// accepted; they are read in as many transfers as needed.
+    ///
+    /// # Safety
+    ///
+    /// See [`read_data_async`][Device::read_data_async].
+    pub unsafe fn read_exact_async<'a, 'b>(
+        &'a self,
+        mut buf: Pin<&'b mut [u8]>,
+    ) -> Result<AsyncReadExact<'a, 'b>> {
+        let first = buf.len().min(MAX_TRANSFER_SIZE);
+        Ok(AsyncReadExact {
+            device: self,
+            phantom: PhantomData,
+            buf: buf.as_mut_ptr(),
+            len: buf.len(),
+            done: 0,
+            transfer_control: self.submit_read(buf.as_mut_ptr(), first)?,
+        })
     }
 
-    /// Directly read pin state, circumventing the read buffer. Useful for bitbang mode.
-    pub fn read_pins(&self) -> Result<u8> {
-        let mut pins: u8 = 0;
-        let pins_ptr = std::slice::from_mut(&mut pins).as_mut_ptr();
+    /// Starts writing all of `buf`, see [`AsyncWriteAll::wait`]
+    ///
+    /// Unlike [`write_data_async`][Device::write_data_async], buffers of any size are
+    /// accepted; they are written in as many transfers as needed.
+    ///
+    /// # Safety
+    ///
+    /// See [`read_data_async`][Device::read_data_async].
+    pub unsafe fn write_all_async<'a, 'b>(
+        &'a self,
+        buf: Pin<&'b [u8]>,
+    ) -> Result<AsyncWriteAll<'a, 'b>> {
+        let first = buf.len().min(MAX_TRANSFER_SIZE);
+        Ok(AsyncWriteAll {
+            device: self,
+            phantom: PhantomData,
+            buf: buf.as_ptr(),
+            len: buf.len(),
+            done: 0,
+            transfer_control: self.submit_write(buf.as_ptr(), first)?,
+        })
+    }
 
-        let rc = unsafe { ftdic::ftdi_read_pins(self.context.get_ftdi_context(), pins_ptr) };
+    // Wait for a transfer submitted on `buf`, and log the data it moved.
+    fn complete_transfer(
+        &self,
+        transfer_control: *mut ftdic::ftdi_transfer_control,
+        direction: capture::Direction,
+        buf: *const u8,
+    ) -> Result<usize> {
+        let done = transfer_done(transfer_control);
+        self.transfer_finished(direction);
+        let done = done?;
+        self.log_transfer(direction, unsafe { std::slice::from_raw_parts(buf, done) });
+        Ok(done)
+    }
 
-        self.context.check_ftdi_error(rc)?;
-        Ok(pins)
+    // Cancel a submitted transfer and wait until libusb is done with its buffer.
+    fn cancel_transfer(
+        &self,
+        transfer_control: *mut ftdic::ftdi_transfer_control,
+        direction: capture::Direction,
+        timeout: Duration,
+    ) {
+        transfer_cancel(transfer_control, timeout);
+        self.transfer_finished(direction);
     }
 
-    /// Reads data in chunks (see [`set_read_chunk_size`][Device::set_read_chunk_size]) from the chip.
-    pub fn read_data(&self, data: &mut [u8]) -> Result<u32> {
-        let raw_ptr = data.as_mut_ptr();
-        let raw_len = data.len() as i32;
+    // libusb may still access the buffer, so it must be done with it before the borrow
+    // ends. `ftdi_transfer_data_cancel` keeps handling events until it is.
+    fn cancel_in_flight(
+        &self,
+        transfer_control: &mut *mut ftdic::ftdi_transfer_control,
+        direction: capture::Direction,
+    ) {
+        if !transfer_control.is_null() {
+            self.cancel_transfer(
+                take_transfer(transfer_control),
+                direction,
+                DROP_CANCEL_TIMEOUT,
+            );
+        }
+    }
 
-        let rc =
-            unsafe { ftdic::ftdi_read_data(self.context.get_ftdi_context(), raw_ptr, raw_len) };
+    fn transfer_finished(&self, direction: capture::Direction) {
+        if direction == capture::Direction::Read {
+            self.reads_in_flight.set(self.reads_in_flight.get() - 1);
+        }
+    }
 
-        self.context.check_ftdi_error(rc)?;
-        Ok(rc as u32)
+    fn submit_read(&self, buf: *mut u8, len: usize) -> Result<*mut ftdic::ftdi_transfer_control> {
+        self.check_cancelled()?;
+        let res = unsafe {
+            ftdic::ftdi_read_data_submit(self.context.get_ftdi_context(), buf, len as raw::c_int)
+        };
+        if res.is_null() {
+            Err(Error::LibFtdi(LibFtdiError::new(
+                "Error starting async read",
+            )))
+        } else {
+            self.reads_in_flight.set(self.reads_in_flight.get() + 1);
+            Ok(res)
+        }
     }
 
-    /// Reads data from the chip. Does not wait for completion of the transfer nor does it make sure that the transfer was successful.
-    pub fn read_data_async<'b>(&self, mut buf: Pin<&'b mut [u8]>) -> Result<AsyncRead<'b>> {
+    fn submit_write(
+        &self,
+        buf: *const u8,
+        len: usize,
+    ) -> Result<*mut ftdic::ftdi_transfer_control> {
+        self.check_cancelled()?;
+        // libftdi only reads from the buffer.
         let res = unsafe {
-            ftdic::ftdi_read_data_submit(
+            ftdic::ftdi_write_data_submit(
                 self.context.get_ftdi_context(),
-                buf.as_mut_ptr(),
-                buf.len() as i32,
+                buf as *mut u8,
+                len as raw::c_int,
             )
         };
         if res.is_null() {
             Err(Error::LibFtdi(LibFtdiError::new(
-                "Error starting async read",
+                "Error starting async write",
             )))
         } else {
-            Ok(AsyncRead {
-                phantom: PhantomData::default(),
-                transfer_control: res,
-            })
+            Ok(res)
         }
     }
 
-    /// Writes data in chunks (see f[`set_write_chunk_size`][Device::set_write_chunk_size]) to the chip
-    pub fn write_data(&self, data: &[u8]) -> Result<u32> {
-        let raw_ptr = data.as_ptr();
-        let raw_len = data.len() as i32;
-
-        let rc =
-            unsafe { ftdic::ftdi_write_data(self.context.get_ftdi_context(), raw_ptr, raw_len) };
+    /// Writes data in chunks (see [`set_write_chunk_size`][Device::set_write_chunk_size]) to the chip
+    pub fn write_data(&self, data: &[u8]) -> Result<usize> {
+        self.check_cancelled()?;
+        let mut total = 0;
+
+        // libftdi takes an int length, so split buffers it cannot describe.
+        for chunk in data.chunks(MAX_TRANSFER_SIZE) {
+            let rc = unsafe {
+                ftdic::ftdi_write_data(
+                    self.context.get_ftdi_context(),
+                    chunk.as_ptr(),
+                    chunk.len() as raw::c_int,
+                )
+            };
+            self.context.check_write_error(rc)?;
+            total += rc as usize;
+        }
 
-        self.context.check_ftdi_error(rc)?;
-        Ok(rc as u32)
+        self.log_transfer(capture::Direction::Write, &data[..total]);
+        Ok(total)
     }
 
     /// Load and decode the data from the chip EEPROM
@@ -448,125 +2446,739 @@ impl Device {
             self.load_eeprom_data()?;
         }
 
-        let mut manufacturer_buf = [0i8; 100];
-        let mut description_buf = [0i8; 100];
-        let mut serial_buf = [0i8; 100];
-
         let rc = unsafe { ftdic::ftdi_read_eeprom(self.context.get_ftdi_context()) };
         self.context.check_ftdi_error(rc)?;
 
+        let strings = fetch_strings(|manufacturer_buf, description_buf, serial_buf| {
+            let rc = unsafe {
+                ftdic::ftdi_eeprom_get_strings(
+                    self.context.get_ftdi_context(),
+                    manufacturer_buf.as_mut_ptr(),
+                    manufacturer_buf.len() as raw::c_int,
+                    description_buf.as_mut_ptr(),
+                    description_buf.len() as raw::c_int,
+                    serial_buf.as_mut_ptr(),
+                    serial_buf.len() as raw::c_int,
+                )
+            };
+            self.context.check_ftdi_error(rc)
+        })?;
+
+        let usb_dev = unsafe {
+            usbc::libusb_get_device((*self.context.get_ftdi_context()).usb_dev as *mut _)
+        };
+        let info = usb_device_info(usb_dev)?;
+
+        Ok(DeviceInfo {
+            manufacturer: strings.manufacturer,
+            description: strings.description,
+            serial: strings.serial,
+            // The chip type detected at open time is authoritative.
+            chip_type: self.chip_type(),
+            ..info
+        })
+    }
+
+    pub fn set_eeprom_value(&mut self, value_name: ftdi_eeprom_value, value: i32) -> Result<u32> {
+        let rc = unsafe {
+            ftdic::ftdi_set_eeprom_value(self.context.get_ftdi_context(), value_name, value)
+        };
+        self.context.check_ftdi_error(rc)?;
+        self.eeprom_read = false;
+        Ok(rc as u32)
+    }
+
+    /// Get a value from the decoded EEPROM structure
+    pub fn get_eeprom_value(&self, value_name: ftdi_eeprom_value) -> Result<i32> {
+        let mut value: raw::c_int = 0;
+        let rc = unsafe {
+            ftdic::ftdi_get_eeprom_value(self.context.get_ftdi_context(), value_name, &mut value)
+        };
+        self.context.check_ftdi_error(rc)?;
+        Ok(value)
+    }
+
+    /// Copy the raw EEPROM image last read from (or built for) the chip.
+    pub fn get_eeprom_buf(&self) -> Result<Vec<u8>> {
+        let mut buf = vec![0u8; MAX_EEPROM_SIZE];
         let rc = unsafe {
-            ftdic::ftdi_eeprom_get_strings(
+            ftdic::ftdi_get_eeprom_buf(
                 self.context.get_ftdi_context(),
-                manufacturer_buf.as_mut_ptr(),
-                manufacturer_buf.len() as i32,
-                description_buf.as_mut_ptr(),
-                description_buf.len() as i32,
-                serial_buf.as_mut_ptr(),
-                serial_buf.len() as i32,
+                buf.as_mut_ptr(),
+                buf.len() as raw::c_int,
+            )
+        };
+        self.context.check_ftdi_error(rc)?;
+
+        // A blank EEPROM reports a size of -1; hand back the whole buffer in that case.
+        let size = self.get_eeprom_value(ftdi_eeprom_value::CHIP_SIZE)?;
+        if size > 0 && (size as usize) < buf.len() {
+            buf.truncate(size as usize);
+        }
+        Ok(buf)
+    }
+
+    /// Replace the raw EEPROM image, e.g. to restore a previously saved one with
+    /// [`write_eeprom`][Device::write_eeprom].
+    pub fn set_eeprom_buf(&mut self, buf: &[u8]) -> Result<()> {
+        let rc = unsafe {
+            ftdic::ftdi_set_eeprom_buf(
+                self.context.get_ftdi_context(),
+                buf.as_ptr(),
+                buf.len() as raw::c_int,
+            )
+        };
+        self.context.check_ftdi_error(rc)
+    }
+
+    /// Set the device ID strings in the decoded EEPROM structure. `None` leaves a string unchanged.
+    pub fn eeprom_set_strings(
+        &mut self,
+        manufacturer: Option<&str>,
+        description: Option<&str>,
+        serial: Option<&str>,
+    ) -> Result<()> {
+        let manufacturer = manufacturer.map(to_cstring).transpose()?;
+        let description = description.map(to_cstring).transpose()?;
+        let serial = serial.map(to_cstring).transpose()?;
+
+        let as_ptr = |s: &Option<CString>| s.as_ref().map_or(std::ptr::null(), |s| s.as_ptr());
+        let rc = unsafe {
+            ftdic::ftdi_eeprom_set_strings(
+                self.context.get_ftdi_context(),
+                as_ptr(&manufacturer),
+                as_ptr(&description),
+                as_ptr(&serial),
             )
         };
+        self.context.check_ftdi_error(rc)
+    }
 
+    /// Build the binary EEPROM image from the decoded EEPROM structure.
+    ///
+    /// Returns the number of bytes left free for user data.
+    pub fn build_eeprom(&mut self) -> Result<usize> {
+        let rc = unsafe { ftdic::ftdi_eeprom_build(self.context.get_ftdi_context()) };
         self.context.check_ftdi_error(rc)?;
+        Ok(rc as usize)
+    }
 
-        let manufacturer = unsafe { CStr::from_ptr(manufacturer_buf.as_mut_ptr()) }
-            .to_string_lossy()
-            .into_owned();
-        let description = unsafe { CStr::from_ptr(description_buf.as_mut_ptr()) }
-            .to_string_lossy()
-            .into_owned();
-        let serial = unsafe { CStr::from_ptr(serial_buf.as_mut_ptr()) }
-            .to_string_lossy()
-            .into_owned();
+    /// Write the built EEPROM image to the chip. Call [`build_eeprom`][Device::build_eeprom] first.
+    pub fn write_eeprom(&mut self) -> Result<()> {
+        let rc = unsafe { ftdic::ftdi_write_eeprom(self.context.get_ftdi_context()) };
+        self.context.check_ftdi_error(rc)?;
+        self.eeprom_read = false;
+        Ok(())
+    }
 
-        Ok(DeviceInfo {
-            manufacturer,
-            description,
-            serial,
-        })
+    /// Read a single 16-bit word from the chip EEPROM, bypassing the decoded EEPROM structure.
+    ///
+    /// `addr` is a word address, not a byte offset.
+    pub fn read_eeprom_word(&mut self, addr: u16) -> Result<u16> {
+        let mut value: raw::c_ushort = 0;
+        let rc = unsafe {
+            ftdic::ftdi_read_eeprom_location(
+                self.context.get_ftdi_context(),
+                addr as raw::c_int,
+                &mut value,
+            )
+        };
+        self.context.check_ftdi_error(rc)?;
+        Ok(value)
     }
 
-    pub fn set_eeprom_value(&mut self, value_name: ftdi_eeprom_value, value: i32) -> Result<u32> {
+    /// Write a single 16-bit word to the chip EEPROM, bypassing the decoded EEPROM structure.
+    ///
+    /// The EEPROM checksum is not updated; patching a word covered by the checksum leaves
+    /// the chip with an invalid configuration unless the checksum word is fixed up as well.
+    pub fn write_eeprom_word(&mut self, addr: u16, value: u16) -> Result<()> {
         let rc = unsafe {
-            ftdic::ftdi_set_eeprom_value(self.context.get_ftdi_context(), value_name, value)
+            ftdic::ftdi_write_eeprom_location(
+                self.context.get_ftdi_context(),
+                addr as raw::c_int,
+                value,
+            )
         };
         self.context.check_ftdi_error(rc)?;
         self.eeprom_read = false;
-        Ok(rc as u32)
+        Ok(())
+    }
+
+    /// Describe the open device, with strings taken from its USB string descriptors
+    pub fn device_info(&self) -> Result<DeviceInfo> {
+        let usb_dev = unsafe {
+            usbc::libusb_get_device((*self.context.get_ftdi_context()).usb_dev as *mut _)
+        };
+
+        let mut info = usb_device_info(usb_dev)?;
+        info.set_strings(usb_device_strings(&self.context, usb_dev)?);
+        info.chip_type = self.chip_type();
+        Ok(info)
+    }
+
+    /// Summarize the device and its current settings, e.g. for a diagnostics banner
+    pub fn describe(&self) -> Result<DeviceDescription> {
+        let info = self.device_info()?;
+        Ok(DeviceDescription {
+            chip_type: info.chip_type,
+            interface: self.interface(),
+            vendor_id: info.vendor_id,
+            product_id: info.product_id,
+            manufacturer: info.manufacturer,
+            description: info.description,
+            serial: info.serial,
+            max_packet_size: self.max_packet_size(),
+            baudrate: self.config().baudrate,
+            bitmode: self.bitmode(),
+        })
+    }
+
+    /// Read the manufacturer, description and serial from the USB string descriptors
+    ///
+    /// Unlike [`eeprom_get_strings`][Device::eeprom_get_strings], this doesn't read and
+    /// decode the EEPROM, so it also works on chips whose EEPROM layout libftdi can't
+    /// decode, such as some FT-X parts.
+    pub fn usb_strings(&self) -> Result<UsbStrings> {
+        let usb_dev = unsafe {
+            usbc::libusb_get_device((*self.context.get_ftdi_context()).usb_dev as *mut _)
+        };
+        usb_device_strings(&self.context, usb_dev)
+    }
+
+    /// Chip family detected when the device was opened
+    pub fn chip_type(&self) -> Option<ChipType> {
+        ChipType::from_raw(unsafe { (*self.context.get_ftdi_context()).type_ })
+    }
+
+    /// Interface actually in use; requesting [`Interface::Any`] selects interface A
+    pub fn interface(&self) -> Interface {
+        self.context.interface()
+    }
+
+    /// Number of interfaces (channels) the attached chip provides
+    pub fn interface_count(&self) -> u8 {
+        self.chip_type().map_or(1, |chip| chip.interface_count())
+    }
+
+    /// Give up ownership of the underlying, opened ftdi_context, see [`Context::into_raw`]
+    ///
+    /// Fails, returning the device back, for handles sharing the device with clones or a
+    /// [`UsbContext`] with other devices.
+    // The device is handed back whole, however large it grows.
+    #[allow(clippy::result_large_err)]
+    pub fn into_raw(self) -> std::result::Result<*mut ftdic::ftdi_context, Device> {
+        let Device {
+            context,
+            eeprom_read,
+            config,
+            capture,
+            cancel,
+            reads_in_flight,
+        } = self;
+
+        context.into_raw().map_err(|context| Device {
+            context,
+            eeprom_read,
+            config,
+            capture,
+            cancel,
+            reads_in_flight,
+        })
+    }
+
+    /// Take ownership of an opened ftdi_context, e.g. one set up by a C library
+    ///
+    /// # Safety
+    ///
+    /// Same as for [`Context::from_raw`]; additionally, `ctx` should have a device open
+    /// for the device methods to work.
+    pub unsafe fn from_raw(ctx: *mut ftdic::ftdi_context) -> Device {
+        Device::from_context(Context::from_raw(ctx))
+    }
+
+    /// Open a second handle to the same device, like [`TcpStream::try_clone`][std::net::TcpStream::try_clone]
+    ///
+    /// Both handles share the USB connection but have their own read buffer, so e.g. one
+    /// thread can poll the modem status while another streams data. If both read at once,
+    /// the incoming data is split between them arbitrarily. Settings changed through one
+    /// handle affect the chip for both, but are only tracked by that handle's [`config`][Device::config].
+    pub fn try_clone(&self) -> Result<Device> {
+        let shared = self.context.share_usb();
+        let clone = Context::new()?;
+
+        let src = self.context.get_ftdi_context();
+        let dst = clone.get_ftdi_context();
+        unsafe {
+            // Use the shared libusb context instead of the one ftdi_new() set up.
+            usbc::libusb_exit((*dst).usb_ctx as *mut usbc::libusb_context);
+            (*dst).usb_ctx = (*src).usb_ctx;
+            (*dst).usb_dev = (*src).usb_dev;
+        }
+        let _ = clone.shared_usb.set(shared);
+
+        unsafe {
+            (*dst).usb_read_timeout = (*src).usb_read_timeout;
+            (*dst).usb_write_timeout = (*src).usb_write_timeout;
+            (*dst).type_ = (*src).type_;
+            (*dst).baudrate = (*src).baudrate;
+            (*dst).bitbang_enabled = (*src).bitbang_enabled;
+            (*dst).bitbang_mode = (*src).bitbang_mode;
+            (*dst).writebuffer_chunksize = (*src).writebuffer_chunksize;
+            (*dst).max_packet_size = (*src).max_packet_size;
+            (*dst).interface = (*src).interface;
+            (*dst).index = (*src).index;
+            (*dst).in_ep = (*src).in_ep;
+            (*dst).out_ep = (*src).out_ep;
+            (*dst).module_detach_mode = (*src).module_detach_mode;
+        }
+
+        let rc = unsafe { ftdic::ftdi_read_data_set_chunksize(dst, (*src).readbuffer_chunksize) };
+        clone.check_ftdi_error(rc)?;
+
+        let device = Device::from_context(clone);
+        device.config.set(self.config());
+        *device.lock_cancel() = self.cancellation_token();
+        Ok(device)
     }
 
     /// Close device
+    ///
+    /// For a handle sharing the device with clones (see [`try_clone`][Device::try_clone]),
+    /// the device is only closed once the last of them is closed or dropped.
     pub fn close(self) -> Result<()> {
+        if !self.context.is_shared() {
+            let rc = unsafe { ftdic::ftdi_usb_close(self.context.get_ftdi_context()) };
+            self.context.check_ftdi_error(rc)?;
+        }
+        drop(self);
+        Ok(())
+    }
+
+    /// Close device, but keep the context so it can open this or another device again
+    ///
+    /// This avoids setting up libftdi and libusb anew for every device when cycling
+    /// through many of them, see e.g. [`Context::open_bus_addr`].
+    ///
+    /// Fails for handles sharing the device with clones, whose contexts can't be reused.
+    pub fn close_keep_context(self) -> Result<Context> {
+        if self.context.is_shared() {
+            return Err(Error::InvalidArgument(
+                "Can't keep the context of a device shared with clones",
+            ));
+        }
+
         let rc = unsafe { ftdic::ftdi_usb_close(self.context.get_ftdi_context()) };
         self.context.check_ftdi_error(rc)?;
-        drop(self);
+        Ok(self.context)
+    }
+}
+
+impl fmt::Debug for Device {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let config = self.config();
+        let mut s = f.debug_struct("Device");
+
+        if let Some((vid, pid)) = self.context.usb_ids() {
+            s.field("vendor_id", &format_args!("{:#06x}", vid))
+                .field("product_id", &format_args!("{:#06x}", pid));
+        }
+
+        s.field("chip_type", &self.chip_type())
+            .field("interface", &self.interface())
+            .field("baudrate", &config.baudrate)
+            .field("bitmode", &config.bitmode)
+            .finish()
+    }
+}
+
+/// Version of a native library, see [`libftdi_version`] and [`libusb_version`]
+///
+/// To gate a workaround on a release, compare [`numbers`][LibraryVersion::numbers], e.g.
+/// `libftdi_version().numbers() >= (1, 5, 0)`.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct LibraryVersion {
+    pub major: u16,
+    pub minor: u16,
+    pub micro: u16,
+    /// Source snapshot (libftdi) or release candidate suffix (libusb) of the build;
+    /// may be empty
+    pub extra: String,
+}
+
+impl LibraryVersion {
+    pub fn numbers(&self) -> (u16, u16, u16) {
+        (self.major, self.minor, self.micro)
+    }
+}
+
+impl fmt::Display for LibraryVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.micro)?;
+        if !self.extra.is_empty() {
+            write!(f, " ({})", self.extra)?;
+        }
         Ok(())
     }
 }
 
+// Copy a static string owned by a C library, which may be NULL.
+fn string_from_c_ptr(ptr: *const raw::c_char) -> String {
+    if ptr.is_null() {
+        return String::new();
+    }
+    unsafe { CStr::from_ptr(ptr) }.to_string_lossy().into_owned()
+}
+
+/// Version of the libftdi library in use
+pub fn libftdi_version() -> LibraryVersion {
+    let version = unsafe { ftdic::ftdi_get_library_version() };
+    let snapshot = string_from_c_ptr(version.snapshot_str);
+    LibraryVersion {
+        major: version.major as u16,
+        minor: version.minor as u16,
+        micro: version.micro as u16,
+        // Release builds report "unknown".
+        extra: if snapshot == "unknown" {
+            String::new()
+        } else {
+            snapshot
+        },
+    }
+}
+
+/// Version of the libusb library in use
+pub fn libusb_version() -> LibraryVersion {
+    let version = unsafe { &*usbc::libusb_get_version() };
+    LibraryVersion {
+        major: version.major,
+        minor: version.minor,
+        micro: version.micro,
+        extra: string_from_c_ptr(version.rc),
+    }
+}
+
 /// List available devices.
 ///
-/// This uses [`to_string_lossy`][std::ffi::CStr::to_string_lossy] when copying strings from libftdi1,
+/// This uses [`from_utf8_lossy`][String::from_utf8_lossy] when copying strings from libftdi1,
 /// meaning it will replace any invalid UTF-8 sequences with
 /// [`U+FFFD REPLACEMENT CHARACTER`][std::char::REPLACEMENT_CHARACTER]
 pub fn list_devices() -> Result<Vec<DeviceInfo>> {
-    let context = Context::new()?;
+    list_devices_filtered(0, 0)
+}
+
+/// List all attached devices with the given vendor and product ids
+///
+/// Passing 0 for both ids matches the standard FTDI vendor/product ids, like
+/// [`list_devices`].
+pub fn list_devices_filtered(vid: u16, pid: u16) -> Result<Vec<DeviceInfo>> {
+    devices_filtered(vid, pid).collect()
+}
+
+/// Lazily walk all attached devices with one of the standard FTDI vendor/product ids
+///
+/// Strings are only fetched from a device when the iterator reaches it, so stopping at
+/// the first match avoids talking to the remaining devices.
+pub fn devices() -> Devices {
+    devices_filtered(0, 0)
+}
+
+/// Lazily walk all attached devices with the given vendor and product ids
+pub fn devices_filtered(vid: u16, pid: u16) -> Devices {
+    let context = match Context::new() {
+        Ok(context) => context,
+        Err(e) => {
+            return Devices {
+                context: None,
+                device_list: std::ptr::null_mut(),
+                curdev: std::ptr::null_mut(),
+                error: Some(e),
+                fetch_strings: true,
+                predicate: None,
+            }
+        }
+    };
+
     let mut device_list: *mut ftdic::ftdi_device_list = std::ptr::null_mut();
+    let rc = unsafe {
+        ftdic::ftdi_usb_find_all(
+            context.get_ftdi_context(),
+            &mut device_list,
+            vid as raw::c_int,
+            pid as raw::c_int,
+        )
+    };
+    let error = context.check_ftdi_error(rc).err();
+
+    Devices {
+        context: Some(context),
+        device_list,
+        curdev: device_list,
+        error,
+        fetch_strings: true,
+        predicate: None,
+    }
+}
+
+type DevicePredicate = Box<dyn FnMut(&DeviceInfo) -> bool>;
+
+/// Iterator over attached devices, created by [`devices`] or [`devices_filtered`]
+pub struct Devices {
+    context: Option<Context>,
+    device_list: *mut ftdic::ftdi_device_list,
+    curdev: *mut ftdic::ftdi_device_list,
+    // Enumeration failure, reported as the first item.
+    error: Option<Error>,
+    fetch_strings: bool,
+    predicate: Option<DevicePredicate>,
+}
+
+impl Devices {
+    /// Leave the manufacturer, description and serial strings empty
+    ///
+    /// Fetching strings requires opening each device, which is slow on large USB trees
+    /// and fails for devices the current user has no permission to open. Descriptor-level
+    /// fields are read without opening the device.
+    pub fn without_strings(mut self) -> Devices {
+        self.fetch_strings = false;
+        self
+    }
+
+    /// Skip devices for which `predicate` returns `false`
+    ///
+    /// The predicate only sees descriptor-level fields; the strings are still empty
+    /// when it runs, and are only fetched for devices it accepts.
+    pub fn filter_descriptors<F>(mut self, predicate: F) -> Devices
+    where
+        F: FnMut(&DeviceInfo) -> bool + 'static,
+    {
+        self.predicate = Some(Box::new(predicate));
+        self
+    }
+}
 
-    let rc =
-        unsafe { ftdic::ftdi_usb_find_all(context.get_ftdi_context(), &mut device_list, 0, 0) };
-    context.check_ftdi_error(rc)?;
+impl Iterator for Devices {
+    type Item = Result<DeviceInfo>;
 
-    let mut devices = Vec::with_capacity(rc as usize);
-    let mut manufacturer_buf = [0i8; 100];
-    let mut description_buf = [0i8; 100];
-    let mut serial_buf = [0i8; 100];
+    fn next(&mut self) -> Option<Result<DeviceInfo>> {
+        if let Some(e) = self.error.take() {
+            return Some(Err(e));
+        }
+
+        let context = match self.context {
+            Some(ref context) => context,
+            None => return None,
+        };
+
+        loop {
+            if self.curdev.is_null() {
+                return None;
+            }
+
+            let dev = unsafe { (*self.curdev).dev as *mut usbc::libusb_device };
+            self.curdev = unsafe { (*self.curdev).next };
+
+            let mut info = match usb_device_info(dev) {
+                Ok(info) => info,
+                Err(e) => return Some(Err(e)),
+            };
+
+            if let Some(ref mut predicate) = self.predicate {
+                if !predicate(&info) {
+                    continue;
+                }
+            }
 
-    let mut curdev = device_list;
-    while !curdev.is_null() {
+            if self.fetch_strings {
+                match usb_device_strings(context, dev) {
+                    Ok(strings) => info.set_strings(strings),
+                    Err(e) => return Some(Err(e)),
+                }
+            }
+
+            return Some(Ok(info));
+        }
+    }
+}
+
+// Fetch the string descriptors of a device. A device that is not already open
+// through `context` is opened and closed again.
+fn usb_device_strings(context: &Context, dev: *mut usbc::libusb_device) -> Result<UsbStrings> {
+    fetch_strings(|manufacturer_buf, description_buf, serial_buf| {
         let rc = unsafe {
-            ftdic::ftdi_usb_get_strings(
+            ftdic::ftdi_usb_get_strings2(
                 context.get_ftdi_context(),
-                (*curdev).dev,
+                dev as *mut _,
                 manufacturer_buf.as_mut_ptr(),
-                manufacturer_buf.len() as i32,
+                manufacturer_buf.len() as raw::c_int,
                 description_buf.as_mut_ptr(),
-                description_buf.len() as i32,
+                description_buf.len() as raw::c_int,
                 serial_buf.as_mut_ptr(),
-                serial_buf.len() as i32,
+                serial_buf.len() as raw::c_int,
             )
         };
-        if let Err(e) = context.check_ftdi_error(rc) {
-            unsafe { ftdic::ftdi_list_free(&mut device_list) };
-            return Err(e);
+        context.check_ftdi_error(rc)
+    })
+}
+
+// libftdi silently truncates strings to the buffers it is given, so call `fill` with
+// the manufacturer, description and serial buffers, growing them until every string
+// fits.
+fn fetch_strings<F>(mut fill: F) -> Result<UsbStrings>
+where
+    F: FnMut(&mut [raw::c_char], &mut [raw::c_char], &mut [raw::c_char]) -> Result<()>,
+{
+    let mut len = STRING_BUF_LEN;
+    loop {
+        let mut manufacturer_buf = vec![0 as raw::c_char; len];
+        let mut description_buf = vec![0 as raw::c_char; len];
+        let mut serial_buf = vec![0 as raw::c_char; len];
+        fill(&mut manufacturer_buf, &mut description_buf, &mut serial_buf)?;
+
+        // A string filling its buffer, with or without the NUL, may have been cut.
+        let full = [&manufacturer_buf, &description_buf, &serial_buf]
+            .iter()
+            .any(|buf| buf.iter().take_while(|&&c| c != 0).count() >= len - 1);
+        if !full || len >= MAX_STRING_BUF_LEN {
+            return Ok(UsbStrings {
+                manufacturer: string_from_c_buf(&manufacturer_buf),
+                description: string_from_c_buf(&description_buf),
+                serial: string_from_c_buf(&serial_buf),
+            });
+        }
+        len *= 2;
+    }
+}
+
+impl Drop for Devices {
+    fn drop(&mut self) {
+        if !self.device_list.is_null() {
+            unsafe { ftdic::ftdi_list_free(&mut self.device_list) };
         }
+    }
+}
 
-        let manufacturer = unsafe { CStr::from_ptr(manufacturer_buf.as_mut_ptr()) }
-            .to_string_lossy()
-            .into_owned();
-        let description = unsafe { CStr::from_ptr(description_buf.as_mut_ptr()) }
-            .to_string_lossy()
-            .into_owned();
-        let serial = unsafe { CStr::from_ptr(serial_buf.as_mut_ptr()) }
-            .to_string_lossy()
-            .into_owned();
+/// USB 3.0 limits hub chains to 7 tiers.
+const MAX_PORT_DEPTH: usize = 7;
+
+// Describe where a device is attached as "<bus>-<port>[.<port>...]", like Linux sysfs does.
+fn usb_port_path(dev: *mut usbc::libusb_device) -> String {
+    let mut ports = [0u8; MAX_PORT_DEPTH];
+    let bus = unsafe { usbc::libusb_get_bus_number(dev) };
+    let depth = unsafe {
+        usbc::libusb_get_port_numbers(dev, ports.as_mut_ptr(), ports.len() as raw::c_int)
+    };
+
+    let ports: Vec<String> = ports[..depth.max(0) as usize]
+        .iter()
+        .map(|port| port.to_string())
+        .collect();
+    format!("{}-{}", bus, ports.join("."))
+}
 
-        devices.push(DeviceInfo {
-            manufacturer,
-            description,
-            serial,
-        });
+// Fill in the descriptor-level details of a libusb device.
+fn usb_device_info(dev: *mut usbc::libusb_device) -> Result<DeviceInfo> {
+    let mut desc: usbc::libusb_device_descriptor = unsafe { std::mem::zeroed() };
 
-        curdev = unsafe { (*curdev).next };
+    let rc = unsafe { usbc::libusb_get_device_descriptor(dev, &mut desc) };
+    if rc < 0 {
+        return Err(Error::LibUsb(LibUsbError::from_code(rc)));
     }
 
-    unsafe { ftdic::ftdi_list_free(&mut device_list) };
-    Ok(devices)
+    Ok(DeviceInfo {
+        manufacturer: String::new(),
+        description: String::new(),
+        serial: String::new(),
+        vendor_id: desc.idVendor,
+        product_id: desc.idProduct,
+        bus: unsafe { usbc::libusb_get_bus_number(dev) },
+        address: unsafe { usbc::libusb_get_device_address(dev) },
+        port_path: usb_port_path(dev),
+        chip_type: ChipType::from_descriptor(&desc),
+    })
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct DeviceInfo {
     pub manufacturer: String,
     pub description: String,
     pub serial: String,
+    pub vendor_id: u16,
+    pub product_id: u16,
+    /// USB bus number
+    pub bus: u8,
+    /// USB device address on the bus
+    pub address: u8,
+    /// Physical port chain the device is attached through, e.g. `"1-4.2"`
+    pub port_path: String,
+    /// `None` if the chip could not be identified
+    pub chip_type: Option<ChipType>,
+}
+
+impl DeviceInfo {
+    fn set_strings(&mut self, strings: UsbStrings) {
+        self.manufacturer = strings.manufacturer;
+        self.description = strings.description;
+        self.serial = strings.serial;
+    }
+}
+
+/// Summary of an open device, see [`Device::describe`]
+///
+/// Displays as a single line, e.g.
+///
+/// ```text
+/// FT232H 0403:6014 interface A "FTDI" "UM232H" serial FT1ABCDE, 512 byte packets, mode mpsse
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct DeviceDescription {
+    pub chip_type: Option<ChipType>,
+    pub interface: Interface,
+    pub vendor_id: u16,
+    pub product_id: u16,
+    pub manufacturer: String,
+    pub description: String,
+    pub serial: String,
+    pub max_packet_size: usize,
+    /// Baud rate set through this handle, if any
+    pub baudrate: Option<u32>,
+    /// Bitmode set through this handle, if any
+    pub bitmode: Option<BitModeConfig>,
+}
+
+impl fmt::Display for DeviceDescription {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.chip_type {
+            Some(chip) => write!(f, "{}", chip)?,
+            None => write!(f, "unknown chip")?,
+        }
+        write!(
+            f,
+            " {:04x}:{:04x} interface {} {:?} {:?} serial {}, {} byte packets",
+            self.vendor_id,
+            self.product_id,
+            self.interface,
+            self.manufacturer,
+            self.description,
+            self.serial,
+            self.max_packet_size
+        )?;
+        if let Some(baudrate) = self.baudrate {
+            write!(f, ", {} baud", baudrate)?;
+        }
+        if let Some(bitmode) = self.bitmode {
+            write!(f, ", mode {}", bitmode.mode())?;
+        }
+        Ok(())
+    }
+}
+
+/// String descriptors of a device, see [`Device::usb_strings`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct UsbStrings {
+    pub manufacturer: String,
+    pub description: String,
+    pub serial: String,
 }
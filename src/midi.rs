@@ -0,0 +1,451 @@
+//! MIDI over a UART, for DIY MIDI interfaces built around an FTDI chip.
+//!
+//! [`Device::into_midi`] sets up the MIDI line settings, 31250 baud 8N1. A
+//! [`MidiParser`] turns the received bytes into messages, handling running status,
+//! real-time messages interleaved with others and SysEx messages spread over many
+//! reads.
+
+use std::collections::VecDeque;
+
+use {BitMode, DataBits, Device, FlowControl, Parity, Result, StopBits};
+
+/// Baud rate of the MIDI serial line.
+pub const MIDI_BAUDRATE: u32 = 31_250;
+
+/// A MIDI message. Channels count from 0.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MidiMessage {
+    NoteOff {
+        channel: u8,
+        note: u8,
+        velocity: u8,
+    },
+    NoteOn {
+        channel: u8,
+        note: u8,
+        velocity: u8,
+    },
+    PolyPressure {
+        channel: u8,
+        note: u8,
+        pressure: u8,
+    },
+    ControlChange {
+        channel: u8,
+        controller: u8,
+        value: u8,
+    },
+    ProgramChange {
+        channel: u8,
+        program: u8,
+    },
+    ChannelPressure {
+        channel: u8,
+        pressure: u8,
+    },
+    /// `value` ranges up to 0x3fff, with 0x2000 meaning no bend.
+    PitchBend {
+        channel: u8,
+        value: u16,
+    },
+    /// System exclusive data, without the leading 0xf0 and trailing 0xf7.
+    SysEx(Vec<u8>),
+    /// MIDI time code quarter frame.
+    TimeCode(u8),
+    /// Song position in MIDI beats.
+    SongPosition(u16),
+    SongSelect(u8),
+    TuneRequest,
+    /// Status byte of a real-time message, e.g. 0xf8 for timing clock or 0xfa for
+    /// start.
+    Realtime(u8),
+}
+
+impl MidiMessage {
+    /// Encode the message, with its status byte; data bytes are cut to 7 bits.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let voice = |status: u8, channel: u8, data: &[u8]| {
+            let mut bytes = vec![status | (channel & 0x0f)];
+            bytes.extend(data.iter().map(|b| b & 0x7f));
+            bytes
+        };
+
+        match *self {
+            MidiMessage::NoteOff {
+                channel,
+                note,
+                velocity,
+            } => voice(0x80, channel, &[note, velocity]),
+            MidiMessage::NoteOn {
+                channel,
+                note,
+                velocity,
+            } => voice(0x90, channel, &[note, velocity]),
+            MidiMessage::PolyPressure {
+                channel,
+                note,
+                pressure,
+            } => voice(0xa0, channel, &[note, pressure]),
+            MidiMessage::ControlChange {
+                channel,
+                controller,
+                value,
+            } => voice(0xb0, channel, &[controller, value]),
+            MidiMessage::ProgramChange { channel, program } => voice(0xc0, channel, &[program]),
+            MidiMessage::ChannelPressure { channel, pressure } => voice(0xd0, channel, &[pressure]),
+            MidiMessage::PitchBend { channel, value } => {
+                voice(0xe0, channel, &[value as u8, (value >> 7) as u8])
+            }
+            MidiMessage::SysEx(ref data) => {
+                let mut bytes = vec![0xf0];
+                bytes.extend(data.iter().map(|b| b & 0x7f));
+                bytes.push(0xf7);
+                bytes
+            }
+            MidiMessage::TimeCode(value) => vec![0xf1, value & 0x7f],
+            MidiMessage::SongPosition(beats) => {
+                vec![0xf2, beats as u8 & 0x7f, (beats >> 7) as u8 & 0x7f]
+            }
+            MidiMessage::SongSelect(song) => vec![0xf3, song & 0x7f],
+            MidiMessage::TuneRequest => vec![0xf6],
+            MidiMessage::Realtime(status) => vec![status],
+        }
+    }
+}
+
+/// Splits a stream of MIDI bytes into messages.
+#[derive(Debug, Clone)]
+pub struct MidiParser {
+    // Status of the message being received, kept as running status afterwards.
+    status: Option<u8>,
+    data: Vec<u8>,
+    // Receiving a SysEx message into `data`.
+    in_sysex: bool,
+    max_sysex_len: usize,
+    skipped: u64,
+}
+
+impl Default for MidiParser {
+    fn default() -> MidiParser {
+        MidiParser::new()
+    }
+}
+
+impl MidiParser {
+    pub fn new() -> MidiParser {
+        MidiParser {
+            status: None,
+            data: Vec::new(),
+            in_sysex: false,
+            max_sysex_len: 4096,
+            skipped: 0,
+        }
+    }
+
+    /// Drop SysEx messages longer than `len` bytes instead of collecting them; 4096
+    /// by default.
+    pub fn with_max_sysex_len(mut self, len: usize) -> MidiParser {
+        self.max_sysex_len = len;
+        self
+    }
+
+    /// Number of bytes dropped so far, e.g. data bytes without a status, incomplete
+    /// messages and oversized SysEx messages.
+    pub fn skipped(&self) -> u64 {
+        self.skipped
+    }
+
+    /// Feed a byte, returning the message it completes, if any.
+    pub fn push(&mut self, byte: u8) -> Option<MidiMessage> {
+        match byte {
+            // Real-time messages may appear anywhere, even within other messages.
+            0xf8..=0xff => Some(MidiMessage::Realtime(byte)),
+            0xf7 if self.in_sysex => {
+                self.in_sysex = false;
+                let data = std::mem::take(&mut self.data);
+                if data.len() > self.max_sysex_len {
+                    self.skipped += data.len() as u64 + 2;
+                    return None;
+                }
+                Some(MidiMessage::SysEx(data))
+            }
+            0x80..=0xf7 => {
+                self.drop_incomplete();
+                self.in_sysex = byte == 0xf0;
+                // System messages cancel running status.
+                self.status = if byte < 0xf0 { Some(byte) } else { None };
+                match byte {
+                    0xf1..=0xf3 => self.status = Some(byte),
+                    0xf6 => return Some(MidiMessage::TuneRequest),
+                    // 0xf4, 0xf5 are undefined, and an 0xf7 outside SysEx is stray.
+                    0xf4 | 0xf5 | 0xf7 => self.skipped += 1,
+                    _ => {}
+                }
+                None
+            }
+            _ if self.in_sysex => {
+                // Count oversized messages, but stop storing them.
+                if self.data.len() <= self.max_sysex_len {
+                    self.data.push(byte);
+                } else {
+                    self.skipped += 1;
+                }
+                None
+            }
+            _ => self.push_data(byte),
+        }
+    }
+
+    /// Forget a partially received message and the running status, e.g. after
+    /// purging the device's buffers.
+    pub fn clear(&mut self) {
+        self.status = None;
+        self.data.clear();
+        self.in_sysex = false;
+    }
+
+    fn push_data(&mut self, byte: u8) -> Option<MidiMessage> {
+        let status = match self.status {
+            Some(status) => status,
+            None => {
+                self.skipped += 1;
+                return None;
+            }
+        };
+
+        self.data.push(byte);
+        let len = match status {
+            0xc0..=0xdf | 0xf1 | 0xf3 => 1,
+            _ => 2,
+        };
+        if self.data.len() < len {
+            return None;
+        }
+
+        let channel = status & 0x0f;
+        let d = std::mem::take(&mut self.data);
+        let message = match status {
+            0xf1 => MidiMessage::TimeCode(d[0]),
+            0xf2 => MidiMessage::SongPosition(u16::from(d[0]) | u16::from(d[1]) << 7),
+            0xf3 => MidiMessage::SongSelect(d[0]),
+            _ => match status & 0xf0 {
+                0x80 => MidiMessage::NoteOff {
+                    channel,
+                    note: d[0],
+                    velocity: d[1],
+                },
+                0x90 => MidiMessage::NoteOn {
+                    channel,
+                    note: d[0],
+                    velocity: d[1],
+                },
+                0xa0 => MidiMessage::PolyPressure {
+                    channel,
+                    note: d[0],
+                    pressure: d[1],
+                },
+                0xb0 => MidiMessage::ControlChange {
+                    channel,
+                    controller: d[0],
+                    value: d[1],
+                },
+                0xc0 => MidiMessage::ProgramChange {
+                    channel,
+                    program: d[0],
+                },
+                0xd0 => MidiMessage::ChannelPressure {
+                    channel,
+                    pressure: d[0],
+                },
+                _ => MidiMessage::PitchBend {
+                    channel,
+                    value: u16::from(d[0]) | u16::from(d[1]) << 7,
+                },
+            },
+        };
+        // Only channel messages keep their status for running status.
+        if status >= 0xf0 {
+            self.status = None;
+        }
+        Some(message)
+    }
+
+    // A new status byte interrupts the message being received.
+    fn drop_incomplete(&mut self) {
+        if self.in_sysex {
+            self.skipped += 1;
+        }
+        self.skipped += self.data.len() as u64;
+        self.data.clear();
+    }
+}
+
+/// A UART set up for MIDI, see [`Device::into_midi`].
+pub struct MidiPort {
+    device: Device,
+    parser: MidiParser,
+    pending: VecDeque<MidiMessage>,
+}
+
+impl Device {
+    /// Reset the chip to UART mode with the MIDI line settings, 31250 baud 8N1
+    /// without flow control.
+    pub fn into_midi(self) -> Result<MidiPort> {
+        self.set_bitmode(0, BitMode::Reset)?;
+        self.set_baudrate(MIDI_BAUDRATE)?;
+        self.set_line_property(DataBits::Eight, StopBits::One, Parity::None)?;
+        self.set_flow_control(FlowControl::Disabled)?;
+        Ok(MidiPort {
+            device: self,
+            parser: MidiParser::new(),
+            pending: VecDeque::new(),
+        })
+    }
+}
+
+impl MidiPort {
+    /// Wait for the next message.
+    ///
+    /// Fails with a timeout error if none arrives within the read timeout; a message
+    /// received in part is kept for the next call.
+    pub fn read_message(&mut self) -> Result<MidiMessage> {
+        let mut buf = [0; 64];
+        loop {
+            if let Some(message) = self.pending.pop_front() {
+                return Ok(message);
+            }
+            let read = self.device.read_some(&mut buf)?;
+            for &byte in &buf[..read] {
+                self.pending.extend(self.parser.push(byte));
+            }
+        }
+    }
+
+    /// Send a message, without using running status.
+    pub fn send(&self, message: &MidiMessage) -> Result<()> {
+        self.device.write_data(&message.to_bytes())?;
+        Ok(())
+    }
+
+    pub fn parser(&self) -> &MidiParser {
+        &self.parser
+    }
+
+    pub fn device(&self) -> &Device {
+        &self.device
+    }
+
+    pub fn into_inner(self) -> Device {
+        self.device
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(parser: &mut MidiParser, bytes: &[u8]) -> Vec<MidiMessage> {
+        bytes.iter().filter_map(|&byte| parser.push(byte)).collect()
+    }
+
+    fn note_on(note: u8, velocity: u8) -> MidiMessage {
+        MidiMessage::NoteOn {
+            channel: 2,
+            note,
+            velocity,
+        }
+    }
+
+    #[test]
+    fn running_status() {
+        let mut parser = MidiParser::new();
+        let messages = parse(&mut parser, &[0x92, 60, 100, 62, 90, 64, 0]);
+        assert_eq!(
+            messages,
+            vec![note_on(60, 100), note_on(62, 90), note_on(64, 0)]
+        );
+        assert_eq!(parser.skipped(), 0);
+    }
+
+    #[test]
+    fn system_messages_cancel_running_status() {
+        let mut parser = MidiParser::new();
+        let messages = parse(&mut parser, &[0x92, 60, 100, 0xf3, 5, 62, 90]);
+        assert_eq!(messages, vec![note_on(60, 100), MidiMessage::SongSelect(5)]);
+        assert_eq!(parser.skipped(), 2);
+    }
+
+    #[test]
+    fn sysex() {
+        let mut parser = MidiParser::new();
+        let messages = parse(&mut parser, &[0xf0, 0x7e, 0x7f, 0x06, 0x01, 0xf7]);
+        assert_eq!(
+            messages,
+            vec![MidiMessage::SysEx(vec![0x7e, 0x7f, 0x06, 0x01])]
+        );
+        assert_eq!(parser.skipped(), 0);
+    }
+
+    #[test]
+    fn oversized_sysex_is_dropped() {
+        let mut parser = MidiParser::new().with_max_sysex_len(2);
+        let messages = parse(&mut parser, &[0xf0, 1, 2, 3, 4, 0xf7, 0xf6]);
+        assert_eq!(messages, vec![MidiMessage::TuneRequest]);
+        assert!(parser.skipped() > 0);
+    }
+
+    #[test]
+    fn realtime_within_messages() {
+        let mut parser = MidiParser::new();
+        let messages = parse(
+            &mut parser,
+            &[0x92, 0xf8, 60, 0xfa, 100, 0xf0, 1, 0xfe, 2, 0xf7],
+        );
+        assert_eq!(
+            messages,
+            vec![
+                MidiMessage::Realtime(0xf8),
+                MidiMessage::Realtime(0xfa),
+                note_on(60, 100),
+                MidiMessage::Realtime(0xfe),
+                MidiMessage::SysEx(vec![1, 2]),
+            ]
+        );
+        assert_eq!(parser.skipped(), 0);
+    }
+
+    #[test]
+    fn data_without_status_is_skipped() {
+        let mut parser = MidiParser::new();
+        let messages = parse(&mut parser, &[60, 100, 0xc5, 7]);
+        assert_eq!(
+            messages,
+            vec![MidiMessage::ProgramChange {
+                channel: 5,
+                program: 7,
+            }]
+        );
+        assert_eq!(parser.skipped(), 2);
+    }
+
+    #[test]
+    fn new_status_drops_incomplete_message() {
+        let mut parser = MidiParser::new();
+        let messages = parse(&mut parser, &[0x92, 60, 0xe0, 0, 0x40]);
+        assert_eq!(
+            messages,
+            vec![MidiMessage::PitchBend {
+                channel: 0,
+                value: 0x2000,
+            }]
+        );
+        assert_eq!(parser.skipped(), 1);
+    }
+
+    #[test]
+    fn to_bytes_parses_back() {
+        let message = MidiMessage::SongPosition(0x1234);
+        let mut parser = MidiParser::new();
+        assert_eq!(parse(&mut parser, &message.to_bytes()), vec![message]);
+    }
+}
@@ -0,0 +1,399 @@
+//! A scripted stand-in for a device, to test code built on this crate without
+//! hardware.
+//!
+//! [`MockDevice`] follows a script of expected writes, data to return from reads and
+//! failures to inject, and fails on any write the script doesn't expect. Besides raw
+//! reads and writes, it implements [`Gpio`], [`SpiBus`]/[`Spi`] and [`I2c`], so
+//! drivers generic over those traits (e.g. [`I2cEeprom`][::i2c_eeprom::I2cEeprom] or
+//! the flash programmers) can run against it.
+
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::path::Path;
+
+use capture::{self, Direction, Record};
+use error::{Error, LibUsbError};
+use gpio::Gpio;
+use i2c::I2c;
+use spi::{Spi, SpiBus};
+use {BitMode, ChipType, Result};
+
+enum Step {
+    Write(Vec<u8>),
+    Read(Vec<u8>),
+    Fail(Error),
+    Nack,
+}
+
+#[derive(Default)]
+struct Script {
+    steps: VecDeque<Step>,
+    // Bytes of the front step already consumed.
+    offset: usize,
+    written: Vec<u8>,
+}
+
+impl Script {
+    fn push(&mut self, step: Step) {
+        match step {
+            Step::Write(ref data) | Step::Read(ref data) if data.is_empty() => {}
+            step => self.steps.push_back(step),
+        }
+    }
+
+    fn take_failure(&mut self) -> Result<()> {
+        if let Some(Step::Fail(_)) = self.steps.front() {
+            if let Some(Step::Fail(e)) = self.steps.pop_front() {
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
+
+    fn read(&mut self, data: &mut [u8]) -> Result<usize> {
+        self.take_failure()?;
+        let mut filled = 0;
+        while filled < data.len() {
+            let n = match self.steps.front() {
+                Some(Step::Read(available)) => {
+                    let available = &available[self.offset..];
+                    let n = available.len().min(data.len() - filled);
+                    data[filled..filled + n].copy_from_slice(&available[..n]);
+                    n
+                }
+                _ => break,
+            };
+            filled += n;
+            self.advance(n);
+        }
+        Ok(filled)
+    }
+
+    fn write(&mut self, data: &[u8]) -> Result<()> {
+        self.take_failure()?;
+        let mut checked = 0;
+        while checked < data.len() {
+            let n = match self.steps.front() {
+                Some(Step::Write(expected)) => {
+                    let expected = &expected[self.offset..];
+                    let n = expected.len().min(data.len() - checked);
+                    if let Some(i) = (0..n).find(|&i| expected[i] != data[checked + i]) {
+                        return Err(unexpected(format!(
+                            "Written byte {} is {:#04x}, expected {:#04x}",
+                            self.written.len() + i,
+                            data[checked + i],
+                            expected[i]
+                        )));
+                    }
+                    n
+                }
+                _ => {
+                    return Err(unexpected(format!(
+                        "Write of byte {} isn't in the script",
+                        self.written.len()
+                    )))
+                }
+            };
+            self.written.extend_from_slice(&data[checked..checked + n]);
+            checked += n;
+            self.advance(n);
+        }
+        Ok(())
+    }
+
+    fn advance(&mut self, n: usize) {
+        self.offset += n;
+        let done = match self.steps.front() {
+            Some(Step::Write(data)) | Some(Step::Read(data)) => self.offset == data.len(),
+            _ => false,
+        };
+        if done {
+            self.steps.pop_front();
+            self.offset = 0;
+        }
+    }
+}
+
+/// A scripted device, see the [module documentation][self].
+///
+/// Writes must match the script exactly, but may be split into differently sized
+/// pieces than the scripted ones; the same goes for reads.
+pub struct MockDevice {
+    script: RefCell<Script>,
+    chip_type: Option<ChipType>,
+    bitmode: Cell<Option<(u8, BitMode)>>,
+    baudrate: Cell<Option<u32>>,
+    directions: Cell<u16>,
+    latch: Cell<u16>,
+    inputs: Cell<u16>,
+    cs: u8,
+    // An I2C transaction was started and not stopped yet.
+    i2c_busy: Cell<bool>,
+}
+
+impl Default for MockDevice {
+    fn default() -> MockDevice {
+        MockDevice::new()
+    }
+}
+
+impl MockDevice {
+    /// A device with an empty script, posing as an FT232H with chip select on ADBUS3.
+    pub fn new() -> MockDevice {
+        MockDevice {
+            script: RefCell::new(Script::default()),
+            chip_type: Some(ChipType::FT232H),
+            bitmode: Cell::new(None),
+            baudrate: Cell::new(None),
+            directions: Cell::new(0),
+            latch: Cell::new(0),
+            inputs: Cell::new(0),
+            cs: 3,
+            i2c_busy: Cell::new(false),
+        }
+    }
+
+    /// A device whose script is a capture, to replay it against the code that made
+    /// it (see [`capture`]).
+    pub fn from_capture(records: Vec<Record>) -> MockDevice {
+        records
+            .into_iter()
+            .fold(MockDevice::new(), |mock, record| match record.direction {
+                Direction::Read => mock.respond(&record.data),
+                Direction::Write => mock.expect_write(&record.data),
+            })
+    }
+
+    /// Load a capture file, see [`from_capture`][MockDevice::from_capture].
+    pub fn open_capture<P: AsRef<Path>>(path: P) -> Result<MockDevice> {
+        let file = BufReader::new(File::open(path)?);
+        Ok(MockDevice::from_capture(capture::read_capture(file)?))
+    }
+
+    /// Expect `data` to be written next.
+    pub fn expect_write(self, data: &[u8]) -> MockDevice {
+        self.script.borrow_mut().push(Step::Write(data.to_vec()));
+        self
+    }
+
+    /// Return `data` from the next reads.
+    pub fn respond(self, data: &[u8]) -> MockDevice {
+        self.script.borrow_mut().push(Step::Read(data.to_vec()));
+        self
+    }
+
+    /// Fail the next operation with `error`.
+    pub fn fail(self, error: Error) -> MockDevice {
+        self.script.borrow_mut().push(Step::Fail(error));
+        self
+    }
+
+    /// Fail the next operation with a timeout.
+    pub fn time_out(self) -> MockDevice {
+        self.fail(Error::LibUsb(LibUsbError::Timeout))
+    }
+
+    /// Don't acknowledge the I2C byte written just before.
+    pub fn nack(self) -> MockDevice {
+        self.script.borrow_mut().push(Step::Nack);
+        self
+    }
+
+    pub fn with_chip_type(mut self, chip_type: Option<ChipType>) -> MockDevice {
+        self.chip_type = chip_type;
+        self
+    }
+
+    /// Use GPIO pin `cs` as the chip select of [`Spi`].
+    pub fn with_chip_select(mut self, cs: u8) -> MockDevice {
+        self.cs = cs;
+        self
+    }
+
+    /// Whether the whole script was played.
+    pub fn is_finished(&self) -> bool {
+        self.script.borrow().steps.is_empty()
+    }
+
+    /// Everything written so far.
+    pub fn written(&self) -> Vec<u8> {
+        self.script.borrow().written.clone()
+    }
+
+    /// Whether every I2C START so far was followed by a STOP, leaving the bus free.
+    pub fn is_i2c_idle(&self) -> bool {
+        !self.i2c_busy.get()
+    }
+
+    /// Set the levels read from input pins.
+    pub fn set_inputs(&self, levels: u16) {
+        self.inputs.set(levels);
+    }
+
+    /// See [`Device::read_data`][::Device::read_data].
+    pub fn read_data(&self, data: &mut [u8]) -> Result<usize> {
+        self.script.borrow_mut().read(data)
+    }
+
+    /// See [`Device::read_exact`][::Device::read_exact]; times out at once if the
+    /// script runs out of read data.
+    pub fn read_exact(&self, data: &mut [u8]) -> Result<()> {
+        if self.read_data(data)? < data.len() {
+            return Err(Error::LibUsb(LibUsbError::Timeout));
+        }
+        Ok(())
+    }
+
+    /// See [`Device::write_data`][::Device::write_data].
+    pub fn write_data(&self, data: &[u8]) -> Result<usize> {
+        self.script.borrow_mut().write(data)?;
+        Ok(data.len())
+    }
+
+    /// See [`Device::read_pins`][::Device::read_pins].
+    pub fn read_pins(&self) -> Result<u8> {
+        self.read_levels().map(|levels| levels as u8)
+    }
+
+    pub fn chip_type(&self) -> Option<ChipType> {
+        self.chip_type
+    }
+
+    /// Record the mode, see [`bitmode`][MockDevice::bitmode].
+    pub fn set_bitmode(&self, bitmask: u8, mode: BitMode) -> Result<()> {
+        self.script.borrow_mut().take_failure()?;
+        self.bitmode.set(Some((bitmask, mode)));
+        Ok(())
+    }
+
+    pub fn bitmode(&self) -> Option<(u8, BitMode)> {
+        self.bitmode.get()
+    }
+
+    /// Record the baud rate, see [`baudrate`][MockDevice::baudrate].
+    pub fn set_baudrate(&self, baudrate: u32) -> Result<()> {
+        self.script.borrow_mut().take_failure()?;
+        self.baudrate.set(Some(baudrate));
+        Ok(())
+    }
+
+    pub fn baudrate(&self) -> Option<u32> {
+        self.baudrate.get()
+    }
+
+    pub fn purge_usb_buffers(&self) -> Result<()> {
+        self.script.borrow_mut().take_failure()
+    }
+}
+
+/// 16 pins; inputs read the levels given to
+/// [`set_inputs`][MockDevice::set_inputs], outputs their latch.
+impl Gpio for MockDevice {
+    fn pin_count(&self) -> u8 {
+        16
+    }
+
+    fn direction_mask(&self) -> u16 {
+        self.directions.get()
+    }
+
+    fn output_latch(&self) -> u16 {
+        self.latch.get()
+    }
+
+    fn write_directions(&self, directions: u16) -> Result<()> {
+        self.script.borrow_mut().take_failure()?;
+        self.directions.set(directions);
+        Ok(())
+    }
+
+    fn write_outputs(&self, levels: u16) -> Result<()> {
+        self.script.borrow_mut().take_failure()?;
+        self.latch.set(levels);
+        Ok(())
+    }
+
+    fn read_levels(&self) -> Result<u16> {
+        self.script.borrow_mut().take_failure()?;
+        let directions = self.directions.get();
+        Ok((self.latch.get() & directions) | (self.inputs.get() & !directions))
+    }
+}
+
+/// Writes and reads go through the script; chip selects drive GPIO pins.
+impl SpiBus for MockDevice {
+    fn set_cs(&mut self, pin: u8, active: bool) -> Result<()> {
+        if pin >= self.pin_count() {
+            return Err(Error::InvalidArgument("No such chip select pin"));
+        }
+        let bit = 1 << pin;
+        self.write_directions(self.directions.get() | bit)?;
+        let latch = self.latch.get();
+        self.write_outputs(if active { latch & !bit } else { latch | bit })
+    }
+
+    fn write(&mut self, data: &[u8]) -> Result<()> {
+        self.write_data(data).map(|_| ())
+    }
+
+    fn read(&mut self, data: &mut [u8]) -> Result<()> {
+        self.read_exact(data)
+    }
+
+    fn transfer(&mut self, data: &mut [u8]) -> Result<()> {
+        self.write_data(data)?;
+        self.read_exact(data)
+    }
+}
+
+impl Spi for MockDevice {
+    fn select(&mut self) -> Result<()> {
+        let cs = self.cs;
+        self.set_cs(cs, true)
+    }
+
+    fn deselect(&mut self) -> Result<()> {
+        let cs = self.cs;
+        self.set_cs(cs, false)
+    }
+}
+
+/// Address and data bytes are written through the script, and acknowledged unless
+/// followed by [`nack`][MockDevice::nack]. START and STOP aren't scripted.
+impl I2c for MockDevice {
+    fn start(&mut self) -> Result<()> {
+        self.script.borrow_mut().take_failure()?;
+        self.i2c_busy.set(true);
+        Ok(())
+    }
+
+    fn stop(&mut self) -> Result<()> {
+        self.script.borrow_mut().take_failure()?;
+        self.i2c_busy.set(false);
+        Ok(())
+    }
+
+    fn write_byte(&mut self, byte: u8) -> Result<bool> {
+        let mut script = self.script.borrow_mut();
+        script.write(&[byte])?;
+        match script.steps.front() {
+            Some(&Step::Nack) => {
+                script.steps.pop_front();
+                Ok(false)
+            }
+            _ => Ok(true),
+        }
+    }
+
+    fn read_byte(&mut self, _ack: bool) -> Result<u8> {
+        let mut byte = [0];
+        self.read_exact(&mut byte)?;
+        Ok(byte[0])
+    }
+}
+
+fn unexpected(message: String) -> Error {
+    Error::Io(io::Error::new(io::ErrorKind::InvalidData, message))
+}
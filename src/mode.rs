@@ -0,0 +1,575 @@
+//! Mode-specific wrappers around [`Device`].
+//!
+//! [`Device::into_uart`], [`Device::into_bitbang`], [`Device::into_mpsse`],
+//! [`Device::into_mcu`] and [`Device::into_ft1284`] switch the chip into a mode
+//! and return a type offering only the operations that make sense in it, so
+//! e.g. flow control can't be configured on an MPSSE device by mistake.
+//! [`into_inner`][UartDevice::into_inner] gives the plain [`Device`] back.
+
+use std::cell::Cell;
+use std::time::Duration;
+
+use eeprom::{ChannelType, Ft1284Config};
+use error::{Error, LibUsbError};
+use gpio::Direction;
+use mpsse;
+use {BitMode, ChipType, DataBits, Device, FlowControl, Interface, Parity, Result, StopBits};
+
+impl Device {
+    /// Reset the chip to its default serial (UART/FIFO) mode.
+    pub fn into_uart(self) -> Result<UartDevice> {
+        self.set_bitmode(0, BitMode::Reset)?;
+        Ok(UartDevice { device: self })
+    }
+
+    /// Enter asynchronous bitbang mode; a HIGH bit in `direction` makes a pin an output.
+    pub fn into_bitbang(self, direction: u8) -> Result<BitbangDevice> {
+        self.set_bitmode(direction, BitMode::Bitbang)?;
+
+        // Outputs read back the levels they drive, so this picks up the latch state.
+        let output = self.read_pins()? & direction;
+        Ok(BitbangDevice {
+            device: self,
+            direction: Cell::new(direction),
+            output: Cell::new(output),
+        })
+    }
+
+    /// Enter MPSSE mode. Pin directions are set with MPSSE commands afterwards.
+    ///
+    /// Fails for chips and interfaces known to have no MPSSE, see
+    /// [`capabilities`][Device::capabilities].
+    pub fn into_mpsse(self) -> Result<MpsseDevice> {
+        if self.capabilities().is_some_and(|c| !c.mpsse) {
+            return Err(Error::InvalidArgument(
+                "The chip has no MPSSE on this interface",
+            ));
+        }
+        self.set_bitmode(0, BitMode::Mpsse)?;
+
+        // Entering MPSSE mode makes every pin an input.
+        Ok(MpsseDevice {
+            device: self,
+            direction: Cell::new(0),
+            output: Cell::new(0),
+        })
+    }
+
+    /// Enter MCU host bus emulation mode, available on the FT2232C/D/H.
+    pub fn into_mcu(self) -> Result<McuDevice> {
+        match self.chip_type() {
+            Some(ChipType::FT2232C) | Some(ChipType::FT2232H) => {}
+            _ => {
+                return Err(Error::InvalidArgument(
+                    "MCU host bus emulation requires an FT2232",
+                ))
+            }
+        }
+        self.set_bitmode(0, BitMode::Mcu)?;
+        Ok(McuDevice { device: self })
+    }
+
+    /// Enter FT1284 mode on an FT232H whose EEPROM configures the channel for it.
+    ///
+    /// The clock idle state, bit order and flow control come from the EEPROM, see
+    /// [`set_ft1284_config`][Device::set_ft1284_config].
+    pub fn into_ft1284(mut self) -> Result<Ft1284Device> {
+        if self.chip_type() != Some(ChipType::FT232H) {
+            return Err(Error::InvalidArgument("FT1284 mode requires an FT232H"));
+        }
+        if self.channel_type(Interface::A)? != ChannelType::FT1284 {
+            return Err(Error::InvalidArgument(
+                "EEPROM does not configure the channel for FT1284",
+            ));
+        }
+
+        let config = self.ft1284_config()?;
+        self.set_bitmode(0, BitMode::FT1284)?;
+        Ok(Ft1284Device {
+            device: self,
+            config,
+        })
+    }
+}
+
+/// A device in serial (UART/FIFO) mode.
+pub struct UartDevice {
+    device: Device,
+}
+
+impl UartDevice {
+    /// See [`Device::set_baudrate`].
+    pub fn set_baudrate(&self, baudrate: u32) -> Result<()> {
+        self.device.set_baudrate(baudrate)
+    }
+
+    /// See [`Device::set_baudrate_checked`].
+    pub fn set_baudrate_checked(&self, baudrate: u32) -> Result<u32> {
+        self.device.set_baudrate_checked(baudrate)
+    }
+
+    /// See [`Device::set_line_property`].
+    pub fn set_line_property(
+        &self,
+        bits: DataBits,
+        stop_bits: StopBits,
+        parity: Parity,
+    ) -> Result<()> {
+        self.device.set_line_property(bits, stop_bits, parity)
+    }
+
+    /// See [`Device::set_flow_control`].
+    pub fn set_flow_control(&self, flow_control: FlowControl) -> Result<()> {
+        self.device.set_flow_control(flow_control)
+    }
+
+    /// See [`Device::set_flow_control_xonxoff`].
+    #[cfg(feature = "libftdi1-5")]
+    pub fn set_flow_control_xonxoff(&self, xon: u8, xoff: u8) -> Result<()> {
+        self.device.set_flow_control_xonxoff(xon, xoff)
+    }
+
+    /// See [`Device::set_event_char`].
+    pub fn set_event_char(&self, event_char: u8, enable: bool) -> Result<()> {
+        self.device.set_event_char(event_char, enable)
+    }
+
+    /// See [`Device::set_error_char`].
+    pub fn set_error_char(&self, error_char: u8, enable: bool) -> Result<()> {
+        self.device.set_error_char(error_char, enable)
+    }
+
+    /// See [`Device::read_frame`].
+    pub fn read_frame(&self, event_char: u8, frame: &mut [u8], timeout: Duration) -> Result<usize> {
+        self.device.read_frame(event_char, frame, timeout)
+    }
+
+    /// See [`Device::set_latency_timer`].
+    pub fn set_latency_timer(&self, latency: u8) -> Result<()> {
+        self.device.set_latency_timer(latency)
+    }
+
+    /// See [`Device::set_timeouts`].
+    pub fn set_timeouts(&self, read_timeout: i32, write_timeout: i32) {
+        self.device.set_timeouts(read_timeout, write_timeout)
+    }
+
+    /// See [`Device::purge_usb_buffers`].
+    pub fn purge_usb_buffers(&self) -> Result<()> {
+        self.device.purge_usb_buffers()
+    }
+
+    /// See [`Device::read_data`].
+    pub fn read_data(&self, data: &mut [u8]) -> Result<usize> {
+        self.device.read_data(data)
+    }
+
+    /// See [`Device::write_data`].
+    pub fn write_data(&self, data: &[u8]) -> Result<usize> {
+        self.device.write_data(data)
+    }
+
+    /// See [`Device::chip_type`].
+    pub fn chip_type(&self) -> Option<ChipType> {
+        self.device.chip_type()
+    }
+
+    /// Return the underlying device, leaving the chip in its current mode.
+    pub fn into_inner(self) -> Device {
+        self.device
+    }
+}
+
+/// A device in asynchronous bitbang mode.
+///
+/// The pin directions and the levels last written to the outputs are tracked, so
+/// single pins can be changed without disturbing the others, see [`gpio`][::gpio].
+pub struct BitbangDevice {
+    device: Device,
+    direction: Cell<u8>,
+    output: Cell<u8>,
+}
+
+impl BitbangDevice {
+    /// Change which pins are outputs; a HIGH bit makes a pin an output.
+    ///
+    /// This doesn't leave bitbang mode in between, and pins turned into outputs start
+    /// out at their level in [`output_latch`][BitbangDevice::output_latch].
+    pub fn set_direction(&self, direction: u8) -> Result<()> {
+        // Load the latch first, so new outputs don't briefly drive a stale level.
+        self.device.write_data(&[self.output.get()])?;
+        self.device.set_bitmode(direction, BitMode::Bitbang)?;
+        self.direction.set(direction);
+        Ok(())
+    }
+
+    /// Change the direction of a single pin, leaving the others as they are.
+    pub fn set_pin_direction(&self, pin: u8, direction: Direction) -> Result<()> {
+        if pin >= 8 {
+            return Err(Error::InvalidArgument("Bitbang pin must be below 8"));
+        }
+
+        let mask = match direction {
+            Direction::Output => self.direction.get() | (1 << pin),
+            Direction::Input => self.direction.get() & !(1 << pin),
+        };
+        self.set_direction(mask)
+    }
+
+    /// Which pins are outputs; a HIGH bit is an output.
+    pub fn direction(&self) -> u8 {
+        self.direction.get()
+    }
+
+    /// Levels last written to the pins.
+    pub fn output_latch(&self) -> u8 {
+        self.output.get()
+    }
+
+    /// Set the rate at which written bytes are clocked out to the pins.
+    ///
+    /// See [`Device::set_baudrate`]; in bitbang mode the actual rate is a multiple of it.
+    pub fn set_baudrate(&self, baudrate: u32) -> Result<()> {
+        self.device.set_baudrate(baudrate)
+    }
+
+    /// See [`Device::set_bitbang_sample_rate`].
+    pub fn set_sample_rate(&self, hz: u32) -> Result<u32> {
+        self.device.set_bitbang_sample_rate(hz)
+    }
+
+    /// Read the current state of all pins. See [`Device::read_pins`].
+    pub fn read_pins(&self) -> Result<u8> {
+        self.device.read_pins()
+    }
+
+    /// Drive the output pins to `value`.
+    pub fn write_pins(&self, value: u8) -> Result<()> {
+        self.device.write_data(&[value])?;
+        self.output.set(value);
+        Ok(())
+    }
+
+    /// See [`Device::set_latency_timer`].
+    pub fn set_latency_timer(&self, latency: u8) -> Result<()> {
+        self.device.set_latency_timer(latency)
+    }
+
+    /// See [`Device::set_timeouts`].
+    pub fn set_timeouts(&self, read_timeout: i32, write_timeout: i32) {
+        self.device.set_timeouts(read_timeout, write_timeout)
+    }
+
+    /// See [`Device::purge_usb_buffers`].
+    pub fn purge_usb_buffers(&self) -> Result<()> {
+        self.device.purge_usb_buffers()
+    }
+
+    /// Read sampled pin states. See [`Device::read_data`].
+    pub fn read_data(&self, data: &mut [u8]) -> Result<usize> {
+        self.device.read_data(data)
+    }
+
+    /// Write a sequence of pin states. See [`Device::write_data`].
+    pub fn write_data(&self, data: &[u8]) -> Result<usize> {
+        let written = self.device.write_data(data)?;
+        if written > 0 {
+            self.output.set(data[written - 1]);
+        }
+        Ok(written)
+    }
+
+    /// Write a sequence of pin states at the bitbang clock rate, see
+    /// [`Device::write_paced`].
+    ///
+    /// Fails unless the rate was set through [`set_baudrate`][BitbangDevice::set_baudrate].
+    pub fn write_paced(&self, data: &[u8]) -> Result<usize> {
+        let rate = self
+            .device
+            .bitbang_clock()
+            .ok_or(Error::InvalidArgument("The bitbang clock rate isn't set"))?;
+        let written = self.device.write_paced(data, rate)?;
+        if written > 0 {
+            self.output.set(data[written - 1]);
+        }
+        Ok(written)
+    }
+
+    /// See [`Device::chip_type`].
+    pub fn chip_type(&self) -> Option<ChipType> {
+        self.device.chip_type()
+    }
+
+    /// Return the underlying device, leaving the chip in its current mode.
+    pub fn into_inner(self) -> Device {
+        self.device
+    }
+}
+
+/// A device in MPSSE mode, driven by writing MPSSE commands with
+/// [`write_data`][MpsseDevice::write_data].
+///
+/// The GPIO levels and directions set through [`set_gpio`][MpsseDevice::set_gpio]
+/// are tracked, see [`gpio`][::gpio]. Bits 0..7 are ADBUS0..7, bits 8..15 ACBUS0..7.
+pub struct MpsseDevice {
+    device: Device,
+    direction: Cell<u16>,
+    output: Cell<u16>,
+}
+
+impl MpsseDevice {
+    /// Number of GPIO pins: ACBUS is only available on the FT232H and FT2232H.
+    pub fn gpio_count(&self) -> u8 {
+        match self.chip_type() {
+            Some(ChipType::FT232H) | Some(ChipType::FT2232H) => 16,
+            _ => 8,
+        }
+    }
+
+    /// Which GPIO pins are outputs; a HIGH bit is an output.
+    pub fn gpio_direction(&self) -> u16 {
+        self.direction.get()
+    }
+
+    /// Levels last written to the GPIO pins.
+    pub fn gpio_output_latch(&self) -> u16 {
+        self.output.get()
+    }
+
+    /// Set the levels and directions of all GPIO pins.
+    ///
+    /// Pins used by the MPSSE engine itself (e.g. ADBUS0..3 for SPI or JTAG) must keep
+    /// the direction the protocol needs.
+    pub fn set_gpio(&self, levels: u16, directions: u16) -> Result<()> {
+        let mut cmd = vec![mpsse::SET_BITS_LOW, levels as u8, directions as u8];
+        if self.gpio_count() > 8 {
+            cmd.extend_from_slice(&[
+                mpsse::SET_BITS_HIGH,
+                (levels >> 8) as u8,
+                (directions >> 8) as u8,
+            ]);
+        }
+        self.device.write_data(&cmd)?;
+
+        self.output.set(levels);
+        self.direction.set(directions);
+        Ok(())
+    }
+
+    /// Read the level of all GPIO pins.
+    pub fn read_gpio(&self) -> Result<u16> {
+        if self.gpio_count() > 8 {
+            self.device.write_data(&[
+                mpsse::GET_BITS_LOW,
+                mpsse::GET_BITS_HIGH,
+                mpsse::SEND_IMMEDIATE,
+            ])?;
+            let mut levels = [0; 2];
+            self.read_exact(&mut levels)?;
+            Ok(u16::from(levels[0]) | (u16::from(levels[1]) << 8))
+        } else {
+            self.device
+                .write_data(&[mpsse::GET_BITS_LOW, mpsse::SEND_IMMEDIATE])?;
+            let mut levels = [0; 1];
+            self.read_exact(&mut levels)?;
+            Ok(levels[0].into())
+        }
+    }
+
+    /// Set the data clock as close to `frequency` Hz as possible without exceeding it,
+    /// returning the actual frequency.
+    ///
+    /// H-series chips run the clock from 60 MHz (up to 30 MHz), the FT2232C/D from
+    /// 12 MHz (up to 6 MHz).
+    pub fn set_clock_frequency(&self, frequency: u32) -> Result<u32> {
+        if frequency == 0 {
+            return Err(Error::InvalidArgument("Clock frequency must not be zero"));
+        }
+
+        let (base, mut cmd) = match self.chip_type() {
+            Some(ChipType::FT232H) | Some(ChipType::FT2232H) | Some(ChipType::FT4232H) => {
+                (60_000_000, vec![mpsse::DISABLE_CLK_DIV5])
+            }
+            _ => (12_000_000, vec![]),
+        };
+        // The clock runs at base / (2 * (1 + divisor)).
+        let divisor = u64::from(base).div_ceil(2 * u64::from(frequency));
+        let divisor = divisor.clamp(1, 0x1_0000) as u32 - 1;
+        cmd.extend_from_slice(&[mpsse::TCK_DIVISOR, divisor as u8, (divisor >> 8) as u8]);
+        self.device.write_data(&cmd)?;
+
+        Ok(base / (2 * (1 + divisor)))
+    }
+
+    /// Feed the data output back into the data input inside the chip, so command
+    /// sequences can be tested without anything attached.
+    pub fn set_mpsse_loopback(&self, enable: bool) -> Result<()> {
+        let cmd = if enable {
+            mpsse::LOOPBACK_START
+        } else {
+            mpsse::LOOPBACK_END
+        };
+        self.device.write_data(&[cmd]).map(|_| ())
+    }
+
+    /// See [`Device::read_exact`].
+    pub fn read_exact(&self, data: &mut [u8]) -> Result<()> {
+        self.device.read_exact(data)
+    }
+
+    /// See [`Device::set_latency_timer`].
+    pub fn set_latency_timer(&self, latency: u8) -> Result<()> {
+        self.device.set_latency_timer(latency)
+    }
+
+    /// See [`Device::set_timeouts`].
+    pub fn set_timeouts(&self, read_timeout: i32, write_timeout: i32) {
+        self.device.set_timeouts(read_timeout, write_timeout)
+    }
+
+    /// See [`Device::purge_usb_buffers`].
+    pub fn purge_usb_buffers(&self) -> Result<()> {
+        self.device.purge_usb_buffers()
+    }
+
+    /// Read command responses. See [`Device::read_data`].
+    pub fn read_data(&self, data: &mut [u8]) -> Result<usize> {
+        self.device.read_data(data)
+    }
+
+    /// Write MPSSE commands. See [`Device::write_data`].
+    pub fn write_data(&self, data: &[u8]) -> Result<usize> {
+        self.device.write_data(data)
+    }
+
+    /// See [`Device::chip_type`].
+    pub fn chip_type(&self) -> Option<ChipType> {
+        self.device.chip_type()
+    }
+
+    /// Return the underlying device, leaving the chip in its current mode.
+    pub fn into_inner(self) -> Device {
+        self.device
+    }
+}
+
+/// A device in MCU host bus emulation mode, reading and writing an 8051-style
+/// multiplexed address/data bus with the `mcu_*` commands of
+/// [`Commands`][::mpsse::Commands].
+pub struct McuDevice {
+    device: Device,
+}
+
+impl McuDevice {
+    /// See [`Device::read_exact`].
+    pub fn read_exact(&self, data: &mut [u8]) -> Result<()> {
+        self.device.read_exact(data)
+    }
+
+    /// See [`Device::set_latency_timer`].
+    pub fn set_latency_timer(&self, latency: u8) -> Result<()> {
+        self.device.set_latency_timer(latency)
+    }
+
+    /// See [`Device::set_timeouts`].
+    pub fn set_timeouts(&self, read_timeout: i32, write_timeout: i32) {
+        self.device.set_timeouts(read_timeout, write_timeout)
+    }
+
+    /// See [`Device::purge_usb_buffers`].
+    pub fn purge_usb_buffers(&self) -> Result<()> {
+        self.device.purge_usb_buffers()
+    }
+
+    /// Read command responses. See [`Device::read_data`].
+    pub fn read_data(&self, data: &mut [u8]) -> Result<usize> {
+        self.device.read_data(data)
+    }
+
+    /// Write MCU host bus commands. See [`Device::write_data`].
+    pub fn write_data(&self, data: &[u8]) -> Result<usize> {
+        self.device.write_data(data)
+    }
+
+    /// See [`Device::chip_type`].
+    pub fn chip_type(&self) -> Option<ChipType> {
+        self.device.chip_type()
+    }
+
+    /// Return the underlying device, leaving the chip in its current mode.
+    pub fn into_inner(self) -> Device {
+        self.device
+    }
+}
+
+/// An FT232H in FT1284 mode, exchanging bytes over its 1, 2 or 4-bit serial
+/// interface.
+pub struct Ft1284Device {
+    device: Device,
+    config: Ft1284Config,
+}
+
+impl Ft1284Device {
+    /// The settings read from the EEPROM when entering the mode.
+    pub fn config(&self) -> Ft1284Config {
+        self.config
+    }
+
+    /// Send `data`, then wait for exactly `response.len()` bytes back.
+    pub fn transfer(&self, data: &[u8], response: &mut [u8]) -> Result<()> {
+        self.write_all(data)?;
+        self.read_exact(response)
+    }
+
+    /// Send all of `data`, retrying while the peripheral holds off the host.
+    pub fn write_all(&self, mut data: &[u8]) -> Result<()> {
+        while !data.is_empty() {
+            let written = self.device.write_data(data)?;
+            if written == 0 {
+                return Err(Error::LibUsb(LibUsbError::Timeout));
+            }
+            data = &data[written..];
+        }
+        Ok(())
+    }
+
+    /// See [`Device::read_exact`].
+    pub fn read_exact(&self, data: &mut [u8]) -> Result<()> {
+        self.device.read_exact(data)
+    }
+
+    /// See [`Device::set_latency_timer`].
+    pub fn set_latency_timer(&self, latency: u8) -> Result<()> {
+        self.device.set_latency_timer(latency)
+    }
+
+    /// See [`Device::set_timeouts`].
+    pub fn set_timeouts(&self, read_timeout: i32, write_timeout: i32) {
+        self.device.set_timeouts(read_timeout, write_timeout)
+    }
+
+    /// See [`Device::purge_usb_buffers`].
+    pub fn purge_usb_buffers(&self) -> Result<()> {
+        self.device.purge_usb_buffers()
+    }
+
+    /// See [`Device::read_data`].
+    pub fn read_data(&self, data: &mut [u8]) -> Result<usize> {
+        self.device.read_data(data)
+    }
+
+    /// See [`Device::write_data`].
+    pub fn write_data(&self, data: &[u8]) -> Result<usize> {
+        self.device.write_data(data)
+    }
+
+    /// See [`Device::chip_type`].
+    pub fn chip_type(&self) -> Option<ChipType> {
+        self.device.chip_type()
+    }
+
+    /// Return the underlying device, leaving the chip in its current mode.
+    pub fn into_inner(self) -> Device {
+        self.device
+    }
+}
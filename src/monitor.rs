@@ -0,0 +1,224 @@
+//! Watching bitbang pins for edges on a background thread.
+
+use std::sync::atomic::Ordering;
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use background::{self, BackgroundThread};
+use shared::SharedDevice;
+use threads::ThreadOptions;
+use Result;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Edge {
+    Rising,
+    Falling,
+}
+
+/// A debounced level change on a monitored pin.
+#[derive(Debug, Copy, Clone)]
+pub struct PinEvent {
+    pub pin: u8,
+    pub edge: Edge,
+    /// When the new level was first seen, before debouncing.
+    pub time: Instant,
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct MonitorConfig {
+    /// Pins to watch; a HIGH bit selects a pin.
+    pub pins: u8,
+    pub poll_interval: Duration,
+    /// How long a new level must hold before it is reported.
+    pub debounce: Duration,
+}
+
+impl Default for MonitorConfig {
+    fn default() -> MonitorConfig {
+        MonitorConfig {
+            pins: 0xff,
+            poll_interval: Duration::from_millis(5),
+            debounce: Duration::from_secs(0),
+        }
+    }
+}
+
+/// Polls pins through [`read_pins`][::Device::read_pins] on a background thread.
+///
+/// The device is locked only for each poll, so other threads can keep using it.
+/// Stops when dropped, or through [`stop`][PinMonitor::stop].
+pub struct PinMonitor {
+    thread: BackgroundThread<Result<()>>,
+}
+
+// Debouncing state of the monitored pins.
+struct Debouncer {
+    config: MonitorConfig,
+    stable: u8,
+    // Pins whose level differs from `stable`, and since when.
+    pending: [Option<Instant>; 8],
+}
+
+impl Debouncer {
+    fn update<F: FnMut(PinEvent)>(&mut self, levels: u8, now: Instant, handler: &mut F) {
+        for pin in 0..8 {
+            let bit = 1 << pin;
+            if self.config.pins & bit == 0 {
+                continue;
+            }
+
+            if (levels ^ self.stable) & bit == 0 {
+                self.pending[pin as usize] = None;
+                continue;
+            }
+
+            let since = *self.pending[pin as usize].get_or_insert(now);
+            if now.duration_since(since) >= self.config.debounce {
+                self.stable ^= bit;
+                self.pending[pin as usize] = None;
+                handler(PinEvent {
+                    pin,
+                    edge: if levels & bit != 0 {
+                        Edge::Rising
+                    } else {
+                        Edge::Falling
+                    },
+                    time: since,
+                });
+            }
+        }
+    }
+}
+
+impl PinMonitor {
+    /// Start calling `handler` on the monitor thread for every edge.
+    ///
+    /// Fails with [`Error::Io`][::error::Error::Io] if the thread can't be created.
+    pub fn spawn<F>(
+        device: SharedDevice,
+        config: MonitorConfig,
+        mut handler: F,
+    ) -> Result<PinMonitor>
+    where
+        F: FnMut(PinEvent) + Send + 'static,
+    {
+        let thread = BackgroundThread::spawn(&ThreadOptions::new(), move |running| {
+            let mut debouncer = Debouncer {
+                config,
+                stable: device.lock().read_pins()?,
+                pending: [None; 8],
+            };
+
+            while running.load(Ordering::SeqCst) {
+                thread::sleep(config.poll_interval);
+                let levels = device.lock().read_pins()?;
+                debouncer.update(levels, Instant::now(), &mut handler);
+            }
+            Ok(())
+        })?;
+        Ok(PinMonitor { thread })
+    }
+
+    /// Start monitoring, delivering edges through a channel.
+    pub fn spawn_channel(
+        device: SharedDevice,
+        config: MonitorConfig,
+    ) -> Result<(PinMonitor, Receiver<PinEvent>)> {
+        let (sender, events) = mpsc::channel();
+        let monitor = PinMonitor::spawn(device, config, move |event| {
+            background::notify(&sender, event)
+        })?;
+        Ok((monitor, events))
+    }
+
+    /// Whether the monitor thread is still polling; it stops on the first error.
+    pub fn is_running(&self) -> bool {
+        self.thread.is_running()
+    }
+
+    /// Stop monitoring, returning the error that stopped the thread early, if any.
+    pub fn stop(mut self) -> Result<()> {
+        self.thread.join().unwrap_or(Ok(()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn debouncer(pins: u8, debounce_ms: u64) -> Debouncer {
+        Debouncer {
+            config: MonitorConfig {
+                pins,
+                debounce: Duration::from_millis(debounce_ms),
+                ..MonitorConfig::default()
+            },
+            stable: 0,
+            pending: [None; 8],
+        }
+    }
+
+    // Feed `(ms, levels)` samples, returning the edges as `(pin, edge, ms)`.
+    fn run(debouncer: &mut Debouncer, samples: &[(u64, u8)]) -> Vec<(u8, Edge, u64)> {
+        let start = Instant::now();
+        let mut events = Vec::new();
+        for &(ms, levels) in samples {
+            debouncer.update(levels, start + Duration::from_millis(ms), &mut |event| {
+                let ms = event.time.duration_since(start).as_millis() as u64;
+                events.push((event.pin, event.edge, ms));
+            });
+        }
+        events
+    }
+
+    #[test]
+    fn edges_without_debouncing() {
+        let mut debouncer = debouncer(0xff, 0);
+        let events = run(
+            &mut debouncer,
+            &[(0, 0x00), (1, 0x05), (2, 0x04), (3, 0x04)],
+        );
+        assert_eq!(
+            events,
+            vec![
+                (0, Edge::Rising, 1),
+                (2, Edge::Rising, 1),
+                (0, Edge::Falling, 2),
+            ]
+        );
+    }
+
+    #[test]
+    fn level_must_hold_for_the_window() {
+        let early = [(0, 0x01), (5, 0x01), (9, 0x01)];
+        assert_eq!(run(&mut debouncer(0xff, 10), &early), vec![]);
+        // Reported once it held for the window, stamped with when it was first seen.
+        let held = [(0, 0x01), (5, 0x01), (9, 0x01), (10, 0x01), (11, 0x01)];
+        assert_eq!(
+            run(&mut debouncer(0xff, 10), &held),
+            vec![(0, Edge::Rising, 0)]
+        );
+    }
+
+    #[test]
+    fn bounces_restart_the_window() {
+        let mut debouncer = debouncer(0xff, 10);
+        let samples = [
+            (0, 0x01),
+            (3, 0x00),
+            (6, 0x01),
+            (12, 0x01),
+            (16, 0x01),
+            (20, 0x00),
+        ];
+        assert_eq!(run(&mut debouncer, &samples), vec![(0, Edge::Rising, 6)]);
+    }
+
+    #[test]
+    fn unmonitored_pins_are_ignored() {
+        let mut debouncer = debouncer(0x01, 0);
+        let events = run(&mut debouncer, &[(0, 0xfe), (1, 0xff), (2, 0x00)]);
+        assert_eq!(events, vec![(0, Edge::Rising, 1), (0, Edge::Falling, 2)]);
+    }
+}
@@ -0,0 +1,839 @@
+//! MPSSE command opcodes, as described in FTDI application note AN_108, a builder
+//! for command sequences, and the SPI and I2C masters built from them.
+
+use error::Error;
+use i2c;
+use mode::{McuDevice, MpsseDevice};
+use spi::{self, BitOrder, SpiMode};
+use {ChipType, Result};
+
+/// Set the levels and directions of ADBUS0..7: followed by value and direction bytes.
+pub const SET_BITS_LOW: u8 = 0x80;
+/// Read ADBUS0..7, returning one byte.
+pub const GET_BITS_LOW: u8 = 0x81;
+/// Set the levels and directions of ACBUS0..7: followed by value and direction bytes.
+pub const SET_BITS_HIGH: u8 = 0x82;
+/// Read ACBUS0..7, returning one byte.
+pub const GET_BITS_HIGH: u8 = 0x83;
+/// Connect the data output (ADBUS1) to the data input (ADBUS2) internally.
+pub const LOOPBACK_START: u8 = 0x84;
+/// Undo `LOOPBACK_START`.
+pub const LOOPBACK_END: u8 = 0x85;
+/// Flush the chip's response buffer back to the host right away.
+pub const SEND_IMMEDIATE: u8 = 0x87;
+/// MCU host bus emulation: read from an 8-bit address.
+pub const MCU_READ_SHORT: u8 = 0x90;
+/// MCU host bus emulation: read from a 16-bit address (high byte first).
+pub const MCU_READ_EXTENDED: u8 = 0x91;
+/// MCU host bus emulation: write to an 8-bit address, then data.
+pub const MCU_WRITE_SHORT: u8 = 0x92;
+/// MCU host bus emulation: write to a 16-bit address (high byte first), then data.
+pub const MCU_WRITE_EXTENDED: u8 = 0x93;
+/// Pause until GPIOL1 (ADBUS5) is HIGH.
+pub const WAIT_ON_HIGH: u8 = 0x88;
+/// Pause until GPIOL1 (ADBUS5) is LOW.
+pub const WAIT_ON_LOW: u8 = 0x89;
+
+/// Flags of the data shifting commands (opcodes below 0x80), combined with `|`.
+///
+/// Shift bytes out, taking the length minus one as two bytes (LSB first).
+pub const DO_WRITE: u8 = 0x10;
+/// Shift bytes in; combined with `DO_WRITE` for full duplex.
+pub const DO_READ: u8 = 0x20;
+/// Change the output on the falling clock edge instead of the rising one.
+pub const WRITE_NEG: u8 = 0x01;
+/// Shift 1..8 bits instead of bytes, taking the length minus one as a single byte.
+pub const BITMODE: u8 = 0x02;
+/// Sample the input on the falling clock edge instead of the rising one.
+pub const READ_NEG: u8 = 0x04;
+/// Shift the LSB first instead of the MSB.
+pub const LSB: u8 = 0x08;
+/// Clock bits 0..6 of the data byte out on TMS instead, holding TDI at bit 7.
+pub const WRITE_TMS: u8 = 0x40;
+
+/// Set the clock divisor: followed by the divisor as two bytes, LSB first.
+pub const TCK_DIVISOR: u8 = 0x86;
+/// Run the clock from the 60 MHz master clock instead of 12 MHz (H-series only).
+pub const DISABLE_CLK_DIV5: u8 = 0x8A;
+/// Hold data for a third of a clock period after the falling edge, as I2C needs
+/// (H-series only). The clock then runs at two thirds of the set frequency.
+pub const ENABLE_3_PHASE_CLK: u8 = 0x8C;
+/// Wait for each clock edge to be echoed back on ADBUS7 (RTCK) before continuing
+/// (H-series only).
+pub const ENABLE_ADAPTIVE_CLK: u8 = 0x96;
+pub const DISABLE_ADAPTIVE_CLK: u8 = 0x97;
+
+/// Clock 1..8 times without data: followed by the count minus one (H-series only).
+pub const CLK_BITS: u8 = 0x8E;
+/// Clock 8 times per count without data: followed by the count minus one as two
+/// bytes (H-series only).
+pub const CLK_BYTES: u8 = 0x8F;
+/// Clock until GPIOL1 (ADBUS5) is HIGH (H-series only).
+pub const CLK_WAIT_ON_HIGH: u8 = 0x94;
+/// Clock until GPIOL1 (ADBUS5) is LOW (H-series only).
+pub const CLK_WAIT_ON_LOW: u8 = 0x95;
+/// Like `CLK_BYTES`, stopping early once GPIOL1 is HIGH (H-series only).
+pub const CLK_BYTES_OR_HIGH: u8 = 0x9C;
+/// Like `CLK_BYTES`, stopping early once GPIOL1 is LOW (H-series only).
+pub const CLK_BYTES_OR_LOW: u8 = 0x9D;
+
+/// Largest number of bytes a single data shifting command can move.
+pub const MAX_SHIFT_LEN: usize = 0x1_0000;
+
+/// A sequence of MPSSE commands, sent in a single write by [`MpsseDevice::execute`].
+///
+/// The shifting commands take flags such as [`WRITE_NEG`] and [`LSB`]; the direction
+/// flags are added by each method.
+#[derive(Debug, Clone, Default)]
+pub struct Commands {
+    data: Vec<u8>,
+    read_len: usize,
+    // Where each reading command ends in `data`, with `read_len` up to it.
+    replies: Vec<(usize, usize)>,
+}
+
+impl Commands {
+    pub fn new() -> Commands {
+        Commands::default()
+    }
+
+    /// The encoded commands.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Number of bytes the commands send back.
+    pub fn read_len(&self) -> usize {
+        self.read_len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// Append already encoded commands, which send back `read_len` bytes.
+    pub fn raw(mut self, data: &[u8], read_len: usize) -> Commands {
+        self.data.extend_from_slice(data);
+        self.reply(read_len)
+    }
+
+    // Account for `len` more bytes sent back by the commands appended so far.
+    fn reply(mut self, len: usize) -> Commands {
+        if len > 0 {
+            self.read_len += len;
+            self.replies.push((self.data.len(), self.read_len));
+        }
+        self
+    }
+
+    /// Set the levels and directions of ADBUS0..7.
+    pub fn set_bits_low(self, levels: u8, directions: u8) -> Commands {
+        self.raw(&[SET_BITS_LOW, levels, directions], 0)
+    }
+
+    /// Set the levels and directions of ACBUS0..7.
+    pub fn set_bits_high(self, levels: u8, directions: u8) -> Commands {
+        self.raw(&[SET_BITS_HIGH, levels, directions], 0)
+    }
+
+    /// Read ADBUS0..7.
+    pub fn get_bits_low(self) -> Commands {
+        self.raw(&[GET_BITS_LOW], 1)
+    }
+
+    /// Read ACBUS0..7.
+    pub fn get_bits_high(self) -> Commands {
+        self.raw(&[GET_BITS_HIGH], 1)
+    }
+
+    // Shifting commands moving `len` bytes in total, at most `MAX_SHIFT_LEN` each.
+    // `data` is appended to the commands when writing.
+    fn shift(mut self, opcode: u8, len: usize, data: Option<&[u8]>) -> Commands {
+        let mut start = 0;
+        while start < len {
+            let chunk = (len - start).min(MAX_SHIFT_LEN);
+            let count = chunk - 1;
+            self = self.raw(&[opcode, count as u8, (count >> 8) as u8], 0);
+            if let Some(data) = data {
+                self = self.raw(&data[start..start + chunk], 0);
+            }
+            if opcode & DO_READ != 0 {
+                self = self.reply(chunk);
+            }
+            start += chunk;
+        }
+        self
+    }
+
+    /// Shift out `data`, split into as many commands as needed.
+    pub fn write_bytes(self, flags: u8, data: &[u8]) -> Commands {
+        self.shift(DO_WRITE | flags, data.len(), Some(data))
+    }
+
+    /// Shift in `len` bytes, split into as many commands as needed.
+    pub fn read_bytes(self, flags: u8, len: usize) -> Commands {
+        self.shift(DO_READ | flags, len, None)
+    }
+
+    /// Shift out `data` while shifting in as many bytes.
+    pub fn transfer_bytes(self, flags: u8, data: &[u8]) -> Commands {
+        self.shift(DO_WRITE | DO_READ | flags, data.len(), Some(data))
+    }
+
+    /// Shift out the first `count` (1..8) bits of `bits`.
+    ///
+    /// # Panics
+    ///
+    /// If `count` is 0 or more than 8.
+    pub fn write_bits(self, flags: u8, bits: u8, count: u8) -> Commands {
+        assert!((1..=8).contains(&count), "MPSSE shift of {} bits", count);
+        self.raw(&[DO_WRITE | BITMODE | flags, count - 1, bits], 0)
+    }
+
+    /// Shift in `count` (1..8) bits, returned in one byte.
+    ///
+    /// # Panics
+    ///
+    /// If `count` is 0 or more than 8.
+    pub fn read_bits(self, flags: u8, count: u8) -> Commands {
+        assert!((1..=8).contains(&count), "MPSSE shift of {} bits", count);
+        self.raw(&[DO_READ | BITMODE | flags, count - 1], 1)
+    }
+
+    /// Shift out the first `count` (1..8) bits of `bits` while shifting in as many,
+    /// returned in one byte.
+    ///
+    /// # Panics
+    ///
+    /// If `count` is 0 or more than 8.
+    pub fn transfer_bits(self, flags: u8, bits: u8, count: u8) -> Commands {
+        assert!((1..=8).contains(&count), "MPSSE shift of {} bits", count);
+        self.raw(&[DO_WRITE | DO_READ | BITMODE | flags, count - 1, bits], 1)
+    }
+
+    /// Clock the first `count` (1..7) bits of `tms` out on TMS, LSB first, with TDI
+    /// held at `tdi`.
+    ///
+    /// # Panics
+    ///
+    /// If `count` is 0 or more than 7.
+    pub fn write_tms(self, flags: u8, tms: u8, count: u8, tdi: bool) -> Commands {
+        assert!(
+            (1..=7).contains(&count),
+            "MPSSE TMS shift of {} bits",
+            count
+        );
+        let data = (tms & 0x7f) | ((tdi as u8) << 7);
+        self.raw(&[WRITE_TMS | BITMODE | LSB | flags, count - 1, data], 0)
+    }
+
+    /// Like [`write_tms`][Commands::write_tms], also shifting in TDO, returned in one
+    /// byte.
+    ///
+    /// # Panics
+    ///
+    /// If `count` is 0 or more than 7.
+    pub fn transfer_tms(self, flags: u8, tms: u8, count: u8, tdi: bool) -> Commands {
+        assert!(
+            (1..=7).contains(&count),
+            "MPSSE TMS shift of {} bits",
+            count
+        );
+        let data = (tms & 0x7f) | ((tdi as u8) << 7);
+        self.raw(
+            &[WRITE_TMS | DO_READ | BITMODE | LSB | flags, count - 1, data],
+            1,
+        )
+    }
+
+    /// Pulse the clock `cycles` times without shifting data (H-series only).
+    pub fn clock_cycles(mut self, cycles: usize) -> Commands {
+        let mut bytes = cycles / 8;
+        while bytes > 0 {
+            let chunk = bytes.min(MAX_SHIFT_LEN);
+            let count = chunk - 1;
+            self = self.raw(&[CLK_BYTES, count as u8, (count >> 8) as u8], 0);
+            bytes -= chunk;
+        }
+        match cycles % 8 {
+            0 => self,
+            bits => self.raw(&[CLK_BITS, bits as u8 - 1], 0),
+        }
+    }
+
+    /// Pulse the clock until GPIOL1 (ADBUS5) is at `high` (H-series only).
+    pub fn clock_until_gpiol1(self, high: bool) -> Commands {
+        self.raw(
+            &[if high {
+                CLK_WAIT_ON_HIGH
+            } else {
+                CLK_WAIT_ON_LOW
+            }],
+            0,
+        )
+    }
+
+    /// Pulse the clock until GPIOL1 (ADBUS5) is at `high`, but at most about
+    /// `cycles` times, rounded up to a multiple of 8 (H-series only).
+    pub fn clock_until_gpiol1_or(mut self, high: bool, cycles: usize) -> Commands {
+        let opcode = if high {
+            CLK_BYTES_OR_HIGH
+        } else {
+            CLK_BYTES_OR_LOW
+        };
+        let mut bytes = cycles.div_ceil(8);
+        while bytes > 0 {
+            let chunk = bytes.min(MAX_SHIFT_LEN);
+            let count = chunk - 1;
+            self = self.raw(&[opcode, count as u8, (count >> 8) as u8], 0);
+            bytes -= chunk;
+        }
+        self
+    }
+
+    /// Read a byte from `address` on the MCU host bus.
+    pub fn mcu_read(self, address: u16) -> Commands {
+        self.raw(&[MCU_READ_EXTENDED, (address >> 8) as u8, address as u8], 1)
+    }
+
+    /// Read a byte from `address` on the MCU host bus, driving only the low address
+    /// byte.
+    pub fn mcu_read_short(self, address: u8) -> Commands {
+        self.raw(&[MCU_READ_SHORT, address], 1)
+    }
+
+    /// Write `value` to `address` on the MCU host bus.
+    pub fn mcu_write(self, address: u16, value: u8) -> Commands {
+        self.raw(
+            &[
+                MCU_WRITE_EXTENDED,
+                (address >> 8) as u8,
+                address as u8,
+                value,
+            ],
+            0,
+        )
+    }
+
+    /// Write `value` to `address` on the MCU host bus, driving only the low address
+    /// byte.
+    pub fn mcu_write_short(self, address: u8, value: u8) -> Commands {
+        self.raw(&[MCU_WRITE_SHORT, address, value], 0)
+    }
+
+    /// Pause the command stream until GPIOL1 (ADBUS5) is at `high`.
+    pub fn wait_on_gpiol1(self, high: bool) -> Commands {
+        self.raw(&[if high { WAIT_ON_HIGH } else { WAIT_ON_LOW }], 0)
+    }
+
+    pub fn send_immediate(self) -> Commands {
+        self.raw(&[SEND_IMMEDIATE], 0)
+    }
+
+    /// Freeze the commands into a [`Macro`] called `name`.
+    pub fn record<S: Into<String>>(self, name: S) -> Macro {
+        let mut program = self.data;
+        if self.read_len > 0 {
+            program.push(SEND_IMMEDIATE);
+        }
+        Macro {
+            name: name.into(),
+            program,
+            read_len: self.read_len,
+            replies: self.replies,
+        }
+    }
+}
+
+// The I/O of the modes that execute `Commands`.
+trait CommandPort {
+    fn write_data(&self, data: &[u8]) -> Result<usize>;
+    fn read_data(&self, data: &mut [u8]) -> Result<usize>;
+    fn read_exact(&self, data: &mut [u8]) -> Result<()>;
+    fn chip_type(&self) -> Option<ChipType>;
+}
+
+impl CommandPort for MpsseDevice {
+    fn write_data(&self, data: &[u8]) -> Result<usize> {
+        MpsseDevice::write_data(self, data)
+    }
+
+    fn read_data(&self, data: &mut [u8]) -> Result<usize> {
+        MpsseDevice::read_data(self, data)
+    }
+
+    fn read_exact(&self, data: &mut [u8]) -> Result<()> {
+        MpsseDevice::read_exact(self, data)
+    }
+
+    fn chip_type(&self) -> Option<ChipType> {
+        MpsseDevice::chip_type(self)
+    }
+}
+
+impl CommandPort for McuDevice {
+    fn write_data(&self, data: &[u8]) -> Result<usize> {
+        McuDevice::write_data(self, data)
+    }
+
+    fn read_data(&self, data: &mut [u8]) -> Result<usize> {
+        McuDevice::read_data(self, data)
+    }
+
+    fn read_exact(&self, data: &mut [u8]) -> Result<()> {
+        McuDevice::read_exact(self, data)
+    }
+
+    fn chip_type(&self) -> Option<ChipType> {
+        McuDevice::chip_type(self)
+    }
+}
+
+fn execute<P: CommandPort>(port: &P, commands: &Commands) -> Result<Vec<u8>> {
+    let mut response = vec![0; commands.read_len()];
+    if commands.read_len() == 0 {
+        run(port, commands.as_bytes(), &[], &mut response)?;
+    } else {
+        let mut program = commands.as_bytes().to_vec();
+        program.push(SEND_IMMEDIATE);
+        run(port, &program, &commands.replies, &mut response)?;
+    }
+    Ok(response)
+}
+
+// Send `program`, which ends in SEND_IMMEDIATE if it reads anything, and fill
+// `response` with what it sends back. `replies` are the program's reading
+// commands, as kept by `Commands`.
+fn run<P: CommandPort>(
+    port: &P,
+    program: &[u8],
+    replies: &[(usize, usize)],
+    response: &mut [u8],
+) -> Result<()> {
+    if response.is_empty() {
+        return port.write_data(program).map(|_| ());
+    }
+
+    // The chip stops taking commands while its response buffer is full, so long
+    // sequences are written in rounds, each ending after the last reading command
+    // that fits. What a round's commands send back is flushed and read in full
+    // before the next round is written.
+    let round_size = match port.chip_type() {
+        Some(ChipType::FT2232H) => 4096,
+        Some(ChipType::FT4232H) => 2048,
+        Some(ChipType::FT232H) => 1024,
+        _ => 128,
+    };
+    let mut replies = replies.iter().peekable();
+    let mut start = 0;
+    let mut filled = 0;
+    while start < program.len() {
+        let limit = (start + round_size).min(program.len());
+        let mut end = limit;
+        let mut expected = filled;
+        while let Some(&&(at, len)) = replies.peek() {
+            if at > limit {
+                break;
+            }
+            end = at;
+            expected = len;
+            replies.next();
+        }
+        if program[end..] == [SEND_IMMEDIATE] {
+            end = program.len();
+        }
+
+        port.write_data(&program[start..end])?;
+        if expected > filled {
+            if end < program.len() {
+                port.write_data(&[SEND_IMMEDIATE])?;
+            }
+            port.read_exact(&mut response[filled..expected])?;
+            filled = expected;
+        } else if filled < response.len() {
+            // A round inside a long shift: take what has come back so far, so the
+            // chip keeps going.
+            filled += port.read_data(&mut response[filled..])?;
+        }
+        start = end;
+    }
+    Ok(())
+}
+
+/// A command sequence frozen for replaying, e.g. a status poll run in a loop, see
+/// [`Commands::record`].
+#[derive(Debug, Clone)]
+pub struct Macro {
+    name: String,
+    program: Vec<u8>,
+    read_len: usize,
+    replies: Vec<(usize, usize)>,
+}
+
+impl Macro {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The encoded commands, including the final [`SEND_IMMEDIATE`] if any.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.program
+    }
+
+    /// Number of bytes each replay sends back.
+    pub fn read_len(&self) -> usize {
+        self.read_len
+    }
+}
+
+impl MpsseDevice {
+    /// Send `commands` and return the bytes they send back.
+    ///
+    /// A [`SEND_IMMEDIATE`] is added if the commands read anything.
+    pub fn execute(&self, commands: &Commands) -> Result<Vec<u8>> {
+        execute(self, commands)
+    }
+
+    /// Run `commands` and return the bytes they send back.
+    pub fn replay(&self, commands: &Macro) -> Result<Vec<u8>> {
+        let mut response = vec![0; commands.read_len()];
+        self.replay_into(commands, &mut response)?;
+        Ok(response)
+    }
+
+    /// Run `commands`, storing what they send back in `response`, which must hold
+    /// [`read_len`][Macro::read_len] bytes. Nothing is allocated.
+    pub fn replay_into(&self, commands: &Macro, response: &mut [u8]) -> Result<()> {
+        if response.len() != commands.read_len() {
+            return Err(Error::InvalidArgument(
+                "Response buffer doesn't match the macro's read length",
+            ));
+        }
+        run(self, commands.as_bytes(), &commands.replies, response)
+    }
+
+    /// Pulse the clock `cycles` times without shifting data, e.g. for JTAG
+    /// Run-Test/Idle delays. Needs an H-series chip.
+    pub fn clock_cycles(&self, cycles: usize) -> Result<()> {
+        match self.chip_type() {
+            Some(ChipType::FT232H) | Some(ChipType::FT2232H) | Some(ChipType::FT4232H) => {}
+            _ => {
+                return Err(Error::InvalidArgument(
+                    "Clocking without data needs an H-series chip",
+                ))
+            }
+        }
+        self.execute(&Commands::new().clock_cycles(cycles))
+            .map(|_| ())
+    }
+
+    /// Block until GPIOL1 (ADBUS5) is at `high`, with the wait done by the chip.
+    ///
+    /// Fails with a timeout error after the read timeout, see
+    /// [`set_timeouts`][MpsseDevice::set_timeouts]. The chip keeps waiting then;
+    /// purging its buffers doesn't stop it, only leaving MPSSE mode does.
+    pub fn wait_for_gpiol1(&self, high: bool) -> Result<()> {
+        let commands = Commands::new().wait_on_gpiol1(high).get_bits_low();
+        self.execute(&commands).map(|_| ())
+    }
+}
+
+/// Access to the FT2232's MCU host bus, see [`Device::into_mcu`][::Device::into_mcu].
+impl McuDevice {
+    /// Send `commands`, typically built from the `mcu_*` commands, and return the
+    /// bytes they send back.
+    pub fn execute(&self, commands: &Commands) -> Result<Vec<u8>> {
+        execute(self, commands)
+    }
+
+    /// Read the byte at `address`.
+    pub fn read(&self, address: u16) -> Result<u8> {
+        Ok(self.execute(&Commands::new().mcu_read(address))?[0])
+    }
+
+    /// Write `value` to `address`.
+    pub fn write(&self, address: u16, value: u8) -> Result<()> {
+        self.execute(&Commands::new().mcu_write(address, value))
+            .map(|_| ())
+    }
+}
+
+// ADBUS pins with a fixed role while shifting data.
+const SCK: u16 = 1 << 0;
+const MOSI: u16 = 1 << 1;
+const MISO: u16 = 1 << 2;
+
+/// SPI master using the MPSSE engine.
+///
+/// SCK, MOSI and MISO are ADBUS0..2; chip select can be any other GPIO pin. Set the
+/// clock with [`MpsseDevice::set_clock_frequency`] first. Transfers of any length
+/// are split into as many MPSSE commands as needed.
+///
+/// The bus starts out in mode 0 with the MSB first, see [`set_mode`][Spi::set_mode]
+/// and [`set_bit_order`][Spi::set_bit_order].
+pub struct Spi<'a> {
+    device: &'a MpsseDevice,
+    cs: u8,
+    mode: SpiMode,
+    bit_order: BitOrder,
+}
+
+impl<'a> Spi<'a> {
+    /// Configure the SPI pins, using GPIO pin `cs` as an active LOW chip select.
+    pub fn new(device: &'a MpsseDevice, cs: u8) -> Result<Spi<'a>> {
+        if cs < 3 || cs >= device.gpio_count() {
+            return Err(Error::InvalidArgument(
+                "Chip select must be a GPIO pin other than ADBUS0..2",
+            ));
+        }
+
+        let cs_bit = 1 << cs;
+        let levels = (device.gpio_output_latch() | cs_bit) & !SCK;
+        let directions = (device.gpio_direction() | SCK | MOSI | cs_bit) & !MISO;
+        device.set_gpio(levels, directions)?;
+        Ok(Spi {
+            device,
+            cs,
+            mode: SpiMode::Mode0,
+            bit_order: BitOrder::MsbFirst,
+        })
+    }
+
+    pub fn mode(&self) -> SpiMode {
+        self.mode
+    }
+
+    /// Switch to another SPI mode, moving SCK to its new idle level.
+    pub fn set_mode(&mut self, mode: SpiMode) -> Result<()> {
+        let latch = self.device.gpio_output_latch();
+        let latch = if mode.cpol() {
+            latch | SCK
+        } else {
+            latch & !SCK
+        };
+        self.device.set_gpio(latch, self.device.gpio_direction())?;
+        self.mode = mode;
+        Ok(())
+    }
+
+    pub fn bit_order(&self) -> BitOrder {
+        self.bit_order
+    }
+
+    pub fn set_bit_order(&mut self, bit_order: BitOrder) {
+        self.bit_order = bit_order;
+    }
+
+    // Flags of the write and read commands for the mode and bit order. Data is
+    // sampled on the rising edge in modes 0 and 3, and changes on the other edge.
+    fn flags(&self) -> (u8, u8) {
+        let (write, read) = if self.mode.cpol() == self.mode.cpha() {
+            (WRITE_NEG, 0)
+        } else {
+            (0, READ_NEG)
+        };
+        let lsb = match self.bit_order {
+            BitOrder::MsbFirst => 0,
+            BitOrder::LsbFirst => LSB,
+        };
+        (write | lsb, read | lsb)
+    }
+}
+
+impl<'a> spi::SpiBus for Spi<'a> {
+    fn set_cs(&mut self, pin: u8, active: bool) -> Result<()> {
+        if pin < 3 || pin >= self.device.gpio_count() {
+            return Err(Error::InvalidArgument(
+                "Chip select must be a GPIO pin other than ADBUS0..2",
+            ));
+        }
+
+        let latch = self.device.gpio_output_latch();
+        let latch = if active {
+            latch & !(1 << pin)
+        } else {
+            latch | (1 << pin)
+        };
+        let directions = self.device.gpio_direction() | (1 << pin);
+        self.device.set_gpio(latch, directions)
+    }
+
+    fn write(&mut self, data: &[u8]) -> Result<()> {
+        let (write, _) = self.flags();
+        self.device
+            .execute(&Commands::new().write_bytes(write, data))
+            .map(|_| ())
+    }
+
+    fn read(&mut self, data: &mut [u8]) -> Result<()> {
+        let (_, read) = self.flags();
+        let response = self
+            .device
+            .execute(&Commands::new().read_bytes(read, data.len()))?;
+        data.copy_from_slice(&response);
+        Ok(())
+    }
+
+    fn transfer(&mut self, data: &mut [u8]) -> Result<()> {
+        let (write, read) = self.flags();
+        let response = self
+            .device
+            .execute(&Commands::new().transfer_bytes(write | read, data))?;
+        data.copy_from_slice(&response);
+        Ok(())
+    }
+}
+
+impl<'a> spi::Spi for Spi<'a> {
+    fn select(&mut self) -> Result<()> {
+        let cs = self.cs;
+        spi::SpiBus::set_cs(self, cs, true)
+    }
+
+    fn deselect(&mut self) -> Result<()> {
+        let cs = self.cs;
+        spi::SpiBus::set_cs(self, cs, false)
+    }
+}
+
+// ADBUS pins of the I2C master; SDA must be wired to both ADBUS1 and ADBUS2.
+const SCL: u16 = 1 << 0;
+const SDA_OUT: u16 = 1 << 1;
+// Clock feedback input for adaptive clocking.
+const RTCK: u16 = 1 << 7;
+
+/// I2C master using the MPSSE engine.
+///
+/// SCL is ADBUS0, and SDA is driven from ADBUS1 and read back on ADBUS2, so both
+/// must be connected to the SDA line. SDA is only driven LOW, and while the
+/// controller shifts out a byte; otherwise ADBUS1 is an input and the SDA pull-up
+/// sets the level. SCL is driven push-pull; to let targets stretch the clock, see
+/// [`set_clock_stretching`][I2c::set_clock_stretching].
+pub struct I2c<'a> {
+    device: &'a MpsseDevice,
+}
+
+impl<'a> I2c<'a> {
+    /// Release the bus and set SCL to at most `frequency` Hz.
+    pub fn new(device: &'a MpsseDevice, frequency: u32) -> Result<I2c<'a>> {
+        match device.chip_type() {
+            Some(ChipType::FT232H) | Some(ChipType::FT2232H) | Some(ChipType::FT4232H) => {
+                device.write_data(&[ENABLE_3_PHASE_CLK])?;
+                device.set_clock_frequency(frequency.saturating_mul(3) / 2)?;
+            }
+            _ => {
+                device.set_clock_frequency(frequency)?;
+            }
+        }
+
+        let i2c = I2c { device };
+        i2c.set_lines(true, true)?;
+        Ok(i2c)
+    }
+
+    /// Wait for SCL to actually go HIGH before continuing each clock cycle, so
+    /// targets can stretch the clock.
+    ///
+    /// SCL must also be wired to ADBUS7, which becomes an input; only H-series chips
+    /// support this. Since ADBUS0 drives SCL push-pull, put a series resistor between
+    /// them so a target can hold SCL LOW.
+    pub fn set_clock_stretching(&self, enable: bool) -> Result<()> {
+        match self.device.chip_type() {
+            Some(ChipType::FT232H) | Some(ChipType::FT2232H) | Some(ChipType::FT4232H) => {}
+            _ => {
+                return Err(Error::InvalidArgument(
+                    "Clock stretching needs an H-series chip",
+                ))
+            }
+        }
+
+        if enable {
+            let directions = self.device.gpio_direction() & !RTCK;
+            self.device
+                .set_gpio(self.device.gpio_output_latch(), directions)?;
+            self.device.write_data(&[ENABLE_ADAPTIVE_CLK])?;
+        } else {
+            self.device.write_data(&[DISABLE_ADAPTIVE_CLK])?;
+        }
+        Ok(())
+    }
+
+    // SDA is open drain: a HIGH level releases it to the pull-up by making ADBUS1 an
+    // input, so a target pulling it LOW (ACK, arbitration) isn't fought.
+    fn set_lines(&self, scl: bool, sda: bool) -> Result<()> {
+        let mut levels = self.device.gpio_output_latch() & !(SCL | SDA_OUT);
+        if scl {
+            levels |= SCL;
+        }
+        let mut directions = (self.device.gpio_direction() | SCL) & !(SDA_OUT | MISO);
+        if !sda {
+            directions |= SDA_OUT;
+        }
+        self.device.set_gpio(levels, directions)
+    }
+
+    // Drive SDA while SCL stays LOW, for shifting out the bits of a byte; only the
+    // controller drives SDA during them.
+    fn drive_sda(&self) -> Result<()> {
+        let levels = self.device.gpio_output_latch() & !SCL;
+        let directions = (self.device.gpio_direction() | SDA_OUT) & !MISO;
+        self.device.set_gpio(levels, directions)
+    }
+
+    // Let the target drive SDA while SCL stays LOW.
+    fn release_sda(&self) -> Result<()> {
+        let levels = self.device.gpio_output_latch() & !SCL;
+        let directions = self.device.gpio_direction() & !(SDA_OUT | MISO);
+        self.device.set_gpio(levels, directions)
+    }
+
+    // Shift one bit in on the rising edge and return it.
+    fn read_bit(&self) -> Result<bool> {
+        self.device
+            .write_data(&[DO_READ | BITMODE, 0, SEND_IMMEDIATE])?;
+        let mut bit = [0];
+        self.device.read_exact(&mut bit)?;
+        Ok(bit[0] & 0x01 != 0)
+    }
+}
+
+impl<'a> i2c::I2c for I2c<'a> {
+    fn start(&mut self) -> Result<()> {
+        // SDA goes first, in case SCL is LOW during a repeated START.
+        self.set_lines(false, true)?;
+        self.set_lines(true, true)?;
+        self.set_lines(true, false)?;
+        self.set_lines(false, false)
+    }
+
+    fn stop(&mut self) -> Result<()> {
+        self.set_lines(false, false)?;
+        self.set_lines(true, false)?;
+        self.set_lines(true, true)
+    }
+
+    fn write_byte(&mut self, byte: u8) -> Result<bool> {
+        self.drive_sda()?;
+        self.device
+            .write_data(&[DO_WRITE | WRITE_NEG, 0, 0, byte])?;
+        self.release_sda()?;
+        // The target pulls SDA LOW to acknowledge.
+        let ack = !self.read_bit()?;
+        self.set_lines(false, true)?;
+        Ok(ack)
+    }
+
+    fn read_byte(&mut self, ack: bool) -> Result<u8> {
+        self.release_sda()?;
+        self.device.write_data(&[DO_READ, 0, 0, SEND_IMMEDIATE])?;
+        let mut byte = [0];
+        self.device.read_exact(&mut byte)?;
+
+        // A NACK leaves SDA released, so shifting out the HIGH level drives nothing.
+        self.set_lines(false, !ack)?;
+        let level = if ack { 0x00 } else { 0xff };
+        self.device
+            .write_data(&[DO_WRITE | WRITE_NEG | BITMODE, 0, level])?;
+        Ok(byte[0])
+    }
+}
@@ -0,0 +1,70 @@
+//! Coordinated access to all interfaces of a multi-channel chip.
+
+use {Device, DeviceInfo, Interface, Result};
+
+const INTERFACES: [Interface; 4] = [Interface::A, Interface::B, Interface::C, Interface::D];
+
+/// Every interface of one FT2232/FT4232 chip, opened together.
+///
+/// All channels are guaranteed to belong to the same physical chip, which is not the case
+/// when opening each interface separately by vendor/product id and serial.
+pub struct MultiDevice {
+    info: DeviceInfo,
+    channels: Vec<Device>,
+}
+
+impl MultiDevice {
+    /// Open all interfaces of the first chip with the given vendor id, product id and,
+    /// optionally, serial.
+    ///
+    /// Chips with a single interface yield a `MultiDevice` with one channel.
+    pub fn open_all(vid: u16, pid: u16, serial: Option<&str>) -> Result<MultiDevice> {
+        let first = Device::from_description_serial(Interface::A, vid, pid, None, serial)?;
+        let info = first.device_info()?;
+        let count = first.interface_count() as usize;
+
+        let mut channels = Vec::with_capacity(count);
+        channels.push(first);
+
+        // Reopen by bus address so every channel lands on the same chip.
+        for &interface in INTERFACES[1..count].iter() {
+            channels.push(Device::from_bus_addr(interface, info.bus, info.address)?);
+        }
+
+        Ok(MultiDevice { info, channels })
+    }
+
+    /// Identification shared by all channels.
+    pub fn info(&self) -> &DeviceInfo {
+        &self.info
+    }
+
+    /// Number of opened channels.
+    pub fn channel_count(&self) -> usize {
+        self.channels.len()
+    }
+
+    /// Get the `Device` for a channel. [`Interface::Any`] selects channel A.
+    pub fn channel(&self, interface: Interface) -> Option<&Device> {
+        self.channels.get(channel_index(interface))
+    }
+
+    /// Get the `Device` for a channel mutably. [`Interface::Any`] selects channel A.
+    pub fn channel_mut(&mut self, interface: Interface) -> Option<&mut Device> {
+        self.channels.get_mut(channel_index(interface))
+    }
+
+    /// Split into the per-channel devices, in interface order.
+    pub fn into_channels(self) -> Vec<Device> {
+        self.channels
+    }
+}
+
+fn channel_index(interface: Interface) -> usize {
+    match interface {
+        Interface::Any | Interface::A => 0,
+        Interface::B => 1,
+        Interface::C => 2,
+        Interface::D => 3,
+    }
+}
@@ -0,0 +1,87 @@
+//! Repeating a short pattern on the output, e.g. a clock, a train of stepper motor
+//! steps or a stress test, without building the whole output in memory.
+
+use cancel::CancellationToken;
+use error::Error;
+use {Device, Result};
+
+/// Writes a pattern over and over through a [`StreamWriter`][::stream::StreamWriter].
+///
+/// The pattern is repeated into blocks of about [`with_block_size`] bytes, so short
+/// patterns don't cost a transfer each.
+///
+/// [`with_block_size`]: PatternGenerator::with_block_size
+#[derive(Debug, Clone)]
+pub struct PatternGenerator {
+    pattern: Vec<u8>,
+    // `None` repeats until stopped.
+    count: Option<u64>,
+    block_size: usize,
+    depth: usize,
+}
+
+impl PatternGenerator {
+    /// Repeat `pattern` until stopped.
+    pub fn new(pattern: &[u8]) -> PatternGenerator {
+        PatternGenerator {
+            pattern: pattern.to_vec(),
+            count: None,
+            block_size: 4096,
+            depth: 4,
+        }
+    }
+
+    /// Stop after `count` repetitions.
+    pub fn repeat(mut self, count: u64) -> PatternGenerator {
+        self.count = Some(count);
+        self
+    }
+
+    /// Write blocks of about `bytes`, rounded to whole patterns.
+    pub fn with_block_size(mut self, bytes: usize) -> PatternGenerator {
+        self.block_size = bytes;
+        self
+    }
+
+    /// Keep up to `depth` blocks in flight, see [`Device::stream_writer`].
+    pub fn with_depth(mut self, depth: usize) -> PatternGenerator {
+        self.depth = depth;
+        self
+    }
+
+    /// Write the repetitions, returning how many were written.
+    ///
+    /// Without a [`repeat`][PatternGenerator::repeat] count, this only ends with an
+    /// error; use [`run_until`][PatternGenerator::run_until] to stop it.
+    pub fn run(&self, device: &Device) -> Result<u64> {
+        self.generate(device, None)
+    }
+
+    /// Like [`run`][PatternGenerator::run], but stop cleanly once `stop` is cancelled.
+    pub fn run_until(&self, device: &Device, stop: &CancellationToken) -> Result<u64> {
+        self.generate(device, Some(stop))
+    }
+
+    fn generate(&self, device: &Device, stop: Option<&CancellationToken>) -> Result<u64> {
+        if self.pattern.is_empty() {
+            return Err(Error::InvalidArgument("The pattern is empty"));
+        }
+
+        let per_block = (self.block_size / self.pattern.len()).max(1) as u64;
+        let block = self.pattern.repeat(per_block as usize);
+        device.stream_writer(self.depth.max(1), |writer| {
+            let mut written = 0;
+            while self.count.is_none_or(|count| written < count) {
+                if stop.is_some_and(CancellationToken::is_cancelled) {
+                    break;
+                }
+                let reps = self
+                    .count
+                    .map_or(per_block, |count| (count - written).min(per_block));
+                writer.write(&block[..reps as usize * self.pattern.len()])?;
+                written += reps;
+            }
+            Ok(written)
+        })
+    }
+}
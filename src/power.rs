@@ -0,0 +1,74 @@
+//! USB autosuspend: keeping the host from suspending an idle device, and noticing
+//! when it did.
+//!
+//! Hosts may suspend a device that has been idle for a while. A device that is reset
+//! while resuming comes back with its power-on settings, or re-enumerates and leaves
+//! the open handle dead, so it seems to stop responding after being left alone.
+//! Both are only supported on Linux, which reports a device's power management in
+//! sysfs; elsewhere these report [`Error::InvalidArgument`] or nothing.
+
+use std::time::Duration;
+
+use error::Error;
+use usbc;
+use {usb_port_path, Device, Result};
+
+impl Device {
+    /// Allow or prevent the host from autosuspending the device when idle
+    ///
+    /// Linux only. Preventing autosuspend writes the device's `power/control` in sysfs,
+    /// which needs write access to it, e.g. through a udev rule. The setting is lost
+    /// when the device re-enumerates.
+    pub fn set_autosuspend(&self, enable: bool) -> Result<()> {
+        let value = if enable { "auto" } else { "on" };
+        write_power_attr(&self.usb_port_path()?, "control", value)
+    }
+
+    /// Total time the device has spent autosuspended since it was attached, or `None`
+    /// if the platform doesn't tell.
+    ///
+    /// A device that was suspended in between two calls reports a longer time in the
+    /// second; [`ReconnectingDevice`][::reconnect::ReconnectingDevice] uses this to
+    /// restore its settings after a resume.
+    pub fn suspended_time(&self) -> Result<Option<Duration>> {
+        Ok(
+            read_power_attr(&self.usb_port_path()?, "runtime_suspended_time")
+                .and_then(|ms| ms.trim().parse().ok())
+                .map(Duration::from_millis),
+        )
+    }
+
+    fn usb_port_path(&self) -> Result<String> {
+        let usb_dev = unsafe { (*self.context.get_ftdi_context()).usb_dev };
+        if usb_dev.is_null() {
+            return Err(Error::InvalidArgument("The device is not open"));
+        }
+        Ok(usb_port_path(unsafe {
+            usbc::libusb_get_device(usb_dev as *mut _)
+        }))
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn read_power_attr(port_path: &str, attr: &str) -> Option<String> {
+    let path = format!("/sys/bus/usb/devices/{}/power/{}", port_path, attr);
+    std::fs::read_to_string(path).ok()
+}
+
+#[cfg(not(target_os = "linux"))]
+fn read_power_attr(_port_path: &str, _attr: &str) -> Option<String> {
+    None
+}
+
+#[cfg(target_os = "linux")]
+fn write_power_attr(port_path: &str, attr: &str, value: &str) -> Result<()> {
+    let path = format!("/sys/bus/usb/devices/{}/power/{}", port_path, attr);
+    Ok(std::fs::write(path, value)?)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn write_power_attr(_port_path: &str, _attr: &str, _value: &str) -> Result<()> {
+    Err(Error::InvalidArgument(
+        "Autosuspend can only be controlled on Linux",
+    ))
+}
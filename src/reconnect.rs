@@ -0,0 +1,191 @@
+//! Keeping a device usable across unplug/replug cycles.
+
+use std::thread;
+use std::time::{Duration, Instant};
+
+use error::Error;
+use {usb_device_gone, Device, Interface, Result};
+
+/// How often to look for the device while it is gone.
+const DEFAULT_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Idle time after which to check whether the device was autosuspended in between;
+/// hosts don't suspend devices that were used more recently.
+const RESUME_CHECK_IDLE: Duration = Duration::from_secs(1);
+
+/// How a [`ReconnectingDevice`] finds its device again after a disconnect.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DeviceMatch {
+    /// First device with the given vendor id, product id and serial.
+    Serial { vid: u16, pid: u16, serial: String },
+    /// Device attached at the given physical port, see [`Device::from_port_path`].
+    PortPath(String),
+}
+
+impl DeviceMatch {
+    fn open(&self, interface: Interface) -> Result<Device> {
+        match *self {
+            DeviceMatch::Serial {
+                vid,
+                pid,
+                ref serial,
+            } => Device::from_description_serial(interface, vid, pid, None, Some(serial)),
+            DeviceMatch::PortPath(ref path) => Device::from_port_path(interface, path),
+        }
+    }
+}
+
+/// A [`Device`] that reopens itself when it is disconnected.
+///
+/// Operations run through [`run`][ReconnectingDevice::run] that fail with
+/// [`Error::Disconnected`] wait for the device to reappear, reopen it, restore the
+/// settings made through its setters (see [`Device::config`]) and are then retried.
+///
+/// Likewise, when the device was autosuspended while idle (see
+/// [`Device::suspended_time`]), its settings are restored before the next operation,
+/// in case it was reset while resuming.
+pub struct ReconnectingDevice {
+    device: Device,
+    matcher: DeviceMatch,
+    interface: Interface,
+    poll_interval: Duration,
+    timeout: Option<Duration>,
+    // Last call to `set_autosuspend`, reapplied after reconnecting.
+    autosuspend: Option<bool>,
+    suspended_time: Option<Duration>,
+    last_used: Instant,
+}
+
+impl ReconnectingDevice {
+    /// Open the device described by `matcher`.
+    pub fn open(interface: Interface, matcher: DeviceMatch) -> Result<ReconnectingDevice> {
+        let device = matcher.open(interface)?;
+        Ok(ReconnectingDevice::new(device, matcher))
+    }
+
+    /// Wrap an already open device; `matcher` must describe the same device.
+    pub fn new(device: Device, matcher: DeviceMatch) -> ReconnectingDevice {
+        ReconnectingDevice {
+            interface: device.interface(),
+            suspended_time: device.suspended_time().unwrap_or(None),
+            device,
+            matcher,
+            poll_interval: DEFAULT_POLL_INTERVAL,
+            timeout: None,
+            autosuspend: None,
+            last_used: Instant::now(),
+        }
+    }
+
+    /// Give up reconnecting after `timeout`; by default, wait forever.
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.timeout = timeout;
+    }
+
+    /// Set how often to look for the device while it is gone.
+    pub fn set_poll_interval(&mut self, interval: Duration) {
+        self.poll_interval = interval;
+    }
+
+    /// See [`Device::set_autosuspend`]; kept across reconnects.
+    pub fn set_autosuspend(&mut self, enable: bool) -> Result<()> {
+        self.device.set_autosuspend(enable)?;
+        self.autosuspend = Some(enable);
+        Ok(())
+    }
+
+    pub fn device(&self) -> &Device {
+        &self.device
+    }
+
+    pub fn device_mut(&mut self) -> &mut Device {
+        &mut self.device
+    }
+
+    /// Run `f` on the device, reconnecting and retrying whenever it reports
+    /// [`Error::Disconnected`].
+    ///
+    /// `f` may run more than once, so it should be safe to repeat after a partial run.
+    pub fn run<T, F>(&mut self, mut f: F) -> Result<T>
+    where
+        F: FnMut(&mut Device) -> Result<T>,
+    {
+        if self.last_used.elapsed() >= RESUME_CHECK_IDLE {
+            self.check_resumed()?;
+        }
+        loop {
+            let res = f(&mut self.device);
+            self.last_used = Instant::now();
+            match res {
+                Err(Error::Disconnected) => self.reconnect()?,
+                res => return res,
+            }
+        }
+    }
+
+    // Restore the settings if the device was suspended since the last check.
+    fn check_resumed(&mut self) -> Result<()> {
+        let suspended_time = self.device.suspended_time().unwrap_or(None);
+        if suspended_time <= self.suspended_time {
+            return Ok(());
+        }
+        self.suspended_time = suspended_time;
+
+        #[cfg(feature = "tracing")]
+        tracing::debug!("device resumed from autosuspend, restoring settings");
+        match self.device.apply_config(&self.device.config()) {
+            Err(Error::Disconnected) => self.reconnect(),
+            res => res,
+        }
+    }
+
+    /// Wait for the device to reappear, reopen it and restore its settings.
+    ///
+    /// Returns [`Error::Disconnected`] if the timeout expires first.
+    pub fn reconnect(&mut self) -> Result<()> {
+        let config = self.device.config();
+        let start = Instant::now();
+
+        loop {
+            if let Ok(device) = self.matcher.open(self.interface) {
+                let restored = device
+                    .apply_config(&config)
+                    .and_then(|()| match self.autosuspend {
+                        Some(enable) => device.set_autosuspend(enable),
+                        None => Ok(()),
+                    });
+                match restored {
+                    Ok(()) => {
+                        self.suspended_time = device.suspended_time().unwrap_or(None);
+                        self.device = device;
+                        return Ok(());
+                    }
+                    // It dropped out again right after reopening; keep polling.
+                    Err(Error::Disconnected) => {}
+                    Err(e) => return Err(e),
+                }
+            }
+
+            if self.timeout.iter().any(|&t| start.elapsed() >= t) {
+                return Err(Error::Disconnected);
+            }
+            thread::sleep(self.poll_interval);
+        }
+    }
+
+    /// Read data, reconnecting if needed. See [`Device::read_data`].
+    pub fn read_data(&mut self, data: &mut [u8]) -> Result<usize> {
+        self.run(|dev| dev.read_data(data))
+    }
+
+    /// Write data, reconnecting if needed. See [`Device::write_data`].
+    pub fn write_data(&mut self, data: &[u8]) -> Result<usize> {
+        self.run(|dev| match dev.write_data(data) {
+            // libftdi reports failed writes without the libusb error, so make sure a
+            // device that went away leads to a reconnect whatever the error says.
+            Err(Error::Cancelled) => Err(Error::Cancelled),
+            Err(_) if usb_device_gone(dev.context.get_ftdi_context()) => Err(Error::Disconnected),
+            res => res,
+        })
+    }
+}
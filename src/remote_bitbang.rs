@@ -0,0 +1,136 @@
+//! Server for OpenOCD's `remote_bitbang` JTAG adapter protocol, driving the JTAG
+//! signals on pins of any [`Gpio`] device.
+//!
+//! Each command is a single ASCII character: `0`..`7` set TCK, TMS and TDI (bits 2,
+//! 1 and 0), `R` reads TDO and answers `0` or `1`, `r`..`u` set TRST and SRST
+//! (bits 1 and 0, asserted when set), `B`/`b` switch an activity LED and `Q` ends
+//! the session. OpenOCD connects with e.g.
+//! `adapter driver remote_bitbang; remote_bitbang port 3335`.
+
+use std::io::{BufReader, Read, Write};
+use std::net::TcpListener;
+#[cfg(unix)]
+use std::os::unix::net::UnixListener;
+
+use error::Error;
+use gpio::Gpio;
+use {client_done, Result};
+
+/// Which pins carry the JTAG signals. The reset lines are active LOW.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct JtagPins {
+    pub tck: u8,
+    pub tms: u8,
+    pub tdi: u8,
+    pub tdo: u8,
+    pub trst: Option<u8>,
+    pub srst: Option<u8>,
+}
+
+/// Serves `remote_bitbang` clients one at a time.
+pub struct RemoteBitbangServer<'a, G: 'a> {
+    device: &'a G,
+    pins: JtagPins,
+}
+
+impl<'a, G: Gpio> RemoteBitbangServer<'a, G> {
+    /// Make the JTAG signals outputs, except TDO, with the reset lines released.
+    pub fn new(device: &'a G, pins: JtagPins) -> Result<RemoteBitbangServer<'a, G>> {
+        let all = [
+            Some(pins.tck),
+            Some(pins.tms),
+            Some(pins.tdi),
+            Some(pins.tdo),
+            pins.trst,
+            pins.srst,
+        ];
+        if all.iter().flatten().any(|&pin| pin >= device.pin_count()) {
+            return Err(Error::InvalidArgument("Device has no such pin"));
+        }
+
+        let resets = bit(pins.trst) | bit(pins.srst);
+        let outputs = bit(Some(pins.tck)) | bit(Some(pins.tms)) | bit(Some(pins.tdi)) | resets;
+        let port = device.port();
+        port.write_masked(resets, outputs)?;
+        port.set_direction((port.direction() | outputs) & !bit(Some(pins.tdo)))?;
+        Ok(RemoteBitbangServer { device, pins })
+    }
+
+    /// Accept clients on `listener` and serve each until it disconnects.
+    ///
+    /// A client that fails is dropped (and logged with the `tracing` feature) and the
+    /// next one accepted. Only returns on an error accepting clients, or with
+    /// [`Error::Disconnected`] once the device is gone.
+    pub fn serve(&self, listener: &TcpListener) -> Result<()> {
+        loop {
+            let (stream, _) = listener.accept()?;
+            let res = stream
+                .set_nodelay(true)
+                .map_err(Error::from)
+                .and_then(|()| self.handle(&stream));
+            client_done("remote bitbang", res)?;
+        }
+    }
+
+    /// Like [`serve`][RemoteBitbangServer::serve], on a Unix domain socket.
+    #[cfg(unix)]
+    pub fn serve_unix(&self, listener: &UnixListener) -> Result<()> {
+        loop {
+            let (stream, _) = listener.accept()?;
+            client_done("remote bitbang", self.handle(&stream))?;
+        }
+    }
+
+    /// Serve one client until it quits or disconnects.
+    pub fn handle<S: Read + Write>(&self, stream: S) -> Result<()> {
+        let mut stream = BufReader::new(stream);
+        let mut command = [0];
+        while stream.read(&mut command)? > 0 {
+            let command = command[0];
+            match command {
+                b'0'..=b'7' => {
+                    let value = command - b'0';
+                    let levels = level(self.pins.tck, value & 0x04 != 0)
+                        | level(self.pins.tms, value & 0x02 != 0)
+                        | level(self.pins.tdi, value & 0x01 != 0);
+                    let mask = bit(Some(self.pins.tck))
+                        | bit(Some(self.pins.tms))
+                        | bit(Some(self.pins.tdi));
+                    self.device.port().write_masked(levels, mask)?;
+                }
+                b'R' => {
+                    let high = self.device.pin(self.pins.tdo).is_high()?;
+                    stream.get_mut().write_all(if high { b"1" } else { b"0" })?;
+                }
+                b'r'..=b'u' => {
+                    let value = command - b'r';
+                    let mut levels = 0;
+                    let mut mask = 0;
+                    if let Some(trst) = self.pins.trst {
+                        levels |= level(trst, value & 0x02 == 0);
+                        mask |= bit(Some(trst));
+                    }
+                    if let Some(srst) = self.pins.srst {
+                        levels |= level(srst, value & 0x01 == 0);
+                        mask |= bit(Some(srst));
+                    }
+                    self.device.port().write_masked(levels, mask)?;
+                }
+                b'B' | b'b' => {}
+                b'Q' => return Ok(()),
+                // OpenOCD may send line breaks between commands.
+                b'\n' | b'\r' => {}
+                _ => return Err(Error::InvalidArgument("Unknown remote_bitbang command")),
+            }
+        }
+        Ok(())
+    }
+}
+
+fn bit(pin: Option<u8>) -> u16 {
+    pin.map_or(0, |pin| 1 << pin)
+}
+
+fn level(pin: u8, high: bool) -> u16 {
+    (high as u16) << pin
+}
@@ -0,0 +1,160 @@
+//! Async transfers on borrowed buffers, guaranteed to finish before the buffers go
+//! away.
+//!
+//! [`Device::read_data_async`] is `unsafe`, leaving it to the caller to wait for the
+//! transfer before touching or freeing the buffer. Inside [`Device::transfer_scope`],
+//! buffers are borrowed for the whole scope, and the scope waits for every transfer
+//! still in flight when it ends, even when unwinding from a panic.
+
+use std::cell::RefCell;
+use std::marker::PhantomData;
+use std::time::Duration;
+
+use capture::Direction;
+use error::Error;
+use ftdic;
+use {Device, Result, MAX_TRANSFER_SIZE};
+
+/// A transfer submitted in a [`TransferScope`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct TransferHandle(usize);
+
+// A transfer in flight, with the buffer to log once it completes.
+struct InFlight {
+    transfer_control: *mut ftdic::ftdi_transfer_control,
+    direction: Direction,
+    buf: *const u8,
+}
+
+/// Submits transfers borrowing buffers for `'env`, see [`Device::transfer_scope`].
+pub struct TransferScope<'a, 'env> {
+    device: &'a Device,
+    // Transfers by handle; `None` once waited for or cancelled.
+    transfers: RefCell<Vec<Option<InFlight>>>,
+    // Invariant, so buffers can't be borrowed for less than the scope.
+    phantom: PhantomData<&'env mut &'env ()>,
+}
+
+impl Device {
+    /// Run `f` with a scope for submitting async transfers on borrowed buffers
+    ///
+    /// Returns the result of `f`, or the first error of the transfers the scope had
+    /// to wait for at its end; wait for transfers in `f` to handle their errors there.
+    pub fn transfer_scope<'env, F, R>(&self, f: F) -> Result<R>
+    where
+        F: FnOnce(&TransferScope<'_, 'env>) -> R,
+    {
+        let scope = TransferScope {
+            device: self,
+            transfers: RefCell::new(Vec::new()),
+            phantom: PhantomData,
+        };
+        let result = f(&scope);
+        scope.finish()?;
+        Ok(result)
+    }
+}
+
+impl<'a, 'env> TransferScope<'a, 'env> {
+    /// Start reading into `buf`, see [`Device::read_data_async`].
+    ///
+    /// Only one read can be in flight at a time, since libftdi passes every read
+    /// through the same buffer of the context; wait for or cancel the previous one
+    /// first. Writes aren't limited.
+    pub fn read(&self, buf: &'env mut [u8]) -> Result<TransferHandle> {
+        check_len(buf.len())?;
+        let reading = self
+            .transfers
+            .borrow()
+            .iter()
+            .flatten()
+            .any(|transfer| transfer.direction == Direction::Read);
+        if reading {
+            return Err(Error::InvalidArgument(
+                "A read is already in flight in this scope",
+            ));
+        }
+        let transfer_control = self.device.submit_read(buf.as_mut_ptr(), buf.len())?;
+        Ok(self.add(InFlight {
+            transfer_control,
+            direction: Direction::Read,
+            buf: buf.as_ptr(),
+        }))
+    }
+
+    /// Start writing `buf`, see [`Device::write_data_async`].
+    pub fn write(&self, buf: &'env [u8]) -> Result<TransferHandle> {
+        check_len(buf.len())?;
+        let transfer_control = self.device.submit_write(buf.as_ptr(), buf.len())?;
+        Ok(self.add(InFlight {
+            transfer_control,
+            direction: Direction::Write,
+            buf: buf.as_ptr(),
+        }))
+    }
+
+    /// Wait for a transfer, returning the number of bytes transferred.
+    pub fn wait(&self, handle: TransferHandle) -> Result<usize> {
+        self.complete(self.take(handle)?)
+    }
+
+    /// Cancel a transfer and wait for its completion.
+    pub fn cancel(&self, handle: TransferHandle, timeout: Duration) -> Result<()> {
+        let transfer = self.take(handle)?;
+        self.device
+            .cancel_transfer(transfer.transfer_control, transfer.direction, timeout);
+        Ok(())
+    }
+
+    fn add(&self, transfer: InFlight) -> TransferHandle {
+        let mut transfers = self.transfers.borrow_mut();
+        transfers.push(Some(transfer));
+        TransferHandle(transfers.len() - 1)
+    }
+
+    fn take(&self, handle: TransferHandle) -> Result<InFlight> {
+        self.transfers
+            .borrow_mut()
+            .get_mut(handle.0)
+            .and_then(Option::take)
+            .ok_or(Error::InvalidArgument("Transfer already finished"))
+    }
+
+    // Wait for the transfers in flight, reporting the first failure.
+    fn finish(&self) -> Result<()> {
+        let mut result = Ok(());
+        for transfer in self
+            .transfers
+            .borrow_mut()
+            .iter_mut()
+            .filter_map(Option::take)
+        {
+            let done = self.complete(transfer);
+            if result.is_ok() {
+                result = done.map(|_| ());
+            }
+        }
+        result
+    }
+
+    fn complete(&self, transfer: InFlight) -> Result<usize> {
+        self.device
+            .complete_transfer(transfer.transfer_control, transfer.direction, transfer.buf)
+    }
+}
+
+impl<'a, 'env> Drop for TransferScope<'a, 'env> {
+    fn drop(&mut self) {
+        // Only has work left when unwinding out of the scope.
+        let _ = self.finish();
+    }
+}
+
+fn check_len(len: usize) -> Result<()> {
+    if len > MAX_TRANSFER_SIZE {
+        return Err(Error::InvalidArgument(
+            "Buffer too large for a single transfer",
+        ));
+    }
+    Ok(())
+}
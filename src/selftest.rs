@@ -0,0 +1,170 @@
+//! One-call sanity checks of the data path, for factory test fixtures and board
+//! bring-up.
+
+use std::thread;
+use std::time::{Duration, Instant};
+
+use error::Error;
+use mpsse::{Commands, LOOPBACK_END, LOOPBACK_START, LSB, WRITE_NEG};
+use {BitMode, Device, Result};
+
+// Largest amount of data shifted per MPSSE round trip, small enough to fit the
+// response buffer of every MPSSE-capable chip.
+const MPSSE_CHUNK: usize = 128;
+
+/// Where the test data is looped back.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LoopbackPath {
+    /// Through a jumper from TX to RX, in the current (UART or FIFO) mode and at the
+    /// current baud rate.
+    External,
+    /// Inside the chip, from the MPSSE data output to its input. The device is put
+    /// into MPSSE mode for the test and back into its previous mode afterwards.
+    Mpsse,
+}
+
+/// Parameters of [`Device::loopback_test`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct LoopbackConfig {
+    pub path: LoopbackPath,
+    /// Total number of bytes sent.
+    pub len: usize,
+    /// Bytes sent before waiting for them to come back.
+    pub chunk_size: usize,
+    /// Seed of the pseudo-random test data.
+    pub seed: u32,
+    /// How long to wait for a chunk to come back before counting it as lost.
+    pub timeout: Duration,
+}
+
+impl Default for LoopbackConfig {
+    fn default() -> LoopbackConfig {
+        LoopbackConfig {
+            path: LoopbackPath::External,
+            len: 64 * 1024,
+            chunk_size: 4096,
+            seed: 1,
+            timeout: Duration::from_secs(1),
+        }
+    }
+}
+
+/// Result of [`Device::loopback_test`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct LoopbackReport {
+    pub sent: usize,
+    pub received: usize,
+    /// Received bytes that differ from the ones sent.
+    pub errors: usize,
+    pub elapsed: Duration,
+}
+
+impl LoopbackReport {
+    /// Whether every byte came back intact.
+    pub fn passed(&self) -> bool {
+        self.errors == 0 && self.received == self.sent
+    }
+
+    /// Bytes looped back per second.
+    pub fn throughput(&self) -> f64 {
+        self.received as f64 / self.elapsed.as_secs_f64().max(f64::MIN_POSITIVE)
+    }
+}
+
+impl Device {
+    /// Send pseudo-random data along `config.path`, read it back and compare.
+    ///
+    /// Lost bytes show up as `received < sent` rather than as an error, so a report is
+    /// returned even when nothing is wired up.
+    pub fn loopback_test(&self, config: &LoopbackConfig) -> Result<LoopbackReport> {
+        if config.chunk_size == 0 {
+            return Err(Error::InvalidArgument("Chunk size must not be zero"));
+        }
+
+        let mut rng = config.seed.max(1);
+        let data: Vec<u8> = (0..config.len).map(|_| xorshift(&mut rng)).collect();
+
+        let start = Instant::now();
+        let (received, errors) = match config.path {
+            LoopbackPath::External => {
+                self.purge_usb_buffers()?;
+                self.loop_external(&data, config)?
+            }
+            LoopbackPath::Mpsse => {
+                let previous = self.bitmode();
+                self.set_bitmode(0, BitMode::Mpsse)?;
+                // Drop anything left over from before MPSSE mode so it isn't compared.
+                self.purge_usb_rx_buffer()?;
+                self.write_data(&[LOOPBACK_START])?;
+                let res = self.loop_mpsse(&data, config);
+                let restored = self
+                    .write_data(&[LOOPBACK_END])
+                    .and_then(|_| match previous {
+                        Some(previous) => self.set_bitmode_config(previous),
+                        None => self.set_bitmode(0, BitMode::Reset),
+                    });
+                let counts = res?;
+                restored?;
+                counts
+            }
+        };
+
+        Ok(LoopbackReport {
+            sent: data.len(),
+            received,
+            errors,
+            elapsed: start.elapsed(),
+        })
+    }
+
+    // Returns the number of bytes received and how many of them were wrong.
+    fn loop_external(&self, data: &[u8], config: &LoopbackConfig) -> Result<(usize, usize)> {
+        let (mut received, mut errors) = (0, 0);
+        let mut buf = vec![0; config.chunk_size];
+        for chunk in data.chunks(config.chunk_size) {
+            let mut written = 0;
+            while written < chunk.len() {
+                written += self.write_data(&chunk[written..])?;
+            }
+
+            let mut filled = 0;
+            let deadline = Instant::now() + config.timeout;
+            while filled < chunk.len() && Instant::now() < deadline {
+                let n = self.read_data(&mut buf[filled..chunk.len()])?;
+                if n == 0 {
+                    thread::sleep(Duration::from_millis(1));
+                }
+                filled += n;
+            }
+            received += filled;
+            errors += mismatches(&chunk[..filled], &buf[..filled]);
+        }
+        Ok((received, errors))
+    }
+
+    fn loop_mpsse(&self, data: &[u8], config: &LoopbackConfig) -> Result<(usize, usize)> {
+        let (mut received, mut errors) = (0, 0);
+        let mut buf = [0; MPSSE_CHUNK];
+        for chunk in data.chunks(config.chunk_size.min(MPSSE_CHUNK)) {
+            let commands = Commands::new()
+                .transfer_bytes(LSB | WRITE_NEG, chunk)
+                .send_immediate();
+            self.write_data(commands.as_bytes())?;
+            self.read_exact(&mut buf[..chunk.len()])?;
+            received += chunk.len();
+            errors += mismatches(chunk, &buf[..chunk.len()]);
+        }
+        Ok((received, errors))
+    }
+}
+
+fn mismatches(sent: &[u8], received: &[u8]) -> usize {
+    sent.iter().zip(received).filter(|(a, b)| a != b).count()
+}
+
+fn xorshift(state: &mut u32) -> u8 {
+    *state ^= *state << 13;
+    *state ^= *state >> 17;
+    *state ^= *state << 5;
+    *state as u8
+}
@@ -0,0 +1,58 @@
+//! A device usable from several threads.
+
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use Device;
+
+/// A [`Device`] behind a lock, cheaply cloneable and shareable between threads.
+///
+/// Each call to [`lock`][SharedDevice::lock] gets exclusive access to the device, so
+/// a sequence of commands issued while holding the guard isn't interleaved with
+/// other threads' commands.
+#[derive(Clone)]
+pub struct SharedDevice {
+    device: Arc<Mutex<Device>>,
+}
+
+impl SharedDevice {
+    pub fn new(device: Device) -> SharedDevice {
+        SharedDevice {
+            device: Arc::new(Mutex::new(device)),
+        }
+    }
+
+    /// Wait for exclusive access to the device.
+    pub fn lock(&self) -> MutexGuard<'_, Device> {
+        // A thread panicking mid-command leaves nothing for us to repair in the
+        // device handle itself, so ignore poisoning.
+        self.device.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Run `f` with exclusive access to the device.
+    pub fn with<T, F: FnOnce(&mut Device) -> T>(&self, f: F) -> T {
+        f(&mut self.lock())
+    }
+
+    /// Get the device back if this is the last handle to it.
+    pub fn try_unwrap(self) -> Result<Device, SharedDevice> {
+        match Arc::try_unwrap(self.device) {
+            Ok(mutex) => Ok(mutex.into_inner().unwrap_or_else(|e| e.into_inner())),
+            Err(device) => Err(SharedDevice { device }),
+        }
+    }
+}
+
+impl From<Device> for SharedDevice {
+    fn from(device: Device) -> SharedDevice {
+        SharedDevice::new(device)
+    }
+}
+
+// Fail the build if the thread-safety promised in the docs is ever lost.
+#[allow(dead_code)]
+fn assert_thread_safety() {
+    fn send<T: Send>() {}
+    fn send_sync<T: Send + Sync>() {}
+    send::<Device>();
+    send_sync::<SharedDevice>();
+}
@@ -0,0 +1,214 @@
+//! SPI master interface shared by the bitbang and MPSSE implementations.
+
+use std::cell::{Cell, RefCell};
+use std::thread;
+use std::time::Duration;
+
+use Result;
+
+/// Clock polarity and phase, numbered as usual.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SpiMode {
+    /// Clock idles LOW, data is sampled on the rising edge.
+    Mode0,
+    /// Clock idles LOW, data is sampled on the falling edge.
+    Mode1,
+    /// Clock idles HIGH, data is sampled on the falling edge.
+    Mode2,
+    /// Clock idles HIGH, data is sampled on the rising edge.
+    Mode3,
+}
+
+impl SpiMode {
+    /// Whether the clock idles HIGH.
+    pub fn cpol(self) -> bool {
+        match self {
+            SpiMode::Mode0 | SpiMode::Mode1 => false,
+            SpiMode::Mode2 | SpiMode::Mode3 => true,
+        }
+    }
+
+    /// Whether data is sampled on the trailing rather than the leading clock edge.
+    pub fn cpha(self) -> bool {
+        match self {
+            SpiMode::Mode0 | SpiMode::Mode2 => false,
+            SpiMode::Mode1 | SpiMode::Mode3 => true,
+        }
+    }
+}
+
+/// Order in which the bits of each byte are shifted.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum BitOrder {
+    MsbFirst,
+    LsbFirst,
+}
+
+impl BitOrder {
+    /// Bit positions of a byte, in the order they are shifted.
+    pub fn positions(self) -> [u8; 8] {
+        match self {
+            BitOrder::MsbFirst => [7, 6, 5, 4, 3, 2, 1, 0],
+            BitOrder::LsbFirst => [0, 1, 2, 3, 4, 5, 6, 7],
+        }
+    }
+}
+
+/// The clock and data lines of an SPI master, with any number of chip selects.
+///
+/// To share one bus between several devices, see [`SharedSpiBus`].
+pub trait SpiBus {
+    /// Make GPIO pin `pin` a chip select output and drive it, LOW when `active`.
+    fn set_cs(&mut self, pin: u8, active: bool) -> Result<()>;
+
+    /// Clock out `data`, ignoring what the device sends back.
+    fn write(&mut self, data: &[u8]) -> Result<()>;
+
+    /// Clock in `data.len()` bytes while sending zeros.
+    fn read(&mut self, data: &mut [u8]) -> Result<()>;
+
+    /// Clock out `data` and replace it with the bytes received at the same time.
+    fn transfer(&mut self, data: &mut [u8]) -> Result<()>;
+}
+
+/// An SPI bus with the chip select line of one device.
+///
+/// Chip select stays asserted between [`select`][Spi::select] and
+/// [`deselect`][Spi::deselect], so a command and its response can be split across
+/// several calls.
+pub trait Spi: SpiBus {
+    /// Assert chip select.
+    fn select(&mut self) -> Result<()>;
+
+    /// Release chip select.
+    fn deselect(&mut self) -> Result<()>;
+
+    /// Run a complete command: select, write `command`, read the response into
+    /// `response` and deselect, even if a step fails.
+    fn command(&mut self, command: &[u8], response: &mut [u8]) -> Result<()> {
+        self.select()?;
+        let res = self.write(command).and_then(|_| {
+            if response.is_empty() {
+                Ok(())
+            } else {
+                self.read(response)
+            }
+        });
+        let deselected = self.deselect();
+        res.and(deselected)
+    }
+}
+
+/// An [`SpiBus`] shared by several devices, each with its own chip select.
+///
+/// At most one device is selected at a time: selecting one releases the chip
+/// select of any other first.
+pub struct SharedSpiBus<B> {
+    bus: RefCell<B>,
+    selected: Cell<Option<u8>>,
+}
+
+impl<B: SpiBus> SharedSpiBus<B> {
+    pub fn new(bus: B) -> SharedSpiBus<B> {
+        SharedSpiBus {
+            bus: RefCell::new(bus),
+            selected: Cell::new(None),
+        }
+    }
+
+    /// Handle to the device whose chip select is GPIO pin `cs`, which is released.
+    pub fn device(&self, cs: u8) -> Result<SpiDeviceHandle<'_, B>> {
+        self.bus.borrow_mut().set_cs(cs, false)?;
+        Ok(SpiDeviceHandle {
+            bus: self,
+            cs,
+            setup: Duration::from_secs(0),
+            hold: Duration::from_secs(0),
+        })
+    }
+
+    /// Return the bus. Chip selects are left as they are.
+    pub fn into_inner(self) -> B {
+        self.bus.into_inner()
+    }
+
+    fn select(&self, cs: u8) -> Result<()> {
+        let mut bus = self.bus.borrow_mut();
+        if let Some(other) = self.selected.get() {
+            if other != cs {
+                bus.set_cs(other, false)?;
+            }
+        }
+        self.selected.set(None);
+        bus.set_cs(cs, true)?;
+        self.selected.set(Some(cs));
+        Ok(())
+    }
+
+    fn deselect(&self, cs: u8) -> Result<()> {
+        self.bus.borrow_mut().set_cs(cs, false)?;
+        if self.selected.get() == Some(cs) {
+            self.selected.set(None);
+        }
+        Ok(())
+    }
+}
+
+/// One device on a [`SharedSpiBus`].
+pub struct SpiDeviceHandle<'a, B: 'a> {
+    bus: &'a SharedSpiBus<B>,
+    cs: u8,
+    setup: Duration,
+    hold: Duration,
+}
+
+impl<'a, B: SpiBus> SpiDeviceHandle<'a, B> {
+    /// The chip select pin.
+    pub fn cs(&self) -> u8 {
+        self.cs
+    }
+
+    /// Wait at least `setup` after asserting chip select before clocking data, and
+    /// `hold` after the last transfer before releasing it.
+    ///
+    /// The delays are timed on the host, on top of USB latency.
+    pub fn set_cs_delays(&mut self, setup: Duration, hold: Duration) {
+        self.setup = setup;
+        self.hold = hold;
+    }
+}
+
+impl<'a, B: SpiBus> SpiBus for SpiDeviceHandle<'a, B> {
+    fn set_cs(&mut self, pin: u8, active: bool) -> Result<()> {
+        if active {
+            self.bus.select(pin)
+        } else {
+            self.bus.deselect(pin)
+        }
+    }
+
+    fn write(&mut self, data: &[u8]) -> Result<()> {
+        self.bus.bus.borrow_mut().write(data)
+    }
+
+    fn read(&mut self, data: &mut [u8]) -> Result<()> {
+        self.bus.bus.borrow_mut().read(data)
+    }
+
+    fn transfer(&mut self, data: &mut [u8]) -> Result<()> {
+        self.bus.bus.borrow_mut().transfer(data)
+    }
+}
+
+impl<'a, B: SpiBus> Spi for SpiDeviceHandle<'a, B> {
+    fn select(&mut self) -> Result<()> {
+        self.bus.select(self.cs)?;
+        thread::sleep(self.setup);
+        Ok(())
+    }
+
+    fn deselect(&mut self) -> Result<()> {
+        thread::sleep(self.hold);
+        self.bus.deselect(self.cs)
+    }
+}
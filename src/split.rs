@@ -0,0 +1,89 @@
+//! Reading and writing a device from different threads.
+
+use std::sync::Arc;
+
+use {Device, Result};
+
+/// Receiving half of a [`Device`], see [`Device::split`].
+pub struct Reader {
+    device: Arc<Device>,
+}
+
+/// Transmitting half of a [`Device`], see [`Device::split`].
+pub struct Writer {
+    device: Arc<Device>,
+}
+
+// The halves only use the read and write paths of the context, which touch disjoint
+// parts of it (the read buffer and the IN endpoint vs. the OUT endpoint), and libusb
+// allows transfers on different endpoints of a handle to run concurrently. Neither
+// half changes settings, so the device's config Cell is never touched; the cancel
+// token and capture are behind mutexes. The one field both paths store to is libftdi's
+// error string, on failure, and the halves never read it back: writes and purges
+// report fixed messages, and reads report libusb's error codes.
+unsafe impl Send for Reader {}
+unsafe impl Send for Writer {}
+
+impl Device {
+    /// Split the device into halves that can read and write concurrently, e.g. on
+    /// two threads of a full-duplex bridge.
+    ///
+    /// Settings can't be changed while split; use [`Reader::unsplit`] to get the
+    /// device back.
+    // The halves are made Send above; the Arc itself never needs to be.
+    #[allow(clippy::arc_with_non_send_sync)]
+    pub fn split(self) -> (Reader, Writer) {
+        let device = Arc::new(self);
+        (
+            Reader {
+                device: device.clone(),
+            },
+            Writer { device },
+        )
+    }
+}
+
+impl Reader {
+    /// See [`Device::read_data`].
+    pub fn read_data(&self, data: &mut [u8]) -> Result<usize> {
+        self.device.read_data(data)
+    }
+
+    /// See [`Device::purge_usb_rx_buffer`].
+    pub fn purge_usb_rx_buffer(&self) -> Result<()> {
+        self.device.purge(true, false)
+    }
+
+    /// Whether `writer` is the other half of the same device.
+    pub fn is_pair_of(&self, writer: &Writer) -> bool {
+        Arc::ptr_eq(&self.device, &writer.device)
+    }
+
+    /// Join the halves back into the original device.
+    ///
+    /// Panics if `writer` came from a different device, see [`is_pair_of`][Reader::is_pair_of].
+    pub fn unsplit(self, writer: Writer) -> Device {
+        assert!(
+            self.is_pair_of(&writer),
+            "unsplit() of halves of different devices"
+        );
+
+        drop(writer);
+        match Arc::try_unwrap(self.device) {
+            Ok(device) => device,
+            Err(_) => unreachable!("halves are the only references to the device"),
+        }
+    }
+}
+
+impl Writer {
+    /// See [`Device::write_data`].
+    pub fn write_data(&self, data: &[u8]) -> Result<usize> {
+        self.device.write_data(data)
+    }
+
+    /// See [`Device::purge_usb_tx_buffer`].
+    pub fn purge_usb_tx_buffer(&self) -> Result<()> {
+        self.device.purge(false, true)
+    }
+}
@@ -0,0 +1,125 @@
+//! Continuous output with several write transfers in flight.
+//!
+//! A single blocking write leaves the bus idle between the end of one transfer and
+//! the submission of the next, which is enough to underrun a FIFO feeding a DAC or an
+//! LED strip. A [`StreamWriter`] keeps up to a fixed number of transfers queued with
+//! libusb instead, and only blocks once that many are in flight.
+//!
+//! Writers are only lent out by [`Device::stream_writer`], which waits for every
+//! transfer before returning, so none can outlive the device.
+
+use std::collections::VecDeque;
+
+use capture::Direction;
+use error::{Error, LibUsbError};
+use ftdic;
+use {Device, Result, MAX_TRANSFER_SIZE};
+
+struct InFlight {
+    // Kept alive until the transfer completes.
+    buf: Vec<u8>,
+    transfer_control: *mut ftdic::ftdi_transfer_control,
+}
+
+/// Streams writes to a device, see the [module documentation][self].
+pub struct StreamWriter<'a> {
+    device: &'a Device,
+    depth: usize,
+    in_flight: VecDeque<InFlight>,
+    // Buffers of completed transfers, for reuse.
+    spare: Vec<Vec<u8>>,
+}
+
+impl Device {
+    /// Run `f` with a [`StreamWriter`] keeping up to `depth` transfers in flight, then
+    /// wait for the transfers still in flight
+    ///
+    /// Returns the result of `f`, or the first error of those last transfers. They are
+    /// waited for even if `f` fails or panics.
+    ///
+    /// Panics if `depth` is 0.
+    pub fn stream_writer<F, R>(&self, depth: usize, f: F) -> Result<R>
+    where
+        F: FnOnce(&mut StreamWriter<'_>) -> Result<R>,
+    {
+        assert!(depth > 0, "At least one transfer must be in flight");
+        let mut writer = StreamWriter {
+            device: self,
+            depth,
+            in_flight: VecDeque::with_capacity(depth),
+            spare: Vec::new(),
+        };
+        let result = f(&mut writer)?;
+        writer.flush()?;
+        Ok(result)
+    }
+}
+
+impl<'a> StreamWriter<'a> {
+    /// Submit a copy of `data`, first waiting for the oldest transfer to complete if
+    /// `depth` transfers are in flight.
+    ///
+    /// Fails if that transfer failed or came up short.
+    pub fn write(&mut self, data: &[u8]) -> Result<()> {
+        for chunk in data.chunks(MAX_TRANSFER_SIZE) {
+            if self.in_flight.len() == self.depth {
+                self.complete_oldest()?;
+            }
+
+            let mut buf = self.spare.pop().unwrap_or_default();
+            buf.clear();
+            buf.extend_from_slice(chunk);
+            let transfer_control = self.device.submit_write(buf.as_ptr(), buf.len())?;
+            self.in_flight.push_back(InFlight {
+                buf,
+                transfer_control,
+            });
+        }
+        Ok(())
+    }
+
+    /// Number of transfers submitted but not completed yet.
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.len()
+    }
+
+    /// Wait for all transfers in flight.
+    pub fn flush(&mut self) -> Result<()> {
+        while !self.in_flight.is_empty() {
+            self.complete_oldest()?;
+        }
+        Ok(())
+    }
+
+    fn complete_oldest(&mut self) -> Result<()> {
+        let transfer = match self.in_flight.pop_front() {
+            Some(transfer) => transfer,
+            None => return Ok(()),
+        };
+        let written = self.device.complete_transfer(
+            transfer.transfer_control,
+            Direction::Write,
+            transfer.buf.as_ptr(),
+        )?;
+        let complete = written == transfer.buf.len();
+        self.spare.push(transfer.buf);
+        if !complete {
+            return Err(Error::LibUsb(LibUsbError::Timeout));
+        }
+        Ok(())
+    }
+}
+
+impl<'a> Drop for StreamWriter<'a> {
+    fn drop(&mut self) {
+        // Only has work left when `f` failed or panicked. libusb may still read from
+        // the buffers; errors can't be reported here.
+        for transfer in self.in_flight.drain(..) {
+            let _ = self.device.complete_transfer(
+                transfer.transfer_control,
+                Direction::Write,
+                transfer.buf.as_ptr(),
+            );
+        }
+    }
+}
@@ -0,0 +1,205 @@
+//! ARM Serial Wire Debug on the MPSSE, for Cortex-M targets without JTAG.
+//!
+//! SWDIO is bidirectional, so it is wired to two pins: ADBUS1 drives it through a
+//! resistor (around 470 Ω) and ADBUS2 reads it directly. ADBUS1 is made an input
+//! whenever the target drives the line.
+
+use error::{Error, SwdFault};
+use mode::MpsseDevice;
+use mpsse::{Commands, LSB, WRITE_NEG};
+use Result;
+
+/// SWCLK, ADBUS0.
+pub const SWCLK: u8 = 0;
+/// SWDIO output, ADBUS1.
+pub const SWDIO_OUT: u8 = 1;
+/// SWDIO input, ADBUS2.
+pub const SWDIO_IN: u8 = 2;
+
+// SWDIO changes on the falling edge and is sampled on the rising one.
+const FLAGS: u8 = LSB | WRITE_NEG;
+
+const ACK_OK: u8 = 0b001;
+const ACK_WAIT: u8 = 0b010;
+const ACK_FAULT: u8 = 0b100;
+
+/// Number of times a transfer answered with WAIT is retried by default.
+pub const DEFAULT_WAIT_RETRIES: usize = 100;
+
+/// Debug port register holding the identification of the debug port.
+pub const DP_DPIDR: u8 = 0x0;
+/// Debug port register clearing sticky errors when written.
+pub const DP_ABORT: u8 = 0x0;
+/// Debug port control and status register.
+pub const DP_CTRL_STAT: u8 = 0x4;
+/// Debug port register selecting the access port and its register bank.
+pub const DP_SELECT: u8 = 0x8;
+/// Debug port register holding the result of the last access port read.
+pub const DP_RDBUFF: u8 = 0xC;
+
+/// An SWD host on ADBUS0..2.
+pub struct Swd<'a> {
+    device: &'a MpsseDevice,
+    retries: usize,
+}
+
+impl<'a> Swd<'a> {
+    /// Claim the SWD pins and set SWCLK as close to `frequency` Hz as possible. The
+    /// other GPIO pins keep their levels and directions.
+    ///
+    /// Call [`connect`][Swd::connect] to switch the target to SWD.
+    pub fn new(device: &'a MpsseDevice, frequency: u32) -> Result<Swd<'a>> {
+        let levels = device.gpio_output_latch() & !0x07;
+        let directions = (device.gpio_direction() & !0x07) | (1 << SWCLK) | (1 << SWDIO_OUT);
+        device.set_gpio(levels, directions)?;
+        device.set_clock_frequency(frequency)?;
+
+        Ok(Swd {
+            device,
+            retries: DEFAULT_WAIT_RETRIES,
+        })
+    }
+
+    /// Retry a transfer answered with WAIT up to `retries` times before failing with
+    /// [`SwdFault::Wait`].
+    pub fn set_wait_retries(&mut self, retries: usize) {
+        self.retries = retries;
+    }
+
+    /// Clock SWDIO HIGH for more than 50 cycles, followed by idle cycles.
+    pub fn line_reset(&self) -> Result<()> {
+        self.device
+            .execute(&line_reset(Commands::new()))
+            .map(|_| ())
+    }
+
+    /// Send the JTAG-to-SWD switch sequence, surrounded by line resets.
+    pub fn jtag_to_swd(&self) -> Result<()> {
+        let commands = line_reset(Commands::new()).write_bytes(FLAGS, &[0x9e, 0xe7]);
+        self.device.execute(&line_reset(commands)).map(|_| ())
+    }
+
+    /// Switch the target to SWD and return its DPIDR, which must be read before the
+    /// debug port accepts other transfers.
+    pub fn connect(&self) -> Result<u32> {
+        self.jtag_to_swd()?;
+        self.read_dp(DP_DPIDR)
+    }
+
+    /// Read debug port register `address`.
+    pub fn read_dp(&self, address: u8) -> Result<u32> {
+        self.read(false, address)
+    }
+
+    /// Write `value` to debug port register `address`.
+    pub fn write_dp(&self, address: u8, value: u32) -> Result<()> {
+        self.write(false, address, value)
+    }
+
+    /// Read register `address` of the access port chosen in [`DP_SELECT`].
+    ///
+    /// Access port reads are posted: this returns the result of the previous one, and
+    /// the result of the last is read from [`DP_RDBUFF`].
+    pub fn read_ap(&self, address: u8) -> Result<u32> {
+        self.read(true, address)
+    }
+
+    /// Write `value` to register `address` of the access port chosen in
+    /// [`DP_SELECT`].
+    pub fn write_ap(&self, address: u8, value: u32) -> Result<()> {
+        self.write(true, address, value)
+    }
+
+    fn read(&self, ap: bool, address: u8) -> Result<u32> {
+        self.request(ap, true, address)?;
+
+        let response = self.device.execute(
+            &Commands::new()
+                .read_bytes(FLAGS, 4)
+                .read_bits(FLAGS, 1)
+                .write_bits(FLAGS, 0, 1)
+                .set_bits_low(self.levels(), self.directions(true)),
+        )?;
+        let value = u32::from_le_bytes([response[0], response[1], response[2], response[3]]);
+        let parity = response[4] >> 7;
+        if value.count_ones() % 2 != u32::from(parity) {
+            return Err(Error::Swd(SwdFault::Parity));
+        }
+        Ok(value)
+    }
+
+    fn write(&self, ap: bool, address: u8, value: u32) -> Result<()> {
+        self.request(ap, false, address)?;
+
+        let parity = (value.count_ones() % 2) as u8;
+        self.device.execute(
+            &Commands::new()
+                .write_bits(FLAGS, 0, 1)
+                .set_bits_low(self.levels(), self.directions(true))
+                .write_bytes(FLAGS, &value.to_le_bytes())
+                .write_bits(FLAGS, parity, 1)
+                // Idle cycles let the target complete the write.
+                .write_bytes(FLAGS, &[0]),
+        )?;
+        Ok(())
+    }
+
+    // Send a request header and read its acknowledgement, retrying on WAIT. On OK,
+    // the target is left driving SWDIO.
+    fn request(&self, ap: bool, read: bool, address: u8) -> Result<()> {
+        let header = request_header(ap, read, address);
+        let mut retries = self.retries;
+        loop {
+            // Turnaround, then three acknowledgement bits.
+            let response = self.device.execute(
+                &Commands::new()
+                    .write_bits(FLAGS, header, 8)
+                    .set_bits_low(self.levels(), self.directions(false))
+                    .read_bits(FLAGS, 4),
+            )?;
+            let ack = (response[0] >> 5) & 0x07;
+            if ack == ACK_OK {
+                return Ok(());
+            }
+
+            // Take SWDIO back after the turnaround.
+            self.device.execute(
+                &Commands::new()
+                    .write_bits(FLAGS, 0, 1)
+                    .set_bits_low(self.levels(), self.directions(true)),
+            )?;
+            match ack {
+                ACK_WAIT if retries > 0 => retries -= 1,
+                ACK_WAIT => return Err(Error::Swd(SwdFault::Wait)),
+                ACK_FAULT => return Err(Error::Swd(SwdFault::Fault)),
+                _ => return Err(Error::Swd(SwdFault::Protocol(ack))),
+            }
+        }
+    }
+
+    fn levels(&self) -> u8 {
+        self.device.gpio_output_latch() as u8
+    }
+
+    fn directions(&self, drive: bool) -> u8 {
+        let directions = self.device.gpio_direction() as u8;
+        if drive {
+            directions
+        } else {
+            directions & !(1 << SWDIO_OUT)
+        }
+    }
+}
+
+fn line_reset(commands: Commands) -> Commands {
+    commands
+        .write_bytes(FLAGS, &[0xff; 7])
+        .write_bytes(FLAGS, &[0])
+}
+
+// Start bit, APnDP, RnW, A[3:2], parity over the previous four, stop bit and park bit.
+fn request_header(ap: bool, read: bool, address: u8) -> u8 {
+    let fields = (ap as u8) | ((read as u8) << 1) | (((address >> 2) & 0x03) << 2);
+    let parity = (fields.count_ones() % 2) as u8;
+    0x01 | (fields << 1) | (parity << 5) | 0x80
+}
@@ -0,0 +1,288 @@
+use proptest::prelude::*;
+
+use eeprom::{
+    CbusConfig, CbusFunction, CbusHFunction, ChannelDriver, ChannelType, DriveStrength,
+    EepromConfig,
+};
+
+// Strings `ftdi_eeprom` can quote: no quotes, and no comment markers to trip over.
+fn descriptor() -> impl Strategy<Value = String> {
+    "[A-Za-z0-9 _.-]{0,32}"
+}
+
+fn boolean() -> impl Strategy<Value = &'static str> {
+    prop::sample::select(vec!["true", "yes", "on", "false", "no", "off"])
+}
+
+fn is_true(value: &str) -> bool {
+    value == "true" || value == "yes" || value == "on"
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(128))]
+
+    #[test]
+    fn conf_parser_rejects_garbage_cleanly(conf in "(([a-z_0-9]{0,12}) ?=? ?([\"0-9a-zA-Z#/]{0,8})\n){0,8}") {
+        if let Err(e) = EepromConfig::from_ftdi_eeprom_conf(&conf) {
+            prop_assert!(e.line >= 1 && e.line <= conf.lines().count(), "{:?}", e);
+        }
+    }
+
+    #[test]
+    fn conf_settings_are_read_back(
+        vendor_id in any::<u16>(),
+        product_id in any::<u16>(),
+        manufacturer in descriptor(),
+        product in descriptor(),
+        serial in descriptor(),
+        max_power in 0..=500u16,
+        self_powered in boolean(),
+        remote_wakeup in boolean(),
+        invert_txd in boolean(),
+    ) {
+        let conf = format!(
+            "vendor_id=0x{:04x}\nproduct_id={} # decimal\nmanufacturer=\"{}\"\nproduct=\"{}\"\n\
+             serial=\"{}\" // trailing\nmax_power={}\nself_powered={}\nremote_wakeup={}\n\
+             invert_txd={}\n",
+            vendor_id, product_id, manufacturer, product, serial, max_power, self_powered,
+            remote_wakeup, invert_txd,
+        );
+        let config = EepromConfig::from_ftdi_eeprom_conf(&conf)
+            .map_err(|e| TestCaseError::fail(e.to_string()))?;
+
+        prop_assert_eq!(config.vendor_id, vendor_id);
+        prop_assert_eq!(config.product_id, product_id);
+        prop_assert_eq!(&config.manufacturer, &manufacturer);
+        prop_assert_eq!(&config.description, &product);
+        prop_assert_eq!(&config.serial, &serial);
+        prop_assert_eq!(config.max_power, max_power);
+        prop_assert_eq!(config.self_powered, is_true(self_powered));
+        prop_assert_eq!(config.remote_wakeup, is_true(remote_wakeup));
+        prop_assert_eq!(config.invert.txd, is_true(invert_txd));
+
+        let report = config.to_string();
+        for descriptor in &[&manufacturer, &product, &serial] {
+            prop_assert!(report.contains(&format!("{:?}", descriptor)), "{}", report);
+        }
+        prop_assert!(config.diff(&config).is_empty());
+    }
+
+    #[test]
+    fn out_of_range_values_are_rejected(vendor_id in 0x10000..0x100000i64, max_power in 501..0x10000u32) {
+        let conf = format!("vendor_id={}", vendor_id);
+        prop_assert!(EepromConfig::from_ftdi_eeprom_conf(&conf).is_err());
+        let e = EepromConfig::from_ftdi_eeprom_conf(&format!("\nmax_power={}", max_power));
+        prop_assert_eq!(e.map_err(|e| e.line), Err(2));
+    }
+}
+
+#[cfg(feature = "serde")]
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(64))]
+
+    #[test]
+    fn toml_round_trips(
+        vendor_id in any::<u16>(),
+        serial in ".{0,32}",
+        max_power in 0..=500u16,
+        use_serial in any::<bool>(),
+    ) {
+        let mut config = EepromConfig::from_ftdi_eeprom_conf("")
+            .map_err(|e| TestCaseError::fail(e.to_string()))?;
+        config.vendor_id = vendor_id;
+        config.serial = serial;
+        config.max_power = max_power;
+        config.use_serial = use_serial;
+
+        let toml = config.to_toml().map_err(|e| TestCaseError::fail(e.to_string()))?;
+        let parsed = EepromConfig::from_toml(&toml).map_err(|e| TestCaseError::fail(e.to_string()))?;
+        prop_assert_eq!(parsed, config);
+    }
+}
+
+#[test]
+fn non_ascii_pin_group_keys_are_rejected() {
+    for key in &["groupé_drive", "group0é", "group_é", "groupé"] {
+        let e = EepromConfig::from_ftdi_eeprom_conf(&format!("{}=true", key));
+        assert_eq!(e.map_err(|e| e.line), Err(1), "{}", key);
+    }
+}
+
+// An ftdi_eeprom configuration in the layout of the one shipped with libftdi.
+const EXAMPLE_CONF: &str = r#"
+vendor_id=0x403			# Vendor ID
+product_id=0x6001		# Product ID
+
+max_power=100			# Max. power consumption: value * 2 mA. Use 0 if self_powered = true.
+
+###########
+# Strings #
+###########
+manufacturer="ACME Inc"		# Manufacturer
+product="USB Serial Converter"	# Product
+serial="08-15"			# Serial
+
+###########
+# Options #
+###########
+self_powered=false		# Turn this off for bus powered
+remote_wakeup=false		# Turn this on for remote wakeup feature
+use_serial=true			# Use the serial number string
+
+# Normally out don't have to change one of these flags
+in_is_isochronous=false		# In Endpoint is Isochronous
+out_is_isochronous=false	# Out Endpoint is Isochronous
+suspend_pull_downs=false	# Enable suspend pull downs for lower power
+change_usb_version=false	# Change USB Version
+usb_version=0x0200		# Only used when change_usb_version is enabled
+
+cbus0=TXLED
+cbus1=RXLED
+cbus2=TXDEN
+cbus3=PWREN
+cbus4=SLEEP
+
+########
+# Misc #
+########
+
+filename="eeprom.new"		# Filename, leave empty to skip file writing
+"#;
+
+fn conf_error(conf: &str) -> Option<(usize, &'static str)> {
+    EepromConfig::from_ftdi_eeprom_conf(conf)
+        .err()
+        .map(|e| (e.line, e.reason))
+}
+
+#[test]
+fn example_conf_is_read() {
+    let config = EepromConfig::from_ftdi_eeprom_conf(EXAMPLE_CONF).unwrap();
+    assert_eq!(config.vendor_id, 0x0403);
+    assert_eq!(config.product_id, 0x6001);
+    assert_eq!(config.max_power, 100);
+    assert_eq!(config.manufacturer, "ACME Inc");
+    assert_eq!(config.description, "USB Serial Converter");
+    assert_eq!(config.serial, "08-15");
+    assert!(!config.self_powered);
+    assert!(!config.remote_wakeup);
+    assert!(config.use_serial);
+    assert_eq!(
+        config.cbus,
+        Some(CbusConfig::FT232R([
+            CbusFunction::TxLed,
+            CbusFunction::RxLed,
+            CbusFunction::TxDen,
+            CbusFunction::PwrEn,
+            CbusFunction::Sleep,
+        ]))
+    );
+}
+
+#[test]
+fn missing_keys_take_ftdi_eeprom_defaults() {
+    let config = EepromConfig::from_ftdi_eeprom_conf("").unwrap();
+    assert_eq!(config.vendor_id, 0x0403);
+    assert_eq!(config.product_id, 0x6001);
+    assert_eq!(config.manufacturer, "Acme Inc.");
+    assert!(config.self_powered);
+    assert_eq!(config.channel_a_type, ChannelType::Uart);
+    assert_eq!(config.cbus, None);
+}
+
+#[test]
+fn comments_are_stripped() {
+    let conf = "# vendor_id=0x1234\n\
+                // product_id=0x1234\n\
+                vendor_id=0x0403 // Vendor ID\n\
+                \t  # indented comment\n\
+                product_id=0x6010 # Product ID\n";
+    let config = EepromConfig::from_ftdi_eeprom_conf(conf).unwrap();
+    assert_eq!(config.vendor_id, 0x0403);
+    assert_eq!(config.product_id, 0x6010);
+}
+
+#[test]
+fn quoted_strings_keep_comment_markers() {
+    let conf = "manufacturer=\"Lab #2\" # where it was built\n\
+                product=\"http://example.com\"\n\
+                serial=FT123456\n\
+                cha_type=\"FIFO\"\n";
+    let config = EepromConfig::from_ftdi_eeprom_conf(conf).unwrap();
+    assert_eq!(config.manufacturer, "Lab #2");
+    assert_eq!(config.description, "http://example.com");
+    assert_eq!(config.serial, "FT123456");
+    assert_eq!(config.channel_a_type, ChannelType::Fifo);
+}
+
+#[test]
+fn integers_take_any_base() {
+    for &(value, expected) in &[
+        ("0x6014", 0x6014),
+        ("0X6014", 0x6014),
+        ("24596", 0x6014),
+        ("060024", 0x6014),
+    ] {
+        let config = EepromConfig::from_ftdi_eeprom_conf(&format!("product_id={}", value)).unwrap();
+        assert_eq!(config.product_id, expected, "{}", value);
+    }
+}
+
+#[test]
+fn settings_of_other_chips_are_read() {
+    let conf = "cha_vcp=false\n\
+                chb_rs485=true\n\
+                group1_drive=16mA\n\
+                group1_schmitt=on\n\
+                cbush9=CLK7_5\n";
+    let config = EepromConfig::from_ftdi_eeprom_conf(conf).unwrap();
+    assert_eq!(config.channel_a_driver, ChannelDriver::D2xx);
+    assert!(config.channel_b_rs485);
+    assert_eq!(config.pin_groups[1].drive, DriveStrength::Ma16);
+    assert!(config.pin_groups[1].schmitt);
+    match config.cbus {
+        Some(CbusConfig::FT232H(functions)) => {
+            assert_eq!(functions[9], CbusHFunction::Clk7_5)
+        }
+        ref cbus => panic!("{:?}", cbus),
+    }
+}
+
+#[test]
+fn ignored_keys_are_accepted() {
+    let conf = "flash_raw=true\neeprom_type=0x56\nuser_data_file=\"data.bin\"\n";
+    assert!(EepromConfig::from_ftdi_eeprom_conf(conf).is_ok());
+}
+
+#[test]
+fn unknown_keys_are_rejected() {
+    assert_eq!(
+        conf_error("vendor_id=0x403\nfrobnicate=true"),
+        Some((2, "unknown key"))
+    );
+    assert_eq!(conf_error("group9_drive=4MA"), Some((1, "unknown key")));
+    assert_eq!(conf_error("group0_color=red"), Some((1, "unknown key")));
+    assert_eq!(conf_error("cbus5=TXLED"), Some((1, "unknown key")));
+}
+
+#[test]
+fn invalid_values_are_rejected() {
+    let cases = [
+        ("self_powered=maybe", "expected a boolean"),
+        ("vendor_id=0x10000", "expected an integer in range"),
+        ("vendor_id=-1", "expected an integer in range"),
+        ("product_id=0xzz", "expected an integer in range"),
+        ("max_power=501", "expected an integer in range"),
+        ("serial=\"08-15", "malformed string"),
+        ("manufacturer=ACME\"Inc", "malformed string"),
+        ("cha_type=SPI", "unknown channel type"),
+        ("group0_drive=5MA", "unknown drive strength"),
+        ("cbus0=CLK30", "unknown CBUS function"),
+        ("cbus0=TXLED\ncbusx0=TXLED", "mixed CBUS function sets"),
+        ("vendor_id", "expected `key = value`"),
+    ];
+    for &(conf, reason) in &cases {
+        let line = conf.lines().count();
+        assert_eq!(conf_error(conf), Some((line, reason)), "{}", conf);
+    }
+}
@@ -0,0 +1,128 @@
+use proptest::prelude::*;
+
+use error::Error;
+use i2c_eeprom::I2cEeprom;
+use mock::MockDevice;
+
+const ADDRESS: u8 = 0x50;
+
+// A 24xx part from 128 bytes to 128 KiB, with its page size.
+fn geometry() -> impl Strategy<Value = (usize, usize)> {
+    (7..=17usize).prop_flat_map(|size| {
+        (
+            Just(1 << size),
+            (0..=size.min(8)).prop_map(|page| 1 << page),
+        )
+    })
+}
+
+// Device and memory address bytes selecting `offset`, as the datasheets lay them out.
+fn header(size: usize, offset: usize) -> (u8, Vec<u8>) {
+    let bytes = if size <= 0x800 { 1 } else { 2 };
+    let device = ADDRESS | (offset >> (8 * bytes)) as u8;
+    let mut header = vec![device << 1];
+    header.extend((0..bytes).rev().map(|i| (offset >> (8 * i)) as u8));
+    (device, header)
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(128))]
+
+    #[test]
+    fn writes_stay_within_pages(
+        (size, page) in geometry(),
+        offset in any::<usize>(),
+        data in prop::collection::vec(any::<u8>(), 0..300),
+        nack in any::<Option<usize>>(),
+    ) {
+        let offset = offset % (size - data.len().min(size) + 1);
+        let data = &data[..data.len().min(size)];
+
+        // One write per page touched, each followed by an acknowledged poll for the
+        // end of the write cycle.
+        let mut stream = Vec::new();
+        let mut nackable = Vec::new();
+        let mut position = offset;
+        while position < offset + data.len() {
+            let end = ((position / page + 1) * page).min(offset + data.len());
+            prop_assert_eq!((end - 1) / page, position / page, "Write crosses a page");
+            let (device, header) = header(size, position);
+            let start = stream.len();
+            stream.extend(header);
+            stream.extend_from_slice(&data[position - offset..end - offset]);
+            nackable.extend(start..stream.len());
+            stream.push(device << 1);
+            position = end;
+        }
+
+        let nack = nack.filter(|_| !nackable.is_empty()).map(|n| nackable[n % nackable.len()]);
+        let mock = match nack {
+            Some(n) => MockDevice::new().expect_write(&stream[..=n]).nack(),
+            None => MockDevice::new().expect_write(&stream),
+        };
+        let mut eeprom = I2cEeprom::new(mock, ADDRESS, size, page)
+            .map_err(|e| TestCaseError::fail(e.to_string()))?;
+        let res = eeprom.write(offset, data);
+        let mock = eeprom.into_inner();
+
+        match nack {
+            Some(_) => prop_assert!(matches!(res, Err(Error::Nack)), "{:?}", res),
+            None => {
+                prop_assert!(res.is_ok(), "{:?}", res);
+                prop_assert!(mock.is_finished());
+            }
+        }
+        prop_assert!(mock.is_i2c_idle(), "STOP missing");
+    }
+
+    #[test]
+    fn reads_release_the_bus(
+        (size, page) in geometry(),
+        offset in any::<usize>(),
+        data in prop::collection::vec(any::<u8>(), 1..300),
+        short in any::<Option<usize>>(),
+    ) {
+        let data = &data[..data.len().min(size)];
+        let offset = offset % (size - data.len() + 1);
+        let sent = match short {
+            Some(n) => &data[..n % data.len()],
+            None => data,
+        };
+
+        let (device, header) = header(size, offset);
+        let mock = MockDevice::new()
+            .expect_write(&header)
+            .expect_write(&[device << 1 | 1])
+            .respond(sent);
+        let mut eeprom = I2cEeprom::new(mock, ADDRESS, size, page)
+            .map_err(|e| TestCaseError::fail(e.to_string()))?;
+        let mut read = vec![0; data.len()];
+        let res = eeprom.read(offset, &mut read);
+        let mock = eeprom.into_inner();
+
+        if short.is_some() {
+            prop_assert!(res.is_err());
+        } else {
+            prop_assert!(res.is_ok(), "{:?}", res);
+            prop_assert_eq!(&read[..], data);
+        }
+        prop_assert!(mock.is_i2c_idle(), "STOP missing");
+    }
+
+    #[test]
+    fn out_of_range_accesses_are_rejected(
+        (size, page) in geometry(),
+        offset in any::<usize>(),
+        len in 1..300usize,
+    ) {
+        prop_assume!(offset.checked_add(len).is_none_or(|end| end > size));
+        let mut eeprom = I2cEeprom::new(MockDevice::new(), ADDRESS, size, page)
+            .map_err(|e| TestCaseError::fail(e.to_string()))?;
+
+        let write = eeprom.write(offset, &vec![0; len]);
+        prop_assert!(matches!(write, Err(Error::InvalidArgument(_))));
+        let read = eeprom.read(offset, &mut vec![0; len]);
+        prop_assert!(matches!(read, Err(Error::InvalidArgument(_))));
+        prop_assert!(eeprom.into_inner().written().is_empty());
+    }
+}
@@ -0,0 +1,103 @@
+use proptest::prelude::*;
+
+use jtag::TapState;
+
+const STATES: [TapState; 16] = [
+    TapState::TestLogicReset,
+    TapState::RunTestIdle,
+    TapState::SelectDrScan,
+    TapState::CaptureDr,
+    TapState::ShiftDr,
+    TapState::Exit1Dr,
+    TapState::PauseDr,
+    TapState::Exit2Dr,
+    TapState::UpdateDr,
+    TapState::SelectIrScan,
+    TapState::CaptureIr,
+    TapState::ShiftIr,
+    TapState::Exit1Ir,
+    TapState::PauseIr,
+    TapState::Exit2Ir,
+    TapState::UpdateIr,
+];
+
+fn state() -> impl Strategy<Value = TapState> {
+    prop::sample::select(STATES.to_vec())
+}
+
+fn follow(from: TapState, tms: &[bool]) -> TapState {
+    tms.iter().fold(from, |state, &tms| state.next(tms))
+}
+
+proptest! {
+    #[test]
+    fn paths_lead_to_their_target(from in state(), to in state()) {
+        let path = from.path(to);
+
+        prop_assert_eq!(follow(from, &path), to);
+        // No state of the TAP controller is more than 8 clocks from any other.
+        prop_assert!(path.len() <= 8, "{:?} -> {:?} takes {:?}", from, to, path);
+        for len in 0..path.len() {
+            prop_assert_ne!(follow(from, &path[..len]), to, "Path passes its target early");
+        }
+    }
+
+    #[test]
+    fn five_tms_high_clocks_reset(from in state(), tms in prop::collection::vec(any::<bool>(), 0..32)) {
+        let state = follow(from, &tms);
+        prop_assert_eq!(follow(state, &[true; 5]), TapState::TestLogicReset);
+    }
+}
+
+#[test]
+fn paths_match_the_standard_tms_sequences() {
+    use jtag::TapState::*;
+
+    let cases: &[(TapState, TapState, &[u8])] = &[
+        (TestLogicReset, TestLogicReset, &[]),
+        (TestLogicReset, RunTestIdle, &[0]),
+        (TestLogicReset, ShiftDr, &[0, 1, 0, 0]),
+        (TestLogicReset, ShiftIr, &[0, 1, 1, 0, 0]),
+        (TestLogicReset, PauseIr, &[0, 1, 1, 0, 1, 0]),
+        (RunTestIdle, ShiftDr, &[1, 0, 0]),
+        (RunTestIdle, ShiftIr, &[1, 1, 0, 0]),
+        (RunTestIdle, TestLogicReset, &[1, 1, 1]),
+        (ShiftDr, RunTestIdle, &[1, 1, 0]),
+        (ShiftIr, RunTestIdle, &[1, 1, 0]),
+        (ShiftDr, PauseDr, &[1, 0]),
+        (PauseDr, ShiftDr, &[1, 0]),
+        (Exit1Dr, ShiftDr, &[0, 1, 0]),
+        (ShiftDr, ShiftIr, &[1, 1, 1, 1, 0, 0]),
+        (ShiftIr, ShiftDr, &[1, 1, 1, 0, 0]),
+        (UpdateIr, ShiftDr, &[1, 0, 0]),
+        (PauseIr, RunTestIdle, &[1, 1, 0]),
+        (ShiftDr, TestLogicReset, &[1, 1, 1, 1, 1]),
+    ];
+    for &(from, to, tms) in cases {
+        let tms: Vec<bool> = tms.iter().map(|&bit| bit == 1).collect();
+        assert_eq!(from.path(to), tms, "{:?} -> {:?}", from, to);
+    }
+}
+
+#[test]
+fn paths_take_the_fewest_clocks() {
+    for &from in &STATES {
+        for &to in &STATES {
+            let len = from.path(to).len();
+            // No TMS sequence shorter than the path reaches the target.
+            for shorter in 0..len {
+                for bits in 0..1u32 << shorter {
+                    let tms: Vec<bool> = (0..shorter).map(|bit| bits >> bit & 1 != 0).collect();
+                    assert_ne!(
+                        follow(from, &tms),
+                        to,
+                        "{:?} -> {:?} in {:?}",
+                        from,
+                        to,
+                        tms
+                    );
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,114 @@
+//! Property-based tests of the protocol layers, run against [`MockDevice`] and the
+//! MPSSE command builder without hardware.
+//!
+//! Each test feeds randomized transfer sizes, short reads and injected failures
+//! through a layer and checks an invariant that must hold however it ends, e.g. that
+//! chip select is released or that no MPSSE command is longer than the chip accepts.
+//!
+//! [`MockDevice`]: ::mock::MockDevice
+
+mod eeprom;
+mod i2c_eeprom;
+mod jtag;
+mod mpsse;
+mod spi;
+
+use mpsse::*;
+
+/// Totals of an MPSSE command stream, as the chip would execute it.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Decoded {
+    /// Bytes shifted out by byte-mode data commands.
+    pub written: usize,
+    /// Bytes the chip sends back.
+    pub response: usize,
+    /// Bits shifted by bit-mode data and TMS commands.
+    pub bits: usize,
+    /// Clock pulses without data.
+    pub clocks: usize,
+}
+
+/// Walk an MPSSE command stream, failing on unknown opcodes, truncated commands and
+/// lengths the chip can't take.
+pub fn decode_commands(mut data: &[u8]) -> Result<Decoded, String> {
+    let mut decoded = Decoded::default();
+    while let Some(&opcode) = data.first() {
+        let len = |n: usize| -> Result<(), String> {
+            if data.len() < n {
+                Err(format!("Command {:#04x} cut short", opcode))
+            } else {
+                Ok(())
+            }
+        };
+        let size = match opcode {
+            SET_BITS_LOW | SET_BITS_HIGH | TCK_DIVISOR => 3,
+            GET_BITS_LOW | GET_BITS_HIGH => {
+                decoded.response += 1;
+                1
+            }
+            LOOPBACK_START | LOOPBACK_END | SEND_IMMEDIATE | DISABLE_CLK_DIV5
+            | ENABLE_3_PHASE_CLK | ENABLE_ADAPTIVE_CLK | DISABLE_ADAPTIVE_CLK | WAIT_ON_HIGH
+            | WAIT_ON_LOW | CLK_WAIT_ON_HIGH | CLK_WAIT_ON_LOW => 1,
+            CLK_BITS => {
+                len(2)?;
+                if data[1] > 7 {
+                    return Err(format!("CLK_BITS of {} bits", data[1] + 1));
+                }
+                decoded.clocks += usize::from(data[1]) + 1;
+                2
+            }
+            CLK_BYTES | CLK_BYTES_OR_HIGH | CLK_BYTES_OR_LOW => {
+                len(3)?;
+                decoded.clocks += 8 * (usize::from(data[1]) | usize::from(data[2]) << 8) + 8;
+                3
+            }
+            MCU_READ_SHORT => {
+                decoded.response += 1;
+                2
+            }
+            MCU_READ_EXTENDED => {
+                decoded.response += 1;
+                3
+            }
+            MCU_WRITE_SHORT => 3,
+            MCU_WRITE_EXTENDED => 4,
+            op if op < 0x80 && op & BITMODE != 0 => {
+                len(2)?;
+                let count = usize::from(data[1]) + 1;
+                let max = if op & WRITE_TMS != 0 { 7 } else { 8 };
+                if count > max {
+                    return Err(format!("Bit shift {:#04x} of {} bits", op, count));
+                }
+                decoded.bits += count;
+                if op & DO_READ != 0 {
+                    decoded.response += 1;
+                }
+                if op & (DO_WRITE | WRITE_TMS) != 0 {
+                    3
+                } else {
+                    2
+                }
+            }
+            op if op < 0x80 && op & WRITE_TMS == 0 && op & (DO_WRITE | DO_READ) != 0 => {
+                len(3)?;
+                let count = (usize::from(data[1]) | usize::from(data[2]) << 8) + 1;
+                if count > MAX_SHIFT_LEN {
+                    return Err(format!("Byte shift of {} bytes", count));
+                }
+                if op & DO_READ != 0 {
+                    decoded.response += count;
+                }
+                if op & DO_WRITE != 0 {
+                    decoded.written += count;
+                    3 + count
+                } else {
+                    3
+                }
+            }
+            op => return Err(format!("Unknown opcode {:#04x}", op)),
+        };
+        len(size)?;
+        data = &data[size..];
+    }
+    Ok(decoded)
+}
@@ -0,0 +1,126 @@
+use proptest::prelude::*;
+
+use super::{decode_commands, Decoded};
+use mpsse::{Commands, MAX_SHIFT_LEN};
+
+#[derive(Debug, Clone)]
+enum Op {
+    WriteBytes(usize),
+    ReadBytes(usize),
+    TransferBytes(usize),
+    WriteBits(u8),
+    ReadBits(u8),
+    TransferBits(u8),
+    WriteTms(u8),
+    TransferTms(u8),
+    ClockCycles(usize),
+    SetBitsLow(u8, u8),
+    GetBitsLow,
+}
+
+// Lengths around the single command limit are the interesting ones.
+fn shift_len() -> impl Strategy<Value = usize> {
+    prop_oneof![
+        0..16usize,
+        MAX_SHIFT_LEN - 2..MAX_SHIFT_LEN + 3,
+        0..3 * MAX_SHIFT_LEN,
+    ]
+}
+
+fn op() -> impl Strategy<Value = Op> {
+    prop_oneof![
+        shift_len().prop_map(Op::WriteBytes),
+        shift_len().prop_map(Op::ReadBytes),
+        shift_len().prop_map(Op::TransferBytes),
+        (1..=8u8).prop_map(Op::WriteBits),
+        (1..=8u8).prop_map(Op::ReadBits),
+        (1..=8u8).prop_map(Op::TransferBits),
+        (1..=7u8).prop_map(Op::WriteTms),
+        (1..=7u8).prop_map(Op::TransferTms),
+        (0..8 * MAX_SHIFT_LEN + 20).prop_map(Op::ClockCycles),
+        any::<(u8, u8)>().prop_map(|(levels, directions)| Op::SetBitsLow(levels, directions)),
+        Just(Op::GetBitsLow),
+    ]
+}
+
+// Add `op` to `commands`, along with what it should do.
+fn apply(commands: Commands, op: &Op, expected: &mut Decoded) -> Commands {
+    match *op {
+        Op::WriteBytes(len) => {
+            expected.written += len;
+            commands.write_bytes(0, &vec![0xa5; len])
+        }
+        Op::ReadBytes(len) => {
+            expected.response += len;
+            commands.read_bytes(0, len)
+        }
+        Op::TransferBytes(len) => {
+            expected.written += len;
+            expected.response += len;
+            commands.transfer_bytes(0, &vec![0x5a; len])
+        }
+        Op::WriteBits(count) => {
+            expected.bits += usize::from(count);
+            commands.write_bits(0, 0xff, count)
+        }
+        Op::ReadBits(count) => {
+            expected.bits += usize::from(count);
+            expected.response += 1;
+            commands.read_bits(0, count)
+        }
+        Op::TransferBits(count) => {
+            expected.bits += usize::from(count);
+            expected.response += 1;
+            commands.transfer_bits(0, 0xff, count)
+        }
+        Op::WriteTms(count) => {
+            expected.bits += usize::from(count);
+            commands.write_tms(0, 0x7f, count, true)
+        }
+        Op::TransferTms(count) => {
+            expected.bits += usize::from(count);
+            expected.response += 1;
+            commands.transfer_tms(0, 0x7f, count, true)
+        }
+        Op::ClockCycles(cycles) => {
+            expected.clocks += cycles;
+            commands.clock_cycles(cycles)
+        }
+        Op::SetBitsLow(levels, directions) => commands.set_bits_low(levels, directions),
+        Op::GetBitsLow => {
+            expected.response += 1;
+            commands.get_bits_low()
+        }
+    }
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(64))]
+
+    #[test]
+    fn commands_stay_within_mpsse_limits(ops in prop::collection::vec(op(), 0..8)) {
+        let mut expected = Decoded::default();
+        let commands = ops
+            .iter()
+            .fold(Commands::new(), |commands, op| apply(commands, op, &mut expected));
+
+        let decoded = decode_commands(commands.as_bytes()).map_err(TestCaseError::fail)?;
+        prop_assert_eq!(&decoded, &expected);
+        prop_assert_eq!(commands.read_len(), expected.response);
+    }
+
+    #[test]
+    fn recorded_macros_flush_their_response(ops in prop::collection::vec(op(), 0..4)) {
+        let mut expected = Decoded::default();
+        let commands = ops
+            .iter()
+            .fold(Commands::new(), |commands, op| apply(commands, op, &mut expected));
+        let recorded = commands.record("test");
+
+        prop_assert_eq!(recorded.read_len(), expected.response);
+        prop_assert!(decode_commands(recorded.as_bytes()).is_ok());
+        if expected.response > 0 {
+            prop_assert_eq!(recorded.as_bytes().last(), Some(&::mpsse::SEND_IMMEDIATE));
+        }
+    }
+}
@@ -0,0 +1,117 @@
+use proptest::prelude::*;
+
+use error::Error;
+use gpio::Gpio;
+use mock::MockDevice;
+use spi::{SharedSpiBus, Spi, SpiBus};
+
+// Where a failure is injected into an SPI command.
+#[derive(Debug, Copy, Clone)]
+enum Fault {
+    None,
+    // Selecting the device fails.
+    Select,
+    // Reading the response fails, after the command was written.
+    Read,
+    // The device sends back less than the whole response.
+    ShortRead(usize),
+    // A different command than expected is written.
+    Mismatch(usize),
+}
+
+fn fault() -> impl Strategy<Value = Fault> {
+    prop_oneof![
+        Just(Fault::None),
+        Just(Fault::Select),
+        Just(Fault::Read),
+        any::<usize>().prop_map(Fault::ShortRead),
+        any::<usize>().prop_map(Fault::Mismatch),
+    ]
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(128))]
+
+    #[test]
+    fn command_releases_chip_select(
+        cs in 3..16u8,
+        command in prop::collection::vec(any::<u8>(), 0..64),
+        response in prop::collection::vec(any::<u8>(), 0..64),
+        fault in fault(),
+    ) {
+        let mut mock = MockDevice::new().with_chip_select(cs);
+        // Start out deselected, as a fresh bus would be.
+        mock.deselect().map_err(|e| TestCaseError::fail(e.to_string()))?;
+
+        let mut expected = command.clone();
+        let mut sent = response.clone();
+        let faulty = match fault {
+            Fault::None => false,
+            Fault::Select => {
+                mock = mock.fail(Error::Disconnected);
+                true
+            }
+            Fault::Read => !response.is_empty(),
+            Fault::ShortRead(n) => {
+                sent.truncate(n % (response.len() + 1));
+                sent.len() < response.len()
+            }
+            Fault::Mismatch(n) if !command.is_empty() => {
+                expected[n % command.len()] ^= 0xff;
+                true
+            }
+            Fault::Mismatch(_) => false,
+        };
+        mock = mock.expect_write(&expected);
+        if let Fault::Read = fault {
+            // Only a command with a response reads it.
+            if !response.is_empty() {
+                mock = mock.fail(Error::Disconnected);
+            }
+        }
+        mock = mock.respond(&sent);
+
+        let mut received = vec![0; response.len()];
+        let res = mock.command(&command, &mut received);
+
+        prop_assert_eq!(res.is_err(), faulty, "{:?}", res);
+        prop_assert!(mock.output_latch() & (1 << cs) != 0, "CS left asserted");
+        if !faulty {
+            prop_assert_eq!(mock.written(), command);
+            prop_assert_eq!(received, response);
+        }
+    }
+
+    #[test]
+    fn shared_bus_selects_one_device_at_a_time(
+        steps in prop::collection::vec((3..8u8, any::<bool>(), 0..4usize), 0..32),
+    ) {
+        // Every write is accepted, so only the chip selects matter.
+        let writes: usize = steps.iter().map(|&(_, _, len)| len).sum();
+        let bus = SharedSpiBus::new(MockDevice::new().expect_write(&vec![0; writes]));
+
+        let mut handles = Vec::new();
+        for cs in 3..8 {
+            handles.push(bus.device(cs).map_err(|e| TestCaseError::fail(e.to_string()))?);
+        }
+        let mut selected = None;
+        for (cs, select, len) in steps {
+            let handle = &mut handles[usize::from(cs - 3)];
+            if select {
+                handle.select().map_err(|e| TestCaseError::fail(e.to_string()))?;
+                selected = Some(cs);
+            } else {
+                handle.deselect().map_err(|e| TestCaseError::fail(e.to_string()))?;
+                if selected == Some(cs) {
+                    selected = None;
+                }
+            }
+            handle.write(&vec![0; len]).map_err(|e| TestCaseError::fail(e.to_string()))?;
+        }
+        drop(handles);
+
+        let latch = bus.into_inner().output_latch();
+        let active: Vec<u8> = (3..8).filter(|&cs| latch & (1 << cs) == 0).collect();
+        prop_assert_eq!(active, selected.into_iter().collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,80 @@
+//! Setting up the background threads that stream data from a device.
+//!
+//! Under load, a streaming thread that doesn't get scheduled in time lets the chip's
+//! buffer overflow and data get lost. The standard library can't raise a thread's
+//! priority or pin it to a CPU, so [`ThreadOptions::on_start`] runs a callback on the
+//! new thread before it starts streaming, where a crate such as `thread-priority` or
+//! `core_affinity` can do so.
+
+use std::fmt;
+use std::io;
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+
+/// How to create a background thread, e.g. for
+/// [`EventService::spawn_with`][::events::EventService::spawn_with].
+#[derive(Clone, Default)]
+pub struct ThreadOptions {
+    name: Option<String>,
+    stack_size: Option<usize>,
+    on_start: Option<Arc<dyn Fn() + Send + Sync>>,
+}
+
+impl ThreadOptions {
+    /// Options of a plain [`thread::spawn`].
+    pub fn new() -> ThreadOptions {
+        ThreadOptions::default()
+    }
+
+    pub fn name(mut self, name: &str) -> ThreadOptions {
+        self.name = Some(name.to_string());
+        self
+    }
+
+    pub fn stack_size(mut self, bytes: usize) -> ThreadOptions {
+        self.stack_size = Some(bytes);
+        self
+    }
+
+    /// Call `f` on the new thread before it does anything else, e.g. to raise its
+    /// priority or set its CPU affinity.
+    pub fn on_start<F>(mut self, f: F) -> ThreadOptions
+    where
+        F: Fn() + Send + Sync + 'static,
+    {
+        self.on_start = Some(Arc::new(f));
+        self
+    }
+
+    /// Spawn a thread running `f` with these options.
+    pub fn spawn<F, T>(&self, f: F) -> io::Result<JoinHandle<T>>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let mut builder = thread::Builder::new();
+        if let Some(ref name) = self.name {
+            builder = builder.name(name.clone());
+        }
+        if let Some(bytes) = self.stack_size {
+            builder = builder.stack_size(bytes);
+        }
+        let on_start = self.on_start.clone();
+        builder.spawn(move || {
+            if let Some(on_start) = on_start {
+                on_start();
+            }
+            f()
+        })
+    }
+}
+
+impl fmt::Debug for ThreadOptions {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ThreadOptions")
+            .field("name", &self.name)
+            .field("stack_size", &self.stack_size)
+            .field("on_start", &self.on_start.is_some())
+            .finish()
+    }
+}
@@ -0,0 +1,69 @@
+//! Noticing promptly that a device was unplugged.
+//!
+//! A program that only reads an idle port learns about unplugging from the next
+//! transfer that fails, which may take long or never happen. A [`Watchdog`] polls the
+//! modem status, a cheap control transfer, and reports the first poll that finds the
+//! device gone.
+
+use std::sync::atomic::Ordering;
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::Duration;
+
+use background::{self, BackgroundThread};
+use error::Error;
+use shared::SharedDevice;
+use threads::ThreadOptions;
+use Result;
+
+/// Checks on a background thread that a device is still present.
+///
+/// The device is locked only for each check, so other threads can keep using it.
+/// Stops after reporting a disconnect, when dropped, or through
+/// [`stop`][Watchdog::stop].
+pub struct Watchdog {
+    thread: BackgroundThread<()>,
+}
+
+impl Watchdog {
+    /// Check every `interval`, calling `on_disconnect` on the watchdog thread once the
+    /// device is gone.
+    ///
+    /// Failures other than a disconnect are ignored. Fails with [`Error::Io`] if the
+    /// thread can't be created.
+    pub fn spawn<F>(device: SharedDevice, interval: Duration, on_disconnect: F) -> Result<Watchdog>
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        let thread = BackgroundThread::spawn(&ThreadOptions::new(), move |running| {
+            while running.load(Ordering::SeqCst) {
+                if let Err(Error::Disconnected) = device.lock().poll_modem_status() {
+                    on_disconnect();
+                    return;
+                }
+                thread::sleep(interval);
+            }
+        })?;
+        Ok(Watchdog { thread })
+    }
+
+    /// Start watching, reporting a disconnect through a channel.
+    pub fn spawn_channel(
+        device: SharedDevice,
+        interval: Duration,
+    ) -> Result<(Watchdog, Receiver<()>)> {
+        let (sender, disconnected) = mpsc::channel();
+        let watchdog = Watchdog::spawn(device, interval, move || background::notify(&sender, ()))?;
+        Ok((watchdog, disconnected))
+    }
+
+    /// Whether the watchdog thread is still checking; it stops after a disconnect.
+    pub fn is_running(&self) -> bool {
+        self.thread.is_running()
+    }
+
+    /// Stop watching, propagating a panic of `on_disconnect`.
+    pub fn stop(mut self) {
+        self.thread.join();
+    }
+}
@@ -0,0 +1,181 @@
+//! Driving WS2812 ("NeoPixel") LED strips from the SPI data output of the MPSSE.
+//!
+//! The strip's data input goes to ADBUS1. Every bit sent to the LEDs is encoded as
+//! three SPI bits clocked at about 2.4 MHz: `100` for a 0 and `110` for a 1, giving
+//! the 1.25 µs bit period with the short or long HIGH pulse the LEDs expect. The
+//! clock output on ADBUS0 is not used.
+//!
+//! The LEDs latch their colors once the data line stays LOW for the reset time, so
+//! the stream must not pause for that long in the middle of a frame. A frame goes out
+//! in a single write, which the chip clocks out without gaps as long as USB keeps up.
+
+use std::time::Duration;
+
+use error::Error;
+use mode::MpsseDevice;
+use mpsse::Commands;
+use Result;
+
+const SCK: u16 = 1 << 0;
+const DOUT: u16 = 1 << 1;
+
+/// Target SPI clock, three SPI bits per LED bit.
+const SPI_CLOCK: u32 = 2_400_000;
+
+/// SPI clocks the LEDs tolerate, with the pulse lengths staying within their specs.
+const MIN_SPI_CLOCK: u32 = 2_100_000;
+const MAX_SPI_CLOCK: u32 = 2_700_000;
+
+/// Order in which the LEDs take the color components.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ColorOrder {
+    /// Green, red, blue, as on WS2812 and WS2812B LEDs.
+    Grb,
+    Rgb,
+    Bgr,
+}
+
+/// A WS2812 strip on ADBUS1 of an MPSSE device.
+pub struct Ws2812<'a> {
+    device: &'a MpsseDevice,
+    clock: u32,
+    order: ColorOrder,
+    reset_time: Duration,
+}
+
+impl<'a> Ws2812<'a> {
+    /// Set the clock and make ADBUS0..1 LOW outputs.
+    ///
+    /// Fails if the chip can't run the clock close enough to 2.4 MHz, which so far
+    /// needs an H-series chip.
+    pub fn new(device: &'a MpsseDevice) -> Result<Ws2812<'a>> {
+        let clock = device.set_clock_frequency(SPI_CLOCK)?;
+        if !(MIN_SPI_CLOCK..=MAX_SPI_CLOCK).contains(&clock) {
+            return Err(Error::InvalidArgument(
+                "The chip can't clock SPI at WS2812 speed",
+            ));
+        }
+
+        let levels = device.gpio_output_latch() & !(SCK | DOUT);
+        device.set_gpio(levels, device.gpio_direction() | SCK | DOUT)?;
+        Ok(Ws2812 {
+            device,
+            clock,
+            order: ColorOrder::Grb,
+            reset_time: Duration::from_micros(300),
+        })
+    }
+
+    /// Send the color components in `order`; GRB by default.
+    pub fn with_color_order(mut self, order: ColorOrder) -> Ws2812<'a> {
+        self.order = order;
+        self
+    }
+
+    /// Hold the data line LOW for `time` after each frame, so the LEDs latch it.
+    ///
+    /// The default of 300 µs suits current WS2812B LEDs; older WS2812 LEDs only need
+    /// 50 µs.
+    pub fn with_reset_time(mut self, time: Duration) -> Ws2812<'a> {
+        self.reset_time = time;
+        self
+    }
+
+    /// Show `pixels`, given as red, green and blue, starting with the LED closest to
+    /// the chip.
+    pub fn write(&self, pixels: &[[u8; 3]]) -> Result<()> {
+        let mut data = encode(pixels, self.order);
+        // The reset gap is sent as zeros, so it starts right after the last pixel.
+        let reset_bits = self.reset_time.as_nanos() * u128::from(self.clock) / 1_000_000_000;
+        data.resize(data.len() + reset_bits.div_ceil(8) as usize, 0);
+        self.device
+            .execute(&Commands::new().write_bytes(0, &data))
+            .map(|_| ())
+    }
+
+    /// Turn off the first `count` LEDs.
+    pub fn clear(&self, count: usize) -> Result<()> {
+        self.write(&vec![[0; 3]; count])
+    }
+}
+
+/// Encode `pixels` as SPI data, three bytes per color component, MSB first.
+pub fn encode(pixels: &[[u8; 3]], order: ColorOrder) -> Vec<u8> {
+    let mut data = Vec::with_capacity(pixels.len() * 9);
+    for &[r, g, b] in pixels {
+        let components = match order {
+            ColorOrder::Grb => [g, r, b],
+            ColorOrder::Rgb => [r, g, b],
+            ColorOrder::Bgr => [b, g, r],
+        };
+        for &component in &components {
+            let bits = (0..8).rev().fold(0u32, |bits, bit| {
+                let pattern = if component & (1 << bit) != 0 {
+                    0b110
+                } else {
+                    0b100
+                };
+                bits << 3 | pattern
+            });
+            data.extend_from_slice(&bits.to_be_bytes()[1..]);
+        }
+    }
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ZERO: [u8; 3] = [0x92, 0x49, 0x24];
+    const ONE: [u8; 3] = [0xdb, 0x6d, 0xb6];
+
+    // The LED bits carried by SPI data, checking that each symbol is `100` or `110`.
+    fn decode(data: &[u8]) -> Vec<u8> {
+        assert_eq!(data.len() % 3, 0);
+        data.chunks(3)
+            .map(|chunk| {
+                let bits = u32::from_be_bytes([0, chunk[0], chunk[1], chunk[2]]);
+                (0..8).rev().fold(0, |byte, bit| {
+                    let symbol = bits >> (bit * 3) & 0b111;
+                    assert!(symbol == 0b100 || symbol == 0b110, "{:03b}", symbol);
+                    byte << 1 | (symbol >> 1 & 1) as u8
+                })
+            })
+            .collect()
+    }
+
+    #[test]
+    fn symbols() {
+        assert_eq!(encode(&[[0x00; 3]], ColorOrder::Grb), [ZERO; 3].concat());
+        assert_eq!(encode(&[[0xff; 3]], ColorOrder::Grb), [ONE; 3].concat());
+        // MSB first: 110 100 100 100 100 100 100 100, then 100 ... 100 110.
+        assert_eq!(
+            encode(&[[0x80, 0x80, 0x01]], ColorOrder::Rgb),
+            [[0xd2, 0x49, 0x24], [0xd2, 0x49, 0x24], [0x92, 0x49, 0x26]].concat()
+        );
+    }
+
+    #[test]
+    fn grb_order() {
+        let data = encode(&[[0xff, 0x00, 0x80]], ColorOrder::Grb);
+        assert_eq!(&data[..3], &ZERO);
+        assert_eq!(&data[3..6], &ONE);
+        assert_eq!(decode(&data), [0x00, 0xff, 0x80]);
+    }
+
+    #[test]
+    fn color_orders() {
+        let pixels = [[0x12, 0x34, 0x56], [0xa5, 0x5a, 0x0f]];
+        let cases = [
+            (ColorOrder::Grb, [0x34, 0x12, 0x56, 0x5a, 0xa5, 0x0f]),
+            (ColorOrder::Rgb, [0x12, 0x34, 0x56, 0xa5, 0x5a, 0x0f]),
+            (ColorOrder::Bgr, [0x56, 0x34, 0x12, 0x0f, 0x5a, 0xa5]),
+        ];
+        for &(order, expected) in &cases {
+            let data = encode(&pixels, order);
+            assert_eq!(data.len(), pixels.len() * 9);
+            assert_eq!(decode(&data), expected, "{:?}", order);
+        }
+    }
+}
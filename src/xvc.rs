@@ -0,0 +1,124 @@
+//! Xilinx Virtual Cable server, letting tools such as Vivado or openFPGALoader use
+//! an MPSSE JTAG master over TCP.
+//!
+//! The protocol (version 1.0) has three commands: `getinfo:`, `settck:` followed
+//! by the TCK period in ns, and `shift:` followed by a bit count and the TMS and
+//! TDI vectors, answered with the TDO vector.
+
+use std::io::{self, Read, Write};
+use std::net::TcpListener;
+
+use error::Error;
+use jtag::Jtag;
+use {client_done, Result};
+
+/// Largest `shift:` payload (TMS and TDI vectors together) accepted by default.
+pub const DEFAULT_MAX_VECTOR_LEN: usize = 2048;
+
+// Longest command name, including the colon.
+const MAX_COMMAND_LEN: usize = 8;
+
+/// Serves XVC clients one at a time with a JTAG master.
+pub struct XvcServer<'a> {
+    jtag: Jtag<'a>,
+    max_vector_len: usize,
+}
+
+impl<'a> XvcServer<'a> {
+    pub fn new(jtag: Jtag<'a>) -> XvcServer<'a> {
+        XvcServer {
+            jtag,
+            max_vector_len: DEFAULT_MAX_VECTOR_LEN,
+        }
+    }
+
+    /// Advertise and accept `shift:` payloads of up to `len` bytes.
+    pub fn set_max_vector_len(&mut self, len: usize) {
+        self.max_vector_len = len;
+    }
+
+    /// Accept clients on `listener` and serve each until it disconnects.
+    ///
+    /// A client that fails, e.g. by sending a malformed command, is dropped (and logged
+    /// with the `tracing` feature) and the next one accepted. Only returns on an error
+    /// accepting clients, or with [`Error::Disconnected`] once the device is gone.
+    pub fn serve(&mut self, listener: &TcpListener) -> Result<()> {
+        loop {
+            let (stream, _) = listener.accept()?;
+            let res = stream
+                .set_nodelay(true)
+                .map_err(Error::from)
+                .and_then(|()| self.handle(stream));
+            client_done("XVC", res)?;
+        }
+    }
+
+    /// Serve one client until it disconnects.
+    pub fn handle<S: Read + Write>(&mut self, mut stream: S) -> Result<()> {
+        loop {
+            let command = match read_command(&mut stream)? {
+                Some(command) => command,
+                None => return Ok(()),
+            };
+            match &command[..] {
+                b"getinfo:" => {
+                    writeln!(stream, "xvcServer_v1.0:{}", self.max_vector_len)?;
+                }
+                b"settck:" => {
+                    let period = read_u32(&mut stream)?.max(1);
+                    let frequency = self.jtag.set_frequency((1_000_000_000 / period).max(1))?;
+                    stream.write_all(&(1_000_000_000 / frequency).to_le_bytes())?;
+                }
+                b"shift:" => {
+                    let bits = read_u32(&mut stream)? as usize;
+                    let len = bits.div_ceil(8);
+                    if 2 * len > self.max_vector_len {
+                        return Err(Error::InvalidArgument("XVC shift exceeds the vector size"));
+                    }
+
+                    let mut vectors = vec![0; 2 * len];
+                    stream.read_exact(&mut vectors)?;
+                    let (tms, tdi) = vectors.split_at(len);
+                    let tdo = self.jtag.shift_raw(tms, tdi, bits)?;
+                    stream.write_all(&tdo)?;
+                }
+                _ => return Err(Error::InvalidArgument("Unknown XVC command")),
+            }
+            stream.flush()?;
+        }
+    }
+
+    /// Return the JTAG master.
+    pub fn into_inner(self) -> Jtag<'a> {
+        self.jtag
+    }
+}
+
+// Read a command name up to its colon, or `None` if the client disconnected
+// before sending one.
+fn read_command<S: Read>(stream: &mut S) -> Result<Option<Vec<u8>>> {
+    let mut command = Vec::new();
+    let mut byte = [0];
+    loop {
+        match stream.read_exact(&mut byte) {
+            Ok(()) => {}
+            Err(ref e) if e.kind() == io::ErrorKind::UnexpectedEof && command.is_empty() => {
+                return Ok(None);
+            }
+            Err(e) => return Err(e.into()),
+        }
+        command.push(byte[0]);
+        if byte[0] == b':' {
+            return Ok(Some(command));
+        }
+        if command.len() == MAX_COMMAND_LEN {
+            return Err(Error::InvalidArgument("Unknown XVC command"));
+        }
+    }
+}
+
+fn read_u32<S: Read>(stream: &mut S) -> Result<u32> {
+    let mut bytes = [0; 4];
+    stream.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}